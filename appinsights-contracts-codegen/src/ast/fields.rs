@@ -34,10 +34,21 @@ impl Field {
         }
     }
 
+    pub fn json_name(&self) -> &str {
+        &self.field_name
+    }
+
     pub fn attributes(&self) -> &Vec<Attribute> {
         &self.field_attributes
     }
 
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.field_attributes
+            .iter()
+            .find(|attribute| attribute.names().iter().any(|attr_name| attr_name == name))
+            .map(Attribute::value)
+    }
+
     pub fn default_value(&self) -> Option<String> {
         match (&self.field_default, self.field_type.enum_()) {
             (Some(FieldDefault::Integer { value }), None) => Some(format!("{}", value)),
@@ -60,7 +71,7 @@ impl Field {
         }
     }
 
-    fn unwrap_option(type_: &Type) -> &Type {
+    pub fn unwrap_option(type_: &Type) -> &Type {
         type_.nullable().map_or(type_, |type_| Field::unwrap_option(type_))
     }
 }
@@ -94,6 +94,15 @@ pub enum UserType {
     Enum(Enum),
 }
 
+impl UserType {
+    pub fn name(&self) -> &str {
+        match self {
+            UserType::Struct(struct_) => struct_.name(),
+            UserType::Enum(enum_) => enum_.name(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 #[serde(deny_unknown_fields)]
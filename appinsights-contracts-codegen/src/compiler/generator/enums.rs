@@ -12,6 +12,7 @@ impl EnumGenerator {
             .derive("Debug")
             .derive("Clone")
             .derive("Serialize")
+            .derive("Deserialize")
             .vis("pub");
 
         Self { declaration }
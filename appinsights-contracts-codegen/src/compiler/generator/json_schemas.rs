@@ -0,0 +1,157 @@
+use serde_json::{json, Map, Value};
+
+use crate::ast::{Attribute, BasicType, ComplexType, EnumConstant, Field, Type};
+use crate::compiler::Visitor;
+
+/// Generates a JSON schema of a serialized data contract.
+pub struct JsonSchemaGenerator {
+    name: String,
+    schema: Map<String, Value>,
+    properties: Map<String, Value>,
+    required: Vec<Value>,
+    constants: Vec<Value>,
+}
+
+impl JsonSchemaGenerator {
+    pub fn new(name: &str) -> Self {
+        let mut schema = Map::new();
+        schema.insert("$schema".into(), "http://json-schema.org/draft-07/schema#".into());
+        schema.insert("$id".into(), file_name(name).into());
+        schema.insert("title".into(), name.into());
+
+        Self {
+            name: name.into(),
+            schema,
+            properties: Map::new(),
+            required: Vec::default(),
+            constants: Vec::default(),
+        }
+    }
+
+    pub fn file_name(&self) -> String {
+        file_name(&self.name)
+    }
+
+    fn schema(&self) -> Value {
+        let mut schema = self.schema.clone();
+        if self.constants.is_empty() {
+            schema.insert("type".into(), "object".into());
+            schema.insert("properties".into(), self.properties.clone().into());
+            schema.insert("required".into(), self.required.clone().into());
+            schema.insert("additionalProperties".into(), false.into());
+        } else {
+            schema.insert("type".into(), "string".into());
+            schema.insert("enum".into(), self.constants.clone().into());
+        }
+        schema.into()
+    }
+}
+
+impl Visitor for JsonSchemaGenerator {
+    fn visit_struct_attribute(&mut self, attribute: &Attribute) {
+        self.visit_enum_attribute(attribute);
+    }
+
+    fn visit_field(&mut self, field: &Field) {
+        let nullable = field.type_().nullable().is_some() || !field.is_required();
+        let mut schema = type_schema(Field::unwrap_option(field.type_()), field);
+        if !nullable {
+            self.required.push(field.json_name().into());
+        } else if let Some(Value::String(type_)) = schema.remove("type") {
+            schema.insert("type".into(), json!([type_, "null"]));
+        } else {
+            let mut nullable = Map::new();
+            nullable.insert("anyOf".into(), json!([schema, { "type": "null" }]));
+            schema = nullable;
+        }
+
+        if let Some(description) = field.attribute("Description") {
+            schema.insert("description".into(), description.into());
+        }
+        self.properties.insert(field.json_name().into(), schema.into());
+    }
+
+    fn visit_enum_constant(&mut self, constant: &EnumConstant) {
+        if constant.value().is_some() {
+            panic!("enum value is not supported: {:#?}", constant)
+        }
+        self.constants.push(constant.name().into());
+    }
+
+    fn visit_enum_attribute(&mut self, attribute: &Attribute) {
+        if attribute.names().iter().any(|name| name == "Description") {
+            self.schema.insert("description".into(), attribute.value().into());
+        }
+    }
+}
+
+impl std::fmt::Display for JsonSchemaGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let schema = serde_json::to_string_pretty(&self.schema()).map_err(|_| std::fmt::Error)?;
+        writeln!(f, "{}", schema)
+    }
+}
+
+fn file_name(name: &str) -> String {
+    format!("{}.schema.json", name)
+}
+
+fn type_schema(type_: &Type, field: &Field) -> Map<String, Value> {
+    let mut schema = Map::new();
+    match type_ {
+        Type::Basic(type_) => {
+            schema.insert("type".into(), basic_type(type_).into());
+            if let Some(max) = field.attribute("MaxStringLength") {
+                schema.insert("maxLength".into(), max_length(max));
+            }
+        }
+        Type::Complex(ComplexType::Map { element, .. }) => {
+            let element: Type = element.parse().expect("unexpected type: element");
+            let mut element = type_schema(&element, field);
+            if let Some(max) = field.attribute("MaxValueLength") {
+                element.insert("maxLength".into(), max_length(max));
+            }
+
+            schema.insert("type".into(), "object".into());
+            if let Some(max) = field.attribute("MaxKeyLength") {
+                schema.insert("propertyNames".into(), json!({ "maxLength": max_length(max) }));
+            }
+            schema.insert("additionalProperties".into(), element.into());
+        }
+        Type::Complex(ComplexType::Vector { element }) => {
+            schema.insert("type".into(), "array".into());
+            schema.insert("items".into(), type_schema(element, field).into());
+        }
+        Type::Complex(ComplexType::Nullable { element }) => {
+            schema = type_schema(element, field);
+        }
+        Type::Complex(ComplexType::User { declaration }) => {
+            schema.insert("$ref".into(), file_name(declaration.name()).into());
+        }
+        Type::Complex(ComplexType::Parameter { .. }) => {
+            // a concrete type of a generic field is known only from a serialized value
+            schema.insert("type".into(), "object".into());
+        }
+    }
+    schema
+}
+
+fn basic_type(type_: &BasicType) -> &'static str {
+    match type_ {
+        BasicType::Bool => "boolean",
+        BasicType::UInt8
+        | BasicType::UInt16
+        | BasicType::UInt32
+        | BasicType::UInt64
+        | BasicType::Int8
+        | BasicType::Int16
+        | BasicType::Int32
+        | BasicType::Int64 => "integer",
+        BasicType::Float | BasicType::Double => "number",
+        BasicType::String | BasicType::WString => "string",
+    }
+}
+
+fn max_length(value: &str) -> Value {
+    value.parse::<u64>().expect("unexpected max length").into()
+}
@@ -1,10 +1,12 @@
 mod enums;
+mod json_schemas;
 mod packages;
 mod schemas;
 mod structs;
 mod types;
 
 pub use enums::EnumGenerator;
+pub use json_schemas::JsonSchemaGenerator;
 pub use packages::PackageGenerator;
 pub use schemas::SchemaGenerator;
 pub use structs::{BuilderGenerator, StructGenerator, TelemetryDataTraitGenerator};
@@ -18,6 +18,7 @@ impl Visitor for SchemaGenerator {
     fn visit_schema(&mut self, schema: &Schema) {
         self.body.raw("// NOTE: This file was automatically generated.");
         self.body.import("serde", "Serialize");
+        self.body.import("serde", "Deserialize");
         self.body.import("crate::contracts", "*");
 
         self.visit_declarations(schema.declarations());
@@ -50,8 +51,8 @@ impl Visitor for SchemaGenerator {
     }
 }
 
-impl ToString for SchemaGenerator {
-    fn to_string(&self) -> String {
-        self.body.to_string()
+impl std::fmt::Display for SchemaGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.body.to_string())
     }
 }
@@ -1,9 +1,11 @@
 use std::collections::HashSet;
 
 use crate::ast::{Attribute, Field};
+use crate::compiler::generator::types::field_type;
 use crate::compiler::Visitor;
 
 pub struct StructGenerator {
+    name: String,
     declaration: codegen::Struct,
     generics: HashSet<String>,
     field_names: HashSet<String>,
@@ -16,9 +18,11 @@ impl StructGenerator {
             .derive("Debug")
             .derive("Clone")
             .derive("Serialize")
+            .derive("Deserialize")
             .vis("pub");
 
         Self {
+            name: name.to_string(),
             declaration,
             generics: HashSet::default(),
             field_names: HashSet::default(),
@@ -48,13 +52,14 @@ impl Visitor for StructGenerator {
             }
 
             // add a field declaration to struct
-            let field_type = codegen::Type::from(field.clone());
+            let field_type = field_type(&self.name, field);
             self.declaration.field(&field.name(), &field_type);
         }
     }
 }
 
 pub struct BuilderGenerator {
+    name: String,
     declaration: codegen::Struct,
     implementation: codegen::Impl,
     constructor: codegen::Function,
@@ -96,6 +101,7 @@ impl BuilderGenerator {
         let build_body = codegen::Block::new(name);
 
         Self {
+            name: name.to_string(),
             declaration,
             implementation,
             constructor,
@@ -162,7 +168,7 @@ impl Visitor for BuilderGenerator {
             }
 
             let field_name = field.name();
-            let field_type = codegen::Type::from(field.clone());
+            let field_type = field_type(&self.name, field);
 
             // add a field declaration to builder declaration
             self.declaration.field(&field_name, &field_type);
@@ -238,13 +244,13 @@ impl TelemetryDataTraitGenerator {
         implementation
             .impl_trait("TelemetryData")
             .new_fn("base_type")
-            .doc(&format!(
+            .doc(format!(
                 "Returns the base type when placed within an [{name}](trait.{name}.html) container.",
                 name = "Data"
             ))
             .arg_ref_self()
             .ret("String")
-            .line(&format!(r#"String::from("{}")"#, name));
+            .line(format!(r#"String::from("{}")"#, name));
 
         Self {
             implementation,
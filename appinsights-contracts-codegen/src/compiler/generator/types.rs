@@ -2,6 +2,19 @@ use std::str::FromStr;
 
 use crate::ast::{BasicType, ComplexType, Field, Type, UserType};
 
+/// Fields that mostly hold names known at compile time. They borrow static strings instead of copying them into
+/// every telemetry item.
+const STATIC_STR_FIELDS: &[(&str, &str)] = &[("Envelope", "name"), ("EventData", "name")];
+
+/// Returns a type of the field declared in the struct with the given name.
+pub fn field_type(struct_name: &str, field: &Field) -> codegen::Type {
+    if STATIC_STR_FIELDS.contains(&(struct_name, field.name().as_str())) {
+        codegen::Type::new("std::borrow::Cow<'static, str>")
+    } else {
+        codegen::Type::from(field.clone())
+    }
+}
+
 impl From<Field> for codegen::Type {
     fn from(field: Field) -> Self {
         let field_type = field.type_().clone();
@@ -62,8 +75,10 @@ impl From<ComplexType> for codegen::Type {
             }
             ComplexType::Parameter { value } => codegen::Type::new(value.name()),
             ComplexType::Vector { element } => {
-                let type_: Type = *element;
-                type_.into()
+                let mut type_ = codegen::Type::new("Vec");
+                let element = *element;
+                type_.generic(element);
+                type_
             }
             ComplexType::Nullable { element } => {
                 let mut type_ = codegen::Type::new("Option");
@@ -9,11 +9,11 @@ use std::convert::TryFrom;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use crate::compiler::generator::{PackageGenerator, SchemaGenerator};
+use crate::compiler::generator::{JsonSchemaGenerator, PackageGenerator, SchemaGenerator};
 use crate::parser::Parser;
 use crate::Result;
 
-pub fn compile_all(input_dir: PathBuf, output_dir: PathBuf) -> Result<()> {
+pub fn compile_all(input_dir: PathBuf, output_dir: PathBuf, schema_dir: Option<PathBuf>) -> Result<()> {
     let mut modules: Vec<_> = fs::read_dir(&input_dir)?
         .filter_map(|entry| entry.ok().map(|entry| entry.path()))
         .map(|path| Module::try_from((path, output_dir.clone())).expect("unable to read module path"))
@@ -23,6 +23,10 @@ pub fn compile_all(input_dir: PathBuf, output_dir: PathBuf) -> Result<()> {
     compile_files(modules.iter())?;
     compile_package(modules.iter(), &output_dir.join("mod.rs"))?;
 
+    if let Some(schema_dir) = schema_dir {
+        compile_json_schemas(modules.iter(), &schema_dir)?;
+    }
+
     Ok(())
 }
 
@@ -39,13 +43,13 @@ fn compile_files<'a>(modules: impl Iterator<Item = &'a Module>) -> Result<()> {
 }
 
 fn compile(module: &Module) -> Result<()> {
-    let parser = Parser::default();
+    let parser = Parser;
     let schema = parser.parse(module.source_path())?;
 
     let mut generator = SchemaGenerator::new();
     generator.visit_schema(&schema);
 
-    fs::write(&module.path(), generator.to_string())?;
+    fs::write(module.path(), generator.to_string())?;
     Ok(())
 }
 
@@ -58,3 +62,18 @@ fn compile_package<'a>(modules: impl Iterator<Item = &'a Module>, path: &Path) -
     fs::write(path, generator.to_string())?;
     Ok(())
 }
+
+fn compile_json_schemas<'a>(modules: impl Iterator<Item = &'a Module>, dir: &Path) -> Result<()> {
+    let parser = Parser;
+    for module in modules {
+        let schema = parser.parse(module.source_path())?;
+        for declaration in schema.declarations() {
+            let mut generator = JsonSchemaGenerator::new(declaration.name());
+            generator.visit_declarations(std::slice::from_ref(declaration));
+
+            fs::write(dir.join(generator.file_name()), generator.to_string())?;
+        }
+    }
+
+    Ok(())
+}
@@ -6,7 +6,7 @@ use appinsights_contracts_codegen::compiler;
 
 fn main() {
     let opts = Opt::from_args();
-    if let Err(err) = compiler::compile_all(opts.input_dir, opts.output_dir) {
+    if let Err(err) = compiler::compile_all(opts.input_dir, opts.output_dir, opts.schema_dir) {
         eprintln!("{}", err)
     }
 }
@@ -21,4 +21,8 @@ pub struct Opt {
     /// A path to directory to output generate data contract files to
     #[structopt(parse(from_os_str), short = "o", long = "output-dir")]
     output_dir: PathBuf,
+
+    /// A path to directory to output JSON schemas of serialized data contracts to
+    #[structopt(parse(from_os_str), short = "s", long = "schema-dir")]
+    schema_dir: Option<PathBuf>,
 }
@@ -9,7 +9,7 @@ pub struct Parser;
 
 impl Parser {
     pub fn parse(&self, path: &Path) -> Result<Schema> {
-        let schema = serde_json::from_reader(File::open(&path)?)?;
+        let schema = serde_json::from_reader(File::open(path)?)?;
         Ok(schema)
     }
 }
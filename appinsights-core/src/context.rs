@@ -0,0 +1,203 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+use crate::telemetry::{ContextTags, Properties};
+
+/// Encapsulates contextual data common to all telemetry submitted through a telemetry client.
+/// # Examples
+/// ```rust
+/// use appinsights_core::telemetry::{ContextTags, Properties};
+/// use appinsights_core::TelemetryContext;
+///
+/// let mut properties = Properties::default();
+/// properties.insert("Resource Group".to_string(), "my-rg".to_string());
+///
+/// let mut tags = ContextTags::default();
+/// tags.insert("account_id".to_string(), "123-345-777".to_string());
+///
+/// let context = TelemetryContext::new("instrumentation".to_string(), tags, properties);
+///
+/// assert_eq!(context.properties().get("Resource Group"), Some(&"my-rg".to_string()));
+/// assert_eq!(context.tags().get("account_id"), Some(&"123-345-777".to_string()));
+/// ```
+///
+/// The context is a snapshot taken for every telemetry item, so its contents are shared between clones and
+/// cloning it does not allocate. Contents are copied on write when a shared context gets modified.
+#[derive(Debug, Clone)]
+pub struct TelemetryContext {
+    /// An instrumentation key.
+    pub(crate) i_key: Arc<str>,
+
+    // A collection of tags to attach to telemetry event.
+    pub(crate) tags: Arc<ContextTags>,
+
+    // A collection of common properties to attach to telemetry event.
+    pub(crate) properties: Arc<Properties>,
+}
+
+impl TelemetryContext {
+    /// Creates a new instance of telemetry context.
+    pub fn new(i_key: String, tags: ContextTags, properties: Properties) -> Self {
+        Self {
+            i_key: i_key.into(),
+            tags: Arc::new(tags),
+            properties: Arc::new(properties),
+        }
+    }
+
+    /// Returns an instrumentation key.
+    pub fn i_key(&self) -> &str {
+        &self.i_key
+    }
+
+    /// Replaces an instrumentation key, e.g. when the key is rotated.
+    pub fn set_i_key(&mut self, i_key: impl Into<String>) {
+        self.i_key = i_key.into().into();
+    }
+
+    /// Returns mutable reference to a collection of common properties to attach to telemetry event.
+    pub fn properties_mut(&mut self) -> &mut Properties {
+        Arc::make_mut(&mut self.properties)
+    }
+
+    /// Returns immutable reference to a collection of common properties to attach to telemetry event.
+    pub fn properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    /// Returns mutable reference to a collection of common tags to attach to telemetry event.
+    pub fn tags_mut(&mut self) -> &mut ContextTags {
+        Arc::make_mut(&mut self.tags)
+    }
+
+    /// Returns immutable reference to a collection of common tags to attach to telemetry event.
+    pub fn tags(&self) -> &ContextTags {
+        &self.tags
+    }
+
+    /// Serializes the context into a compact JSON string that can be passed to another process, e.g. as an
+    /// environment variable of a spawned worker process, so the process reports telemetry with the same
+    /// instrumentation key, tags and properties, including tags of the operation it was spawned within.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights_core::telemetry::{ContextTags, Properties};
+    /// # use appinsights_core::TelemetryContext;
+    /// let mut context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+    /// context.tags_mut().insert("ai.operation.id".into(), "0af7651916cd43dd8448eb211c80319c".into());
+    ///
+    /// let value = context.serialize();
+    /// let context = TelemetryContext::deserialize(&value).unwrap();
+    ///
+    /// assert_eq!(context.tags()["ai.operation.id"], "0af7651916cd43dd8448eb211c80319c");
+    /// ```
+    pub fn serialize(&self) -> String {
+        let snapshot = SnapshotRef {
+            i_key: &self.i_key,
+            tags: &self.tags,
+            properties: &self.properties,
+        };
+        serde_json::to_string(&snapshot).expect("context is always serializable")
+    }
+
+    /// Restores a context serialized with [`serialize`](#method.serialize). Tags and properties are optional.
+    pub fn deserialize(value: &str) -> Result<Self, serde_json::Error> {
+        let snapshot: Snapshot = serde_json::from_str(value)?;
+        let mut context = Self::new(snapshot.i_key, ContextTags::default(), Properties::default());
+        context.tags_mut().extend(snapshot.tags);
+        context.properties_mut().extend(snapshot.properties);
+        Ok(context)
+    }
+
+    /// Returns common tags combined with tags of a telemetry item, which override common ones.
+    pub(crate) fn combine_tags(&self, tags: ContextTags) -> ContextTags {
+        let mut combined = (*self.tags).clone();
+        combined.extend(BTreeMap::from(tags));
+        combined
+    }
+
+    /// Returns common properties combined with properties of a telemetry item, which override common ones.
+    pub(crate) fn combine_properties(&self, properties: Properties) -> Properties {
+        let mut combined = (*self.properties).clone();
+        combined.extend(BTreeMap::from(properties));
+        combined
+    }
+}
+
+/// A serialized form of a context.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SnapshotRef<'a> {
+    i_key: &'a str,
+    tags: &'a BTreeMap<String, String>,
+    properties: &'a BTreeMap<String, String>,
+}
+
+/// A deserialized form of a context.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Snapshot {
+    i_key: String,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+    #[serde(default)]
+    properties: BTreeMap<String, String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_shares_contents_between_clones() {
+        let mut tags = ContextTags::default();
+        tags.insert("tag".into(), "value".into());
+        let context = TelemetryContext::new("instrumentation".into(), tags, Properties::default());
+
+        let snapshot = context.clone();
+
+        assert!(Arc::ptr_eq(&context.i_key, &snapshot.i_key));
+        assert!(Arc::ptr_eq(&context.tags, &snapshot.tags));
+        assert!(Arc::ptr_eq(&context.properties, &snapshot.properties));
+    }
+
+    #[test]
+    fn it_does_not_change_snapshot_when_context_modified() {
+        let mut context =
+            TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        let snapshot = context.clone();
+
+        context.set_i_key("rotated");
+        context.tags_mut().insert("tag".into(), "value".into());
+        context.properties_mut().insert("property".into(), "value".into());
+
+        assert_eq!(snapshot.i_key(), "instrumentation");
+        assert!(snapshot.tags().is_empty());
+        assert!(snapshot.properties().is_empty());
+    }
+
+    #[test]
+    fn it_serializes_and_deserializes_context() {
+        let mut context =
+            TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        context.tags_mut().insert("ai.operation.id".into(), "operation".into());
+        context.properties_mut().insert("tenant".into(), "contoso".into());
+
+        let value = context.serialize();
+        assert_eq!(
+            value,
+            r#"{"iKey":"instrumentation","tags":{"ai.operation.id":"operation"},"properties":{"tenant":"contoso"}}"#
+        );
+
+        let restored = TelemetryContext::deserialize(&value).unwrap();
+        assert_eq!(restored.i_key(), "instrumentation");
+        assert_eq!(**restored.tags(), **context.tags());
+        assert_eq!(**restored.properties(), **context.properties());
+
+        let restored = TelemetryContext::deserialize(r#"{"iKey":"instrumentation"}"#).unwrap();
+        assert!(restored.tags().is_empty());
+        assert!(TelemetryContext::deserialize("instrumentation").is_err());
+    }
+}
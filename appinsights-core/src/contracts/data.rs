@@ -1,10 +1,10 @@
 use crate::contracts::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // NOTE: This file was automatically generated.
 
 /// Data struct to contain both B and C sections.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "baseType", content = "baseData")]
 pub enum Data {
     AvailabilityData(AvailabilityData),
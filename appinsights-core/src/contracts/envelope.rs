@@ -1,14 +1,14 @@
 use crate::contracts::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // NOTE: This file was automatically generated.
 
 /// System variables for a telemetry item.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Envelope {
     pub ver: Option<i32>,
-    pub name: String,
+    pub name: std::borrow::Cow<'static, str>,
     pub time: String,
     pub sample_rate: Option<f64>,
     pub seq: Option<String>,
@@ -22,7 +22,7 @@ impl Default for Envelope {
     fn default() -> Self {
         Self {
             ver: Some(1),
-            name: String::default(),
+            name: std::borrow::Cow::default(),
             time: String::default(),
             sample_rate: Some(100.0),
             seq: Option::default(),
@@ -1,6 +1,6 @@
 // NOTE: This file was automatically generated.
 
-#![allow(unused_imports)]
+#![allow(unused_imports, clippy::derivable_impls, clippy::enum_variant_names)]
 
 mod availability_data;
 mod base;
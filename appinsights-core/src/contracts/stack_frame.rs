@@ -1,17 +1,17 @@
 use crate::contracts::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // NOTE: This file was automatically generated.
 
 /// Stack frame information.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StackFrame {
-    level: i32,
-    method: String,
-    assembly: Option<String>,
-    file_name: Option<String>,
-    line: Option<i32>,
+    pub level: i32,
+    pub method: String,
+    pub assembly: Option<String>,
+    pub file_name: Option<String>,
+    pub line: Option<i32>,
 }
 
 impl Default for StackFrame {
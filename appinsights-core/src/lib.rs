@@ -0,0 +1,54 @@
+//! # Application Insights for Rust: telemetry types
+//! The crate contains telemetry items and context types of the
+//! [`appinsights`](https://docs.rs/appinsights) SDK without the submission pipeline, so it does not
+//! depend on an async runtime or an HTTP client. The `appinsights` crate re-exports all of these types.
+//!
+//! ## Instrumenting libraries
+//! A library should not decide whether and where its telemetry is submitted. Instead it can depend on
+//! `appinsights-core`, optionally behind a feature flag, and accept any [`Tracker`](trait.Tracker.html)
+//! from the application. Telemetry clients of the `appinsights` crate implement [`Tracker`], so the final
+//! binary configures the pipeline once and passes its client to the library.
+//!
+//! ```toml
+//! [dependencies]
+//! appinsights-core = { version = "0.2", optional = true }
+//! ```
+//!
+//! ```rust
+//! use appinsights_core::{telemetry::RemoteDependencyTelemetry, Tracker};
+//! use std::time::Duration;
+//!
+//! pub fn query_users(tracker: &impl Tracker) {
+//!     // query database
+//!     let telemetry = RemoteDependencyTelemetry::new(
+//!         "SELECT * FROM users",
+//!         "SQL",
+//!         Duration::from_millis(42),
+//!         "db.example.com",
+//!         true,
+//!     );
+//!     tracker.track(telemetry);
+//! }
+//! ```
+#![deny(unused_extern_crates)]
+#![deny(missing_docs)]
+
+#[doc(hidden)]
+#[allow(missing_docs)]
+pub mod contracts;
+
+mod context;
+pub use context::TelemetryContext;
+
+pub mod telemetry;
+
+#[doc(hidden)]
+pub mod time;
+
+mod tracker;
+pub use tracker::Tracker;
+
+#[doc(hidden)]
+pub mod uuid;
+
+pub mod validation;
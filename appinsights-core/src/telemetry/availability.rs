@@ -1,12 +1,10 @@
 use std::time::Duration as StdDuration;
 
-use chrono::{DateTime, SecondsFormat, Utc};
-
 use crate::{
     context::TelemetryContext,
     contracts::{AvailabilityData, Base, Data, Envelope},
     telemetry::{ContextTags, Measurements, Properties, Telemetry},
-    time::{self, Duration},
+    time::{Duration, Timestamp},
     uuid::Uuid,
 };
 
@@ -50,7 +48,7 @@ pub struct AvailabilityTelemetry {
     success: bool,
 
     /// The time stamp when this telemetry was measured.
-    timestamp: DateTime<Utc>,
+    timestamp: Timestamp,
 
     /// Name of the location where the test was run.
     run_location: Option<String>,
@@ -78,13 +76,43 @@ impl AvailabilityTelemetry {
             run_location: Option::default(),
             message: Option::default(),
             success,
-            timestamp: time::now(),
+            timestamp: Timestamp::now(),
             properties: Properties::default(),
             tags: ContextTags::default(),
             measurements: Measurements::default(),
         }
     }
 
+    /// Returns an identifier of the test run, if any.
+    pub fn id(&self) -> Option<Uuid> {
+        self.id
+    }
+
+    /// Sets an identifier of the test run to correlate steps of the run and telemetry of the tested service.
+    pub fn set_id(&mut self, id: Uuid) {
+        self.id = Some(id);
+    }
+
+    /// Returns a name of the location where the test was run, if any.
+    pub fn run_location(&self) -> Option<&str> {
+        self.run_location.as_deref()
+    }
+
+    /// Sets a name of the location where the test was run, e.g. a region of the monitoring agent.
+    pub fn set_run_location(&mut self, run_location: impl Into<String>) {
+        self.run_location = Some(run_location.into());
+    }
+
+    /// Returns a diagnostic message for the result, if any.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// Sets a diagnostic message for the result, e.g. details of a failure.
+    pub fn set_message(&mut self, message: impl Into<String>) {
+        self.message = Some(message.into());
+    }
+
     /// Returns custom measurements to submit with the telemetry item.
     pub fn measurements(&self) -> &Measurements {
         &self.measurements
@@ -94,11 +122,16 @@ impl AvailabilityTelemetry {
     pub fn measurements_mut(&mut self) -> &mut Measurements {
         &mut self.measurements
     }
+
+    /// Sets the time when the measured operation started.
+    pub fn set_timestamp(&mut self, timestamp: Timestamp) {
+        self.timestamp = timestamp;
+    }
 }
 
 impl Telemetry for AvailabilityTelemetry {
     /// Returns the time when this telemetry was measured.
-    fn timestamp(&self) -> DateTime<Utc> {
+    fn timestamp(&self) -> Timestamp {
         self.timestamp
     }
 
@@ -127,9 +160,9 @@ impl From<(TelemetryContext, AvailabilityTelemetry)> for Envelope {
     fn from((context, telemetry): (TelemetryContext, AvailabilityTelemetry)) -> Self {
         Self {
             name: "Microsoft.ApplicationInsights.Availability".into(),
-            time: telemetry.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
-            i_key: Some(context.i_key),
-            tags: Some(ContextTags::combine(context.tags, telemetry.tags).into()),
+            time: telemetry.timestamp.to_string(),
+            i_key: Some(context.i_key.to_string()),
+            tags: Some(context.combine_tags(telemetry.tags).into()),
             data: Some(Base::Data(Data::AvailabilityData(AvailabilityData {
                 id: telemetry
                     .id
@@ -140,7 +173,7 @@ impl From<(TelemetryContext, AvailabilityTelemetry)> for Envelope {
                 success: telemetry.success,
                 run_location: telemetry.run_location,
                 message: telemetry.message,
-                properties: Some(Properties::combine(context.properties, telemetry.properties).into()),
+                properties: Some(context.combine_properties(telemetry.properties).into()),
                 measurements: Some(telemetry.measurements.into()),
                 ..AvailabilityData::default()
             }))),
@@ -153,9 +186,10 @@ impl From<(TelemetryContext, AvailabilityTelemetry)> for Envelope {
 mod tests {
     use std::collections::BTreeMap;
 
-    use chrono::TimeZone;
+    use chrono::{TimeZone, Utc};
 
     use super::*;
+    use crate::time;
 
     #[test]
     fn it_overrides_properties_from_context() {
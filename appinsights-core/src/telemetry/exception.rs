@@ -0,0 +1,499 @@
+use crate::{
+    context::TelemetryContext,
+    contracts::*,
+    telemetry::{ContextTags, Measurements, Properties, SeverityLevel, StackFrame, Telemetry},
+    time::Timestamp,
+};
+
+/// Represents a handled or unhandled error that occurred during execution of the monitored application.
+///
+/// # Examples
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// use appinsights::telemetry::{ExceptionTelemetry, SeverityLevel, Telemetry};
+///
+/// // create a telemetry item
+/// let mut telemetry = ExceptionTelemetry::new("std::io::Error", "Connection refused");
+/// telemetry.set_severity(SeverityLevel::Critical);
+///
+/// // attach custom properties, measurements and context tags
+/// telemetry.properties_mut().insert("component".to_string(), "data_processor".to_string());
+/// telemetry.tags_mut().insert("os_version".to_string(), "linux x86_64".to_string());
+/// telemetry.measurements_mut().insert("attempts".to_string(), 3.0);
+///
+/// // submit telemetry item to server
+/// client.track(telemetry);
+/// ```
+#[derive(Debug)]
+pub struct ExceptionTelemetry {
+    /// A type name of the error.
+    type_name: String,
+
+    /// An error message.
+    message: String,
+
+    /// A stack trace of the error.
+    stack: Option<String>,
+
+    /// Frames of the stack trace of the error, innermost first.
+    parsed_stack: Vec<StackFrame>,
+
+    /// Severity level.
+    severity: SeverityLevel,
+
+    /// The time stamp when this telemetry was measured.
+    timestamp: Timestamp,
+
+    /// Custom properties.
+    properties: Properties,
+
+    /// Telemetry context containing extra, optional tags.
+    tags: ContextTags,
+
+    /// Custom measurements.
+    measurements: Measurements,
+}
+
+impl ExceptionTelemetry {
+    /// Creates an exception telemetry item with specified error type name and message.
+    pub fn new(type_name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            type_name: type_name.into(),
+            message: message.into(),
+            stack: None,
+            parsed_stack: Vec::new(),
+            severity: SeverityLevel::Error,
+            timestamp: Timestamp::now(),
+            properties: Properties::default(),
+            tags: ContextTags::default(),
+            measurements: Measurements::default(),
+        }
+    }
+
+    /// Returns a type name of the error.
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    /// Returns an error message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns a stack trace of the error if any.
+    pub fn stack(&self) -> Option<&str> {
+        self.stack.as_deref()
+    }
+
+    /// Sets a stack trace of the error.
+    pub fn set_stack(&mut self, stack: impl Into<String>) {
+        self.stack = Some(stack.into());
+    }
+
+    /// Returns frames of the stack trace of the error, innermost first.
+    pub fn parsed_stack(&self) -> &[StackFrame] {
+        &self.parsed_stack
+    }
+
+    /// Sets frames of the stack trace of the error, innermost first. Frames are submitted in addition to the
+    /// stack trace set with [`set_stack`](#method.set_stack), if any.
+    pub fn set_parsed_stack(&mut self, frames: impl IntoIterator<Item = StackFrame>) {
+        self.parsed_stack = frames.into_iter().collect();
+    }
+
+    /// Returns severity level of the error. Defaults to [`SeverityLevel::Error`](enum.SeverityLevel.html).
+    pub fn severity(&self) -> SeverityLevel {
+        self.severity
+    }
+
+    /// Sets severity level of the error.
+    pub fn set_severity(&mut self, severity: SeverityLevel) {
+        self.severity = severity;
+    }
+
+    /// Returns custom measurements to submit with the telemetry item.
+    pub fn measurements(&self) -> &Measurements {
+        &self.measurements
+    }
+
+    /// Returns mutable reference to custom measurements.
+    pub fn measurements_mut(&mut self) -> &mut Measurements {
+        &mut self.measurements
+    }
+}
+
+#[cfg(feature = "anyhow")]
+impl ExceptionTelemetry {
+    /// Creates an exception telemetry item from [`anyhow::Error`] with specified severity level.
+    ///
+    /// The message contains the whole chain of context messages, each context message is also recorded as
+    /// `anyhow.context.<n>` property starting from the outermost one, and the root cause is recorded as
+    /// `anyhow.root_cause` property. A backtrace is attached as a stack trace when it was captured.
+    ///
+    /// # Examples
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use anyhow::Context;
+    /// use appinsights::telemetry::{ExceptionTelemetry, SeverityLevel};
+    ///
+    /// let result: anyhow::Result<String> = std::fs::read_to_string("settings.json").context("loading settings");
+    /// if let Err(err) = result {
+    ///     client.track(ExceptionTelemetry::from_anyhow(&err, SeverityLevel::Critical));
+    /// }
+    /// ```
+    pub fn from_anyhow(error: &anyhow::Error, severity: SeverityLevel) -> Self {
+        let mut telemetry = Self::new("anyhow::Error", format!("{:#}", error));
+        telemetry.set_severity(severity);
+
+        let mut chain = error.chain();
+        let root_cause = chain.next_back();
+        for (n, context) in chain.enumerate() {
+            telemetry
+                .properties_mut()
+                .insert(format!("anyhow.context.{}", n), context.to_string());
+        }
+        if let Some(root_cause) = root_cause {
+            telemetry
+                .properties_mut()
+                .insert("anyhow.root_cause".into(), root_cause.to_string());
+        }
+
+        telemetry.set_backtrace(error.backtrace());
+        telemetry
+    }
+}
+
+impl ExceptionTelemetry {
+    /// Creates an exception telemetry item from an error with specified severity level.
+    ///
+    /// The type name of the error is used as a type of the exception. The message contains the whole chain of
+    /// errors, outermost first, and each source of the error is also recorded as `error.source.<n>` property.
+    ///
+    /// # Examples
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use appinsights::telemetry::{ExceptionTelemetry, SeverityLevel};
+    ///
+    /// if let Err(err) = std::fs::read_to_string("settings.json") {
+    ///     client.track(ExceptionTelemetry::from_error(&err, SeverityLevel::Warning));
+    /// }
+    /// ```
+    pub fn from_error<E>(error: &E, severity: SeverityLevel) -> Self
+    where
+        E: std::error::Error + ?Sized,
+    {
+        let mut message = error.to_string();
+        let mut properties = Properties::default();
+        let mut source = error.source();
+        let mut n = 0;
+        while let Some(cause) = source {
+            let cause_message = cause.to_string();
+            message.push_str(": ");
+            message.push_str(&cause_message);
+            properties.insert(format!("error.source.{}", n), cause_message);
+            source = cause.source();
+            n += 1;
+        }
+
+        let mut telemetry = Self::new(std::any::type_name::<E>(), message);
+        telemetry.set_severity(severity);
+        telemetry.properties = properties;
+        telemetry
+    }
+
+    /// Attaches a backtrace of the error if it was captured. With the `backtrace` feature enabled, the
+    /// backtrace is submitted as structured frames, and as a single stack trace string otherwise.
+    pub fn set_backtrace(&mut self, backtrace: &std::backtrace::Backtrace) {
+        if backtrace.status() != std::backtrace::BacktraceStatus::Captured {
+            return;
+        }
+
+        let backtrace = backtrace.to_string();
+        if cfg!(feature = "backtrace") {
+            self.set_parsed_stack(StackFrame::parse_backtrace(&backtrace));
+        } else {
+            self.set_stack(backtrace);
+        }
+    }
+}
+
+#[cfg(feature = "anyhow")]
+impl From<&anyhow::Error> for ExceptionTelemetry {
+    /// Creates an exception telemetry item from [`anyhow::Error`] with [`SeverityLevel::Error`](enum.SeverityLevel.html).
+    fn from(error: &anyhow::Error) -> Self {
+        Self::from_anyhow(error, SeverityLevel::Error)
+    }
+}
+
+impl Telemetry for ExceptionTelemetry {
+    /// Returns the time when this telemetry was measured.
+    fn timestamp(&self) -> Timestamp {
+        self.timestamp
+    }
+
+    /// Returns custom properties to submit with the telemetry item.
+    fn properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    /// Returns mutable reference to custom properties.
+    fn properties_mut(&mut self) -> &mut Properties {
+        &mut self.properties
+    }
+
+    /// Returns context data containing extra, optional tags. Overrides values found on client telemetry context.
+    fn tags(&self) -> &ContextTags {
+        &self.tags
+    }
+
+    /// Returns mutable reference to custom tags.
+    fn tags_mut(&mut self) -> &mut ContextTags {
+        &mut self.tags
+    }
+}
+
+impl From<(TelemetryContext, ExceptionTelemetry)> for Envelope {
+    fn from((context, telemetry): (TelemetryContext, ExceptionTelemetry)) -> Self {
+        Self {
+            name: "Microsoft.ApplicationInsights.Exception".into(),
+            time: telemetry.timestamp.to_string(),
+            i_key: Some(context.i_key.to_string()),
+            tags: Some(context.combine_tags(telemetry.tags).into()),
+            data: Some(Base::Data(Data::ExceptionData(ExceptionData {
+                exceptions: vec![ExceptionDetails {
+                    type_name: telemetry.type_name,
+                    message: telemetry.message,
+                    has_full_stack: Some(telemetry.stack.is_some() || !telemetry.parsed_stack.is_empty()),
+                    stack: telemetry.stack,
+                    parsed_stack: if telemetry.parsed_stack.is_empty() {
+                        None
+                    } else {
+                        Some(
+                            telemetry
+                                .parsed_stack
+                                .into_iter()
+                                .enumerate()
+                                .map(|(level, frame)| frame.into_contract(level))
+                                .collect(),
+                        )
+                    },
+                    ..ExceptionDetails::default()
+                }],
+                severity_level: Some(telemetry.severity.into()),
+                properties: Some(context.combine_properties(telemetry.properties).into()),
+                measurements: Some(telemetry.measurements.into()),
+                ..ExceptionData::default()
+            }))),
+            ..Envelope::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+    use crate::time;
+
+    #[test]
+    fn it_overrides_properties_from_context() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 800));
+
+        let mut context =
+            TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        context.properties_mut().insert("test".into(), "ok".into());
+        context.properties_mut().insert("no-write".into(), "fail".into());
+
+        let mut telemetry = ExceptionTelemetry::new("std::io::Error", "connection refused");
+        telemetry.set_severity(SeverityLevel::Critical);
+        telemetry.set_stack("main.rs:42");
+        telemetry.properties_mut().insert("no-write".into(), "ok".into());
+        telemetry.measurements_mut().insert("value".into(), 5.0);
+
+        let envelop = Envelope::from((context, telemetry));
+
+        let expected = Envelope {
+            name: "Microsoft.ApplicationInsights.Exception".into(),
+            time: "2019-01-02T03:04:05.800Z".into(),
+            i_key: Some("instrumentation".into()),
+            tags: Some(BTreeMap::default()),
+            data: Some(Base::Data(Data::ExceptionData(ExceptionData {
+                exceptions: vec![ExceptionDetails {
+                    type_name: "std::io::Error".into(),
+                    message: "connection refused".into(),
+                    has_full_stack: Some(true),
+                    stack: Some("main.rs:42".into()),
+                    ..ExceptionDetails::default()
+                }],
+                severity_level: Some(crate::contracts::SeverityLevel::Critical),
+                properties: Some({
+                    let mut properties = BTreeMap::default();
+                    properties.insert("test".into(), "ok".into());
+                    properties.insert("no-write".into(), "ok".into());
+                    properties
+                }),
+                measurements: Some({
+                    let mut measurements = BTreeMap::default();
+                    measurements.insert("value".into(), 5.0);
+                    measurements
+                }),
+                ..ExceptionData::default()
+            }))),
+            ..Envelope::default()
+        };
+
+        assert_eq!(envelop, expected)
+    }
+
+    #[test]
+    fn it_overrides_tags_from_context() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 700));
+
+        let mut context =
+            TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        context.tags_mut().insert("test".into(), "ok".into());
+        context.tags_mut().insert("no-write".into(), "fail".into());
+
+        let mut telemetry = ExceptionTelemetry::new("std::io::Error", "connection refused");
+        telemetry.tags_mut().insert("no-write".into(), "ok".into());
+
+        let envelop = Envelope::from((context, telemetry));
+
+        let expected = Envelope {
+            name: "Microsoft.ApplicationInsights.Exception".into(),
+            time: "2019-01-02T03:04:05.700Z".into(),
+            i_key: Some("instrumentation".into()),
+            tags: Some({
+                let mut tags = BTreeMap::default();
+                tags.insert("test".into(), "ok".into());
+                tags.insert("no-write".into(), "ok".into());
+                tags
+            }),
+            data: Some(Base::Data(Data::ExceptionData(ExceptionData {
+                exceptions: vec![ExceptionDetails {
+                    type_name: "std::io::Error".into(),
+                    message: "connection refused".into(),
+                    has_full_stack: Some(false),
+                    ..ExceptionDetails::default()
+                }],
+                severity_level: Some(crate::contracts::SeverityLevel::Error),
+                properties: Some(BTreeMap::default()),
+                measurements: Some(BTreeMap::default()),
+                ..ExceptionData::default()
+            }))),
+            ..Envelope::default()
+        };
+
+        assert_eq!(envelop, expected)
+    }
+
+    #[test]
+    fn it_submits_parsed_stack() {
+        let mut frame = StackFrame::new("app::main");
+        frame.set_location("./src/main.rs", Some(42));
+
+        let mut telemetry = ExceptionTelemetry::new("panic", "unrecoverable state");
+        telemetry.set_parsed_stack(vec![frame, StackFrame::new("main")]);
+
+        let context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        let envelop = Envelope::from((context, telemetry));
+
+        let exceptions = match envelop.data {
+            Some(Base::Data(Data::ExceptionData(data))) => data.exceptions,
+            data => panic!("unexpected data: {:?}", data),
+        };
+        assert_eq!(exceptions[0].has_full_stack, Some(true));
+        assert_eq!(exceptions[0].stack, None);
+        assert_eq!(
+            exceptions[0].parsed_stack,
+            Some(vec![
+                crate::contracts::StackFrame {
+                    level: 0,
+                    method: "app::main".into(),
+                    assembly: Some("app".into()),
+                    file_name: Some("./src/main.rs".into()),
+                    line: Some(42),
+                },
+                crate::contracts::StackFrame {
+                    level: 1,
+                    method: "main".into(),
+                    ..crate::contracts::StackFrame::default()
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn it_creates_telemetry_from_error_chain() {
+        #[derive(Debug)]
+        struct LoadError(std::io::Error);
+
+        impl std::fmt::Display for LoadError {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "loading user")
+            }
+        }
+
+        impl std::error::Error for LoadError {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+
+        let error = LoadError(std::io::Error::other("connection refused"));
+
+        let telemetry = ExceptionTelemetry::from_error(&error, SeverityLevel::Warning);
+
+        assert!(telemetry.type_name().ends_with("LoadError"));
+        assert_eq!(telemetry.message(), "loading user: connection refused");
+        assert_eq!(telemetry.severity(), SeverityLevel::Warning);
+
+        let mut expected = BTreeMap::default();
+        expected.insert("error.source.0".to_string(), "connection refused".to_string());
+        assert_eq!(**telemetry.properties(), expected);
+    }
+
+    #[cfg(feature = "anyhow")]
+    #[test]
+    fn it_creates_telemetry_from_anyhow_error() {
+        let error = anyhow::anyhow!("connection refused")
+            .context("loading user")
+            .context("handling request");
+
+        let telemetry = ExceptionTelemetry::from(&error);
+
+        assert_eq!(telemetry.type_name(), "anyhow::Error");
+        assert_eq!(
+            telemetry.message(),
+            "handling request: loading user: connection refused"
+        );
+        assert_eq!(telemetry.severity(), SeverityLevel::Error);
+
+        let mut expected = BTreeMap::default();
+        expected.insert("anyhow.context.0".to_string(), "handling request".to_string());
+        expected.insert("anyhow.context.1".to_string(), "loading user".to_string());
+        expected.insert("anyhow.root_cause".to_string(), "connection refused".to_string());
+        assert_eq!(**telemetry.properties(), expected);
+    }
+
+    #[cfg(feature = "anyhow")]
+    #[test]
+    fn it_maps_severity_for_anyhow_error() {
+        let error = anyhow::anyhow!("out of memory");
+
+        let telemetry = ExceptionTelemetry::from_anyhow(&error, SeverityLevel::Critical);
+
+        assert_eq!(telemetry.severity(), SeverityLevel::Critical);
+        assert_eq!(
+            telemetry.properties().get("anyhow.root_cause"),
+            Some(&"out of memory".into())
+        );
+        assert_eq!(telemetry.properties().get("anyhow.context.0"), None);
+    }
+}
@@ -0,0 +1,71 @@
+use crate::contracts::{Base, Data, Envelope};
+
+/// Identifies a type of telemetry items, e.g. to configure how the items of this type are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TelemetryKind {
+    /// Results of availability tests.
+    Availability,
+
+    /// Custom events.
+    Event,
+
+    /// Exceptions.
+    Exception,
+
+    /// Metrics, including aggregated ones.
+    Metric,
+
+    /// Page views.
+    PageView,
+
+    /// Calls to remote dependencies.
+    RemoteDependency,
+
+    /// Requests handled by the application.
+    Request,
+
+    /// Trace messages.
+    Trace,
+}
+
+impl TelemetryKind {
+    /// Returns a type of the telemetry item in the envelope, or `None` if the envelope has no data.
+    #[doc(hidden)]
+    pub fn of(envelope: &Envelope) -> Option<Self> {
+        envelope.data.as_ref().map(|Base::Data(data)| match data {
+            Data::AvailabilityData(_) => TelemetryKind::Availability,
+            Data::EventData(_) => TelemetryKind::Event,
+            Data::ExceptionData(_) => TelemetryKind::Exception,
+            Data::MessageData(_) => TelemetryKind::Trace,
+            Data::MetricData(_) => TelemetryKind::Metric,
+            Data::PageViewData(_) => TelemetryKind::PageView,
+            Data::RemoteDependencyData(_) => TelemetryKind::RemoteDependency,
+            Data::RequestData(_) => TelemetryKind::Request,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        telemetry::{AvailabilityTelemetry, SeverityLevel, TraceTelemetry},
+        TelemetryContext,
+    };
+
+    #[test]
+    fn it_determines_kind_of_envelope() {
+        let context = TelemetryContext::new("instrumentation".into(), Default::default(), Default::default());
+
+        let envelope = Envelope::from((
+            context.clone(),
+            AvailabilityTelemetry::new("test", std::time::Duration::from_secs(1), true),
+        ));
+        assert_eq!(TelemetryKind::of(&envelope), Some(TelemetryKind::Availability));
+
+        let envelope = Envelope::from((context, TraceTelemetry::new("message", SeverityLevel::Information)));
+        assert_eq!(TelemetryKind::of(&envelope), Some(TelemetryKind::Trace));
+
+        assert_eq!(TelemetryKind::of(&Envelope::default()), None);
+    }
+}
@@ -1,10 +1,8 @@
-use chrono::{DateTime, SecondsFormat, Utc};
-
 use crate::{
     context::TelemetryContext,
     contracts::{Base, Data, DataPoint, DataPointType, Envelope, MetricData},
     telemetry::{ContextTags, Properties, Stats, Telemetry},
-    time,
+    time::Timestamp,
 };
 
 /// Aggregated metric telemetry item that represents an aggregation of data points over time.
@@ -37,7 +35,7 @@ pub struct AggregateMetricTelemetry {
     stats: Stats,
 
     /// The time stamp when this telemetry was measured.
-    timestamp: DateTime<Utc>,
+    timestamp: Timestamp,
 
     /// Custom properties.
     properties: Properties,
@@ -52,7 +50,7 @@ impl AggregateMetricTelemetry {
         Self {
             name: name.into(),
             stats: Stats::default(),
-            timestamp: time::now(),
+            timestamp: Timestamp::now(),
             properties: Properties::default(),
             tags: ContextTags::default(),
         }
@@ -67,11 +65,16 @@ impl AggregateMetricTelemetry {
     pub fn stats_mut(&mut self) -> &mut Stats {
         &mut self.stats
     }
+
+    /// Sets the time when the first data point of the aggregation was measured.
+    pub fn set_timestamp(&mut self, timestamp: Timestamp) {
+        self.timestamp = timestamp;
+    }
 }
 
 impl Telemetry for AggregateMetricTelemetry {
     /// Returns the time when this telemetry was measured.
-    fn timestamp(&self) -> DateTime<Utc> {
+    fn timestamp(&self) -> Timestamp {
         self.timestamp
     }
 
@@ -100,9 +103,9 @@ impl From<(TelemetryContext, AggregateMetricTelemetry)> for Envelope {
     fn from((context, telemetry): (TelemetryContext, AggregateMetricTelemetry)) -> Self {
         Self {
             name: "Microsoft.ApplicationInsights.Metric".into(),
-            time: telemetry.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
-            i_key: Some(context.i_key),
-            tags: Some(ContextTags::combine(context.tags, telemetry.tags).into()),
+            time: telemetry.timestamp.to_string(),
+            i_key: Some(context.i_key.to_string()),
+            tags: Some(context.combine_tags(telemetry.tags).into()),
             data: Some(Base::Data(Data::MetricData(MetricData {
                 metrics: vec![DataPoint {
                     name: telemetry.name,
@@ -114,7 +117,7 @@ impl From<(TelemetryContext, AggregateMetricTelemetry)> for Envelope {
                     std_dev: Some(telemetry.stats.std_dev),
                     ..DataPoint::default()
                 }],
-                properties: Some(Properties::combine(context.properties, telemetry.properties).into()),
+                properties: Some(context.combine_properties(telemetry.properties).into()),
                 ..MetricData::default()
             }))),
             ..Envelope::default()
@@ -126,7 +129,7 @@ impl From<(TelemetryContext, AggregateMetricTelemetry)> for Envelope {
 mod tests {
     use std::collections::BTreeMap;
 
-    use chrono::TimeZone;
+    use chrono::{TimeZone, Utc};
 
     use super::*;
     use crate::time;
@@ -229,7 +232,7 @@ mod tests {
         let mut telemetry = AggregateMetricTelemetry::new("stats");
         *telemetry.stats_mut() = stats;
 
-        assert!((telemetry.stats().value - 15.0).abs() < std::f64::EPSILON);
+        assert!((telemetry.stats().value - 15.0).abs() < f64::EPSILON);
     }
 
     #[test]
@@ -1,10 +1,8 @@
-use chrono::{DateTime, SecondsFormat, Utc};
-
 use crate::{
     context::TelemetryContext,
     contracts::{Base, Data, DataPoint, DataPointType, Envelope, MetricData},
     telemetry::{ContextTags, Properties, Telemetry},
-    time,
+    time::Timestamp,
 };
 
 /// Metric telemetry item that represents a single data point.
@@ -34,7 +32,7 @@ pub struct MetricTelemetry {
     value: f64,
 
     /// The time stamp when this telemetry was measured.
-    timestamp: DateTime<Utc>,
+    timestamp: Timestamp,
 
     /// Custom properties.
     properties: Properties,
@@ -49,16 +47,26 @@ impl MetricTelemetry {
         Self {
             name: name.into(),
             value,
-            timestamp: time::now(),
+            timestamp: Timestamp::now(),
             properties: Properties::default(),
             tags: ContextTags::default(),
         }
     }
+
+    /// Returns a metric name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns a sampled value.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
 }
 
 impl Telemetry for MetricTelemetry {
     /// Returns the time when this telemetry was measured.
-    fn timestamp(&self) -> DateTime<Utc> {
+    fn timestamp(&self) -> Timestamp {
         self.timestamp
     }
 
@@ -87,9 +95,9 @@ impl From<(TelemetryContext, MetricTelemetry)> for Envelope {
     fn from((context, telemetry): (TelemetryContext, MetricTelemetry)) -> Self {
         Self {
             name: "Microsoft.ApplicationInsights.Metric".into(),
-            time: telemetry.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
-            i_key: Some(context.i_key),
-            tags: Some(ContextTags::combine(context.tags, telemetry.tags).into()),
+            time: telemetry.timestamp.to_string(),
+            i_key: Some(context.i_key.to_string()),
+            tags: Some(context.combine_tags(telemetry.tags).into()),
             data: Some(Base::Data(Data::MetricData(MetricData {
                 metrics: vec![DataPoint {
                     name: telemetry.name,
@@ -98,7 +106,7 @@ impl From<(TelemetryContext, MetricTelemetry)> for Envelope {
                     count: Some(1),
                     ..DataPoint::default()
                 }],
-                properties: Some(Properties::combine(context.properties, telemetry.properties).into()),
+                properties: Some(context.combine_properties(telemetry.properties).into()),
                 ..MetricData::default()
             }))),
             ..Envelope::default()
@@ -110,7 +118,7 @@ impl From<(TelemetryContext, MetricTelemetry)> for Envelope {
 mod tests {
     use std::collections::BTreeMap;
 
-    use chrono::TimeZone;
+    use chrono::{TimeZone, Utc};
 
     use super::*;
     use crate::time;
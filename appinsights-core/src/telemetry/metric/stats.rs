@@ -65,8 +65,8 @@ impl Stats {
                 mean = self.value / self.count as f64;
             }
 
-            self.min = values.iter().fold(std::f64::NAN, |x, min| min.min(x));
-            self.max = values.iter().fold(std::f64::NAN, |x, max| max.max(x));
+            self.min = values.iter().fold(self.min, |min, x| min.min(*x));
+            self.max = values.iter().fold(self.max, |max, x| max.max(*x));
 
             // Welford's algorithm to compute variance. The divide occurs in the caller.
             let mut value = self.value;
@@ -114,6 +114,20 @@ mod tests {
         )
     }
 
+    #[test]
+    fn it_calculates_stats_incrementally() {
+        let mut stats = Stats::default();
+        for value in [9.0, 10.0, 11.0, 7.0, 13.0] {
+            stats.add_data(&[value]);
+        }
+
+        assert_eq!(stats.value, 50.0);
+        assert_eq!(stats.count, 5);
+        assert_eq!(stats.min, 7.0);
+        assert_eq!(stats.max, 13.0);
+        assert!((stats.std_dev - 2.0).abs() < 1e-9);
+    }
+
     #[test_case(&[],                           0.0,    0.0,    0.0     ; "for empty collection")]
     #[test_case(&[0.0],                        0.0,    0.0,    0.0     ; "for single zero value")]
     #[test_case(&[50.0],                       0.0,    50.0,   50.0    ; "for single non-zero value")]
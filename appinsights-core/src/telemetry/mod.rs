@@ -2,35 +2,44 @@
 mod availability;
 mod event;
 mod exception;
+mod kind;
 mod measurements;
 mod metric;
 mod page_view;
 mod properties;
 mod remote_dependency;
 mod request;
+mod result;
+mod stack;
+pub mod tag_keys;
 mod tags;
 mod trace;
+mod uri;
 
 pub use availability::AvailabilityTelemetry;
 pub use event::EventTelemetry;
+pub use exception::ExceptionTelemetry;
+pub use kind::TelemetryKind;
 pub use measurements::Measurements;
 pub use metric::{AggregateMetricTelemetry, MetricTelemetry, Stats};
 pub use page_view::PageViewTelemetry;
 pub use properties::Properties;
 pub use remote_dependency::RemoteDependencyTelemetry;
-pub use request::RequestTelemetry;
+pub use request::{normalize_request_name, RequestTelemetry, SuccessPolicy};
+pub use result::{OperationResult, RESULT_REASON};
+pub use stack::StackFrame;
 pub use tags::{
     ApplicationTags, CloudTags, ContextTags, DeviceTags, InternalTags, LocationTags, OperationTags, SessionTags,
     UserTags,
 };
-pub use trace::{SeverityLevel, TraceTelemetry};
+pub use trace::{SeverityLevel, ToSeverityLevel, TraceTelemetry};
 
-use chrono::{DateTime, Utc};
+pub use crate::time::Timestamp;
 
 /// A trait that provides Application Insights telemetry items.
 pub trait Telemetry {
     /// Returns the time when this telemetry was measured.
-    fn timestamp(&self) -> DateTime<Utc>;
+    fn timestamp(&self) -> Timestamp;
 
     /// Returns custom properties to submit with the telemetry item.
     fn properties(&self) -> &Properties;
@@ -0,0 +1,101 @@
+use std::{
+    collections::BTreeMap,
+    ops::{Deref, DerefMut},
+};
+
+use log::warn;
+use serde_json::Value;
+
+/// Maximum length of a property value in characters the ingestion endpoint accepts.
+const MAX_VALUE_LENGTH: usize = 8192;
+
+/// Contains all properties for telemetry to submit.
+#[derive(Debug, Clone, Default)]
+pub struct Properties(BTreeMap<String, String>);
+
+impl Properties {
+    /// Combines all properties from two objects. It can override some properties with values found
+    /// in the second properties bag.
+    pub fn combine(a: Properties, b: Properties) -> Self {
+        let items = a.0.into_iter().chain(b.0).collect();
+        Self(items)
+    }
+
+    /// Inserts a structured value as a property serialized to a compact JSON string and returns the previous
+    /// value of the property, if any. A value longer than 8192 characters, the limit of the ingestion endpoint,
+    /// is truncated with a warning, so it is no longer valid JSON.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use appinsights::telemetry::Properties;
+    /// use serde_json::json;
+    ///
+    /// let mut properties = Properties::default();
+    /// properties.insert_json("payload", &json!({ "id": 42, "tags": ["new"] }));
+    ///
+    /// assert_eq!(properties.get("payload"), Some(&r#"{"id":42,"tags":["new"]}"#.to_string()));
+    /// ```
+    pub fn insert_json(&mut self, name: impl Into<String>, value: &Value) -> Option<String> {
+        let name = name.into();
+        let mut value = value.to_string();
+        if let Some((index, _)) = value.char_indices().nth(MAX_VALUE_LENGTH) {
+            warn!(
+                "Value of property {} exceeds maximum length of {} characters and has been truncated",
+                name, MAX_VALUE_LENGTH
+            );
+            value.truncate(index);
+        }
+        self.0.insert(name, value)
+    }
+}
+
+impl From<Properties> for BTreeMap<String, String> {
+    fn from(properties: Properties) -> Self {
+        properties.0
+    }
+}
+
+impl Deref for Properties {
+    type Target = BTreeMap<String, String>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Properties {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn it_inserts_json_value_as_compact_string() {
+        let mut properties = Properties::default();
+
+        properties.insert_json("payload", &json!({ "id": 42, "tags": ["new", "vip"] }));
+
+        assert_eq!(
+            properties.get("payload"),
+            Some(&r#"{"id":42,"tags":["new","vip"]}"#.to_string())
+        );
+    }
+
+    #[test]
+    fn it_truncates_json_value_exceeding_max_length() {
+        let mut properties = Properties::default();
+
+        properties.insert_json("payload", &json!("é".repeat(MAX_VALUE_LENGTH)));
+
+        let value = properties.get("payload").unwrap();
+        assert_eq!(value.chars().count(), MAX_VALUE_LENGTH);
+        assert!(value.starts_with("\"é"));
+    }
+}
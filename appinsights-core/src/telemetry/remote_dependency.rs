@@ -1,12 +1,12 @@
 use std::time::Duration as StdDuration;
 
-use chrono::{DateTime, SecondsFormat, Utc};
+use http::StatusCode;
 
 use crate::{
     context::TelemetryContext,
     contracts::{Base, Data, Envelope, RemoteDependencyData},
-    telemetry::{ContextTags, Measurements, Properties, Telemetry},
-    time::{self, Duration},
+    telemetry::{ContextTags, Measurements, OperationResult, Properties, Telemetry},
+    time::{Duration, Timestamp},
 };
 
 /// Represents interactions of the monitored component with a remote component/service like SQL or an HTTP endpoint.
@@ -68,7 +68,7 @@ pub struct RemoteDependencyTelemetry {
     target: String,
 
     /// The time stamp when this telemetry was measured.
-    timestamp: DateTime<Utc>,
+    timestamp: Timestamp,
 
     /// Custom properties.
     properties: Properties,
@@ -98,13 +98,33 @@ impl RemoteDependencyTelemetry {
             data: Option::default(),
             dependency_type: dependency_type.into(),
             target: target.into(),
-            timestamp: time::now(),
+            timestamp: Timestamp::now(),
             properties: Properties::default(),
             tags: ContextTags::default(),
             measurements: Measurements::default(),
         }
     }
 
+    /// Returns a name of the command that initiated this dependency call.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns a dependency type name.
+    pub fn dependency_type(&self) -> &str {
+        &self.dependency_type
+    }
+
+    /// Returns a target site of a dependency call.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Returns a duration of the remote call.
+    pub fn duration(&self) -> StdDuration {
+        *self.duration
+    }
+
     /// Returns custom measurements to submit with the telemetry item.
     pub fn measurements(&self) -> &Measurements {
         &self.measurements
@@ -121,7 +141,7 @@ impl RemoteDependencyTelemetry {
     /// ```rust,no_run
     /// # use appinsights::TelemetryClient;
     /// # use appinsights::telemetry::{RemoteDependencyTelemetry, SeverityLevel, Telemetry, TraceTelemetry};
-    /// # use http::{Method, Uri};
+    /// # use appinsights::ext::{Method, Uri};
     /// # use std::time::Duration;
     /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
     /// let operation_id = "...".to_string();
@@ -146,11 +166,83 @@ impl RemoteDependencyTelemetry {
     pub fn set_id(&mut self, id: impl Into<String>) {
         self.id = Some(id.into());
     }
+
+    /// Sets the time when the measured operation started.
+    pub fn set_timestamp(&mut self, timestamp: Timestamp) {
+        self.timestamp = timestamp;
+    }
+
+    /// Returns a result code of a dependency call if any.
+    pub fn result_code(&self) -> Option<&str> {
+        self.result_code.as_deref()
+    }
+
+    /// Sets a result code of a dependency call. Prefer [`set_http_status`](#method.set_http_status) and
+    /// [`set_sql_state`](#method.set_sql_state) for well-known kinds of dependencies, so result codes are
+    /// consistent across the application.
+    pub fn set_result_code(&mut self, result_code: impl Into<String>) {
+        self.result_code = Some(result_code.into());
+    }
+
+    /// Returns an indication of successful or unsuccessful call.
+    pub fn is_success(&self) -> bool {
+        self.success
+    }
+
+    /// Sets an indication of successful or unsuccessful call.
+    pub fn set_success(&mut self, success: bool) {
+        self.success = success;
+    }
+
+    /// Sets an indication of successful or unsuccessful call and records a reason the call did not succeed, if
+    /// any, as the `result.reason` custom property.
+    pub fn set_result(&mut self, result: OperationResult) {
+        self.success = result.apply(&mut self.properties);
+    }
+
+    /// Records a HTTP response status as the result code. The call is considered successful unless the status
+    /// is a client or server error.
+    ///
+    /// ```rust
+    /// # use appinsights::telemetry::RemoteDependencyTelemetry;
+    /// # use std::time::Duration;
+    /// use appinsights::ext::StatusCode;
+    ///
+    /// let mut dependency = RemoteDependencyTelemetry::new("GET /users", "HTTP", Duration::from_millis(42), "api.example.com", true);
+    /// dependency.set_http_status(StatusCode::SERVICE_UNAVAILABLE);
+    ///
+    /// assert_eq!(dependency.result_code(), Some("503"));
+    /// assert!(!dependency.is_success());
+    /// ```
+    pub fn set_http_status(&mut self, status: StatusCode) {
+        self.result_code = Some(status.as_str().into());
+        self.success = !(status.is_client_error() || status.is_server_error());
+    }
+
+    /// Records a [SQLSTATE](https://en.wikipedia.org/wiki/SQLSTATE) error code reported by a database as the
+    /// result code. Most databases (PostgreSQL, MySQL, SQL Server, SQLite via ODBC) report one. The call is
+    /// considered successful when the code belongs to successful completion (`00`), warning (`01`) or
+    /// no data (`02`) classes.
+    ///
+    /// ```rust
+    /// # use appinsights::telemetry::RemoteDependencyTelemetry;
+    /// # use std::time::Duration;
+    /// let mut dependency = RemoteDependencyTelemetry::new("INSERT users", "SQL", Duration::from_millis(42), "db.example.com", true);
+    /// dependency.set_sql_state("23505"); // unique_violation
+    ///
+    /// assert_eq!(dependency.result_code(), Some("23505"));
+    /// assert!(!dependency.is_success());
+    /// ```
+    pub fn set_sql_state(&mut self, sql_state: &str) {
+        let sql_state = sql_state.trim().to_ascii_uppercase();
+        self.success = matches!(sql_state.get(..2), Some("00") | Some("01") | Some("02"));
+        self.result_code = Some(sql_state);
+    }
 }
 
 impl Telemetry for RemoteDependencyTelemetry {
     /// Returns the time when this telemetry was measured.
-    fn timestamp(&self) -> DateTime<Utc> {
+    fn timestamp(&self) -> Timestamp {
         self.timestamp
     }
 
@@ -179,9 +271,9 @@ impl From<(TelemetryContext, RemoteDependencyTelemetry)> for Envelope {
     fn from((context, telemetry): (TelemetryContext, RemoteDependencyTelemetry)) -> Self {
         Self {
             name: "Microsoft.ApplicationInsights.RemoteDependency".into(),
-            time: telemetry.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
-            i_key: Some(context.i_key),
-            tags: Some(ContextTags::combine(context.tags, telemetry.tags).into()),
+            time: telemetry.timestamp.to_string(),
+            i_key: Some(context.i_key.to_string()),
+            tags: Some(context.combine_tags(telemetry.tags).into()),
             data: Some(Base::Data(Data::RemoteDependencyData(RemoteDependencyData {
                 name: telemetry.name,
                 id: telemetry.id,
@@ -191,7 +283,7 @@ impl From<(TelemetryContext, RemoteDependencyTelemetry)> for Envelope {
                 data: telemetry.data,
                 target: Some(telemetry.target),
                 type_: Some(telemetry.dependency_type),
-                properties: Some(Properties::combine(context.properties, telemetry.properties).into()),
+                properties: Some(context.combine_properties(telemetry.properties).into()),
                 measurements: Some(telemetry.measurements.into()),
                 ..RemoteDependencyData::default()
             }))),
@@ -204,9 +296,43 @@ impl From<(TelemetryContext, RemoteDependencyTelemetry)> for Envelope {
 mod tests {
     use std::collections::BTreeMap;
 
-    use chrono::TimeZone;
+    use chrono::{TimeZone, Utc};
+    use test_case::test_case;
 
     use super::*;
+    use crate::time;
+
+    #[test_case(StatusCode::OK,                     "200",  true    ; "ok")]
+    #[test_case(StatusCode::NOT_MODIFIED,           "304",  true    ; "redirection")]
+    #[test_case(StatusCode::NOT_FOUND,              "404",  false   ; "client error")]
+    #[test_case(StatusCode::SERVICE_UNAVAILABLE,    "503",  false   ; "server error")]
+    fn it_maps_http_status(status: StatusCode, result_code: &str, success: bool) {
+        let mut telemetry = create_telemetry();
+
+        telemetry.set_http_status(status);
+
+        assert_eq!(telemetry.result_code(), Some(result_code));
+        assert_eq!(telemetry.is_success(), success);
+    }
+
+    #[test_case("00000",    "00000",    true    ; "successful completion")]
+    #[test_case("01004",    "01004",    true    ; "warning")]
+    #[test_case("02000",    "02000",    true    ; "no data")]
+    #[test_case("23505",    "23505",    false   ; "unique violation")]
+    #[test_case(" 42p01 ",  "42P01",    false   ; "normalized")]
+    #[test_case("",         "",         false   ; "empty")]
+    fn it_maps_sql_state(sql_state: &str, result_code: &str, success: bool) {
+        let mut telemetry = create_telemetry();
+
+        telemetry.set_sql_state(sql_state);
+
+        assert_eq!(telemetry.result_code(), Some(result_code));
+        assert_eq!(telemetry.is_success(), success);
+    }
+
+    fn create_telemetry() -> RemoteDependencyTelemetry {
+        RemoteDependencyTelemetry::new("GET /users", "HTTP", StdDuration::from_secs(2), "example.com", true)
+    }
 
     #[test]
     fn it_uses_specified_id() {
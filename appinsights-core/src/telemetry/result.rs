@@ -0,0 +1,117 @@
+use std::fmt;
+
+use crate::telemetry::Properties;
+
+/// A name of the custom property that contains a reason an operation did not succeed.
+pub const RESULT_REASON: &str = "result.reason";
+
+/// An outcome of an operation, such as a request or a dependency call, that determines whether the operation
+/// succeeded and why it did not. Reasons are submitted as the `result.reason` custom property, so failures can
+/// be queried by reason in the portal the same way across services.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+/// use appinsights::telemetry::{OperationResult, RemoteDependencyTelemetry};
+///
+/// let mut dependency = RemoteDependencyTelemetry::new("GET", "Redis", Duration::from_secs(5), "cache:6379", true);
+/// dependency.set_result(OperationResult::Timeout);
+///
+/// assert!(!dependency.is_success());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OperationResult {
+    /// The operation succeeded.
+    Success,
+
+    /// The operation failed for the given reason.
+    Failure(String),
+
+    /// The operation was canceled before it completed.
+    Canceled,
+
+    /// The operation did not complete in time.
+    Timeout,
+}
+
+impl OperationResult {
+    /// Creates a failure with the given reason.
+    pub fn failure(reason: impl Into<String>) -> Self {
+        OperationResult::Failure(reason.into())
+    }
+
+    /// Returns `true` if the operation succeeded.
+    pub fn is_success(&self) -> bool {
+        matches!(self, OperationResult::Success)
+    }
+
+    /// Returns a reason the operation did not succeed, if any.
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            OperationResult::Success => None,
+            OperationResult::Failure(reason) => Some(reason),
+            OperationResult::Canceled => Some("canceled"),
+            OperationResult::Timeout => Some("timeout"),
+        }
+    }
+
+    /// Records the reason, if any, as a custom property and returns the success flag.
+    pub(crate) fn apply(&self, properties: &mut Properties) -> bool {
+        match self.reason() {
+            Some(reason) => {
+                properties.insert(RESULT_REASON.into(), reason.into());
+            }
+            None => {
+                properties.remove(RESULT_REASON);
+            }
+        }
+        self.is_success()
+    }
+}
+
+impl<T, E: fmt::Display> From<&Result<T, E>> for OperationResult {
+    fn from(result: &Result<T, E>) -> Self {
+        match result {
+            Ok(_) => OperationResult::Success,
+            Err(err) => OperationResult::Failure(err.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for OperationResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason() {
+            Some(reason) => write!(f, "{}", reason),
+            None => write!(f, "success"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(OperationResult::Success, true, None; "success")]
+    #[test_case(OperationResult::failure("not found"), false, Some("not found"); "failure")]
+    #[test_case(OperationResult::Canceled, false, Some("canceled"); "canceled")]
+    #[test_case(OperationResult::Timeout, false, Some("timeout"); "timeout")]
+    fn it_records_reason(result: OperationResult, success: bool, reason: Option<&str>) {
+        let mut properties = Properties::default();
+        properties.insert(RESULT_REASON.into(), "previous".into());
+
+        assert_eq!(result.apply(&mut properties), success);
+        assert_eq!(properties.get(RESULT_REASON).map(String::as_str), reason);
+    }
+
+    #[test]
+    fn it_converts_result() {
+        let result: Result<(), String> = Err("connection reset".into());
+        assert_eq!(
+            OperationResult::from(&result),
+            OperationResult::failure("connection reset")
+        );
+    }
+}
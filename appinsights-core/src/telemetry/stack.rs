@@ -0,0 +1,170 @@
+use crate::contracts;
+
+/// A frame of a call stack an error occurred at. Frames are submitted as a structured stack trace, so the
+/// portal shows methods, source files and lines of an exception instead of a single block of text.
+///
+/// # Examples
+/// ```rust
+/// use appinsights_core::telemetry::StackFrame;
+///
+/// let frames = StackFrame::parse_backtrace(
+///     "   0: app::orders::load\n             at ./src/orders.rs:42:5\n   1: main\n",
+/// );
+///
+/// assert_eq!(frames[0].method(), "app::orders::load");
+/// assert_eq!(frames[0].assembly(), Some("app"));
+/// assert_eq!(frames[0].file_name(), Some("./src/orders.rs"));
+/// assert_eq!(frames[0].line(), Some(42));
+/// assert_eq!(frames[1].method(), "main");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    method: String,
+    assembly: Option<String>,
+    file_name: Option<String>,
+    line: Option<u32>,
+}
+
+impl StackFrame {
+    /// Creates a frame of the given method. A crate the method belongs to is derived from its path, if any.
+    pub fn new(method: impl Into<String>) -> Self {
+        let method = method.into();
+        Self {
+            assembly: crate_name(&method).map(Into::into),
+            method,
+            file_name: None,
+            line: None,
+        }
+    }
+
+    /// Parses frames of a backtrace formatted by [`std::backtrace::Backtrace`], outermost frame last. Lines
+    /// that do not describe frames, e.g. notes, are skipped.
+    pub fn parse_backtrace(backtrace: &str) -> Vec<StackFrame> {
+        let mut frames: Vec<StackFrame> = Vec::new();
+        for line in backtrace.lines().map(str::trim) {
+            if let Some(location) = line.strip_prefix("at ") {
+                if let Some(frame) = frames.last_mut().filter(|frame| frame.file_name.is_none()) {
+                    let (file_name, line) = parse_location(location);
+                    frame.file_name = Some(file_name.into());
+                    frame.line = line;
+                }
+            } else if let Some((index, method)) = line.split_once(": ") {
+                if !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()) {
+                    frames.push(StackFrame::new(method));
+                }
+            }
+        }
+        frames
+    }
+
+    /// Returns a name of the method.
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// Returns a name of the crate the method belongs to, if known.
+    pub fn assembly(&self) -> Option<&str> {
+        self.assembly.as_deref()
+    }
+
+    /// Sets a name of the crate the method belongs to.
+    pub fn set_assembly(&mut self, assembly: impl Into<String>) {
+        self.assembly = Some(assembly.into());
+    }
+
+    /// Returns a source file of the method, if known.
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    /// Returns a line in the source file, if known.
+    pub fn line(&self) -> Option<u32> {
+        self.line
+    }
+
+    /// Sets a source file and a line in it.
+    pub fn set_location(&mut self, file_name: impl Into<String>, line: Option<u32>) {
+        self.file_name = Some(file_name.into());
+        self.line = line;
+    }
+
+    /// Converts the frame into a contract at the given level, which is 0 for the innermost frame.
+    pub(crate) fn into_contract(self, level: usize) -> contracts::StackFrame {
+        contracts::StackFrame {
+            level: level as i32,
+            method: self.method,
+            assembly: self.assembly,
+            file_name: self.file_name,
+            line: self.line.map(|line| line as i32),
+        }
+    }
+}
+
+/// Returns a name of the crate a method path starts with, e.g. `alloc` for
+/// `<alloc::boxed::Box<F> as core::ops::Fn<A>>::call`.
+fn crate_name(method: &str) -> Option<&str> {
+    let path = method.trim_start_matches('<');
+    let len = path
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(path.len());
+    if len > 0 && path[len..].starts_with("::") {
+        Some(&path[..len])
+    } else {
+        None
+    }
+}
+
+/// Splits a location such as `./src/main.rs:42:5` into a file name and a line.
+fn parse_location(location: &str) -> (&str, Option<u32>) {
+    let mut parts = location.rsplitn(3, ':');
+    let column = parts.next();
+    let line = parts.next();
+    match (parts.next(), line, column) {
+        (Some(file_name), Some(line), Some(column)) if column.parse::<u32>().is_ok() => (file_name, line.parse().ok()),
+        _ => match location.rsplit_once(':') {
+            Some((file_name, line)) if line.parse::<u32>().is_ok() => (file_name, line.parse().ok()),
+            _ => (location, None),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn it_parses_backtrace() {
+        let backtrace = "   0: std::backtrace::Backtrace::force_capture
+             at /rustc/library/std/src/backtrace.rs:331:13
+   1: <alloc::boxed::Box<F,A> as core::ops::function::Fn<Args>>::call
+             at /rustc/library/alloc/src/boxed.rs:2029:9
+             at /rustc/library/alloc/src/boxed.rs:1000:1
+   2: main
+   3: <unknown>
+note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.";
+
+        let frames = StackFrame::parse_backtrace(backtrace);
+
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[0].method(), "std::backtrace::Backtrace::force_capture");
+        assert_eq!(frames[0].assembly(), Some("std"));
+        assert_eq!(frames[0].file_name(), Some("/rustc/library/std/src/backtrace.rs"));
+        assert_eq!(frames[0].line(), Some(331));
+        assert_eq!(frames[1].assembly(), Some("alloc"));
+        assert_eq!(frames[1].file_name(), Some("/rustc/library/alloc/src/boxed.rs"));
+        assert_eq!(frames[1].line(), Some(2029));
+        assert_eq!(frames[2].assembly(), None);
+        assert_eq!(frames[2].file_name(), None);
+        assert_eq!(frames[3].method(), "<unknown>");
+    }
+
+    #[test_case("./src/main.rs:42:5", "./src/main.rs", Some(42); "line and column")]
+    #[test_case("./src/main.rs:42", "./src/main.rs", Some(42); "line")]
+    #[test_case("C:\\src\\main.rs:42:5", "C:\\src\\main.rs", Some(42); "windows path")]
+    #[test_case("./src/main.rs", "./src/main.rs", None; "file only")]
+    fn it_parses_location(location: &str, file_name: &str, line: Option<u32>) {
+        assert_eq!(parse_location(location), (file_name, line));
+    }
+}
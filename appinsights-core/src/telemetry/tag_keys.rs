@@ -0,0 +1,99 @@
+//! Keys of well-known context tags defined by the Application Insights schema.
+//!
+//! Tag helpers of [`ContextTags`](../struct.ContextTags.html) cover the same keys. The constants are useful
+//! when tags are read or written generically, e.g. by telemetry processors and custom enrichers.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use appinsights::telemetry::{tag_keys, ContextTags};
+//!
+//! let mut tags = ContextTags::default();
+//! tags.insert(tag_keys::CLOUD_ROLE.into(), "rust_server".into());
+//!
+//! assert_eq!(tags.cloud().role(), Some("rust_server"));
+//! ```
+
+/// Application version.
+pub const APPLICATION_VERSION: &str = "ai.application.ver";
+
+/// Unique client device id.
+pub const DEVICE_ID: &str = "ai.device.id";
+
+/// Device locale using <language>-<REGION> pattern.
+pub const DEVICE_LOCALE: &str = "ai.device.locale";
+
+/// Model of the device the end user of the application is using.
+pub const DEVICE_MODEL: &str = "ai.device.model";
+
+/// Client device OEM name.
+pub const DEVICE_OEM_NAME: &str = "ai.device.oemName";
+
+/// Operating system name and version of the device.
+pub const DEVICE_OS_VERSION: &str = "ai.device.osVersion";
+
+/// The type of the device the end user of the application is using.
+pub const DEVICE_TYPE: &str = "ai.device.type";
+
+/// The IP address of the client device.
+pub const LOCATION_IP: &str = "ai.location.ip";
+
+/// The country of the client device.
+pub const LOCATION_COUNTRY: &str = "ai.location.country";
+
+/// The province/state of the client device.
+pub const LOCATION_PROVINCE: &str = "ai.location.province";
+
+/// The city of the client device.
+pub const LOCATION_CITY: &str = "ai.location.city";
+
+/// A unique identifier for the operation instance.
+pub const OPERATION_ID: &str = "ai.operation.id";
+
+/// The name (group) of the operation.
+pub const OPERATION_NAME: &str = "ai.operation.name";
+
+/// The unique identifier of the telemetry item's immediate parent.
+pub const OPERATION_PARENT_ID: &str = "ai.operation.parentId";
+
+/// Name of synthetic source.
+pub const OPERATION_SYNTHETIC_SOURCE: &str = "ai.operation.syntheticSource";
+
+/// The correlation vector of the operation.
+pub const OPERATION_CORRELATION_VECTOR: &str = "ai.operation.correlationVector";
+
+/// Session ID - the instance of the user's interaction with the app.
+pub const SESSION_ID: &str = "ai.session.id";
+
+/// Whether the session is first for the user.
+pub const SESSION_IS_FIRST: &str = "ai.session.isFirst";
+
+/// The account ID or name which the user is acting with.
+pub const USER_ACCOUNT_ID: &str = "ai.user.accountId";
+
+/// Anonymous user id.
+pub const USER_ID: &str = "ai.user.id";
+
+/// Authenticated user id.
+pub const USER_AUTH_USER_ID: &str = "ai.user.authUserId";
+
+/// Name of the role the application is a part of.
+pub const CLOUD_ROLE: &str = "ai.cloud.role";
+
+/// Version of the role the application is a part of.
+pub const CLOUD_ROLE_VER: &str = "ai.cloud.roleVer";
+
+/// Name of the instance where the application is running.
+pub const CLOUD_ROLE_INSTANCE: &str = "ai.cloud.roleInstance";
+
+/// Location of the role the application is a part of.
+pub const CLOUD_LOCATION: &str = "ai.cloud.location";
+
+/// SDK version.
+pub const INTERNAL_SDK_VERSION: &str = "ai.internal.sdkVersion";
+
+/// Agent version.
+pub const INTERNAL_AGENT_VERSION: &str = "ai.internal.agentVersion";
+
+/// The node name used for billing purposes.
+pub const INTERNAL_NODE_NAME: &str = "ai.internal.nodeName";
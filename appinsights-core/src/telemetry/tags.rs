@@ -3,6 +3,10 @@ use std::{
     ops::{Deref, DerefMut},
 };
 
+use log::warn;
+
+use crate::telemetry::tag_keys;
+
 /// Contains all tags for telemetry to submit.
 #[derive(Debug, Clone, Default)]
 pub struct ContextTags(BTreeMap<String, String>);
@@ -36,10 +40,24 @@ impl DerefMut for ContextTags {
     }
 }
 
-/// Macros to generate well-known context tags.
+/// Truncates a tag value that exceeds the maximum length the schema defines for the tag, since the ingestion
+/// endpoint rejects the whole telemetry item otherwise. The length is measured in characters.
+fn truncate(key: &str, mut value: String, max_length: usize) -> String {
+    if let Some((index, _)) = value.char_indices().nth(max_length) {
+        warn!(
+            "Value of tag {} exceeds maximum length of {} characters and has been truncated",
+            key, max_length
+        );
+        value.truncate(index);
+    }
+    value
+}
+
+/// Macros to generate well-known context tags. Each tag is declared with its key and maximum length of its
+/// value in characters, longer values are truncated by setters.
 #[macro_export]
 macro_rules! tags {
-    ( $(#[$attr_factory:meta])* $factory:ident, $(#[$attr:meta])* $name:ident { $( $(#[$attr_method:meta])* $method:ident : $key:expr),* } ) => {
+    ( $(#[$attr_factory:meta])* $factory:ident, $(#[$attr:meta])* $name:ident { $( $(#[$attr_method:meta])* $method:ident : $key:expr => $max_length:expr),* } ) => {
         impl ContextTags{
             $(#[$attr_factory])*
             pub fn $factory(&self) -> $name<'_> {
@@ -87,7 +105,7 @@ macro_rules! tags {
                 $(
                     $(#[$attr_method])*
                     pub fn [<set_ $method>](&mut self, value: String) {
-                        self.items.insert($key.into(), value);
+                        self.items.insert($key.into(), truncate($key, value, $max_length));
                     }
                 )*
             }
@@ -101,7 +119,7 @@ tags!(
     /// Tag helper type that provides access to context fields grouped under 'location'.
     ApplicationTags {
         /// Application version. Information in the application context fields is always about the application that is sending the telemetry.
-        version: "ai.application.ver"
+        version: tag_keys::APPLICATION_VERSION => 1024
     }
 );
 
@@ -111,17 +129,17 @@ tags!(
     /// Tag helper type that provides access to context fields grouped under 'device'.
     DeviceTags {
         /// Unique client device id. Computer name in most cases.
-        id: "ai.device.id",
+        id: tag_keys::DEVICE_ID => 1024,
         /// Device locale using <language>-<REGION> pattern, following RFC 5646. Example 'en-US'.
-        locale: "ai.device.locale",
+        locale: tag_keys::DEVICE_LOCALE => 64,
         /// Model of the device the end user of the application is using. Used for client scenarios. If this field is empty then it is derived from the user agent.
-        model: "ai.device.model",
+        model: tag_keys::DEVICE_MODEL => 256,
         /// Client device OEM name taken from the browser.
-        oem_name: "ai.device.oemName",
+        oem_name: tag_keys::DEVICE_OEM_NAME => 256,
         /// Operating system name and version of the device the end user of the application is using. If this field is empty then it is derived from the user agent. Example 'Windows 10 Pro 10.0.10586.0'
-        os_version: "ai.device.osVersion",
+        os_version: tag_keys::DEVICE_OS_VERSION => 256,
         /// The type of the device the end user of the application is using. Used primarily to distinguish JavaScript telemetry from server side telemetry. Examples: 'PC', 'Phone', 'Browser'. 'PC' is the default value.
-        r#type: "ai.device.type"
+        r#type: tag_keys::DEVICE_TYPE => 64
     }
 );
 
@@ -131,13 +149,13 @@ tags!(
     /// Tag helper type that provides access to context fields grouped under 'location'.
     LocationTags {
         /// The IP address of the client device. IPv4 and IPv6 are supported. Information in the location context fields is always about the end user. When telemetry is sent from a service, the location context is about the user that initiated the operation in the service.
-        ip: "ai.location.ip",
+        ip: tag_keys::LOCATION_IP => 46,
         /// The country of the client device. If any of Country, Province, or City is specified, those values will be preferred over geolocation of the IP address field. Information in the location context fields is always about the end user. When telemetry is sent from a service, the location context is about the user that initiated the operation in the service.
-        country: "ai.location.country",
+        country: tag_keys::LOCATION_COUNTRY => 256,
         /// The province/state of the client device. If any of Country, Province, or City is specified, those values will be preferred over geolocation of the IP address field. Information in the location context fields is always about the end user. When telemetry is sent from a service, the location context is about the user that initiated the operation in the service.
-        province: "ai.location.province",
+        province: tag_keys::LOCATION_PROVINCE => 256,
         /// The city of the client device. If any of Country, Province, or City is specified, those values will be preferred over geolocation of the IP address field. Information in the location context fields is always about the end user. When telemetry is sent from a service, the location context is about the user that initiated the operation in the service.
-        city: "ai.location.city"
+        city: tag_keys::LOCATION_CITY => 256
     }
 );
 
@@ -147,15 +165,15 @@ tags!(
     /// Tag helper type that provides access to context fields grouped under 'operation'.
     OperationTags {
         /// A unique identifier for the operation instance. The operation.id is created by either a request or a page view. All other telemetry sets this to the value for the containing request or page view. Operation.id is used for finding all the telemetry items for a specific operation instance.
-        id: "ai.operation.id",
+        id: tag_keys::OPERATION_ID => 128,
         /// The name (group) of the operation. The operation.name is created by either a request or a page view. All other telemetry items set this to the value for the containing request or page view. Operation.name is used for finding all the telemetry items for a group of operations (i.e. 'GET Home/Index').
-        name: "ai.operation.name",
+        name: tag_keys::OPERATION_NAME => 1024,
         /// The unique identifier of the telemetry item's immediate parent.
-        parent_id: "ai.operation.parentId",
+        parent_id: tag_keys::OPERATION_PARENT_ID => 128,
         /// Name of synthetic source. Some telemetry from the application may represent a synthetic traffic. It may be web crawler indexing the web site, site availability tests or traces from diagnostic libraries like Application Insights SDK itself.
-        synthetic_source: "ai.operation.syntheticSource",
+        synthetic_source: tag_keys::OPERATION_SYNTHETIC_SOURCE => 1024,
         /// The correlation vector is a light weight vector clock which can be used to identify and order related events across clients and services.
-        correlation_vector: "ai.operation.correlationVector"
+        correlation_vector: tag_keys::OPERATION_CORRELATION_VECTOR => 64
     }
 );
 
@@ -165,9 +183,9 @@ tags!(
     /// Tag helper type that provides access to context fields grouped under 'session'.
     SessionTags {
         /// Session ID - the instance of the user's interaction with the app. Information in the session context fields is always about the end user. When telemetry is sent from a service, the session context is about the user that initiated the operation in the service.
-        id: "ai.session.id",
+        id: tag_keys::SESSION_ID => 64,
         /// Boolean value indicating whether the session identified by ai.session.id is first for the user or not.
-        is_first: "ai.session.isFirst"
+        is_first: tag_keys::SESSION_IS_FIRST => 5
     }
 );
 
@@ -177,11 +195,11 @@ tags!(
     /// Tag helper type that provides access to context fields grouped under 'user'.
     UserTags {
         /// In multi-tenant applications this is the account ID or name which the user is acting with. Examples may be subscription ID for Azure portal or blog name blogging platform.
-        account_id: "ai.user.accountId",
+        account_id: tag_keys::USER_ACCOUNT_ID => 1024,
         /// Anonymous user id. Represents the end user of the application. When telemetry is sent from a service, the user context is about the user that initiated the operation in the service.
-        id: "ai.user.id",
+        id: tag_keys::USER_ID => 128,
         /// Authenticated user id. The opposite of ai.user.id, this represents the user with a friendly name. Since it's PII information it is not collected by default by most SDKs.
-        auth_user_id: "ai.user.authUserId"
+        auth_user_id: tag_keys::USER_AUTH_USER_ID => 1024
     }
 );
 
@@ -191,13 +209,13 @@ tags!(
     /// Tag helper type that provides access to context fields grouped under 'cloud'.
     CloudTags {
         /// Name of the role the application is a part of. Maps directly to the role name in azure.
-        role: "ai.cloud.role",
+        role: tag_keys::CLOUD_ROLE => 256,
         /// Version of the role the application is a part of.
-        role_ver: "ai.cloud.roleVer",
+        role_ver: tag_keys::CLOUD_ROLE_VER => 256,
         /// Name of the instance where the application is running. Computer name for on-premisis, instance name for Azure.
-        role_instance: "ai.cloud.roleInstance",
+        role_instance: tag_keys::CLOUD_ROLE_INSTANCE => 256,
         /// Location of the role the application is a part of.
-        location: "ai.cloud.location"
+        location: tag_keys::CLOUD_LOCATION => 256
     }
 );
 
@@ -207,11 +225,11 @@ tags!(
     /// Tag helper type that provides access to context fields grouped under 'internal'.
     InternalTags {
         /// SDK version. See `https://github.com/Microsoft/ApplicationInsights-Home/blob/master/SDK-AUTHORING.md#sdk-version-specification` for information.
-        sdk_version: "ai.internal.sdkVersion",
+        sdk_version: tag_keys::INTERNAL_SDK_VERSION => 64,
         /// Agent version. Used to indicate the version of StatusMonitor installed on the computer if it is used for data collection.
-        agent_version: "ai.internal.agentVersion",
+        agent_version: tag_keys::INTERNAL_AGENT_VERSION => 64,
         /// This is the node name used for billing purposes. Use it to override the standard detection of nodes.
-        node_name: "ai.internal.nodeName"
+        node_name: tag_keys::INTERNAL_NODE_NAME => 256
     }
 );
 
@@ -243,15 +261,44 @@ mod tests {
         assert_eq!(example.bar(), Some("bar"));
     }
 
+    #[test]
+    fn it_truncates_tag_exceeding_max_length() {
+        let mut tags = ContextTags::default();
+
+        tags.example_mut().set_foo("föobar".into());
+
+        assert_eq!(tags.example().foo(), Some("föo"));
+    }
+
+    #[test]
+    fn it_truncates_tag_by_schema_max_length() {
+        let mut tags = ContextTags::default();
+
+        tags.session_mut().set_id("s".repeat(100));
+
+        assert_eq!(tags.session().id().map(str::len), Some(64));
+    }
+
+    #[test]
+    fn it_stores_tags_by_schema_keys() {
+        let mut tags = ContextTags::default();
+
+        tags.cloud_mut().set_role("rust_server".into());
+        tags.operation_mut().set_parent_id("parent".into());
+
+        assert_eq!(tags.get(tag_keys::CLOUD_ROLE), Some(&"rust_server".to_string()));
+        assert_eq!(tags.get(tag_keys::OPERATION_PARENT_ID), Some(&"parent".to_string()));
+    }
+
     tags!(
         /// Returns example wrapper
         example,
         /// Example tags
         ExampleTags {
             /// foo
-            foo: "foo",
+            foo: "foo" => 3,
             /// bar
-            bar: "bar"
+            bar: "bar" => 1024
         }
     );
 }
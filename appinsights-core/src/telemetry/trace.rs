@@ -1,10 +1,8 @@
-use chrono::{DateTime, SecondsFormat, Utc};
-
 use crate::{
     context::TelemetryContext,
     contracts::{SeverityLevel as ContractsSeverityLevel, *},
     telemetry::{ContextTags, Measurements, Properties, Telemetry},
-    time,
+    time::Timestamp,
 };
 
 /// Represents printf-like trace statements that can be text searched. A trace telemetry items have
@@ -36,7 +34,7 @@ pub struct TraceTelemetry {
     severity: SeverityLevel,
 
     /// The time stamp when this telemetry was measured.
-    timestamp: DateTime<Utc>,
+    timestamp: Timestamp,
 
     /// Custom properties.
     properties: Properties,
@@ -49,18 +47,24 @@ pub struct TraceTelemetry {
 }
 
 impl TraceTelemetry {
-    /// Creates an event telemetry item with specified name.
-    pub fn new(message: impl Into<String>, severity: SeverityLevel) -> Self {
+    /// Creates a trace telemetry item with specified message and a severity of any level that converts into
+    /// [`SeverityLevel`](enum.SeverityLevel.html).
+    pub fn new(message: impl Into<String>, severity: impl ToSeverityLevel) -> Self {
         Self {
             message: message.into(),
-            severity,
-            timestamp: time::now(),
+            severity: severity.to_severity_level(),
+            timestamp: Timestamp::now(),
             properties: Properties::default(),
             tags: ContextTags::default(),
             measurements: Measurements::default(),
         }
     }
 
+    /// Returns severity level of the trace.
+    pub fn severity(&self) -> SeverityLevel {
+        self.severity
+    }
+
     /// Returns custom measurements to submit with the telemetry item.
     pub fn measurements(&self) -> &Measurements {
         &self.measurements
@@ -74,7 +78,7 @@ impl TraceTelemetry {
 
 impl Telemetry for TraceTelemetry {
     /// Returns the time when this telemetry was measured.
-    fn timestamp(&self) -> DateTime<Utc> {
+    fn timestamp(&self) -> Timestamp {
         self.timestamp
     }
 
@@ -103,13 +107,13 @@ impl From<(TelemetryContext, TraceTelemetry)> for Envelope {
     fn from((context, telemetry): (TelemetryContext, TraceTelemetry)) -> Self {
         Self {
             name: "Microsoft.ApplicationInsights.Message".into(),
-            time: telemetry.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
-            i_key: Some(context.i_key),
-            tags: Some(ContextTags::combine(context.tags, telemetry.tags).into()),
+            time: telemetry.timestamp.to_string(),
+            i_key: Some(context.i_key.to_string()),
+            tags: Some(context.combine_tags(telemetry.tags).into()),
             data: Some(Base::Data(Data::MessageData(MessageData {
                 message: telemetry.message,
                 severity_level: Some(telemetry.severity.into()),
-                properties: Some(Properties::combine(context.properties, telemetry.properties).into()),
+                properties: Some(context.combine_properties(telemetry.properties).into()),
                 measurements: Some(telemetry.measurements.into()),
                 ..MessageData::default()
             }))),
@@ -119,7 +123,7 @@ impl From<(TelemetryContext, TraceTelemetry)> for Envelope {
 }
 
 /// Defines the level of severity for the event.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SeverityLevel {
     /// Verbose severity level.
     Verbose,
@@ -137,6 +141,68 @@ pub enum SeverityLevel {
     Critical,
 }
 
+/// Converts a level of a logging framework into a [`SeverityLevel`](enum.SeverityLevel.html), so telemetry
+/// items reported via `log`, `tracing` or application-specific levels get the same severity everywhere.
+/// Conversions of `log::Level` and, with the `tracing` feature, `tracing::Level` are provided. Levels below
+/// information, i.e. debug and trace, map to [`SeverityLevel::Verbose`](enum.SeverityLevel.html#variant.Verbose).
+///
+/// # Examples
+/// ```rust
+/// use appinsights::telemetry::{SeverityLevel, ToSeverityLevel, TraceTelemetry};
+///
+/// enum Priority {
+///     Low,
+///     High,
+/// }
+///
+/// impl ToSeverityLevel for Priority {
+///     fn to_severity_level(&self) -> SeverityLevel {
+///         match self {
+///             Priority::Low => SeverityLevel::Information,
+///             Priority::High => SeverityLevel::Critical,
+///         }
+///     }
+/// }
+///
+/// assert_eq!(log::Level::Warn.to_severity_level(), SeverityLevel::Warning);
+///
+/// let trace = TraceTelemetry::new("disk is almost full", Priority::High);
+/// assert_eq!(trace.severity(), SeverityLevel::Critical);
+/// ```
+pub trait ToSeverityLevel {
+    /// Returns a severity level that corresponds to the level.
+    fn to_severity_level(&self) -> SeverityLevel;
+}
+
+impl ToSeverityLevel for SeverityLevel {
+    fn to_severity_level(&self) -> SeverityLevel {
+        *self
+    }
+}
+
+impl ToSeverityLevel for log::Level {
+    fn to_severity_level(&self) -> SeverityLevel {
+        match self {
+            log::Level::Error => SeverityLevel::Error,
+            log::Level::Warn => SeverityLevel::Warning,
+            log::Level::Info => SeverityLevel::Information,
+            log::Level::Debug | log::Level::Trace => SeverityLevel::Verbose,
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl ToSeverityLevel for tracing::Level {
+    fn to_severity_level(&self) -> SeverityLevel {
+        match *self {
+            tracing::Level::ERROR => SeverityLevel::Error,
+            tracing::Level::WARN => SeverityLevel::Warning,
+            tracing::Level::INFO => SeverityLevel::Information,
+            _ => SeverityLevel::Verbose,
+        }
+    }
+}
+
 impl From<SeverityLevel> for ContractsSeverityLevel {
     fn from(severity: SeverityLevel) -> Self {
         match severity {
@@ -154,8 +220,9 @@ mod tests {
     use std::collections::BTreeMap;
 
     use chrono::{TimeZone, Utc};
+    use test_case::test_case;
 
-    use super::{SeverityLevel, TraceTelemetry};
+    use super::{SeverityLevel, ToSeverityLevel, TraceTelemetry};
     use crate::{
         contracts::{Base, Data, Envelope, MessageData},
         telemetry::{ContextTags, Properties, Telemetry},
@@ -240,4 +307,24 @@ mod tests {
 
         assert_eq!(envelop, expected)
     }
+
+    #[test_case(log::Level::Error, SeverityLevel::Error; "error")]
+    #[test_case(log::Level::Warn, SeverityLevel::Warning; "warn")]
+    #[test_case(log::Level::Info, SeverityLevel::Information; "info")]
+    #[test_case(log::Level::Debug, SeverityLevel::Verbose; "debug")]
+    #[test_case(log::Level::Trace, SeverityLevel::Verbose; "trace")]
+    fn it_maps_log_level(level: log::Level, expected: SeverityLevel) {
+        assert_eq!(level.to_severity_level(), expected);
+        assert_eq!(TraceTelemetry::new("message", level).severity(), expected);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test_case(tracing::Level::ERROR, SeverityLevel::Error; "error")]
+    #[test_case(tracing::Level::WARN, SeverityLevel::Warning; "warn")]
+    #[test_case(tracing::Level::INFO, SeverityLevel::Information; "info")]
+    #[test_case(tracing::Level::DEBUG, SeverityLevel::Verbose; "debug")]
+    #[test_case(tracing::Level::TRACE, SeverityLevel::Verbose; "trace")]
+    fn it_maps_tracing_level(level: tracing::Level, expected: SeverityLevel) {
+        assert_eq!(level.to_severity_level(), expected);
+    }
 }
@@ -0,0 +1,279 @@
+#[cfg(any(test, feature = "chrono"))]
+pub use imp::now;
+#[cfg(test)]
+pub use imp::{reset, set};
+
+use std::{
+    fmt::{Display, Formatter},
+    ops::Deref,
+    time::{Duration as StdDuration, SystemTime, UNIX_EPOCH},
+};
+
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+#[cfg(feature = "time")]
+use std::convert::TryFrom;
+
+use imp::system_now;
+
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+const SECS_PER_DAY: i64 = 86_400;
+
+#[cfg(not(test))]
+mod imp {
+    use std::time::SystemTime;
+
+    /// Returns a DateTime which corresponds to a current date.
+    #[cfg(feature = "chrono")]
+    pub fn now() -> chrono::DateTime<chrono::Utc> {
+        chrono::Utc::now()
+    }
+
+    /// Returns the current system time.
+    pub(crate) fn system_now() -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+#[cfg(test)]
+mod imp {
+    use std::{cell::RefCell, time::SystemTime};
+
+    use chrono::{DateTime, Utc};
+
+    thread_local!(static NOW: RefCell<Option<SystemTime>> = const { RefCell::new(None) });
+
+    /// Returns a DateTime which corresponds to a current date or the value user set in advance.
+    pub fn now() -> DateTime<Utc> {
+        system_now().into()
+    }
+
+    /// Returns the current system time or the value user set in advance.
+    pub(crate) fn system_now() -> SystemTime {
+        NOW.with(|ts| ts.borrow().unwrap_or_else(SystemTime::now))
+    }
+
+    /// Sets known DateTime value as now to assert test against it.
+    pub fn set(now: DateTime<Utc>) {
+        NOW.with(|ts| *ts.borrow_mut() = Some(now.into()))
+    }
+
+    /// Resets pre-defined DateTime value to use Utc::now() instead.
+    pub fn reset() {
+        NOW.with(|ts| *ts.borrow_mut() = None)
+    }
+}
+
+/// A point in time when telemetry was measured.
+///
+/// It can be converted from and into [`SystemTime`]. With the default `chrono` feature enabled it can also be
+/// converted from and into `chrono::DateTime<Utc>`, and with the `time` feature enabled from and into
+/// `time::OffsetDateTime`, so applications are free to use any of these crates to work with time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(SystemTime);
+
+impl Timestamp {
+    /// Returns a timestamp of the current moment.
+    pub fn now() -> Self {
+        Timestamp(system_now())
+    }
+
+    /// Returns whole seconds and nanoseconds since the Unix epoch. Seconds are negative for earlier moments,
+    /// while nanoseconds always count forward.
+    fn unix(&self) -> (i64, u32) {
+        match self.0.duration_since(UNIX_EPOCH) {
+            Ok(since) => (since.as_secs() as i64, since.subsec_nanos()),
+            Err(err) => {
+                let before = err.duration();
+                match before.subsec_nanos() {
+                    0 => (-(before.as_secs() as i64), 0),
+                    nanos => (-(before.as_secs() as i64) - 1, NANOS_PER_SEC - nanos),
+                }
+            }
+        }
+    }
+}
+
+impl Display for Timestamp {
+    /// Formats the timestamp in RFC 3339 format in UTC with milliseconds, e.g. `2019-01-02T03:04:05.600Z`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (secs, nanos) = self.unix();
+        let (year, month, day) = civil_from_days(secs.div_euclid(SECS_PER_DAY));
+        let secs_of_day = secs.rem_euclid(SECS_PER_DAY);
+
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            year,
+            month,
+            day,
+            secs_of_day / 3600,
+            secs_of_day / 60 % 60,
+            secs_of_day % 60,
+            nanos / 1_000_000
+        )
+    }
+}
+
+/// Converts a number of days since the Unix epoch into a year, a month and a day of the proleptic Gregorian
+/// calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(timestamp: SystemTime) -> Self {
+        Timestamp(timestamp)
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(timestamp: Timestamp) -> Self {
+        timestamp.0
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<DateTime<Utc>> for Timestamp {
+    fn from(timestamp: DateTime<Utc>) -> Self {
+        Timestamp(timestamp.into())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<Timestamp> for DateTime<Utc> {
+    fn from(timestamp: Timestamp) -> Self {
+        timestamp.0.into()
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<::time::OffsetDateTime> for Timestamp {
+    fn from(timestamp: ::time::OffsetDateTime) -> Self {
+        let secs = timestamp.unix_timestamp();
+        let since_epoch = StdDuration::new(secs.unsigned_abs(), 0);
+        let seconds = if secs >= 0 {
+            UNIX_EPOCH + since_epoch
+        } else {
+            UNIX_EPOCH - since_epoch
+        };
+        Timestamp(seconds + StdDuration::from_nanos(timestamp.nanosecond().into()))
+    }
+}
+
+/// Converts a timestamp into `time::OffsetDateTime`. Fails if the timestamp is out of the range
+/// `time::OffsetDateTime` supports.
+#[cfg(feature = "time")]
+impl TryFrom<Timestamp> for ::time::OffsetDateTime {
+    type Error = ::time::error::ComponentRange;
+
+    fn try_from(timestamp: Timestamp) -> Result<Self, Self::Error> {
+        let (secs, nanos) = timestamp.unix();
+        ::time::OffsetDateTime::from_unix_timestamp_nanos(
+            i128::from(secs) * i128::from(NANOS_PER_SEC) + i128::from(nanos),
+        )
+    }
+}
+
+/// Provides dotnet duration aware formatting rules.
+#[derive(Debug)]
+pub struct Duration(StdDuration);
+
+impl From<StdDuration> for Duration {
+    fn from(duration: StdDuration) -> Self {
+        Duration(duration)
+    }
+}
+
+impl Display for Duration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let nanoseconds = self.0.as_nanos();
+        let ticks = nanoseconds / 100 % 10_000_000;
+        let total_seconds = nanoseconds / 1_000_000_000;
+        let seconds = total_seconds % 60;
+        let minutes = total_seconds / 60 % 60;
+        let hours = total_seconds / 3600 % 24;
+        let days = total_seconds / 86400;
+
+        write!(
+            f,
+            "{}.{:0>2}:{:0>2}:{:0>2}.{:0>7}",
+            days, hours, minutes, seconds, ticks
+        )
+    }
+}
+
+impl Deref for Duration {
+    type Target = StdDuration;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn it_formats_timestamp_with_milliseconds() {
+        let timestamp = Timestamp::from(SystemTime::from(Utc.ymd(2019, 1, 2).and_hms_nano(3, 4, 5, 600_700_800)));
+
+        assert_eq!(timestamp.to_string(), "2019-01-02T03:04:05.600Z");
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn it_converts_timestamp_from_and_into_time_crate() {
+        let expected = ::time::OffsetDateTime::from_unix_timestamp_nanos(1_546_398_245_600_700_800).unwrap();
+
+        let timestamp = Timestamp::from(expected);
+        assert_eq!(
+            SystemTime::from(timestamp),
+            Utc.ymd(2019, 1, 2).and_hms_nano(3, 4, 5, 600_700_800).into()
+        );
+
+        let actual = ::time::OffsetDateTime::try_from(timestamp).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn it_formats_timestamp_before_unix_epoch() {
+        let timestamp = Timestamp::from(SystemTime::from(Utc.ymd(1969, 12, 31).and_hms_milli(23, 59, 59, 250)));
+
+        assert_eq!(timestamp.to_string(), "1969-12-31T23:59:59.250Z");
+    }
+
+    #[test]
+    fn it_formats_timestamp_of_leap_day() {
+        let timestamp = Timestamp::from(SystemTime::from(Utc.ymd(2024, 2, 29).and_hms(12, 0, 0)));
+
+        assert_eq!(timestamp.to_string(), "2024-02-29T12:00:00.000Z");
+    }
+
+    #[test_case(StdDuration::from_secs(3600).into(),  "0.01:00:00.0000000"    ; "hour")]
+    #[test_case(StdDuration::from_secs(60).into(),    "0.00:01:00.0000000"    ; "minute")]
+    #[test_case(StdDuration::from_secs(1).into(),     "0.00:00:01.0000000"    ; "second")]
+    #[test_case(StdDuration::from_millis(1).into(),   "0.00:00:00.0010000"    ; "millisecond")]
+    #[test_case(StdDuration::from_nanos(100).into(),  "0.00:00:00.0000001"    ; "tick")]
+    #[test_case((Utc.ymd(2019, 1, 3).and_hms(1, 2, 3) - Utc.ymd(2019, 1, 1).and_hms(0, 0, 0)).to_std().unwrap().into(), "2.01:02:03.0000000"    ; "custom")]
+    fn it_converts_duration_to_string(duration: Duration, expected: &'static str) {
+        assert_eq!(duration.to_string(), expected.to_string());
+    }
+}
@@ -0,0 +1,11 @@
+use crate::{contracts::Envelope, telemetry::Telemetry, TelemetryContext};
+
+/// Submits telemetry items. Telemetry clients of the `appinsights` crate implement this trait, so
+/// libraries can track telemetry without depending on the submission pipeline.
+pub trait Tracker {
+    /// Submits a specific telemetry event.
+    fn track<E>(&self, event: E)
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>;
+}
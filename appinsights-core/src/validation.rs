@@ -0,0 +1,551 @@
+//! Local checks of telemetry items against the requirements of the ingestion endpoint.
+//!
+//! The ingestion endpoint rejects telemetry items with missing required fields and truncates or rejects
+//! values longer than its limits, but it reports the reason in a response nobody looks at. Validating an
+//! envelope locally explains why an item never shows up in the Application Insights resource.
+//!
+//! With the `schema` feature enabled, [`Envelope::validate_schema`](../contracts/struct.Envelope.html#method.validate_schema)
+//! additionally checks the JSON representation of an envelope against JSON schemas of data contracts,
+//! generated from the Bond schemas of Application Insights by the codegen crate. It is meant for tests:
+//! running it over telemetry items of an application catches contract regressions when the crate is upgraded.
+#[cfg(feature = "schema")]
+mod schema;
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::contracts::{Base, Data, Envelope};
+
+/// Maximum length of a telemetry item name.
+const MAX_NAME_LENGTH: usize = 1024;
+
+/// Maximum length of a custom event name.
+const MAX_EVENT_NAME_LENGTH: usize = 512;
+
+/// Maximum length of an identifier of a request, a dependency call, an availability test or a page view.
+const MAX_ID_LENGTH: usize = 512;
+
+/// Maximum length of a trace or an exception message.
+const MAX_MESSAGE_LENGTH: usize = 32768;
+
+/// Maximum length of a URL.
+const MAX_URL_LENGTH: usize = 2048;
+
+/// Maximum length of a short string value, such as a result code or a dependency type.
+const MAX_VALUE_LENGTH: usize = 1024;
+
+/// Maximum length of a dependency command.
+const MAX_DATA_LENGTH: usize = 8192;
+
+/// Maximum length of a custom property or measurement name.
+const MAX_KEY_LENGTH: usize = 150;
+
+/// Maximum length of a custom property value.
+const MAX_PROPERTY_LENGTH: usize = 8192;
+
+/// A problem with a single field of a telemetry item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    field: String,
+    kind: ViolationKind,
+}
+
+impl Violation {
+    /// Returns a path to the field in the JSON representation of the envelope, e.g. `data.baseData.name`.
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// Returns what is wrong with the field.
+    pub fn kind(&self) -> &ViolationKind {
+        &self.kind
+    }
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.kind)
+    }
+}
+
+/// Describes what is wrong with a field of a telemetry item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ViolationKind {
+    /// A required field is absent or empty.
+    Missing,
+
+    /// A value is longer than the ingestion endpoint accepts.
+    TooLong {
+        /// A length of the value in characters.
+        length: usize,
+
+        /// A maximum length accepted by the ingestion endpoint.
+        max: usize,
+    },
+
+    /// A value has a wrong format.
+    Invalid(String),
+}
+
+impl Display for ViolationKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ViolationKind::Missing => write!(f, "required field is missing"),
+            ViolationKind::TooLong { length, max } => {
+                write!(f, "value of {} characters exceeds limit of {}", length, max)
+            }
+            ViolationKind::Invalid(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl Envelope {
+    /// Returns an indented JSON representation of the envelope as it is sent to the ingestion endpoint.
+    pub fn to_pretty_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("envelope is always serializable")
+    }
+
+    /// Checks required fields and length limits of the envelope and returns all problems found.
+    pub fn validate(&self) -> Result<(), Vec<Violation>> {
+        let mut validator = Validator::default();
+
+        validator.required("name", &self.name);
+        validator.timestamp("time", &self.time);
+        validator.required("iKey", self.i_key.as_deref().unwrap_or_default());
+        if let Some(sample_rate) = self.sample_rate {
+            if !(sample_rate > 0.0 && sample_rate <= 100.0) {
+                validator.invalid("sampleRate", "must be greater than 0 and at most 100");
+            }
+        }
+
+        match &self.data {
+            Some(Base::Data(data)) => validator.data(data),
+            None => validator.violation("data", ViolationKind::Missing),
+        }
+
+        if validator.violations.is_empty() {
+            Ok(())
+        } else {
+            Err(validator.violations)
+        }
+    }
+}
+
+/// Collects violations of fields of a telemetry item.
+#[derive(Default)]
+struct Validator {
+    violations: Vec<Violation>,
+}
+
+impl Validator {
+    fn data(&mut self, data: &Data) {
+        match data {
+            Data::AvailabilityData(data) => {
+                self.id("data.baseData.id", &data.id);
+                self.name("data.baseData.name", &data.name, MAX_NAME_LENGTH);
+                self.duration("data.baseData.duration", &data.duration);
+                self.max_length(
+                    "data.baseData.runLocation",
+                    data.run_location.as_deref(),
+                    MAX_VALUE_LENGTH,
+                );
+                self.max_length("data.baseData.message", data.message.as_deref(), MAX_MESSAGE_LENGTH);
+                self.properties(data.properties.as_ref());
+                self.measurements(data.measurements.as_ref());
+            }
+            Data::EventData(data) => {
+                self.name("data.baseData.name", &data.name, MAX_EVENT_NAME_LENGTH);
+                self.properties(data.properties.as_ref());
+                self.measurements(data.measurements.as_ref());
+            }
+            Data::ExceptionData(data) => {
+                if data.exceptions.is_empty() {
+                    self.violation("data.baseData.exceptions", ViolationKind::Missing);
+                }
+                for (i, exception) in data.exceptions.iter().enumerate() {
+                    let field = format!("data.baseData.exceptions[{}]", i);
+                    self.name(&format!("{}.typeName", field), &exception.type_name, MAX_NAME_LENGTH);
+                    self.max_length(
+                        &format!("{}.message", field),
+                        Some(&exception.message),
+                        MAX_MESSAGE_LENGTH,
+                    );
+                }
+                self.properties(data.properties.as_ref());
+                self.measurements(data.measurements.as_ref());
+            }
+            Data::MessageData(data) => {
+                self.required("data.baseData.message", &data.message);
+                self.max_length("data.baseData.message", Some(&data.message), MAX_MESSAGE_LENGTH);
+                self.properties(data.properties.as_ref());
+                self.measurements(data.measurements.as_ref());
+            }
+            Data::MetricData(data) => {
+                if data.metrics.is_empty() {
+                    self.violation("data.baseData.metrics", ViolationKind::Missing);
+                }
+                for (i, metric) in data.metrics.iter().enumerate() {
+                    let field = format!("data.baseData.metrics[{}]", i);
+                    self.name(&format!("{}.name", field), &metric.name, MAX_NAME_LENGTH);
+                    if !metric.value.is_finite() {
+                        self.invalid(&format!("{}.value", field), "must be a finite number");
+                    }
+                }
+                self.properties(data.properties.as_ref());
+            }
+            Data::PageViewData(data) => {
+                self.id("data.baseData.id", &data.id);
+                self.name("data.baseData.name", &data.name, MAX_NAME_LENGTH);
+                self.max_length("data.baseData.url", data.url.as_deref(), MAX_URL_LENGTH);
+                if let Some(duration) = &data.duration {
+                    self.duration("data.baseData.duration", duration);
+                }
+                self.properties(data.properties.as_ref());
+                self.measurements(data.measurements.as_ref());
+            }
+            Data::RemoteDependencyData(data) => {
+                self.name("data.baseData.name", &data.name, MAX_NAME_LENGTH);
+                self.max_length("data.baseData.id", data.id.as_deref(), MAX_ID_LENGTH);
+                self.max_length(
+                    "data.baseData.resultCode",
+                    data.result_code.as_deref(),
+                    MAX_VALUE_LENGTH,
+                );
+                self.duration("data.baseData.duration", &data.duration);
+                self.max_length("data.baseData.data", data.data.as_deref(), MAX_DATA_LENGTH);
+                self.max_length("data.baseData.target", data.target.as_deref(), MAX_VALUE_LENGTH);
+                self.max_length("data.baseData.type", data.type_.as_deref(), MAX_VALUE_LENGTH);
+                self.properties(data.properties.as_ref());
+                self.measurements(data.measurements.as_ref());
+            }
+            Data::RequestData(data) => {
+                self.id("data.baseData.id", &data.id);
+                self.max_length("data.baseData.name", data.name.as_deref(), MAX_NAME_LENGTH);
+                self.duration("data.baseData.duration", &data.duration);
+                self.required("data.baseData.responseCode", &data.response_code);
+                self.max_length(
+                    "data.baseData.responseCode",
+                    Some(&data.response_code),
+                    MAX_VALUE_LENGTH,
+                );
+                self.max_length("data.baseData.url", data.url.as_deref(), MAX_URL_LENGTH);
+                self.properties(data.properties.as_ref());
+                self.measurements(data.measurements.as_ref());
+            }
+        }
+    }
+
+    fn id(&mut self, field: &str, value: &str) {
+        self.name(field, value, MAX_ID_LENGTH);
+    }
+
+    fn name(&mut self, field: &str, value: &str, max: usize) {
+        self.required(field, value);
+        self.max_length(field, Some(value), max);
+    }
+
+    fn required(&mut self, field: &str, value: &str) {
+        if value.trim().is_empty() {
+            self.violation(field, ViolationKind::Missing);
+        }
+    }
+
+    fn max_length(&mut self, field: &str, value: Option<&str>, max: usize) {
+        if let Some(length) = value.map(|value| value.chars().count()) {
+            if length > max {
+                self.violation(field, ViolationKind::TooLong { length, max });
+            }
+        }
+    }
+
+    fn timestamp(&mut self, field: &str, value: &str) {
+        if value.is_empty() {
+            self.violation(field, ViolationKind::Missing);
+        } else if !is_timestamp(value) {
+            self.invalid(field, "must be an RFC 3339 timestamp");
+        }
+    }
+
+    fn duration(&mut self, field: &str, value: &str) {
+        if value.is_empty() {
+            self.violation(field, ViolationKind::Missing);
+        } else if !is_duration(value) {
+            self.invalid(field, "must be formatted as d.hh:mm:ss.fffffff");
+        }
+    }
+
+    fn properties(&mut self, properties: Option<&BTreeMap<String, String>>) {
+        for (key, value) in properties.into_iter().flatten() {
+            let field = format!("data.baseData.properties.{}", key);
+            self.key(&field, key);
+            self.max_length(&field, Some(value), MAX_PROPERTY_LENGTH);
+        }
+    }
+
+    fn measurements(&mut self, measurements: Option<&BTreeMap<String, f64>>) {
+        for (key, value) in measurements.into_iter().flatten() {
+            let field = format!("data.baseData.measurements.{}", key);
+            self.key(&field, key);
+            if !value.is_finite() {
+                self.invalid(&field, "must be a finite number");
+            }
+        }
+    }
+
+    fn key(&mut self, field: &str, key: &str) {
+        let length = key.chars().count();
+        if length == 0 {
+            self.invalid(field, "name must not be empty");
+        } else if length > MAX_KEY_LENGTH {
+            self.violation(
+                field,
+                ViolationKind::TooLong {
+                    length,
+                    max: MAX_KEY_LENGTH,
+                },
+            );
+        }
+    }
+
+    fn invalid(&mut self, field: &str, reason: &str) {
+        self.violation(field, ViolationKind::Invalid(reason.into()));
+    }
+
+    fn violation(&mut self, field: &str, kind: ViolationKind) {
+        self.violations.push(Violation {
+            field: field.into(),
+            kind,
+        });
+    }
+}
+
+/// Determines whether a value is an RFC 3339 timestamp, e.g. `2019-01-02T03:04:05.800Z`.
+fn is_timestamp(value: &str) -> bool {
+    let number = |part: Option<&str>, len: usize, range: std::ops::RangeInclusive<u32>| {
+        part.filter(|part| part.len() == len && part.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|part| part.parse().ok())
+            .filter(|number| range.contains(number))
+    };
+    let (date, time) = match value.split_once(['T', 't', ' ']) {
+        Some(parts) => parts,
+        None => return false,
+    };
+
+    let mut date_parts = date.split('-');
+    let year = number(date_parts.next(), 4, 0..=9999);
+    let month = number(date_parts.next(), 2, 1..=12);
+    let day = match (year, month) {
+        (Some(year), Some(month)) => {
+            let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+            let days = match month {
+                2 if leap => 29,
+                2 => 28,
+                4 | 6 | 9 | 11 => 30,
+                _ => 31,
+            };
+            number(date_parts.next(), 2, 1..=days)
+        }
+        _ => None,
+    };
+    if day.is_none() || date_parts.next().is_some() {
+        return false;
+    }
+
+    let (time, offset) = match time.strip_suffix(['Z', 'z']) {
+        Some(time) => (time, None),
+        None => match time.rfind(['+', '-']) {
+            Some(index) => (&time[..index], Some(&time[index + 1..])),
+            None => return false,
+        },
+    };
+    let (time, fraction) = match time.split_once('.') {
+        Some((time, fraction)) => (time, Some(fraction)),
+        None => (time, None),
+    };
+
+    let mut time_parts = time.split(':');
+    let time_valid = number(time_parts.next(), 2, 0..=23).is_some()
+        && number(time_parts.next(), 2, 0..=59).is_some()
+        && number(time_parts.next(), 2, 0..=60).is_some()
+        && time_parts.next().is_none();
+    let fraction_valid =
+        fraction.is_none_or(|fraction| !fraction.is_empty() && fraction.bytes().all(|b| b.is_ascii_digit()));
+    let offset_valid = offset.is_none_or(|offset| {
+        let mut offset_parts = offset.split(':');
+        number(offset_parts.next(), 2, 0..=23).is_some()
+            && number(offset_parts.next(), 2, 0..=59).is_some()
+            && offset_parts.next().is_none()
+    });
+    time_valid && fraction_valid && offset_valid
+}
+
+/// Determines whether a value has the `d.hh:mm:ss.fffffff` format of durations.
+fn is_duration(value: &str) -> bool {
+    let digits = |part: &str, len: Option<usize>| {
+        !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()) && len.is_none_or(|len| part.len() == len)
+    };
+
+    match value.split_once('.') {
+        Some((days, rest)) => {
+            let parts: Vec<_> = rest.split([':', '.']).collect();
+            digits(days, None)
+                && parts.len() == 4
+                && digits(parts[0], Some(2))
+                && digits(parts[1], Some(2))
+                && digits(parts[2], Some(2))
+                && digits(parts[3], Some(7))
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration as StdDuration;
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        contracts::{EventData, MetricData, RequestData},
+        telemetry::{EventTelemetry, RequestTelemetry},
+        time::Duration,
+        TelemetryContext,
+    };
+
+    #[test]
+    fn it_accepts_envelope_created_from_telemetry() {
+        let context = TelemetryContext::new("instrumentation".into(), Default::default(), Default::default());
+        let telemetry = RequestTelemetry::new(
+            http::Method::GET,
+            "https://example.com/main.html".parse().unwrap(),
+            StdDuration::from_millis(42),
+            "200",
+        );
+
+        let envelope = Envelope::from((context.clone(), telemetry));
+        assert_eq!(envelope.validate(), Ok(()));
+
+        let envelope = Envelope::from((context, EventTelemetry::new("event")));
+        assert_eq!(envelope.validate(), Ok(()));
+    }
+
+    #[test]
+    fn it_reports_all_violations() {
+        let envelope = Envelope {
+            time: "yesterday".into(),
+            data: Some(Base::Data(Data::RequestData(RequestData {
+                id: "id".into(),
+                duration: "42ms".into(),
+                properties: Some(vec![("k".repeat(151), "value".into())].into_iter().collect()),
+                ..RequestData::default()
+            }))),
+            ..Envelope::default()
+        };
+
+        let violations = envelope.validate().unwrap_err();
+
+        let fields: Vec<_> = violations.iter().map(|violation| violation.to_string()).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "name: required field is missing".to_string(),
+                "time: must be an RFC 3339 timestamp".into(),
+                "iKey: required field is missing".into(),
+                "data.baseData.duration: must be formatted as d.hh:mm:ss.fffffff".into(),
+                "data.baseData.responseCode: required field is missing".into(),
+                format!(
+                    "data.baseData.properties.{}: value of 151 characters exceeds limit of 150",
+                    "k".repeat(151)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_reports_missing_data() {
+        let envelope = Envelope {
+            name: "Microsoft.ApplicationInsights.Event".into(),
+            time: "2019-01-02T03:04:05.800Z".into(),
+            i_key: Some("instrumentation".into()),
+            ..Envelope::default()
+        };
+
+        let violations = envelope.validate().unwrap_err();
+
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].field(), "data");
+        assert_eq!(violations[0].kind(), &ViolationKind::Missing);
+    }
+
+    #[test]
+    fn it_reports_too_long_event_name_and_non_finite_values() {
+        let envelope = Envelope {
+            name: "Microsoft.ApplicationInsights.Event".into(),
+            time: "2019-01-02T03:04:05.800Z".into(),
+            i_key: Some("instrumentation".into()),
+            data: Some(Base::Data(Data::EventData(EventData {
+                name: "e".repeat(513).into(),
+                measurements: Some(vec![("latency".into(), f64::NAN)].into_iter().collect()),
+                ..EventData::default()
+            }))),
+            ..Envelope::default()
+        };
+
+        let violations = envelope.validate().unwrap_err();
+
+        assert_eq!(violations[0].kind(), &ViolationKind::TooLong { length: 513, max: 512 });
+        assert_eq!(violations[1].field(), "data.baseData.measurements.latency");
+    }
+
+    #[test]
+    fn it_reports_empty_metrics() {
+        let envelope = Envelope {
+            data: Some(Base::Data(Data::MetricData(MetricData::default()))),
+            ..Envelope::default()
+        };
+
+        let violations = envelope.validate().unwrap_err();
+
+        assert!(violations
+            .iter()
+            .any(|violation| violation.field() == "data.baseData.metrics"));
+    }
+
+    #[test]
+    fn it_prints_indented_json() {
+        let envelope = Envelope {
+            name: "Microsoft.ApplicationInsights.Event".into(),
+            ..Envelope::default()
+        };
+
+        let json = envelope.to_pretty_json();
+
+        assert!(json.starts_with("{\n  \"ver\": 1,\n  \"name\": \"Microsoft.ApplicationInsights.Event\","));
+    }
+
+    #[test_case(Duration::from(StdDuration::from_millis(42)).to_string(), true; "formatted duration")]
+    #[test_case("10.23:59:59.9999999".into(), true; "several days")]
+    #[test_case("0.00:00:00.042".into(), false; "short fraction")]
+    #[test_case("00:00:00.0420000".into(), false; "no days")]
+    #[test_case("42".into(), false; "number")]
+    fn it_checks_duration_format(value: String, expected: bool) {
+        assert_eq!(is_duration(&value), expected);
+    }
+
+    #[test_case("2019-01-02T03:04:05.800Z", true; "utc")]
+    #[test_case("2019-01-02T03:04:05+01:30", true; "offset without fraction")]
+    #[test_case("2024-02-29T23:59:59.1234567-08:00", true; "leap day")]
+    #[test_case("2023-02-29T03:04:05Z", false; "day out of month")]
+    #[test_case("2019-01-02T24:04:05Z", false; "hour out of range")]
+    #[test_case("2019-01-02T03:04:05.Z", false; "empty fraction")]
+    #[test_case("2019-01-02T03:04:05", false; "no offset")]
+    #[test_case("2019-01-02", false; "date only")]
+    #[test_case("yesterday", false; "text")]
+    fn it_checks_timestamp_format(value: &str, expected: bool) {
+        assert_eq!(is_timestamp(value), expected);
+    }
+}
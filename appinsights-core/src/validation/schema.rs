@@ -0,0 +1,343 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use serde_json::Value;
+
+use crate::{
+    contracts::Envelope,
+    validation::{Violation, ViolationKind},
+};
+
+/// Embeds JSON schemas generated by the codegen crate, keyed by their file names.
+macro_rules! schemas {
+    ($($name:literal),* $(,)?) => {
+        [$((
+            concat!($name, ".schema.json"),
+            include_str!(concat!("../../schema/", $name, ".schema.json")),
+        )),*]
+    };
+}
+
+/// Returns JSON schemas of all data contracts an envelope is serialized from.
+fn schemas() -> &'static HashMap<&'static str, Value> {
+    static SCHEMAS: OnceLock<HashMap<&'static str, Value>> = OnceLock::new();
+    SCHEMAS.get_or_init(|| {
+        schemas![
+            "AvailabilityData",
+            "Data",
+            "DataPoint",
+            "DataPointType",
+            "Envelope",
+            "EventData",
+            "ExceptionData",
+            "ExceptionDetails",
+            "MessageData",
+            "MetricData",
+            "PageViewData",
+            "RemoteDependencyData",
+            "RequestData",
+            "SeverityLevel",
+            "StackFrame",
+        ]
+        .iter()
+        .map(|(name, schema)| (*name, serde_json::from_str(schema).expect("schema is valid JSON")))
+        .collect()
+    })
+}
+
+fn schema(name: &str) -> &'static Value {
+    schemas()
+        .get(format!("{}.schema.json", name).as_str())
+        .expect("schema exists")
+}
+
+impl Envelope {
+    /// Serializes the envelope and validates the JSON representation against JSON schemas of data contracts,
+    /// then checks that it deserializes back to the same envelope. Unlike [`validate`](#method.validate),
+    /// it catches fields of a wrong type, unknown fields and fields renamed by mistake, so a test that runs
+    /// it over telemetry items of an application detects contract regressions on upgrade of the crate.
+    ///
+    /// Available with the `schema` feature only.
+    pub fn validate_schema(&self) -> Result<(), Vec<Violation>> {
+        let json = serde_json::to_value(self).expect("envelope is always serializable");
+        let mut validator = SchemaValidator::default();
+
+        // a schema of envelope refers to the base of all telemetry data, so the data is validated against
+        // a schema of its actual type instead
+        let mut envelope = json.clone();
+        let data = envelope.as_object_mut().and_then(|envelope| envelope.remove("data"));
+        validator.validate("", &envelope, schema("Envelope"));
+
+        if let Some(data) = data.filter(|data| !data.is_null()) {
+            validator.validate("data", &data, schema("Data"));
+            if let (Some(base_type), Some(base_data)) = (data["baseType"].as_str(), data.get("baseData")) {
+                match schemas().get(format!("{}.schema.json", base_type).as_str()) {
+                    Some(schema) => validator.validate("data.baseData", base_data, schema),
+                    None => validator.invalid("data.baseType", "unknown telemetry data type"),
+                }
+            }
+        }
+
+        if validator.violations.is_empty() && serde_json::from_value::<Envelope>(json).ok().as_ref() != Some(self) {
+            validator.invalid("", "does not deserialize back to the same envelope");
+        }
+
+        if validator.violations.is_empty() {
+            Ok(())
+        } else {
+            Err(validator.violations)
+        }
+    }
+}
+
+/// Validates JSON values against the subset of JSON schema the codegen crate generates.
+#[derive(Default)]
+struct SchemaValidator {
+    violations: Vec<Violation>,
+}
+
+impl SchemaValidator {
+    fn validate(&mut self, field: &str, value: &Value, schema: &Value) {
+        if let Some(reference) = schema["$ref"].as_str() {
+            match schemas().get(reference) {
+                Some(schema) => self.validate(field, value, schema),
+                None => self.invalid(field, &format!("refers to unknown schema {}", reference)),
+            }
+            return;
+        }
+
+        if let Some(schemas) = schema["anyOf"].as_array() {
+            let matches = |schema| {
+                let mut validator = SchemaValidator::default();
+                validator.validate(field, value, schema);
+                validator.violations.is_empty()
+            };
+            // report violations of the first alternative, which is the only non-null one in generated schemas
+            match schemas.first() {
+                Some(first) if !schemas.iter().any(matches) => self.validate(field, value, first),
+                _ => {}
+            }
+            return;
+        }
+
+        if !self.type_(field, value, &schema["type"]) {
+            return;
+        }
+
+        if let Some(constants) = schema["enum"].as_array() {
+            if !constants.contains(value) {
+                self.invalid(field, &format!("must be one of {}", Value::from(constants.clone())));
+            }
+        }
+
+        match value {
+            Value::String(value) => self.max_length(field, value, &schema["maxLength"]),
+            Value::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    self.validate(&format!("{}[{}]", field, i), item, &schema["items"]);
+                }
+            }
+            Value::Object(object) => {
+                let child = |key: &str| {
+                    if field.is_empty() {
+                        key.to_string()
+                    } else {
+                        format!("{}.{}", field, key)
+                    }
+                };
+
+                for required in schema["required"].as_array().into_iter().flatten() {
+                    if let Some(required) = required.as_str().filter(|required| !object.contains_key(*required)) {
+                        self.violation(&child(required), ViolationKind::Missing);
+                    }
+                }
+
+                for (key, value) in object {
+                    let field = child(key);
+                    self.max_length(&field, key, &schema["propertyNames"]["maxLength"]);
+                    match (schema["properties"].get(key), &schema["additionalProperties"]) {
+                        (Some(schema), _) => self.validate(&field, value, schema),
+                        (None, Value::Bool(false)) => self.invalid(&field, "unknown field"),
+                        (None, Value::Object(_)) => self.validate(&field, value, &schema["additionalProperties"]),
+                        (None, _) => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Checks that a value is of one of the types a schema allows and reports a violation otherwise.
+    fn type_(&mut self, field: &str, value: &Value, types: &Value) -> bool {
+        let types: Vec<_> = match types {
+            Value::String(type_) => vec![type_.as_str()],
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => return true,
+        };
+
+        let actual = match value {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(number) if number.is_i64() || number.is_u64() => "integer",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        };
+
+        if types
+            .iter()
+            .any(|type_| *type_ == actual || (*type_ == "number" && actual == "integer"))
+        {
+            true
+        } else {
+            if value.is_null() {
+                self.violation(field, ViolationKind::Missing);
+            } else {
+                self.invalid(field, &format!("must be {}, not {}", types.join(" or "), actual));
+            }
+            false
+        }
+    }
+
+    fn max_length(&mut self, field: &str, value: &str, max: &Value) {
+        if let Some(max) = max.as_u64() {
+            let length = value.chars().count();
+            if length as u64 > max {
+                self.violation(
+                    field,
+                    ViolationKind::TooLong {
+                        length,
+                        max: max as usize,
+                    },
+                );
+            }
+        }
+    }
+
+    fn invalid(&mut self, field: &str, reason: &str) {
+        self.violation(field, ViolationKind::Invalid(reason.into()));
+    }
+
+    fn violation(&mut self, field: &str, kind: ViolationKind) {
+        self.violations.push(Violation {
+            field: field.into(),
+            kind,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        contracts::{Base, Data, DataPoint, MetricData},
+        telemetry::{
+            AggregateMetricTelemetry, AvailabilityTelemetry, EventTelemetry, ExceptionTelemetry, MetricTelemetry,
+            PageViewTelemetry, RemoteDependencyTelemetry, RequestTelemetry, SeverityLevel, Telemetry, TraceTelemetry,
+        },
+        TelemetryContext,
+    };
+
+    #[test]
+    fn it_accepts_envelopes_of_all_telemetry_types() {
+        let uri: http::Uri = "https://example.com/main.html".parse().unwrap();
+
+        let mut event = EventTelemetry::new("event");
+        event.properties_mut().insert("key".into(), "value".into());
+        event.measurements_mut().insert("value".into(), 42.0);
+
+        let mut exception = ExceptionTelemetry::new("Error", "failed");
+        exception.set_stack("at main");
+
+        let mut aggregate = AggregateMetricTelemetry::new("metric");
+        aggregate.stats_mut().add_data(&[1.0, 2.0]);
+
+        let envelopes = vec![
+            envelope(event),
+            envelope(exception),
+            envelope(aggregate),
+            envelope(MetricTelemetry::new("metric", 42.0)),
+            envelope(TraceTelemetry::new("message", SeverityLevel::Warning)),
+            envelope(AvailabilityTelemetry::new("ping", Duration::from_millis(42), true)),
+            envelope(PageViewTelemetry::new("page", uri.clone())),
+            envelope(RemoteDependencyTelemetry::new(
+                "GET /users",
+                "HTTP",
+                Duration::from_millis(42),
+                "api.example.com",
+                true,
+            )),
+            envelope(RequestTelemetry::new(
+                http::Method::GET,
+                uri,
+                Duration::from_millis(42),
+                "200",
+            )),
+        ];
+
+        for envelope in envelopes {
+            assert_eq!(envelope.validate_schema(), Ok(()), "{}", envelope.to_pretty_json());
+        }
+    }
+
+    #[test]
+    fn it_reports_schema_violations() {
+        let envelope = Envelope {
+            name: "n".repeat(1025).into(),
+            data: Some(Base::Data(Data::MetricData(MetricData {
+                metrics: vec![DataPoint {
+                    name: "metric".into(),
+                    value: f64::NAN,
+                    ..DataPoint::default()
+                }],
+                properties: Some(vec![("k".repeat(151), "value".into())].into_iter().collect()),
+                ..MetricData::default()
+            }))),
+            ..Envelope::default()
+        };
+
+        let violations = envelope.validate_schema().unwrap_err();
+
+        let violations: Vec<_> = violations.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            violations,
+            vec![
+                "name: value of 1025 characters exceeds limit of 1024".to_string(),
+                "data.baseData.metrics[0].value: required field is missing".into(),
+                format!(
+                    "data.baseData.properties.{}: value of 151 characters exceeds limit of 150",
+                    "k".repeat(151)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_reports_values_of_wrong_type_and_unknown_fields() {
+        let schema = schema("StackFrame");
+        let mut validator = SchemaValidator::default();
+
+        let frame = serde_json::json!({ "level": "0", "method": "main", "column": 42 });
+        validator.validate("frame", &frame, schema);
+
+        let violations: Vec<_> = validator.violations.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            violations,
+            vec![
+                "frame.column: unknown field".to_string(),
+                "frame.level: must be integer, not string".into(),
+            ]
+        );
+    }
+
+    fn envelope<T>(telemetry: T) -> Envelope
+    where
+        T: Telemetry,
+        (TelemetryContext, T): Into<Envelope>,
+    {
+        let context = TelemetryContext::new("instrumentation".into(), Default::default(), Default::default());
+        (context, telemetry).into()
+    }
+}
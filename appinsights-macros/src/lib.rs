@@ -0,0 +1,139 @@
+//! Procedural macros of Application Insights SDK for Rust. Use them through the `macros` feature of the
+//! `appinsights` crate rather than depending on this crate directly.
+#![deny(unused_extern_crates)]
+#![deny(missing_docs)]
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{
+    parse::Parser, parse_macro_input, punctuated::Punctuated, Error, Expr, ExprLit, ItemFn, Lit, LitStr, MetaNameValue,
+    ReturnType, Token, Type,
+};
+
+/// Tracks every call of an async function as an in-process dependency and runs its body within an operation
+/// scope of its own, so telemetry tracked by the function and functions it calls is correlated with the call.
+/// The parent scope is restored once the call completes.
+///
+/// Arguments:
+/// * `client` is a place expression of a telemetry client or any other `Tracker`, which is borrowed for the
+///   call. It is required.
+/// * `name` is a name of the dependency. Defaults to the name of the function.
+/// * `dependency_type` is a type of the dependency. Defaults to `InProc`.
+///
+/// A call is successful unless the function returns a `Result` that is an error.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// use appinsights::{instrument_ai, TelemetryClient};
+///
+/// struct UserRepository {
+///     client: TelemetryClient,
+/// }
+///
+/// impl UserRepository {
+///     #[instrument_ai(client = self.client, name = "load user")]
+///     async fn load(&self, id: u64) -> Result<String, std::io::Error> {
+///         self.client.track_event("cache miss");
+///         Ok(format!("user {}", id))
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn instrument_ai(args: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemFn);
+    match expand(args, item) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(args: TokenStream, item: ItemFn) -> syn::Result<proc_macro2::TokenStream> {
+    let args = Args::parse(args, &item)?;
+    if item.sig.asyncness.is_none() {
+        return Err(Error::new_spanned(
+            item.sig.fn_token,
+            "only async functions can be instrumented",
+        ));
+    }
+
+    let ItemFn { attrs, vis, sig, block } = item;
+    let Args {
+        client,
+        name,
+        dependency_type,
+    } = args;
+    let success = match &sig.output {
+        ReturnType::Type(_, ty) if is_result(ty) => quote! { |output| output.is_ok() },
+        _ => quote! { |_| true },
+    };
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis #sig {
+            ::appinsights::scope::instrument(
+                &#client,
+                #name,
+                #dependency_type,
+                async move #block,
+                #success,
+            )
+            .await
+        }
+    })
+}
+
+/// Arguments of the attribute.
+struct Args {
+    client: Expr,
+    name: LitStr,
+    dependency_type: LitStr,
+}
+
+impl Args {
+    fn parse(args: TokenStream, item: &ItemFn) -> syn::Result<Self> {
+        let mut client = None;
+        let mut name = LitStr::new(&item.sig.ident.to_string(), item.sig.ident.span());
+        let mut dependency_type = LitStr::new("InProc", Span::call_site());
+
+        let args = Punctuated::<MetaNameValue, Token![,]>::parse_terminated.parse(args)?;
+        for arg in args {
+            if arg.path.is_ident("client") {
+                client = Some(arg.value);
+            } else if arg.path.is_ident("name") {
+                name = lit_str(arg.value)?;
+            } else if arg.path.is_ident("dependency_type") {
+                dependency_type = lit_str(arg.value)?;
+            } else {
+                return Err(Error::new_spanned(arg.path, "unknown argument"));
+            }
+        }
+
+        let client = client.ok_or_else(|| Error::new(Span::call_site(), "`client` argument is required"))?;
+        Ok(Self {
+            client,
+            name,
+            dependency_type,
+        })
+    }
+}
+
+fn lit_str(value: Expr) -> syn::Result<LitStr> {
+    match value {
+        Expr::Lit(ExprLit { lit: Lit::Str(lit), .. }) => Ok(lit),
+        value => Err(Error::new_spanned(value, "string literal expected")),
+    }
+}
+
+/// Determines whether the return type is a `Result`, including aliases like `io::Result`.
+fn is_result(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Result"),
+        _ => false,
+    }
+}
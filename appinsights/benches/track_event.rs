@@ -0,0 +1,22 @@
+//! Compares the cost of turning an event into an envelope with an owned name and with a static name.
+//!
+//! Run with `cargo bench --bench track_event`.
+use appinsights::{contracts::Envelope, telemetry::EventTelemetry, TelemetryConfig, TelemetryContext};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+fn track_event(c: &mut Criterion) {
+    let config = TelemetryConfig::new("instrumentation".into());
+    let context = TelemetryContext::from(&config);
+
+    let mut group = c.benchmark_group("track_event");
+    group.bench_function("owned name", |b| {
+        b.iter(|| Envelope::from((context.clone(), EventTelemetry::new(black_box("cache hit")))))
+    });
+    group.bench_function("static name", |b| {
+        b.iter(|| Envelope::from((context.clone(), EventTelemetry::from_static(black_box("cache hit")))))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, track_event);
+criterion_main!(benches);
@@ -0,0 +1,64 @@
+//! Tracks every request an axum server handles as request telemetry and correlates telemetry tracked by
+//! handlers with it.
+//!
+//! Run with `cargo run --example axum_middleware` and open http://127.0.0.1:3000/users/1.
+use std::{env, net::SocketAddr, sync::Arc};
+
+use appinsights::{telemetry::SeverityLevel, TelemetryClient};
+use axum::{
+    extract::{Path, State},
+    http::Request,
+    middleware::{self, Next},
+    response::Response,
+    routing::get,
+    Router,
+};
+use log::LevelFilter;
+
+#[tokio::main]
+async fn main() {
+    env_logger::builder().filter_level(LevelFilter::Debug).init();
+
+    let i_key = env::var("APPINSIGHTS_INSTRUMENTATIONKEY").expect("Set APPINSIGHTS_INSTRUMENTATIONKEY first");
+
+    let mut client = TelemetryClient::new(i_key);
+    client.context_mut().tags_mut().cloud_mut().set_role("users-api".into());
+    let client = Arc::new(client);
+
+    let app = Router::new()
+        .route("/users/:id", get(user))
+        .route_layer(middleware::from_fn_with_state(client.clone(), track_request))
+        .with_state(client.clone());
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
+    axum::Server::bind(&addr)
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.expect("install Ctrl+C handler");
+        })
+        .await
+        .expect("run server");
+
+    // the server and all requests are gone, so the client is not shared anymore
+    if let Ok(client) = Arc::try_unwrap(client) {
+        client.close_channel().await;
+    }
+}
+
+/// Runs the rest of the middleware stack and the handler within an operation, which is tracked as a request
+/// once the response is ready.
+async fn track_request<B>(State(client): State<Arc<TelemetryClient>>, request: Request<B>, next: Next<B>) -> Response {
+    let name = format!("{} {}", request.method(), request.uri().path());
+    let mut operation = client.start_operation(name);
+
+    let response = operation.run(next.run(request)).await;
+    operation.set_response_code(response.status().as_str());
+
+    response
+}
+
+async fn user(State(client): State<Arc<TelemetryClient>>, Path(id): Path<u64>) -> String {
+    // the trace refers to the request it was tracked within
+    client.track_trace(format!("Loading user {}", id), SeverityLevel::Information);
+    format!("user {}", id)
+}
@@ -0,0 +1,64 @@
+//! Tracks runs of a periodic background job as operations with the dependency calls they make, and submits
+//! heartbeats while the job is running.
+use std::{env, sync::Arc, time::Duration};
+
+use appinsights::{
+    heartbeat::Heartbeat,
+    telemetry::{MetricTelemetry, SeverityLevel, TraceTelemetry},
+    TelemetryClient, Tracker,
+};
+use log::LevelFilter;
+
+#[tokio::main]
+async fn main() {
+    env_logger::builder().filter_level(LevelFilter::Debug).init();
+
+    let i_key = env::var("APPINSIGHTS_INSTRUMENTATIONKEY").expect("Set APPINSIGHTS_INSTRUMENTATIONKEY first");
+
+    let client = Arc::new(TelemetryClient::new(i_key));
+
+    let mut heartbeat = Heartbeat::new();
+    heartbeat
+        .properties_mut()
+        .insert("job".into(), "session-cleanup".into());
+    let heartbeat = heartbeat.spawn(&client, Duration::from_secs(60));
+
+    for run in 1..=5 {
+        let mut operation = client.start_operation("cleanup expired sessions");
+        match operation.run(cleanup(&client, run)).await {
+            Ok(removed) => operation.track(MetricTelemetry::new("expired_sessions_removed", removed as f64)),
+            Err(err) => {
+                operation.track(TraceTelemetry::new(
+                    format!("Cleanup failed: {}", err),
+                    SeverityLevel::Error,
+                ));
+                operation.set_response_code("500");
+            }
+        }
+        // the run is tracked as a request here
+        operation.complete();
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    heartbeat.abort();
+    let _ = heartbeat.await;
+
+    if let Ok(client) = Arc::try_unwrap(client) {
+        client.close_channel().await;
+    }
+}
+
+/// Removes expired sessions from a store. Every third run fails to show how failures are reported.
+async fn cleanup(client: &TelemetryClient, run: u32) -> Result<u32, String> {
+    let mut dependency = client.start_dependency("DELETE expired sessions", "SQL", "sessions.db.example.com");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    if run % 3 == 0 {
+        dependency.mark_failed();
+        dependency.set_result_code("40001");
+        return Err("serialization failure".into());
+    }
+
+    Ok(run * 10)
+}
@@ -16,9 +16,9 @@ fn main() {
         std::thread::sleep(Duration::from_millis(300));
 
         if x == 2 {
-            ai.flush_channel();
+            ai.flush_channel().expect("flush telemetry");
         }
     }
 
-    ai.close_channel();
+    ai.close_channel().expect("close telemetry channel");
 }
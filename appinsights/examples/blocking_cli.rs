@@ -0,0 +1,61 @@
+//! Tracks a command line tool that has no async runtime with the blocking client.
+use std::{
+    env,
+    time::{Duration, Instant},
+};
+
+use appinsights::{
+    blocking::TelemetryClient,
+    telemetry::{EventTelemetry, SeverityLevel, Telemetry},
+};
+use log::LevelFilter;
+
+fn main() {
+    env_logger::builder().filter_level(LevelFilter::Debug).init();
+
+    let i_key = env::var("APPINSIGHTS_INSTRUMENTATIONKEY").expect("Set APPINSIGHTS_INSTRUMENTATIONKEY first");
+    let command = env::args().nth(1).unwrap_or_else(|| "help".into());
+
+    let mut client = TelemetryClient::new(i_key);
+    client
+        .context_mut()
+        .tags_mut()
+        .application_mut()
+        .set_version(env!("CARGO_PKG_VERSION").into());
+    client
+        .context_mut()
+        .tags_mut()
+        .session_mut()
+        .set_id(std::process::id().to_string());
+
+    let mut event = EventTelemetry::new("Command invoked");
+    event.properties_mut().insert("command".into(), command.clone());
+    client.track(event);
+
+    let started = Instant::now();
+    let success = run(&command);
+    client.track_availability(format!("cli {}", command), started.elapsed(), success);
+
+    if !success {
+        client.track_trace(format!("Unknown command: {}", command), SeverityLevel::Warning);
+    }
+
+    // a short-lived process has to wait until telemetry is submitted before it exits
+    if let Err(err) = client.close_channel() {
+        eprintln!("telemetry was not submitted: {}", err);
+    }
+}
+
+fn run(command: &str) -> bool {
+    match command {
+        "sync" => {
+            std::thread::sleep(Duration::from_millis(200));
+            true
+        }
+        "help" => {
+            println!("usage: blocking_cli <sync|help>");
+            true
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,58 @@
+//! Extends the telemetry pipeline with a processor that scrubs personal data and a sink that writes batches
+//! to standard output instead of the ingestion endpoint, e.g. to forward them with a log shipper.
+use std::io::{self, Write};
+
+use appinsights::{
+    contracts::{Base, Data, Envelope},
+    sink::{SinkError, TelemetrySink},
+    TelemetryClient, TelemetryConfig,
+};
+use async_trait::async_trait;
+use log::LevelFilter;
+
+/// Writes every batch as a single line of JSON.
+struct StdoutSink;
+
+#[async_trait]
+impl TelemetrySink for StdoutSink {
+    async fn write(&self, batch: Vec<u8>) -> Result<(), SinkError> {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(&batch)?;
+        stdout.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+/// Removes query strings from URLs of requests, which may contain tokens or e-mail addresses.
+fn scrub_urls(envelope: &mut Envelope) -> bool {
+    if let Some(Base::Data(Data::RequestData(data))) = &mut envelope.data {
+        if let Some(url) = &mut data.url {
+            if let Some(query) = url.find('?') {
+                url.truncate(query);
+            }
+        }
+    }
+    true
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::builder().filter_level(LevelFilter::Debug).init();
+
+    let config = TelemetryConfig::builder()
+        .i_key("00000000-0000-0000-0000-000000000000")
+        .sink(StdoutSink)
+        .build();
+
+    let mut client = TelemetryClient::from_config(config);
+    client.add_processor(scrub_urls);
+
+    client.track_request(
+        appinsights::ext::Method::GET,
+        "https://example.com/reset?email=alice@example.com".parse().unwrap(),
+        std::time::Duration::from_millis(42),
+        "200",
+    );
+
+    client.close_channel().await;
+}
@@ -0,0 +1,30 @@
+//! Reports a panic of a worker thread as an exception before the process goes down.
+use std::{env, sync::Arc, thread};
+
+use appinsights::{panics, TelemetryClient};
+use log::LevelFilter;
+
+#[tokio::main]
+async fn main() {
+    env_logger::builder().filter_level(LevelFilter::Debug).init();
+
+    let i_key = env::var("APPINSIGHTS_INSTRUMENTATIONKEY").expect("Set APPINSIGHTS_INSTRUMENTATIONKEY first");
+
+    let client = Arc::new(TelemetryClient::new(i_key));
+
+    // install the hook as early as possible, so panics of any thread are reported
+    panics::register_panic_hook(client.clone());
+
+    client.track_event("Import started");
+
+    // the hook submits the exception before the default hook prints the panic message
+    let worker = thread::spawn(|| {
+        let records: Vec<u32> = Vec::new();
+        records[0]
+    });
+    assert!(worker.join().is_err());
+
+    // the hook keeps a reference to the client forever, so pending telemetry has to be flushed explicitly
+    client.flush_channel();
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+}
@@ -0,0 +1,370 @@
+//! Client-side aggregation of high-frequency dependency calls and metrics.
+//!
+//! Tracking every call to a cache or a message broker as a separate telemetry item produces a lot of
+//! traffic and ingestion costs while individual successful calls are rarely looked at. [`DependencyAggregator`]
+//! rolls up successful calls to the same target into a single dependency per interval with the average
+//! duration and the following custom measurements:
+//! * `count` - a number of calls,
+//! * `durationMin` - a minimum duration in milliseconds,
+//! * `durationMax` - a maximum duration in milliseconds,
+//! * `durationSum` - a total duration in milliseconds.
+//!
+//! Failed calls are tracked individually right away, so they can be investigated as usual.
+//!
+//! Similarly, [`MetricsAggregator`] rolls up metric samples with the same name and dimensions into a single
+//! aggregate metric per interval with a count, a sum, a minimum, a maximum and a standard deviation of the
+//! samples. Metrics are usually aggregated into one-minute buckets.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # use std::{sync::Arc, time::Duration};
+//! use appinsights::{
+//!     aggregation::{DependencyAggregator, MetricsAggregator},
+//!     telemetry::RemoteDependencyTelemetry,
+//!     TelemetryClient,
+//! };
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = Arc::new(TelemetryClient::new("<instrumentation key>".to_string()));
+//! let aggregator = DependencyAggregator::new();
+//! aggregator.clone().spawn(&client, Duration::from_secs(60));
+//!
+//! let telemetry = RemoteDependencyTelemetry::new("GET", "Redis", Duration::from_micros(250), "cache:6379", true);
+//! aggregator.track(client.as_ref(), telemetry);
+//!
+//! let metrics = MetricsAggregator::new();
+//! metrics.clone().spawn(&client, Duration::from_secs(60));
+//!
+//! metrics.track_metric("queue_length", 42.0);
+//! metrics.track_metric_with_dimensions("request_size", 512.0, [("route", "/orders")]);
+//! # }
+//! ```
+use std::{
+    collections::{BTreeMap, HashMap},
+    mem,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+#[cfg(feature = "runtime")]
+use tokio::task::JoinHandle;
+
+#[cfg(feature = "runtime")]
+use crate::{client, TelemetryClient};
+use crate::{
+    telemetry::{AggregateMetricTelemetry, RemoteDependencyTelemetry, Stats, Telemetry, Timestamp},
+    time, Tracker,
+};
+
+/// Aggregates successful dependency calls by their type, target, name and result code.
+#[derive(Debug, Clone, Default)]
+pub struct DependencyAggregator {
+    calls: Arc<Mutex<HashMap<CallKey, CallStats>>>,
+}
+
+impl DependencyAggregator {
+    /// Creates a new aggregator without any calls.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accounts a successful dependency call to submit later as a part of an aggregate. A failed call is
+    /// submitted immediately with the given tracker.
+    pub fn track<T: Tracker>(&self, tracker: &T, telemetry: RemoteDependencyTelemetry) {
+        if !telemetry.is_success() {
+            tracker.track(telemetry);
+            return;
+        }
+
+        let key = CallKey {
+            dependency_type: telemetry.dependency_type().into(),
+            target: telemetry.target().into(),
+            name: telemetry.name().into(),
+            result_code: telemetry.result_code().map(Into::into),
+        };
+
+        let mut calls = self.calls.lock().unwrap_or_else(|err| err.into_inner());
+        calls
+            .entry(key)
+            .or_insert_with(|| CallStats::new(telemetry.timestamp()))
+            .add(telemetry.duration());
+    }
+
+    /// Submits aggregates of calls accounted since the previous submission with the given tracker.
+    pub fn submit<T: Tracker>(&self, tracker: &T) {
+        let calls = {
+            let mut calls = self.calls.lock().unwrap_or_else(|err| err.into_inner());
+            mem::take(&mut *calls)
+        };
+
+        for (key, stats) in calls {
+            tracker.track(stats.into_telemetry(key));
+        }
+    }
+
+    /// Spawns a [periodic task](../index.html#periodic-tasks) that submits aggregates with the given client every
+    /// `interval`.
+    #[cfg(feature = "runtime")]
+    pub fn spawn(self, client: &Arc<TelemetryClient>, interval: Duration) -> JoinHandle<()> {
+        client::spawn_periodic(client, interval, move |client| self.submit(client))
+    }
+}
+
+/// Identifies dependency calls aggregated together.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CallKey {
+    dependency_type: String,
+    target: String,
+    name: String,
+    result_code: Option<String>,
+}
+
+/// Durations of aggregated dependency calls.
+#[derive(Debug)]
+struct CallStats {
+    timestamp: Timestamp,
+    count: u32,
+    sum: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+impl CallStats {
+    fn new(timestamp: Timestamp) -> Self {
+        Self {
+            timestamp,
+            count: 0,
+            sum: Duration::ZERO,
+            min: Duration::MAX,
+            max: Duration::ZERO,
+        }
+    }
+
+    fn add(&mut self, duration: Duration) {
+        self.count += 1;
+        self.sum = self.sum.saturating_add(duration);
+        self.min = self.min.min(duration);
+        self.max = self.max.max(duration);
+    }
+
+    fn into_telemetry(self, key: CallKey) -> RemoteDependencyTelemetry {
+        let average = self.sum / self.count;
+        let mut telemetry = RemoteDependencyTelemetry::new(key.name, key.dependency_type, average, key.target, true);
+        if let Some(result_code) = key.result_code {
+            telemetry.set_result_code(result_code);
+        }
+        telemetry.set_timestamp(self.timestamp);
+
+        let measurements = telemetry.measurements_mut();
+        measurements.insert("count".into(), self.count.into());
+        measurements.insert("durationMin".into(), as_millis(self.min));
+        measurements.insert("durationMax".into(), as_millis(self.max));
+        measurements.insert("durationSum".into(), as_millis(self.sum));
+
+        telemetry
+    }
+}
+
+/// Aggregates metric samples by their name and dimensions.
+#[derive(Debug, Clone, Default)]
+pub struct MetricsAggregator {
+    metrics: Arc<Mutex<HashMap<MetricKey, MetricStats>>>,
+}
+
+impl MetricsAggregator {
+    /// Creates a new aggregator without any samples.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accounts a metric sample to submit later as a part of an aggregate.
+    pub fn track_metric(&self, name: impl Into<String>, value: f64) {
+        self.track_metric_with_dimensions(name, value, std::iter::empty::<(String, String)>());
+    }
+
+    /// Accounts a metric sample with the given dimensions to submit later as a part of an aggregate. Samples
+    /// with different dimensions are aggregated separately and dimensions are submitted as custom properties.
+    pub fn track_metric_with_dimensions<I, K, V>(&self, name: impl Into<String>, value: f64, dimensions: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let key = MetricKey {
+            name: name.into(),
+            dimensions: dimensions
+                .into_iter()
+                .map(|(name, value)| (name.into(), value.into()))
+                .collect(),
+        };
+
+        let mut metrics = self.metrics.lock().unwrap_or_else(|err| err.into_inner());
+        metrics
+            .entry(key)
+            .or_insert_with(|| MetricStats {
+                timestamp: time::timestamp(),
+                stats: Stats::default(),
+            })
+            .stats
+            .add_data(&[value]);
+    }
+
+    /// Submits aggregates of samples accounted since the previous submission with the given tracker.
+    pub fn submit<T: Tracker>(&self, tracker: &T) {
+        let metrics = {
+            let mut metrics = self.metrics.lock().unwrap_or_else(|err| err.into_inner());
+            mem::take(&mut *metrics)
+        };
+
+        for (key, metric) in metrics {
+            let mut telemetry = AggregateMetricTelemetry::new(key.name);
+            *telemetry.stats_mut() = metric.stats;
+            telemetry.set_timestamp(metric.timestamp);
+            telemetry.properties_mut().extend(key.dimensions);
+            tracker.track(telemetry);
+        }
+    }
+
+    /// Spawns a [periodic task](../index.html#periodic-tasks) that submits aggregates with the given client every
+    /// `interval`.
+    #[cfg(feature = "runtime")]
+    pub fn spawn(self, client: &Arc<TelemetryClient>, interval: Duration) -> JoinHandle<()> {
+        client::spawn_periodic(client, interval, move |client| self.submit(client))
+    }
+}
+
+/// Identifies metric samples aggregated together.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct MetricKey {
+    name: String,
+    dimensions: BTreeMap<String, String>,
+}
+
+/// Aggregated metric samples.
+#[derive(Debug)]
+struct MetricStats {
+    timestamp: Timestamp,
+    stats: Stats,
+}
+
+fn as_millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use crossbeam_queue::SegQueue;
+
+    use super::*;
+    use crate::{
+        client::tests::{create_client, metric_data},
+        contracts::{Base, Data, Envelope, RemoteDependencyData},
+    };
+
+    #[tokio::test]
+    async fn it_submits_aggregate_of_successful_calls() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+        let aggregator = DependencyAggregator::new();
+
+        for millis in [1, 2, 6] {
+            let telemetry = dependency("GET", Duration::from_millis(millis), true);
+            aggregator.track(&client, telemetry);
+        }
+        assert!(events.pop().is_none(), "successful calls are not submitted right away");
+
+        aggregator.submit(&client);
+
+        let data = dependency_data(events.pop());
+        assert_eq!(data.name, "GET");
+        assert_eq!(data.duration, "0.00:00:00.0030000");
+        assert_eq!(data.target, Some("cache:6379".into()));
+        let measurements = data.measurements.unwrap();
+        assert_eq!(measurements.get("count"), Some(&3.0));
+        assert_eq!(measurements.get("durationMin"), Some(&1.0));
+        assert_eq!(measurements.get("durationMax"), Some(&6.0));
+        assert_eq!(measurements.get("durationSum"), Some(&9.0));
+
+        aggregator.submit(&client);
+        assert!(events.pop().is_none(), "nothing tracked since previous submission");
+    }
+
+    #[tokio::test]
+    async fn it_aggregates_calls_by_name() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+        let aggregator = DependencyAggregator::new();
+
+        aggregator.track(&client, dependency("GET", Duration::from_millis(1), true));
+        aggregator.track(&client, dependency("SET", Duration::from_millis(1), true));
+        aggregator.track(&client, dependency("GET", Duration::from_millis(1), true));
+        aggregator.submit(&client);
+
+        let mut counts: Vec<_> = (0..2)
+            .map(|_| dependency_data(events.pop()))
+            .map(|data| (data.name, data.measurements.unwrap()["count"]))
+            .collect();
+        counts.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(counts, vec![("GET".to_string(), 2.0), ("SET".to_string(), 1.0)]);
+    }
+
+    #[tokio::test]
+    async fn it_submits_failed_call_immediately() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+        let aggregator = DependencyAggregator::new();
+
+        aggregator.track(&client, dependency("GET", Duration::from_millis(1), false));
+
+        let data = dependency_data(events.pop());
+        assert_eq!(data.success, Some(false));
+        assert_eq!(data.measurements, Some(Default::default()));
+
+        aggregator.submit(&client);
+        assert!(events.pop().is_none(), "failed call is not aggregated");
+    }
+
+    #[tokio::test]
+    async fn it_submits_aggregate_of_metric_samples_by_dimensions() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+        let metrics = MetricsAggregator::new();
+
+        for value in [9.0, 10.0, 11.0, 7.0, 13.0] {
+            metrics.track_metric_with_dimensions("request_size", value, [("route", "/orders")]);
+        }
+        metrics.track_metric_with_dimensions("request_size", 1.0, [("route", "/users")]);
+        assert!(events.pop().is_none(), "samples are not submitted right away");
+
+        metrics.submit(&client);
+
+        let mut data: Vec<_> = (0..2).map(|_| metric_data(events.pop())).collect();
+        data.sort_by_key(|data| data.properties.as_ref().unwrap()["route"].clone());
+
+        let point = &data[0].metrics[0];
+        assert_eq!(point.name, "request_size");
+        assert_eq!(data[0].properties.as_ref().unwrap()["route"], "/orders");
+        assert_eq!(point.count, Some(5));
+        assert_eq!(point.value, 50.0);
+        assert_eq!(point.min, Some(7.0));
+        assert_eq!(point.max, Some(13.0));
+        assert_eq!(data[1].properties.as_ref().unwrap()["route"], "/users");
+        assert_eq!(data[1].metrics[0].count, Some(1));
+
+        metrics.submit(&client);
+        assert!(events.pop().is_none(), "nothing tracked since previous submission");
+    }
+
+    fn dependency(name: &str, duration: Duration, success: bool) -> RemoteDependencyTelemetry {
+        RemoteDependencyTelemetry::new(name, "Redis", duration, "cache:6379", success)
+    }
+
+    fn dependency_data(envelope: Option<Envelope>) -> RemoteDependencyData {
+        match envelope.and_then(|envelope| envelope.data) {
+            Some(Base::Data(Data::RemoteDependencyData(data))) => data,
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+}
@@ -0,0 +1,314 @@
+//! Support for [W3C Baggage](https://www.w3.org/TR/baggage/) propagation.
+//!
+//! Baggage carries business correlation identifiers, such as a tenant or an order id, across services
+//! in a `baggage` HTTP header. Entries allowed explicitly can be recorded as custom properties of telemetry
+//! items, so these identifiers show up in the telemetry of every service that handles a request.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use appinsights::{baggage::Baggage, TelemetryClient};
+//! use appinsights::ext::{HeaderMap, HeaderValue};
+//!
+//! # let mut incoming = HeaderMap::new();
+//! # incoming.insert("baggage", HeaderValue::from_static("tenant=contoso,session=secret"));
+//! # let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+//! // read allowed entries of an incoming request and attach them to all telemetry
+//! let baggage = Baggage::from_headers(&incoming).allow(["tenant", "order_id"]);
+//! baggage.apply(client.context_mut().properties_mut());
+//! assert_eq!(client.context().properties().get("tenant"), Some(&"contoso".to_string()));
+//! assert_eq!(client.context().properties().get("session"), None);
+//!
+//! // propagate the same entries to an outgoing request
+//! let mut outgoing = HeaderMap::new();
+//! Baggage::from_properties(client.context().properties())
+//!     .allow(["tenant", "order_id"])
+//!     .inject(&mut outgoing);
+//! assert_eq!(outgoing["baggage"], "tenant=contoso");
+//! ```
+use std::{collections::BTreeMap, fmt, iter::FromIterator};
+
+use http::{header::HeaderName, HeaderMap, HeaderValue};
+use log::debug;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+
+use crate::telemetry::Properties;
+
+/// A name of the header that carries baggage.
+pub const BAGGAGE_HEADER: &str = "baggage";
+
+/// Maximum number of members a baggage header may contain.
+const MAX_MEMBERS: usize = 180;
+
+/// Maximum length of a baggage header in bytes.
+const MAX_LEN: usize = 8192;
+
+/// Characters to percent-encode in a baggage value: everything but `baggage-octet`.
+const VALUE_ENCODE_SET: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b',').add(b';').add(b'\\').add(b'%');
+
+/// A set of key-value entries propagated with the `baggage` header.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Baggage {
+    entries: BTreeMap<String, String>,
+}
+
+impl Baggage {
+    /// Creates an empty baggage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses a value of the `baggage` header. Malformed members are skipped, and member properties are ignored.
+    pub fn parse(value: &str) -> Self {
+        let mut baggage = Self::new();
+
+        for member in value.split(',') {
+            // drop member properties, if any
+            let member = member.split(';').next().unwrap_or_default();
+
+            match member.split_once('=') {
+                Some((key, value)) if is_token(key.trim()) => match percent_decode_str(value.trim()).decode_utf8() {
+                    Ok(value) => baggage.insert(key.trim(), value),
+                    Err(_) => debug!("Skipping baggage member with invalid value: {}", key.trim()),
+                },
+                _ if member.trim().is_empty() => {}
+                _ => debug!("Skipping malformed baggage member: {}", member),
+            }
+        }
+
+        baggage
+    }
+
+    /// Reads entries from all `baggage` headers of the request.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let mut baggage = Self::new();
+        for value in headers.get_all(BAGGAGE_HEADER) {
+            if let Ok(value) = value.to_str() {
+                baggage.entries.extend(Self::parse(value).entries);
+            }
+        }
+        baggage
+    }
+
+    /// Creates a baggage from custom properties. Use [`allow`](#method.allow) to select properties to propagate.
+    pub fn from_properties(properties: &Properties) -> Self {
+        let entries = properties
+            .iter()
+            .filter(|(key, _)| is_token(key))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        Self { entries }
+    }
+
+    /// Keeps only entries with the given keys.
+    pub fn allow<I, K>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: AsRef<str>,
+    {
+        let keys: Vec<K> = keys.into_iter().collect();
+        self.entries
+            .retain(|key, _| keys.iter().any(|allowed| allowed.as_ref() == key));
+        self
+    }
+
+    /// Returns a value of the entry with the given key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(String::as_str)
+    }
+
+    /// Adds an entry, replacing the value of existing one.
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.entries.insert(key.into(), value.into());
+    }
+
+    /// Returns an iterator over entries sorted by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// Returns a number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Determines whether there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Records entries as custom properties, overriding properties with the same keys.
+    pub fn apply(&self, properties: &mut Properties) {
+        for (key, value) in &self.entries {
+            properties.insert(key.clone(), value.clone());
+        }
+    }
+
+    /// Writes entries to the `baggage` header, replacing existing one. Does nothing if there are no entries.
+    pub fn inject(&self, headers: &mut HeaderMap) {
+        if self.is_empty() {
+            return;
+        }
+
+        match HeaderValue::from_str(&self.to_string()) {
+            Ok(value) => {
+                headers.insert(HeaderName::from_static(BAGGAGE_HEADER), value);
+            }
+            Err(err) => debug!("Unable to write baggage header: {}", err),
+        }
+    }
+}
+
+/// Formats baggage as a value of the `baggage` header. Entries exceeding W3C limits are omitted.
+impl fmt::Display for Baggage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut len = 0;
+        for (count, (key, value)) in self.entries.iter().enumerate() {
+            let member = format!("{}={}", key, utf8_percent_encode(value, VALUE_ENCODE_SET));
+            let separator = if count == 0 { "" } else { "," };
+
+            if count >= MAX_MEMBERS || len + separator.len() + member.len() > MAX_LEN {
+                debug!(
+                    "Baggage exceeds W3C limits. Omitted {} entries",
+                    self.entries.len() - count
+                );
+                break;
+            }
+
+            len += separator.len() + member.len();
+            write!(f, "{}{}", separator, member)?;
+        }
+        Ok(())
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for Baggage
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let entries = iter
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.into()))
+            .collect();
+        Self { entries }
+    }
+}
+
+/// Determines whether a key is a valid RFC 7230 token.
+fn is_token(key: &str) -> bool {
+    !key.is_empty()
+        && key.bytes().all(|c| {
+            c.is_ascii_alphanumeric()
+                || matches!(
+                    c,
+                    b'!' | b'#'
+                        | b'$'
+                        | b'%'
+                        | b'&'
+                        | b'\''
+                        | b'*'
+                        | b'+'
+                        | b'-'
+                        | b'.'
+                        | b'^'
+                        | b'_'
+                        | b'`'
+                        | b'|'
+                        | b'~'
+                )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("tenant=contoso",                               &[("tenant", "contoso")]                        ; "single member")]
+    #[test_case("tenant=contoso, order_id = 42",                &[("order_id", "42"), ("tenant", "contoso")]    ; "several members")]
+    #[test_case("tenant=contoso;ttl=10",                        &[("tenant", "contoso")]                        ; "member with properties")]
+    #[test_case("name=John%20Doe%2C%20Jr.",                     &[("name", "John Doe, Jr.")]                    ; "percent encoded value")]
+    #[test_case("tenant=contoso,,bad key=1,noequals,=empty",    &[("tenant", "contoso")]                        ; "malformed members")]
+    #[test_case("",                                             &[]                                             ; "empty header")]
+    fn it_parses_baggage(value: &str, expected: &[(&str, &str)]) {
+        let baggage = Baggage::parse(value);
+
+        assert_eq!(baggage.iter().collect::<Vec<_>>(), expected.to_vec());
+    }
+
+    #[test]
+    fn it_reads_all_baggage_headers() {
+        let mut headers = HeaderMap::new();
+        headers.append(BAGGAGE_HEADER, HeaderValue::from_static("tenant=contoso"));
+        headers.append(BAGGAGE_HEADER, HeaderValue::from_static("order_id=42"));
+
+        let baggage = Baggage::from_headers(&headers);
+
+        assert_eq!(baggage.get("tenant"), Some("contoso"));
+        assert_eq!(baggage.get("order_id"), Some("42"));
+    }
+
+    #[test]
+    fn it_keeps_only_allowed_entries() {
+        let baggage = Baggage::parse("tenant=contoso,order_id=42,session=secret").allow(["tenant", "order_id"]);
+
+        assert_eq!(
+            baggage.iter().collect::<Vec<_>>(),
+            vec![("order_id", "42"), ("tenant", "contoso")]
+        );
+    }
+
+    #[test]
+    fn it_applies_entries_to_properties() {
+        let mut properties = Properties::default();
+        properties.insert("tenant".into(), "fabrikam".into());
+        properties.insert("component".into(), "api".into());
+
+        Baggage::parse("tenant=contoso").apply(&mut properties);
+
+        assert_eq!(properties.get("tenant"), Some(&"contoso".to_string()));
+        assert_eq!(properties.get("component"), Some(&"api".to_string()));
+    }
+
+    #[test]
+    fn it_injects_baggage_header() {
+        let mut properties = Properties::default();
+        properties.insert("tenant".into(), "contoso".into());
+        properties.insert("name".into(), "John Doe, Jr.".into());
+        properties.insert("not a token".into(), "skipped".into());
+
+        let mut headers = HeaderMap::new();
+        Baggage::from_properties(&properties).inject(&mut headers);
+
+        assert_eq!(headers[BAGGAGE_HEADER], "name=John%20Doe%2C%20Jr.,tenant=contoso");
+        assert_eq!(Baggage::from_headers(&headers).get("name"), Some("John Doe, Jr."));
+    }
+
+    #[test]
+    fn it_does_not_inject_empty_baggage() {
+        let mut headers = HeaderMap::new();
+
+        Baggage::new().inject(&mut headers);
+
+        assert!(headers.is_empty());
+    }
+
+    #[test]
+    fn it_omits_members_exceeding_limits() {
+        let baggage: Baggage = (0..200).map(|i| (format!("key{:03}", i), "value")).collect();
+
+        let value = baggage.to_string();
+
+        assert_eq!(value.split(',').count(), MAX_MEMBERS);
+
+        let baggage: Baggage = (0..3).map(|i| (format!("key{}", i), "x".repeat(4000))).collect();
+
+        let value = baggage.to_string();
+
+        assert_eq!(value.split(',').count(), 2);
+        assert!(value.len() <= MAX_LEN);
+    }
+}
@@ -13,24 +13,17 @@ use hyper::{
     service::{make_service_fn, service_fn},
     Body, Request, Response, Server, StatusCode,
 };
-use lazy_static::lazy_static;
 use matches::assert_matches;
-use parking_lot::Mutex;
 use serde_json::json;
 use tokio::sync::oneshot;
 
-use crate::{blocking::TelemetryClient, timeout, TelemetryConfig};
-
-lazy_static! {
-    /// A global lock since most tests need to run in serial.
-    static ref SERIAL_TEST_MUTEX: Mutex<()> = Mutex::new(());
-}
+use crate::{blocking::TelemetryClient, test_util::DrainMarker, timeout, TelemetryConfig};
 
 macro_rules! manual_timeout_test {
     (fn $name: ident() $body: block) => {
         #[test]
         fn $name() {
-            let _guard = SERIAL_TEST_MUTEX.lock();
+            let _guard = timeout::SERIAL_TEST_MUTEX.lock();
 
             timeout::init();
 
@@ -59,21 +52,20 @@ manual_timeout_test! {
     fn it_does_not_resend_submitted_telemetry_items() {
         let server = server().status(StatusCode::OK).create();
 
-        let client = create_client(server.url());
+        let (client, marker) = create_client_with_marker(server.url());
         client.track_event("--event--");
 
         // verify 1 items is sent after first interval expired
 
         // "wait" until interval expired
         timeout::expire();
+        assert!(marker.wait_blocking(1, Duration::from_secs(5)));
         assert_matches!(server.next_request_timeout(), Ok(_));
 
         // verify no items is sent after next interval expired
         timeout::expire();
-        assert_matches!(
-            server.next_request_timeout(),
-            Err(RecvTimeoutError::Timeout)
-        );
+        assert!(marker.wait_blocking(2, Duration::from_secs(5)));
+        assert_matches!(server.try_next_request(), None);
     }
 }
 
@@ -81,15 +73,16 @@ manual_timeout_test! {
     fn it_sends_telemetry_items_in_several_batches() {
         let server = server().status(StatusCode::OK).status(StatusCode::OK).create();
 
-        let client = create_client(server.url());
+        let (client, marker) = create_client_with_marker(server.url());
 
         // send 10 items and then interval expired
         for i in 0..10 {
             client.track_event(format!("--event {}--", i));
         }
 
-        // "wait" until interval expired
+        // "wait" until interval expired and first batch is sent
         timeout::expire();
+        assert!(marker.wait_blocking(1, Duration::from_secs(5)));
 
         // send next 5 items and then interval expired
         for i in 10..15 {
@@ -124,7 +117,7 @@ manual_timeout_test! {
         }
 
         // force client to send all items to the server
-        client.flush_channel();
+        client.flush_channel().unwrap();
 
         // NOTE no timeout expired
         // assert that 1 request has been sent
@@ -143,6 +136,23 @@ manual_timeout_test! {
     }
 }
 
+manual_timeout_test! {
+    fn it_waits_until_flushed_telemetry_items_sent() {
+        let server = server().status(StatusCode::OK).create();
+
+        let client = create_client(server.url());
+        for i in 0..15 {
+            client.track_event(format!("--event {}--", i));
+        }
+
+        // NOTE no timeout expired
+        let report = client.flush_and_wait().unwrap();
+        assert_eq!(report.sent(), 15);
+        assert_eq!(report.failed(), 0);
+        assert_matches!(server.try_next_request(), Some(_));
+    }
+}
+
 manual_timeout_test! {
     fn it_does_not_send_any_pending_telemetry_items_when_drop_client() {
         let server = server().status(StatusCode::OK).status(StatusCode::OK).create();
@@ -178,7 +188,7 @@ manual_timeout_test! {
 
         // close internal channel means that client will make an attempt to send telemetry items once
         // and then tear down submission flow
-        client.close_channel();
+        client.close_channel().unwrap();
 
         // NOTE no timeout expired
         // verify that 1 request has been sent
@@ -349,6 +359,18 @@ fn create_client(endpoint: &str) -> TelemetryClient {
     TelemetryClient::from_config(config)
 }
 
+fn create_client_with_marker(endpoint: &str) -> (TelemetryClient, DrainMarker) {
+    let marker = DrainMarker::new();
+    let config = TelemetryConfig::builder()
+        .i_key("instrumentation key")
+        .endpoint(endpoint)
+        .interval(Duration::from_millis(300))
+        .drain_marker(marker.clone())
+        .build();
+
+    (TelemetryClient::from_config(config), marker)
+}
+
 struct Builder {
     responses: Vec<Response<String>>,
 }
@@ -372,6 +394,10 @@ impl HyperTestServer {
         self.request_recv.recv_timeout(Duration::from_millis(500))
     }
 
+    fn try_next_request(&self) -> Option<String> {
+        self.request_recv.try_recv().ok()
+    }
+
     fn wait_for_requests(&self, count: usize) -> Vec<String> {
         let mut requests = Vec::new();
 
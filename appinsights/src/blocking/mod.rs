@@ -19,23 +19,49 @@
 //!
 //! // stop the client
 //! // NOT it will **block** the current thread until
-//! client.close_channel();
+//! client.close_channel().expect("submission thread is running");
 //! ```
+//!
+//! All telemetry is processed by a background thread. When that thread is no longer running (for
+//! instance, a custom channel panicked), methods that return a `Result` report [`Error::Disconnected`]
+//! so callers can detect a broken pipeline.
+//!
+//! The blocking client does not shed telemetry items while the channel falls behind, so
+//! [`overload_threshold`](../struct.TelemetryConfigBuilder.html#method.overload_threshold) has no effect on it.
 
-use std::{fmt::Display, time::Duration};
+use std::{
+    fmt::{self, Display},
+    time::Duration,
+};
+#[cfg(feature = "runtime")]
+use std::{future, sync::OnceLock};
 
 use http::{Method, Uri};
+#[cfg(not(feature = "disabled"))]
 use log::debug;
+use log::warn;
+#[cfg(feature = "runtime")]
+use tokio::runtime::Handle;
+#[cfg(not(feature = "disabled"))]
+use tokio::runtime::RuntimeFlavor;
+#[cfg(not(feature = "disabled"))]
 use tokio::sync::mpsc;
 
+#[cfg(feature = "runtime")]
+use crate::channel::TelemetryChannel;
+#[cfg(not(feature = "disabled"))]
+use crate::{callback, scope, startup};
 use crate::{
-    channel::{InMemoryChannel, TelemetryChannel},
+    client::{self, DISABLED},
+    config::{RequestNameNormalizer, Shared},
     contracts::Envelope,
+    processor::{Pipeline, TelemetryProcessor},
+    sampling::{self, Sampler},
     telemetry::{
-        AvailabilityTelemetry, EventTelemetry, MetricTelemetry, RemoteDependencyTelemetry, RequestTelemetry,
-        SeverityLevel, Telemetry, TraceTelemetry,
+        AvailabilityTelemetry, EventTelemetry, MetricTelemetry, PageViewTelemetry, RemoteDependencyTelemetry,
+        RequestTelemetry, Telemetry, ToSeverityLevel, TraceTelemetry,
     },
-    TelemetryConfig, TelemetryContext,
+    FlushReport, TelemetryConfig, TelemetryContext, Tracker,
 };
 
 /// A blocking version of Application Insights telemetry client. It provides an interface to track telemetry items.
@@ -49,21 +75,71 @@ impl TelemetryClient {
         Self::from_config(TelemetryConfig::new(i_key))
     }
 
-    /// Creates a new telemetry client configured with specified configuration.
+    /// Creates a new telemetry client configured with specified configuration. The client picks a channel the
+    /// same way the async client does, e.g. it spools telemetry to the
+    /// [`persistence_dir`](../struct.TelemetryConfigBuilder.html#method.persistence_dir) if configured.
     pub fn from_config(config: TelemetryConfig) -> Self {
-        Self::create(config, |config| InMemoryChannel::new(config))
+        #[cfg(not(feature = "disabled"))]
+        {
+            Self::create(config, client::channel)
+        }
+        #[cfg(feature = "disabled")]
+        {
+            Self {
+                inner: ChannelHandle::new(config),
+            }
+        }
     }
 
-    pub(crate) fn create<C, F>(config: TelemetryConfig, channel: F) -> Self
+    /// Creates a new telemetry client configured with specified configuration that submits telemetry with a
+    /// custom channel. The channel is created with the given function on the background thread, or within the
+    /// configured [`runtime`](../struct.TelemetryConfigBuilder.html#method.runtime), since channels usually
+    /// spawn tasks when they are created. With the `disabled` feature the channel is never created.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use appinsights::{blocking::TelemetryClient, InMemoryChannel, TelemetryConfig};
+    ///
+    /// let config = TelemetryConfig::new("<instrumentation key>".to_string());
+    /// let client = TelemetryClient::with_channel(config, |config| InMemoryChannel::new(config));
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn with_channel<C, F>(config: TelemetryConfig, channel: F) -> Self
     where
-        C: TelemetryChannel,
+        C: TelemetryChannel + 'static,
         F: FnOnce(&TelemetryConfig) -> C + Send + 'static,
     {
-        let inner = ChannelHandle::new(config, channel);
-        Self { inner }
+        #[cfg(not(feature = "disabled"))]
+        {
+            Self::create(config, move |config| {
+                Box::new(channel(config)) as Box<dyn TelemetryChannel>
+            })
+        }
+        #[cfg(feature = "disabled")]
+        {
+            drop(channel);
+            Self::from_config(config)
+        }
+    }
+
+    #[cfg(not(feature = "disabled"))]
+    fn create<F>(config: TelemetryConfig, channel: F) -> Self
+    where
+        F: FnOnce(&TelemetryConfig) -> Box<dyn TelemetryChannel> + Send + 'static,
+    {
+        let startup = config.startup_event().then(|| startup::event(&config));
+        let client = Self {
+            inner: ChannelHandle::new(config, channel),
+        };
+        if let Some(event) = startup {
+            client.track(event);
+        }
+        client
     }
 
-    /// Determines whether this client is enabled and will accept telemetry.
+    /// Determines whether this client is enabled and will accept telemetry. Always returns `false` when the
+    /// crate is compiled with the `disabled` feature.
     pub fn is_enabled(&self) -> bool {
         self.inner.is_enabled()
     }
@@ -73,6 +149,20 @@ impl TelemetryClient {
         self.inner.enabled(enabled);
     }
 
+    /// Registers a processor that can modify or drop telemetry items before they are queued. Processors run in
+    /// the order they were added on the thread that tracks the item. See [`processor`](../processor/index.html)
+    /// for details.
+    pub fn add_processor(&mut self, processor: impl TelemetryProcessor + 'static) {
+        self.inner.processors.add(processor);
+    }
+
+    /// Determines whether telemetry items of the operation with the given id are kept according to the
+    /// configured [`sampling_percentage`](../struct.TelemetryConfig.html#method.sampling_percentage), reduced
+    /// while the ingestion endpoint is overloaded if sampling feedback is enabled.
+    pub fn is_sampled_in(&self, operation_id: &str) -> bool {
+        sampling::is_sampled_in(operation_id, self.inner.sampler.percentage())
+    }
+
     /// Returns an immutable reference to a collection of tag data to attach to the telemetry item.
     pub fn context(&self) -> &TelemetryContext {
         &self.inner.context
@@ -89,8 +179,14 @@ impl TelemetryClient {
         self.track(event)
     }
 
+    /// Logs a user action with the specified static name without copying the name.
+    pub fn track_event_static(&self, name: &'static str) {
+        let event = EventTelemetry::from_static(name);
+        self.track(event)
+    }
+
     /// Logs a trace message with a specified severity level.
-    pub fn track_trace(&self, message: impl Into<String>, severity: SeverityLevel) {
+    pub fn track_trace(&self, message: impl Into<String>, severity: impl ToSeverityLevel) {
         let event = TraceTelemetry::new(message, severity);
         self.track(event)
     }
@@ -102,10 +198,31 @@ impl TelemetryClient {
         self.track(event)
     }
 
-    /// Logs a HTTP request with the specified method, URL, duration and response code.
-    pub fn track_request(&self, method: Method, uri: Uri, duration: Duration, response_code: impl Into<String>) {
-        let event = RequestTelemetry::new(method, uri, duration, response_code);
-        self.track(event)
+    /// Logs a HTTP request with the specified method, URL, duration and response code. Returns a generated id
+    /// of the request that child telemetry can refer to as its operation parent id.
+    pub fn track_request(
+        &self,
+        method: Method,
+        uri: Uri,
+        duration: Duration,
+        response_code: impl Into<String>,
+    ) -> String {
+        let event = self.request(method, uri, duration, response_code);
+        let id = event.id().unwrap_or_default().to_string();
+        self.track(event);
+        id
+    }
+
+    /// Creates a request telemetry item named by the configured request name normalizer, if any.
+    pub(crate) fn request(
+        &self,
+        method: Method,
+        uri: Uri,
+        duration: Duration,
+        response_code: impl Into<String>,
+    ) -> RequestTelemetry {
+        let normalizer = self.inner.request_name_normalizer.as_ref();
+        client::request(normalizer, method, uri, duration, response_code)
     }
 
     /// Logs a dependency with the specified name, type, target, and success status.
@@ -120,24 +237,117 @@ impl TelemetryClient {
         self.track(event)
     }
 
+    /// Logs an outcome of a fallible operation with the specified name and duration. A successful operation is
+    /// tracked as an in-process dependency, and a failed one as an exception with the chain of errors.
+    pub fn track_result<T, E>(&self, name: impl Into<String>, result: &Result<T, E>, duration: Duration)
+    where
+        E: std::error::Error,
+    {
+        match result {
+            Ok(_) => self.track(RemoteDependencyTelemetry::new(
+                name,
+                client::IN_PROC_DEPENDENCY_TYPE,
+                duration,
+                "",
+                true,
+            )),
+            Err(err) => self.track(client::result_exception(name, err, duration)),
+        }
+    }
+
     /// Logs an availability test result with the specified test name, duration, and success status.
     pub fn track_availability(&self, name: impl Into<String>, duration: Duration, success: bool) {
         let event = AvailabilityTelemetry::new(name, duration, success);
         self.track(event)
     }
 
+    /// Logs an availability test result with the specified test name, duration, and success status, and
+    /// details set by the given function, such as where the test ran and why it failed.
+    pub fn track_availability_with(
+        &self,
+        name: impl Into<String>,
+        duration: Duration,
+        success: bool,
+        f: impl FnOnce(&mut AvailabilityTelemetry),
+    ) {
+        let mut event = AvailabilityTelemetry::new(name, duration, success);
+        f(&mut event);
+        self.track(event)
+    }
+
+    /// Logs a page view with the specified name and URL.
+    pub fn track_page_view(&self, name: impl Into<String>, uri: Uri) {
+        let event = PageViewTelemetry::new(name, uri);
+        self.track(event)
+    }
+
     /// Submits a specific telemetry event.
+    ///
+    /// When the background thread is no longer running the telemetry item is discarded and a warning
+    /// is logged. Use [`try_track`](#method.try_track) to detect this case.
     pub fn track<E>(&self, event: E)
     where
         E: Telemetry,
         (TelemetryContext, E): Into<Envelope>,
     {
-        self.inner.track(event);
+        if let Err(err) = self.inner.track(&self.inner.context, event) {
+            warn!("Unable to submit telemetry item: {}", err);
+        }
+    }
+
+    /// Submits a specific telemetry event with the given context instead of the context of the client.
+    /// Applications that handle requests of many tenants or users can supply request-scoped contexts, e.g. with
+    /// a different user id or instrumentation key, without mutating the shared client.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::blocking::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use appinsights::telemetry::EventTelemetry;
+    ///
+    /// let mut context = client.context().clone();
+    /// context.tags_mut().user_mut().set_id("alice".into());
+    ///
+    /// client.track_with_context(&context, EventTelemetry::new("order placed"));
+    /// ```
+    pub fn track_with_context<E>(&self, context: &TelemetryContext, event: E)
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        if let Err(err) = self.inner.track(context, event) {
+            warn!("Unable to submit telemetry item: {}", err);
+        }
+    }
+
+    /// Submits a specific telemetry event and returns an error when the background thread
+    /// is no longer running.
+    pub fn try_track<E>(&self, event: E) -> Result<(), Error>
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        self.inner.track(&self.inner.context, event)
     }
 
     /// Forces all pending telemetry items to be submitted. The current thread will not be blocked.
-    pub fn flush_channel(&self) {
-        self.inner.flush();
+    pub fn flush_channel(&self) -> Result<(), Error> {
+        self.inner.flush()
+    }
+
+    /// Forces all pending telemetry items to be submitted and blocks the current thread until the attempt to
+    /// submit them has completed, successfully or not. Returns numbers of items the ingestion endpoint
+    /// accepted and did not accept.
+    pub fn flush_and_wait(&self) -> Result<FlushReport, Error> {
+        self.inner.flush_and_wait()
+    }
+
+    /// Forces all pending telemetry items to be submitted and releases memory the channel keeps in reserve
+    /// once they are sent. Hosts can call this method when the process is under memory pressure.
+    /// The current thread will not be blocked.
+    pub fn shrink_channel(&self) -> Result<(), Error> {
+        self.inner.shrink()
     }
 
     /// Flushes and tears down the submission flow and closes internal channels.
@@ -149,7 +359,7 @@ impl TelemetryClient {
     /// # Examples
     ///
     /// ```rust, no_run
-    /// # use appinsights::TelemetryClient;
+    /// # use appinsights::blocking::TelemetryClient;
     /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
     /// // send heartbeats while application is running
     /// let running = true;
@@ -158,13 +368,15 @@ impl TelemetryClient {
     /// }
     ///
     /// // wait until pending telemetry is sent at most once and tear down submission flow
-    /// client.close_channel();
+    /// if let Err(err) = client.close_channel() {
+    ///     eprintln!("telemetry may have been lost: {}", err);
+    /// }
     ///
     /// // unable to sent any telemetry after client closes its channel
     /// // client.track_event("app is stopped".to_string());
     /// ```
-    pub fn close_channel(self) {
-        self.inner.close();
+    pub fn close_channel(self) -> Result<(), Error> {
+        self.inner.close()
     }
 
     /// Tears down the submission flow and closes internal channels.
@@ -175,7 +387,7 @@ impl TelemetryClient {
     /// # Examples
     ///
     /// ```rust, no_run
-    /// # use appinsights::TelemetryClient;
+    /// # use appinsights::blocking::TelemetryClient;
     /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
     /// // send heartbeats while application is running
     /// let running = true;
@@ -185,158 +397,406 @@ impl TelemetryClient {
     ///
     /// // wait until pending telemetry is sent at most once and tear down submission flow
     /// // or just drop(client)
-    /// client.terminate();
+    /// client.terminate().expect("submission thread is running");
     ///
     /// // unable to sent any telemetry after client closes its channel
     /// // client.track_event("app is stopped".to_string());
     /// ```
-    pub fn terminate(self) {}
+    pub fn terminate(self) -> Result<(), Error> {
+        self.inner.terminate()
+    }
+}
+
+impl Tracker for TelemetryClient {
+    fn track<E>(&self, event: E)
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        TelemetryClient::track(self, event)
+    }
+}
+
+/// An error returned by the blocking telemetry client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The background thread that submits telemetry has exited or panicked, so telemetry can no longer be
+    /// processed.
+    Disconnected,
+
+    /// The client was asked to wait for its task on a runtime configured with
+    /// [`runtime`](../struct.TelemetryConfigBuilder.html#method.runtime) from a thread that drives a
+    /// single-threaded runtime, where the task might never make progress.
+    WithinRuntime,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Disconnected => write!(f, "telemetry submission thread is not running"),
+            Error::WithinRuntime => write!(
+                f,
+                "unable to wait for telemetry submission within a single-threaded runtime"
+            ),
+        }
+    }
 }
 
+impl std::error::Error for Error {}
+
 struct ChannelHandle {
     enabled: bool,
     context: TelemetryContext,
+    processors: Pipeline,
+    sampler: Sampler,
+    request_name_normalizer: Option<Shared<RequestNameNormalizer>>,
+    #[cfg(not(feature = "disabled"))]
     inner: InnerChannelHandle,
 }
 
 impl ChannelHandle {
-    fn new<C, F>(config: TelemetryConfig, channel: F) -> Self
+    /// Creates a handle that swallows all telemetry, since telemetry is compiled out and there is nothing to
+    /// process in the background.
+    #[cfg(feature = "disabled")]
+    fn new(mut config: TelemetryConfig) -> Self {
+        ChannelHandle {
+            enabled: true,
+            context: TelemetryContext::from(&config),
+            processors: Pipeline::default(),
+            sampler: Sampler::new(&mut config),
+            request_name_normalizer: config.request_name_normalizer().cloned(),
+        }
+    }
+
+    #[cfg(not(feature = "disabled"))]
+    fn new<F>(mut config: TelemetryConfig, channel: F) -> Self
     where
-        C: TelemetryChannel,
-        F: FnOnce(&TelemetryConfig) -> C + Send + 'static,
+        F: FnOnce(&TelemetryConfig) -> Box<dyn TelemetryChannel> + Send + 'static,
     {
-        let context = TelemetryContext::from_config(&config);
+        let context = TelemetryContext::from(&config);
+        let sampler = Sampler::new(&mut config);
+        let request_name_normalizer = config.request_name_normalizer().cloned();
 
         let (tx, mut rx) = mpsc::unbounded_channel::<(ClientCommand, OneshotResponse)>();
 
-        let handle = std::thread::Builder::new()
-            .name("appinsights-internal-sync-runtime".into())
-            .spawn(move || {
-                let rt = tokio::runtime::Builder::new_current_thread()
-                    .enable_all()
-                    .build()
-                    .expect("tokio runtime");
-
-                let f = async move {
-                    let mut channel = channel(&config);
-
-                    while let Some((command, req_tx)) = rx.recv().await {
-                        match command {
-                            ClientCommand::Envelope(envelop) => channel.send(envelop),
-                            ClientCommand::Flush => channel.flush(),
-                            ClientCommand::Stop => channel.close().await,
-                            ClientCommand::Terminate => channel.terminate().await,
-                        }
-                        let _ = req_tx.send(());
+        let runtime = config.runtime().cloned();
+        let name = config.thread_name().unwrap_or(DEFAULT_THREAD_NAME).to_string();
+        let on_thread_start = config.on_thread_start().cloned();
+
+        let f = async move {
+            let mut channel = channel(&config);
+
+            while let Some((command, req_tx)) = rx.recv().await {
+                match command {
+                    ClientCommand::Envelope(envelop) => channel.send(*envelop),
+                    ClientCommand::Flush => channel.flush(),
+                    ClientCommand::FlushAndWait(report_tx) => {
+                        let _ = report_tx.send(channel.flush_and_wait().await).await;
                     }
-                };
-                rt.block_on(f);
-            })
-            .expect("failed to create a thread");
+                    ClientCommand::Shrink => channel.shrink(),
+                    ClientCommand::Stop => channel.close().await,
+                    ClientCommand::Terminate => channel.terminate().await,
+                }
+                let _ = req_tx.send(()).await;
+            }
+        };
+
+        let worker = match runtime {
+            Some(runtime) => {
+                let task = runtime.spawn(f);
+                WorkerHandle::Task(runtime, task)
+            }
+            None => {
+                let handle = std::thread::Builder::new()
+                    .name(name)
+                    .spawn(move || {
+                        if let Some(on_thread_start) = on_thread_start {
+                            callback::call("Thread start callback", &*on_thread_start);
+                        }
+
+                        let rt = tokio::runtime::Builder::new_current_thread()
+                            .enable_all()
+                            .build()
+                            .expect("tokio runtime");
+
+                        rt.block_on(f);
+                    })
+                    .expect("failed to create a thread");
+                WorkerHandle::Thread(handle)
+            }
+        };
 
         let inner = InnerChannelHandle {
             tx: Some(tx),
-            thread: Some(handle),
+            worker: Some(worker),
         };
 
         ChannelHandle {
             inner,
             enabled: true,
             context,
+            processors: Pipeline::default(),
+            sampler,
+            request_name_normalizer,
         }
     }
 
     pub fn is_enabled(&self) -> bool {
-        self.enabled
+        self.enabled && !DISABLED
     }
 
     pub fn enabled(&mut self, enabled: bool) {
         self.enabled = enabled;
     }
 
-    fn track<E>(&self, event: E)
+    fn track<E>(&self, context: &TelemetryContext, event: E) -> Result<(), Error>
     where
         E: Telemetry,
         (TelemetryContext, E): Into<Envelope>,
     {
+        #[cfg(feature = "disabled")]
+        let _ = (context, event);
+        #[cfg(not(feature = "disabled"))]
         if self.is_enabled() {
-            let envelop = (self.context.clone(), event).into();
-            let command = ClientCommand::Envelope(envelop);
+            let mut envelop = (context.clone(), event).into();
+            scope::stamp(&mut envelop);
+            if !self.processors.process(&mut envelop) || !sampling::sample(&mut envelop, self.sampler.percentage()) {
+                return Ok(());
+            }
+            return self.inner.send(ClientCommand::Envelope(Box::new(envelop)));
+        }
+        Ok(())
+    }
 
-            let (tx, mut rx) = mpsc::channel(1);
+    fn flush(&self) -> Result<(), Error> {
+        #[cfg(not(feature = "disabled"))]
+        {
+            self.inner.send(ClientCommand::Flush)
+        }
+        #[cfg(feature = "disabled")]
+        {
+            Ok(())
+        }
+    }
 
-            self.inner
-                .tx
-                .as_ref()
-                .expect("sync thread exited early")
-                .send((command, tx))
-                .expect("sync thread panicked");
+    fn flush_and_wait(&self) -> Result<FlushReport, Error> {
+        #[cfg(not(feature = "disabled"))]
+        {
+            let (tx, mut rx) = mpsc::channel(1);
+            self.inner.send(ClientCommand::FlushAndWait(tx))?;
+            rx.blocking_recv().ok_or(Error::Disconnected)
+        }
+        #[cfg(feature = "disabled")]
+        {
+            Ok(FlushReport::default())
+        }
+    }
 
-            let _ = rx.blocking_recv();
+    fn shrink(&self) -> Result<(), Error> {
+        #[cfg(not(feature = "disabled"))]
+        {
+            self.inner.send(ClientCommand::Shrink)
+        }
+        #[cfg(feature = "disabled")]
+        {
+            Ok(())
         }
     }
 
-    fn flush(&self) {
-        self.inner.flush();
+    #[cfg_attr(feature = "disabled", allow(unused_mut))]
+    fn close(mut self) -> Result<(), Error> {
+        #[cfg(not(feature = "disabled"))]
+        {
+            self.inner.shutdown(ClientCommand::Stop)
+        }
+        #[cfg(feature = "disabled")]
+        {
+            Ok(())
+        }
     }
 
-    fn close(mut self) {
-        self.inner.shutdown(ClientCommand::Stop)
+    #[cfg_attr(feature = "disabled", allow(unused_mut))]
+    fn terminate(mut self) -> Result<(), Error> {
+        #[cfg(not(feature = "disabled"))]
+        {
+            self.inner.shutdown(ClientCommand::Terminate)
+        }
+        #[cfg(feature = "disabled")]
+        {
+            Ok(())
+        }
     }
 }
 
+/// Returns a handle of the runtime that is shared between all blocking clients configured with it.
+///
+/// The runtime is started lazily on a single background thread when this function is called for the
+/// first time and lives until the process exits. Applications creating many clients can use it to avoid
+/// spending a dedicated thread on each client.
+///
+/// ```rust
+/// use appinsights::{blocking::{self, TelemetryClient}, TelemetryConfig};
+///
+/// let clients: Vec<_> = ["<tenant 1 key>", "<tenant 2 key>"]
+///     .iter()
+///     .map(|i_key| {
+///         let config = TelemetryConfig::builder()
+///             .i_key(*i_key)
+///             .runtime(blocking::shared_runtime())
+///             .build();
+///         TelemetryClient::from_config(config)
+///     })
+///     .collect();
+/// ```
+#[cfg(feature = "runtime")]
+pub fn shared_runtime() -> Handle {
+    static RUNTIME: OnceLock<Handle> = OnceLock::new();
+
+    RUNTIME
+        .get_or_init(|| {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("tokio runtime");
+            let handle = rt.handle().clone();
+
+            std::thread::Builder::new()
+                .name(SHARED_THREAD_NAME.into())
+                .spawn(move || rt.block_on(future::pending::<()>()))
+                .expect("failed to create a thread");
+
+            handle
+        })
+        .clone()
+}
+
+#[cfg(feature = "runtime")]
+const SHARED_THREAD_NAME: &str = "appinsights-shared-runtime";
+
+#[cfg(not(feature = "disabled"))]
+const DEFAULT_THREAD_NAME: &str = "appinsights-internal-sync-runtime";
+
+#[cfg(not(feature = "disabled"))]
 type OneshotResponse = mpsc::Sender<()>;
 
+#[cfg(not(feature = "disabled"))]
 type ThreadSender = mpsc::UnboundedSender<(ClientCommand, OneshotResponse)>;
 
+#[cfg(not(feature = "disabled"))]
 struct InnerChannelHandle {
     tx: Option<ThreadSender>,
-    thread: Option<std::thread::JoinHandle<()>>,
+    worker: Option<WorkerHandle>,
+}
+
+/// A handle of the background task that processes client commands.
+#[cfg(not(feature = "disabled"))]
+enum WorkerHandle {
+    /// A dedicated thread running its own runtime.
+    Thread(std::thread::JoinHandle<()>),
+
+    /// A task spawned on a runtime provided by configuration.
+    Task(Handle, tokio::task::JoinHandle<()>),
 }
 
+#[cfg(not(feature = "disabled"))]
+impl WorkerHandle {
+    fn join(self) -> Result<(), Error> {
+        match self {
+            WorkerHandle::Thread(handle) => handle.join().map_err(|_| Error::Disconnected),
+            WorkerHandle::Task(runtime, task) => runtime.block_on(task).map_err(|_| Error::Disconnected),
+        }
+    }
+}
+
+#[cfg(not(feature = "disabled"))]
 impl InnerChannelHandle {
-    fn flush(&self) {
-        if let Some(sender) = &self.tx {
-            send_command(sender, ClientCommand::Flush);
+    fn send(&self, command: ClientCommand) -> Result<(), Error> {
+        match &self.tx {
+            Some(sender) => send_command(sender, command),
+            None => Err(Error::Disconnected),
         }
     }
 
-    fn shutdown(&mut self, command: ClientCommand) {
-        if let Some(sender) = self.tx.take() {
-            send_command(&sender, command);
+    fn shutdown(&mut self, command: ClientCommand) -> Result<(), Error> {
+        let sender = self.tx.take();
+        let worker = self.worker.take();
+
+        let flavor = Handle::try_current().ok().map(|handle| handle.runtime_flavor());
+        if let (Some(RuntimeFlavor::CurrentThread), Some(WorkerHandle::Task(_, _))) = (&flavor, &worker) {
+            // the task may run on the very runtime the current thread drives, so waiting would never end;
+            // the task stops on its own once the sender is dropped
+            return Err(Error::WithinRuntime);
         }
 
-        self.thread.take().map(|h| h.join());
+        outside_runtime(flavor, move || {
+            let sent = match sender {
+                Some(sender) => send_command(&sender, command),
+                None => Ok(()),
+            };
+
+            let joined = match worker {
+                Some(worker) => worker.join(),
+                None => Ok(()),
+            };
+
+            sent.and(joined)
+        })
     }
 }
 
+#[cfg(not(feature = "disabled"))]
 impl Drop for InnerChannelHandle {
     fn drop(&mut self) {
-        self.shutdown(ClientCommand::Terminate)
+        if let Err(err) = self.shutdown(ClientCommand::Terminate) {
+            warn!("Unable to terminate telemetry submission: {}", err);
+        }
     }
 }
 
-fn send_command(sender: &ThreadSender, command: ClientCommand) {
+/// Calls `f` that blocks the current thread. Tokio panics when a thread that runs within a runtime blocks on
+/// another one, e.g. when the client is dropped in an async function, so `f` is called on a dedicated thread
+/// when the current thread runs within a runtime of any `flavor`.
+#[cfg(not(feature = "disabled"))]
+fn outside_runtime<F>(flavor: Option<RuntimeFlavor>, f: F) -> Result<(), Error>
+where
+    F: FnOnce() -> Result<(), Error> + Send,
+{
+    match flavor {
+        Some(_) => std::thread::scope(|scope| scope.spawn(f).join()).unwrap_or(Err(Error::Disconnected)),
+        None => f(),
+    }
+}
+
+#[cfg(not(feature = "disabled"))]
+fn send_command(sender: &ThreadSender, command: ClientCommand) -> Result<(), Error> {
     debug!("Sending {} command to channel", command);
     let (tx, mut rx) = mpsc::channel(1);
-    sender.send((command, tx)).expect("sync thread panicked?");
+    sender.send((command, tx)).map_err(|_| Error::Disconnected)?;
 
-    let _ = rx.blocking_recv();
+    // the background thread acknowledges each command once it has been processed
+    rx.blocking_recv().ok_or(Error::Disconnected)
 }
 
+#[cfg(not(feature = "disabled"))]
 #[derive(Debug, Clone)]
 enum ClientCommand {
-    Envelope(Envelope),
+    Envelope(Box<Envelope>),
     Flush,
+    FlushAndWait(mpsc::Sender<FlushReport>),
+    Shrink,
     Stop,
     Terminate,
 }
 
+#[cfg(not(feature = "disabled"))]
 impl Display for ClientCommand {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let message = match self {
             ClientCommand::Envelope(_) => "event",
             ClientCommand::Flush => "flush",
+            ClientCommand::FlushAndWait(_) => "flush and wait",
+            ClientCommand::Shrink => "shrink",
             ClientCommand::Stop => "stop",
             ClientCommand::Terminate => "terminate",
         };
@@ -345,7 +805,7 @@ impl Display for ClientCommand {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, not(feature = "disabled")))]
 mod tests {
     use std::sync::Arc;
 
@@ -353,7 +813,10 @@ mod tests {
     use matches::assert_matches;
 
     use super::*;
-    use crate::client::tests::{TestChannel, TestTelemetry};
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data},
+    };
 
     #[test]
     fn it_enabled_by_default() {
@@ -375,22 +838,80 @@ mod tests {
         let events = Arc::new(SegQueue::default());
         let client = create_client(events.clone());
 
-        client.track(TestTelemetry {});
+        client.track(EventTelemetry::new("test"));
 
         assert_eq!(events.len(), 1)
     }
 
+    #[test]
+    fn it_submits_telemetry_with_given_context() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let mut context = TelemetryContext::new("tenant".into(), Default::default(), Default::default());
+        context.tags_mut().user_mut().set_id("alice".into());
+        client.track_with_context(&context, EventTelemetry::new("test"));
+        client.track(EventTelemetry::new("test"));
+
+        let envelope = events.pop().unwrap();
+        assert_eq!(envelope.i_key.as_deref(), Some("tenant"));
+        assert_eq!(envelope.tags.unwrap()["ai.user.id"], "alice");
+
+        let envelope = events.pop().unwrap();
+        assert_eq!(envelope.i_key.as_deref(), Some("instrumentation"));
+        assert!(!envelope.tags.unwrap().contains_key("ai.user.id"));
+    }
+
+    #[test]
+    fn it_submits_startup_event_once_client_created() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .startup_event(true)
+            .build();
+        let client = TelemetryClient::with_channel(config, {
+            let events = events.clone();
+            move |_| TestChannel::new(events)
+        });
+        client.track_event("ready");
+
+        assert_matches!(
+            events.pop().and_then(|envelope| envelope.data),
+            Some(Base::Data(Data::EventData(data))) if data.name == startup::STARTUP_EVENT_NAME
+        );
+        assert_eq!(events.len(), 1);
+    }
+
     #[test]
     fn it_swallows_telemetry_when_disabled() {
         let events = Arc::new(SegQueue::default());
         let mut client = create_client(events.clone());
         client.enabled(false);
 
-        client.track(TestTelemetry {});
+        client.track(EventTelemetry::new("test"));
 
         assert!(events.is_empty())
     }
 
+    #[test]
+    fn it_runs_telemetry_through_processors() {
+        let events = Arc::new(SegQueue::default());
+        let mut client = create_client(events.clone());
+        client.add_processor(|envelope: &mut Envelope| {
+            envelope.i_key = Some("processed".into());
+            true
+        });
+        client.add_processor(|envelope: &mut Envelope| {
+            !matches!(&envelope.data, Some(Base::Data(Data::EventData(data))) if data.name == "health")
+        });
+
+        client.track(EventTelemetry::new("health"));
+        client.track(EventTelemetry::new("test"));
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events.pop().unwrap().i_key, Some("processed".into()));
+    }
+
     #[test]
     fn it_creates_client_with_default_tags() {
         let client = TelemetryClient::new("instrumentation".into());
@@ -406,11 +927,161 @@ mod tests {
         assert!(client.is_enabled())
     }
 
+    #[test]
+    fn it_reports_error_when_background_thread_panicked() {
+        let config = TelemetryConfig::new("instrumentation".into());
+        let client = TelemetryClient::with_channel(config, |_| PanickingChannel);
+
+        assert_eq!(client.try_track(EventTelemetry::new("test")), Err(Error::Disconnected));
+        assert_eq!(client.flush_channel(), Err(Error::Disconnected));
+
+        // track does not panic even though the pipeline is broken
+        client.track(EventTelemetry::new("test"));
+
+        assert_eq!(client.close_channel(), Err(Error::Disconnected));
+    }
+
+    #[test]
+    fn it_closes_channel_without_error() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        client.track(EventTelemetry::new("test"));
+
+        assert_eq!(client.close_channel(), Ok(()));
+        assert_eq!(events.len(), 1)
+    }
+
+    #[test]
+    fn it_terminates_channel_without_error() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events);
+
+        assert_eq!(client.terminate(), Ok(()));
+    }
+
+    #[test]
+    fn it_names_background_thread() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let tx = parking_lot::Mutex::new(tx);
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .thread_name("appinsights-tenant")
+            .on_thread_start(move || {
+                let name = std::thread::current().name().map(ToString::to_string);
+                tx.lock().send(name).unwrap();
+            })
+            .build();
+
+        let client = TelemetryClient::with_channel(config, |_| TestChannel::new(Default::default()));
+
+        assert_eq!(rx.recv().unwrap(), Some("appinsights-tenant".into()));
+        client.terminate().unwrap();
+    }
+
+    #[test]
+    fn it_submits_telemetry_when_thread_start_callback_panicked() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .on_thread_start(|| panic!("callback is broken"))
+            .build();
+        let client = {
+            let events = events.clone();
+            TelemetryClient::with_channel(config, move |_| TestChannel::new(events))
+        };
+
+        assert_eq!(client.try_track(EventTelemetry::new("test")), Ok(()));
+        assert_eq!(client.close_channel(), Ok(()));
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn it_submits_telemetry_on_shared_runtime() {
+        let events = Arc::new(SegQueue::default());
+        let clients: Vec<_> = (0..3)
+            .map(|_| {
+                let config = TelemetryConfig::builder()
+                    .i_key("instrumentation")
+                    .runtime(shared_runtime())
+                    .build();
+                let events = events.clone();
+                TelemetryClient::with_channel(config, move |_| TestChannel::new(events))
+            })
+            .collect();
+
+        for client in clients {
+            client.track(EventTelemetry::new("test"));
+            assert_eq!(client.close_channel(), Ok(()));
+        }
+
+        assert_eq!(events.len(), 3)
+    }
+
+    #[test]
+    fn it_reports_error_when_task_on_shared_runtime_panicked() {
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .runtime(shared_runtime())
+            .build();
+        let client = TelemetryClient::with_channel(config, |_| PanickingChannel);
+
+        assert_eq!(client.try_track(EventTelemetry::new("test")), Err(Error::Disconnected));
+        assert_eq!(client.terminate(), Err(Error::Disconnected));
+    }
+
+    #[test]
+    fn it_closes_channel_within_async_context() {
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        let events = Arc::new(SegQueue::default());
+
+        let client = create_client(events.clone());
+        client.track(EventTelemetry::new("test"));
+        assert_eq!(rt.block_on(async move { client.close_channel() }), Ok(()));
+        assert_eq!(events.len(), 1);
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .runtime(rt.handle().clone())
+            .build();
+        let channel = TestChannel::new(events.clone());
+        let client = TelemetryClient::with_channel(config, move |_| channel);
+        client.track(EventTelemetry::new("test"));
+        assert_eq!(rt.block_on(async move { client.close_channel() }), Ok(()));
+        assert_eq!(events.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn it_refuses_to_wait_for_task_within_single_threaded_runtime() {
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .runtime(Handle::current())
+            .build();
+        let client = TelemetryClient::with_channel(config, |_| TestChannel::new(Arc::default()));
+
+        assert_eq!(client.terminate(), Err(Error::WithinRuntime));
+    }
+
+    struct PanickingChannel;
+
+    #[async_trait::async_trait]
+    impl TelemetryChannel for PanickingChannel {
+        fn send(&self, _: Envelope) {
+            panic!("channel is broken");
+        }
+
+        fn flush(&self) {}
+
+        async fn close(&mut self) {}
+
+        async fn terminate(&mut self) {}
+    }
+
     fn create_client(events: Arc<SegQueue<Envelope>>) -> TelemetryClient {
         let config = TelemetryConfig::new("instrumentation".into());
-        TelemetryClient::create(config, |_| TestChannel::new(events))
+        TelemetryClient::with_channel(config, |_| TestChannel::new(events))
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, not(feature = "disabled")))]
 mod integration_tests;
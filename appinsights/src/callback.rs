@@ -0,0 +1,86 @@
+//! Invocation of user-supplied callbacks.
+//!
+//! A panic in a callback must not tear down telemetry submission, so callbacks run under
+//! [`catch_unwind`](std::panic::catch_unwind). A caught panic is logged and the callback result is
+//! treated as a failure by the caller.
+#[cfg(feature = "runtime")]
+use std::future::Future;
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+};
+
+#[cfg(feature = "runtime")]
+use futures_util::FutureExt;
+use log::error;
+
+/// Invokes a callback with the given name and returns its result, or `None` if it panicked.
+pub(crate) fn call<T>(name: &str, f: impl FnOnce() -> T) -> Option<T> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => Some(result),
+        Err(payload) => {
+            error!("{} panicked: {}", name, message(payload.as_ref()));
+            None
+        }
+    }
+}
+
+/// Awaits a future returned by a callback with the given name and returns its output, or `None` if it
+/// panicked.
+#[cfg(feature = "runtime")]
+pub(crate) async fn call_async<F: Future>(name: &str, future: F) -> Option<F::Output> {
+    match AssertUnwindSafe(future).catch_unwind().await {
+        Ok(result) => Some(result),
+        Err(payload) => {
+            error!("{} panicked: {}", name, message(payload.as_ref()));
+            None
+        }
+    }
+}
+
+/// Extracts a message from a panic payload.
+pub(crate) fn message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_returns_callback_result() {
+        assert_eq!(call("callback", || 42), Some(42));
+    }
+
+    #[test]
+    fn it_catches_callback_panic() {
+        let id = 42;
+        assert_eq!(
+            call("callback", || -> i32 { panic!("callback {} is broken", id) }),
+            None
+        );
+    }
+
+    #[cfg(feature = "runtime")]
+    #[tokio::test]
+    async fn it_catches_async_callback_panic() {
+        assert_eq!(call_async("callback", async { 42 }).await, Some(42));
+        assert_eq!(
+            call_async("callback", async { panic!("callback is broken") }).await,
+            None::<i32>
+        );
+    }
+
+    #[test]
+    fn it_extracts_panic_message() {
+        assert_eq!(message(&"static"), "static");
+        assert_eq!(message(&"owned".to_string()), "owned");
+        assert_eq!(message(&42), "unknown panic");
+    }
+}
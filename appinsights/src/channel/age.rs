@@ -0,0 +1,120 @@
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use tokio::{sync::Notify, time::Instant};
+
+use crate::timer::Timer;
+
+/// Tracks when the oldest telemetry item waiting in the queue arrived, so the worker can send a batch
+/// before the item gets older than the configured maximum age and the channel can tell how far behind the
+/// worker is.
+#[derive(Debug, Clone, Default)]
+pub struct ItemAge {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    oldest: Mutex<Option<Instant>>,
+    notify: Notify,
+}
+
+impl ItemAge {
+    /// Records an arrival of an item. Only the first item since the queue was drained counts.
+    pub fn arrived(&self) {
+        let mut oldest = self.lock();
+        if oldest.is_none() {
+            *oldest = Some(Instant::now());
+            self.inner.notify.notify_one();
+        }
+    }
+
+    /// Forgets the oldest item. Called right before the queue is drained, so items that arrive while
+    /// draining are accounted again.
+    pub fn reset(&self) {
+        *self.lock() = None;
+    }
+
+    /// Returns how long the oldest item has been waiting in the queue, or zero if the queue is empty.
+    pub fn elapsed(&self) -> Duration {
+        self.lock().map_or(Duration::ZERO, |oldest| oldest.elapsed())
+    }
+
+    /// Resolves once the oldest item waiting in the queue becomes older than `max_age`.
+    pub async fn expired(&self, timer: &dyn Timer, max_age: Duration) {
+        loop {
+            let oldest = *self.lock();
+            match oldest {
+                Some(oldest) => {
+                    timer.sleep_until((oldest + max_age).into_std()).await;
+                    return;
+                }
+                // a permit stored by an arrival in the meantime wakes up the worker immediately
+                None => self.inner.notify.notified().await,
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<Instant>> {
+        self.inner.oldest.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timer::TokioTimer;
+
+    #[tokio::test]
+    async fn it_expires_after_first_item_gets_old() {
+        let age = ItemAge::default();
+        let started = Instant::now();
+
+        age.arrived();
+        age.expired(&TokioTimer, Duration::from_millis(50)).await;
+
+        assert!(started.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn it_keeps_arrival_of_first_item() {
+        let age = ItemAge::default();
+
+        age.arrived();
+        let oldest = *age.lock();
+        std::thread::sleep(Duration::from_millis(1));
+        age.arrived();
+
+        assert_eq!(*age.lock(), oldest);
+    }
+
+    #[test]
+    fn it_reports_elapsed_time_of_oldest_item() {
+        let age = ItemAge::default();
+        assert_eq!(age.elapsed(), Duration::ZERO);
+
+        age.arrived();
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(age.elapsed() >= Duration::from_millis(10));
+
+        age.reset();
+        assert_eq!(age.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn it_does_not_expire_without_items() {
+        let age = ItemAge::default();
+        age.arrived();
+        age.reset();
+
+        let expired = tokio::time::timeout(
+            Duration::from_millis(50),
+            age.expired(&TokioTimer, Duration::from_millis(10)),
+        )
+        .await;
+
+        assert!(expired.is_err());
+    }
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use crossbeam_queue::SegQueue;
+use tokio::sync::Notify;
+
+use crate::channel::interner::QueuedItem;
+
+/// Wakes up the worker once enough telemetry items are queued to make up a full batch.
+#[derive(Debug, Clone)]
+pub struct BatchSize {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    max: usize,
+    notify: Notify,
+}
+
+impl BatchSize {
+    pub fn new(max: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                max,
+                notify: Notify::new(),
+            }),
+        }
+    }
+
+    /// Records an arrival of an item when `queued` items are waiting in the queue.
+    pub fn arrived(&self, queued: usize) {
+        if queued >= self.inner.max {
+            self.inner.notify.notify_one();
+        }
+    }
+
+    /// Resolves once the queue holds a full batch.
+    pub async fn reached(&self, items: &SegQueue<QueuedItem>) {
+        // a permit stored by an arrival in the meantime wakes up the worker immediately
+        while items.len() < self.inner.max {
+            self.inner.notify.notified().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::contracts::Envelope;
+
+    #[tokio::test]
+    async fn it_resolves_when_batch_is_full() {
+        let batch = BatchSize::new(2);
+        let items = SegQueue::new();
+
+        items.push(QueuedItem::from(Envelope::default()));
+        batch.arrived(items.len());
+        let reached = tokio::time::timeout(Duration::from_millis(10), batch.reached(&items)).await;
+        assert!(reached.is_err());
+
+        items.push(QueuedItem::from(Envelope::default()));
+        batch.arrived(items.len());
+        let reached = tokio::time::timeout(Duration::from_millis(10), batch.reached(&items)).await;
+        assert!(reached.is_ok());
+    }
+}
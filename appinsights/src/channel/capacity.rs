@@ -0,0 +1,241 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use log::warn;
+
+use crate::contracts::{Base, Data, Envelope};
+
+/// Limits a total number of telemetry items held by the channel: items waiting in the queue
+/// and items the worker retains to retry.
+#[derive(Debug, Clone, Default)]
+pub struct Capacity {
+    max: Option<usize>,
+    retained: Arc<AtomicUsize>,
+    room: Arc<(Mutex<()>, Condvar)>,
+}
+
+impl Capacity {
+    pub fn new(max: Option<usize>) -> Self {
+        Self {
+            max,
+            retained: Default::default(),
+            room: Default::default(),
+        }
+    }
+
+    /// Determines whether one more item can be queued when `queued` items are already waiting in the queue.
+    pub fn has_room(&self, queued: usize) -> bool {
+        match self.max {
+            Some(max) => queued + self.retained.load(Ordering::Acquire) < max,
+            None => true,
+        }
+    }
+
+    /// Waits until one more item can be queued, when the number of items waiting in the queue is reported by
+    /// `queued`. Returns `false` if there is still no room after `timeout`.
+    pub fn wait_for_room(&self, queued: impl Fn() -> usize, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let (lock, room) = &*self.room;
+        let mut guard = lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        while !self.has_room(queued()) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return false;
+            }
+            guard = room
+                .wait_timeout(guard, remaining)
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .0;
+        }
+        true
+    }
+
+    /// Accounts items the worker retains to retry. When there are more items than the channel can hold,
+    /// items with the lowest priority are dropped first, the oldest ones among items with the same priority.
+    /// Returns a number of dropped items.
+    pub fn retain(&self, items: &mut Vec<Envelope>) -> usize {
+        let mut dropped = 0;
+        if let Some(max) = self.max {
+            dropped = trim(items, max);
+            if dropped > 0 {
+                warn!(
+                    "Channel capacity of {} exceeded. Dropped {} telemetry items",
+                    max, dropped
+                );
+            }
+        }
+
+        self.retained.store(items.len(), Ordering::Release);
+        self.notify();
+        dropped
+    }
+
+    /// Releases all items previously retained by the worker.
+    pub fn release(&self) {
+        self.retained.store(0, Ordering::Release);
+        self.notify();
+    }
+
+    /// Wakes up callers waiting for room in the channel. The lock makes sure a caller that has just found no
+    /// room does not miss the notification.
+    fn notify(&self) {
+        let (lock, room) = &*self.room;
+        let _guard = lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        room.notify_all();
+    }
+}
+
+/// Removes lowest priority items so that no more than `max` items left and returns a number of removed items.
+fn trim(items: &mut Vec<Envelope>, max: usize) -> usize {
+    let excess = items.len().saturating_sub(max);
+    if excess == 0 {
+        return 0;
+    }
+
+    let mut order: Vec<_> = (0..items.len()).collect();
+    order.sort_by_key(|&index| (priority(&items[index]), index));
+
+    let mut dropped = vec![false; items.len()];
+    for &index in order.iter().take(excess) {
+        dropped[index] = true;
+    }
+
+    let mut index = 0;
+    items.retain(|_| {
+        let keep = !dropped[index];
+        index += 1;
+        keep
+    });
+
+    excess
+}
+
+/// Returns a priority of the telemetry item to keep it when the channel is over capacity.
+fn priority(envelope: &Envelope) -> u8 {
+    match &envelope.data {
+        Some(Base::Data(data)) => match data {
+            Data::MessageData(_) => 0,
+            Data::MetricData(_) | Data::EventData(_) | Data::PageViewData(_) => 1,
+            Data::RemoteDependencyData(_) => 2,
+            Data::RequestData(_) => 3,
+            Data::ExceptionData(_) | Data::AvailabilityData(_) => 4,
+        },
+        None => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::contracts::{AvailabilityData, EventData, MessageData, RequestData};
+
+    #[test_case(None,       10, true    ; "unbounded")]
+    #[test_case(Some(10),   9,  true    ; "not full")]
+    #[test_case(Some(10),   10, false   ; "full")]
+    fn it_checks_room_for_queued_items(max: Option<usize>, queued: usize, expected: bool) {
+        let capacity = Capacity::new(max);
+
+        assert_eq!(capacity.has_room(queued), expected);
+    }
+
+    #[test]
+    fn it_counts_retained_items_against_capacity() {
+        let capacity = Capacity::new(Some(10));
+        let mut items = vec![message("1"), message("2"), message("3")];
+
+        capacity.retain(&mut items);
+        assert!(capacity.has_room(6));
+        assert!(!capacity.has_room(7));
+
+        capacity.release();
+        assert!(capacity.has_room(9));
+    }
+
+    #[test]
+    fn it_waits_for_room_until_released() {
+        let capacity = Capacity::new(Some(3));
+        let mut items = vec![message("1"), message("2"), message("3")];
+        capacity.retain(&mut items);
+
+        assert!(!capacity.wait_for_room(|| 0, Duration::from_millis(10)));
+
+        let worker = capacity.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            worker.release();
+        });
+        assert!(capacity.wait_for_room(|| 0, Duration::from_secs(10)));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn it_drops_lowest_priority_items_first() {
+        let capacity = Capacity::new(Some(3));
+        let mut items = vec![
+            request("request"),
+            message("message 1"),
+            event("event"),
+            availability("availability"),
+            message("message 2"),
+        ];
+
+        let dropped = capacity.retain(&mut items);
+
+        let names: Vec<_> = items.iter().map(|item| item.name.as_ref()).collect();
+        assert_eq!(names, vec!["request", "event", "availability"]);
+        assert_eq!(dropped, 2);
+        assert!(!capacity.has_room(0));
+    }
+
+    #[test]
+    fn it_drops_oldest_items_with_the_same_priority_first() {
+        let capacity = Capacity::new(Some(2));
+        let mut items = vec![message("1"), message("2"), message("3"), message("4")];
+
+        capacity.retain(&mut items);
+
+        let names: Vec<_> = items.iter().map(|item| item.name.as_ref()).collect();
+        assert_eq!(names, vec!["3", "4"]);
+    }
+
+    #[test]
+    fn it_keeps_all_items_when_unbounded() {
+        let capacity = Capacity::new(None);
+        let mut items = vec![message("1"), message("2"), message("3")];
+
+        capacity.retain(&mut items);
+
+        assert_eq!(items.len(), 3);
+    }
+
+    fn message(name: &str) -> Envelope {
+        envelope(name, Data::MessageData(MessageData::default()))
+    }
+
+    fn event(name: &str) -> Envelope {
+        envelope(name, Data::EventData(EventData::default()))
+    }
+
+    fn request(name: &str) -> Envelope {
+        envelope(name, Data::RequestData(RequestData::default()))
+    }
+
+    fn availability(name: &str) -> Envelope {
+        envelope(name, Data::AvailabilityData(AvailabilityData::default()))
+    }
+
+    fn envelope(name: &str, data: Data) -> Envelope {
+        Envelope {
+            name: name.to_string().into(),
+            data: Some(Base::Data(data)),
+            ..Envelope::default()
+        }
+    }
+}
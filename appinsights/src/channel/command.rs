@@ -1,3 +1,6 @@
+use futures_channel::mpsc::UnboundedSender;
+use log::{debug, warn};
+
 /// Describes command to be sent to internal channel.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Command {
@@ -7,6 +10,15 @@ pub enum Command {
     /// A command to force all pending telemetry items to be submitted.
     Flush,
 
+    /// A command to force all pending telemetry items to be submitted and release reserved memory afterwards.
+    Shrink,
+
+    /// A command to stop submitting telemetry items until resumed. Items keep being queued in the meantime.
+    Pause,
+
+    /// A command to continue submitting telemetry items after a pause.
+    Resume,
+
     /// A command to tear down the submission, close internal channels and wait until all pending telemetry items to be sent.
     Close,
 }
@@ -15,9 +27,20 @@ impl std::fmt::Display for Command {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let label = match self {
             Command::Flush => "flush",
+            Command::Shrink => "shrink",
+            Command::Pause => "pause",
+            Command::Resume => "resume",
             Command::Terminate => "terminate",
             Command::Close => "close",
         };
         write!(f, "{}", label)
     }
 }
+
+/// Sends a command to the submission routine of a channel.
+pub fn send_command(sender: &UnboundedSender<Command>, command: Command) {
+    debug!("Sending {} command to channel", command);
+    if let Err(err) = sender.unbounded_send(command.clone()) {
+        warn!("Unable to send {} command to channel: {}", command, err);
+    }
+}
@@ -0,0 +1,125 @@
+use std::future::Future;
+
+use futures_channel::mpsc::UnboundedSender;
+use futures_util::future::join_all;
+use log::debug;
+use tokio::sync::watch;
+
+use crate::channel::command::{send_command, Command};
+
+/// A handle to control the submission of telemetry items independently of the telemetry client that owns
+/// the channel. Infrastructure code such as signal handlers or health endpoints can flush, pause, resume or
+/// close the channel without owning the client. The handle is cheap to clone and stays valid after the
+/// channel is closed, when all its methods do nothing.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # #[tokio::main]
+/// # async fn main() {
+/// let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// let control = client.channel_control();
+///
+/// tokio::spawn(async move {
+///     tokio::signal::ctrl_c().await.unwrap();
+///     // submit pending telemetry items before the process exits
+///     control.close().await;
+/// });
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ChannelControl {
+    routines: Vec<Routine>,
+}
+
+/// A submission routine of a single channel the handle controls.
+#[derive(Debug, Clone)]
+struct Routine {
+    command_sender: UnboundedSender<Command>,
+    stopped: watch::Receiver<()>,
+}
+
+impl ChannelControl {
+    /// Creates a handle that sends commands to the given submission routine. Returns the handle along with
+    /// the routine to spawn, which lets handles know when it stopped.
+    pub(crate) fn attach(
+        command_sender: UnboundedSender<Command>,
+        routine: impl Future<Output = ()>,
+    ) -> (Self, impl Future<Output = ()>) {
+        let (stopped_sender, stopped) = watch::channel(());
+        let control = Self {
+            routines: vec![Routine {
+                command_sender,
+                stopped,
+            }],
+        };
+        let routine = async move {
+            routine.await;
+            drop(stopped_sender);
+        };
+        (control, routine)
+    }
+
+    /// Creates a handle that controls all channels the given handles control, e.g. a primary channel along
+    /// with channels of its mirrors.
+    #[cfg(not(feature = "disabled"))]
+    pub(crate) fn all(controls: impl IntoIterator<Item = ChannelControl>) -> Self {
+        Self {
+            routines: controls.into_iter().flat_map(|control| control.routines).collect(),
+        }
+    }
+
+    /// Forces all pending telemetry items to be submitted. The current task will not be blocked.
+    pub fn flush(&self) {
+        self.send(Command::Flush);
+    }
+
+    /// Stops submitting telemetry items until the channel is resumed. Items keep being queued in the meantime
+    /// up to the channel capacity, and a flush requested while the channel is paused happens on resume. It
+    /// does not affect closing the channel, which submits pending items anyway.
+    pub fn pause(&self) {
+        self.send(Command::Pause);
+    }
+
+    /// Continues submitting telemetry items after a [`pause`](#method.pause).
+    pub fn resume(&self) {
+        self.send(Command::Resume);
+    }
+
+    /// Flushes and tears down the submission flow. It waits until all pending telemetry items have been
+    /// submitted at most once and the submission routine stopped. Telemetry items tracked by the client
+    /// afterwards are not submitted.
+    pub async fn close(&self) {
+        self.send(Command::Close);
+
+        let stopped = self.routines.iter().map(|routine| {
+            let mut stopped = routine.stopped.clone();
+            // the routine never publishes a value, it drops the sender once it stopped
+            async move { while stopped.changed().await.is_ok() {} }
+        });
+        join_all(stopped).await;
+        if !self.routines.is_empty() {
+            debug!("Channel closed by control handle");
+        }
+    }
+
+    /// Returns `true` if the handle controls a submission routine, i.e. the channel supports control handles.
+    pub fn is_attached(&self) -> bool {
+        !self.routines.is_empty()
+    }
+
+    /// Returns `true` if submission routines of all channels the handle controls have stopped, or the channel
+    /// does not support control handles, see [`is_attached`](#method.is_attached).
+    pub fn is_closed(&self) -> bool {
+        self.routines.iter().all(|routine| routine.command_sender.is_closed())
+    }
+
+    fn send(&self, command: Command) {
+        for routine in &self.routines {
+            if !routine.command_sender.is_closed() {
+                send_command(&routine.command_sender, command.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::collections::BTreeMap;
+
+use crate::contracts::{Base, Data, Envelope};
+
+/// A custom property that contains how many times submission of a telemetry item was retried.
+pub const RETRY_COUNT_PROPERTY: &str = "ai.internal.retryCount";
+
+/// Returns custom properties of the telemetry item regardless of its type.
+pub fn properties_mut(envelope: &mut Envelope) -> Option<&mut Option<BTreeMap<String, String>>> {
+    match envelope.data.as_mut()? {
+        Base::Data(data) => Some(match data {
+            Data::AvailabilityData(data) => &mut data.properties,
+            Data::EventData(data) => &mut data.properties,
+            Data::ExceptionData(data) => &mut data.properties,
+            Data::MessageData(data) => &mut data.properties,
+            Data::MetricData(data) => &mut data.properties,
+            Data::PageViewData(data) => &mut data.properties,
+            Data::RemoteDependencyData(data) => &mut data.properties,
+            Data::RequestData(data) => &mut data.properties,
+        }),
+    }
+}
+
+/// Increments the number of times submission of the telemetry item was retried.
+pub fn record_retry(envelope: &mut Envelope) {
+    if let Some(properties) = properties_mut(envelope) {
+        let properties = properties.get_or_insert_with(BTreeMap::default);
+        let count = properties
+            .get(RETRY_COUNT_PROPERTY)
+            .and_then(|count| count.parse::<u32>().ok())
+            .unwrap_or_default();
+        properties.insert(RETRY_COUNT_PROPERTY.into(), (count + 1).to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contracts::EventData;
+
+    #[test]
+    fn it_increments_retry_count() {
+        let mut envelope = Envelope {
+            data: Some(Base::Data(Data::EventData(EventData::default()))),
+            ..Envelope::default()
+        };
+
+        record_retry(&mut envelope);
+        record_retry(&mut envelope);
+
+        let properties = properties_mut(&mut envelope).unwrap().as_ref().unwrap();
+        assert_eq!(properties.get(RETRY_COUNT_PROPERTY), Some(&"2".to_string()));
+    }
+
+    #[test]
+    fn it_ignores_envelope_without_data() {
+        let mut envelope = Envelope::default();
+
+        record_retry(&mut envelope);
+
+        assert_eq!(envelope, Envelope::default());
+    }
+}
@@ -0,0 +1,178 @@
+#[cfg(feature = "runtime")]
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+#[cfg(feature = "runtime")]
+use tokio::sync::watch;
+
+/// Numbers of telemetry items the channel attempted to submit while a flush was in progress. Items tracked
+/// concurrently with the flush and submitted along with it are counted as well.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlushReport {
+    sent: u64,
+    failed: u64,
+}
+
+impl FlushReport {
+    /// Returns a number of telemetry items the ingestion endpoint accepted.
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    /// Returns a number of telemetry items the ingestion endpoint did not accept. Such items are either
+    /// rejected for good or kept in the channel to be retried later.
+    pub fn failed(&self) -> u64 {
+        self.failed
+    }
+
+    /// Returns `true` if all telemetry items submitted have been accepted.
+    pub fn is_success(&self) -> bool {
+        self.failed == 0
+    }
+}
+
+/// Creates a pair of handles a channel and its worker coordinate awaited flushes with.
+#[cfg(feature = "runtime")]
+pub fn flushes() -> (Flushes, FlushSignal) {
+    let counters = Arc::new(Counters::default());
+    let (completed, receiver) = watch::channel(0);
+    (
+        Flushes {
+            counters: counters.clone(),
+            completed: receiver,
+        },
+        FlushSignal { counters, completed },
+    )
+}
+
+#[cfg(feature = "runtime")]
+#[derive(Debug, Default)]
+struct Counters {
+    requested: AtomicU64,
+    sent: AtomicU64,
+    failed: AtomicU64,
+}
+
+#[cfg(feature = "runtime")]
+impl Counters {
+    fn report(&self) -> FlushReport {
+        FlushReport {
+            sent: self.sent.load(Ordering::Acquire),
+            failed: self.failed.load(Ordering::Acquire),
+        }
+    }
+}
+
+/// A channel side of awaited flushes.
+#[cfg(feature = "runtime")]
+#[derive(Debug)]
+pub struct Flushes {
+    counters: Arc<Counters>,
+    completed: watch::Receiver<u64>,
+}
+
+#[cfg(feature = "runtime")]
+impl Flushes {
+    /// Requests a flush with the given function and resolves once the worker completed an attempt to submit
+    /// all items queued before the request, or once the worker has stopped.
+    pub async fn wait(&self, flush: impl FnOnce()) -> FlushReport {
+        let before = self.counters.report();
+        let ticket = self.counters.requested.fetch_add(1, Ordering::AcqRel) + 1;
+        let mut completed = self.completed.clone();
+        flush();
+
+        // the worker dropped its side when it stopped, so there is nothing to wait for anymore
+        let _ = completed.wait_for(|completed| *completed >= ticket).await;
+
+        let after = self.counters.report();
+        FlushReport {
+            sent: after.sent - before.sent,
+            failed: after.failed - before.failed,
+        }
+    }
+}
+
+/// A worker side of awaited flushes.
+#[cfg(feature = "runtime")]
+#[derive(Debug)]
+pub struct FlushSignal {
+    counters: Arc<Counters>,
+    completed: watch::Sender<u64>,
+}
+
+#[cfg(feature = "runtime")]
+impl FlushSignal {
+    /// Returns a ticket of the latest flush requested. An attempt that starts after this call covers all items
+    /// queued before the flush, so the flush completes once the attempt completes.
+    pub fn ticket(&self) -> u64 {
+        self.counters.requested.load(Ordering::Acquire)
+    }
+
+    /// Records results of an attempt to submit items.
+    pub fn record(&self, sent: usize, failed: usize) {
+        self.counters.sent.fetch_add(sent as u64, Ordering::AcqRel);
+        self.counters.failed.fetch_add(failed as u64, Ordering::AcqRel);
+    }
+
+    /// Completes all flushes requested up to the given ticket.
+    pub fn complete(&self, ticket: u64) {
+        self.completed.send_if_modified(|completed| {
+            let modified = ticket > *completed;
+            *completed = (*completed).max(ticket);
+            modified
+        });
+    }
+}
+
+#[cfg(feature = "runtime")]
+impl Default for FlushSignal {
+    fn default() -> Self {
+        flushes().1
+    }
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn it_resolves_once_attempt_after_request_completed() {
+        let (flushes, signal) = flushes();
+        // an attempt in progress that started before the flush was requested
+        let stale = signal.ticket();
+
+        let flush = flushes.wait(|| {});
+        tokio::pin!(flush);
+        assert!(tokio::time::timeout(Duration::from_millis(10), &mut flush)
+            .await
+            .is_err());
+
+        signal.record(3, 0);
+        signal.complete(stale);
+        assert!(tokio::time::timeout(Duration::from_millis(10), &mut flush)
+            .await
+            .is_err());
+
+        let ticket = signal.ticket();
+        signal.record(2, 1);
+        signal.complete(ticket);
+
+        let report = flush.await;
+        assert_eq!(report.sent(), 5);
+        assert_eq!(report.failed(), 1);
+        assert!(!report.is_success());
+    }
+
+    #[tokio::test]
+    async fn it_resolves_once_worker_stopped() {
+        let (flushes, signal) = flushes();
+
+        let report = flushes.wait(move || drop(signal)).await;
+
+        assert_eq!(report, FlushReport::default());
+    }
+}
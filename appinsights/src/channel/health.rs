@@ -0,0 +1,183 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{channel::ChannelStats, TelemetryClient};
+
+/// A status of the telemetry pipeline reported by [`ChannelHealth`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HealthStatus {
+    /// Telemetry items are being submitted.
+    Healthy,
+
+    /// The most recent batch failed, the channel is retrying or the ingestion endpoint throttles it.
+    /// Telemetry items are kept in the queue and submitted later, unless the queue is over capacity.
+    Degraded,
+}
+
+/// A snapshot of the status of the telemetry channel that can be serialized into a response of a health
+/// endpoint, so orchestrators and dashboards see whether telemetry reaches the ingestion endpoint. The
+/// snapshot is informational: a degraded pipeline does not make the application itself unhealthy.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// use appinsights::ChannelHealth;
+///
+/// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// let health = ChannelHealth::snapshot(&client);
+/// // {"status":"healthy","queueDepth":0,"lastSendTime":"2024-01-02T03:04:05Z",...}
+/// let body = serde_json::to_string(&health).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelHealth {
+    status: HealthStatus,
+    queue_depth: u64,
+    dropped_items: u64,
+    last_send_time: Option<DateTime<Utc>>,
+    last_send_status_code: Option<u16>,
+    last_send_succeeded: Option<bool>,
+    throttled_until: Option<DateTime<Utc>>,
+}
+
+impl ChannelHealth {
+    /// Returns a snapshot of the status of the channel the client submits telemetry items with.
+    pub fn snapshot(client: &TelemetryClient) -> Self {
+        Self::from(&client.channel_stats())
+    }
+
+    /// Returns a status of the telemetry pipeline.
+    pub fn status(&self) -> HealthStatus {
+        self.status
+    }
+
+    /// Returns a number of telemetry items waiting in the queue to be sent.
+    pub fn queue_depth(&self) -> u64 {
+        self.queue_depth
+    }
+
+    /// Returns a number of telemetry items dropped because the channel was over capacity or they exceeded the
+    /// maximum envelope size.
+    pub fn dropped_items(&self) -> u64 {
+        self.dropped_items
+    }
+
+    /// Returns time the most recent attempt to send a batch completed at, if any.
+    pub fn last_send_time(&self) -> Option<DateTime<Utc>> {
+        self.last_send_time
+    }
+
+    /// Returns a status code of the response to the most recent attempt to send a batch, if any.
+    pub fn last_send_status_code(&self) -> Option<u16> {
+        self.last_send_status_code
+    }
+
+    /// Returns `true` if the most recent batch has been sent, or `None` if nothing has been sent yet.
+    pub fn last_send_succeeded(&self) -> Option<bool> {
+        self.last_send_succeeded
+    }
+
+    /// Returns time until which the ingestion endpoint asked the channel to hold off sending, if any.
+    pub fn throttled_until(&self) -> Option<DateTime<Utc>> {
+        self.throttled_until
+    }
+}
+
+impl From<&ChannelStats> for ChannelHealth {
+    fn from(stats: &ChannelStats) -> Self {
+        let last_transmission = stats.last_transmission();
+        let failed = last_transmission.is_some_and(|transmission| !transmission.succeeded());
+        let degraded = failed || stats.is_retrying() || stats.throttled_until().is_some();
+
+        Self {
+            status: if degraded {
+                HealthStatus::Degraded
+            } else {
+                HealthStatus::Healthy
+            },
+            queue_depth: stats.queued_items(),
+            dropped_items: stats.dropped_items(),
+            last_send_time: last_transmission.map(|transmission| transmission.time()),
+            last_send_status_code: last_transmission.and_then(|transmission| transmission.status_code()),
+            last_send_succeeded: last_transmission.map(|transmission| transmission.succeeded()),
+            throttled_until: stats.throttled_until(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+    use crate::{
+        channel::stats::StatsCollector,
+        diagnostics::{ChannelEvent, EventListener},
+        time,
+    };
+
+    #[test]
+    fn it_reports_healthy_channel() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms(3, 4, 5));
+        let stats = StatsCollector::default();
+        stats.on_event(&ChannelEvent::BatchSent {
+            items: 2,
+            accepted: 2,
+            status_code: Some(200),
+        });
+        let mut snapshot = stats.snapshot();
+        snapshot.set_queued_items(3);
+
+        let health = ChannelHealth::from(&snapshot);
+
+        assert_eq!(health.status(), HealthStatus::Healthy);
+        assert_eq!(
+            serde_json::to_value(&health).unwrap(),
+            serde_json::json!({
+                "status": "healthy",
+                "queueDepth": 3,
+                "droppedItems": 0,
+                "lastSendTime": "2019-01-02T03:04:05Z",
+                "lastSendStatusCode": 200,
+                "lastSendSucceeded": true,
+                "throttledUntil": null,
+            })
+        );
+        time::reset();
+    }
+
+    #[test]
+    fn it_reports_throttled_channel_as_degraded() {
+        let now = Utc.ymd(2019, 1, 2).and_hms(3, 4, 5);
+        time::set(now);
+        let stats = StatsCollector::default();
+        stats.record_throttled(now + Duration::seconds(30));
+
+        let health = ChannelHealth::from(&stats.snapshot());
+        assert_eq!(health.status(), HealthStatus::Degraded);
+        assert_eq!(health.throttled_until(), Some(now + Duration::seconds(30)));
+
+        time::set(now + Duration::seconds(30));
+        let health = ChannelHealth::from(&stats.snapshot());
+        assert_eq!(health.status(), HealthStatus::Healthy);
+        assert_eq!(health.throttled_until(), None);
+        time::reset();
+    }
+
+    #[test]
+    fn it_reports_failed_channel_as_degraded() {
+        let stats = StatsCollector::default();
+        stats.on_event(&ChannelEvent::BatchFailed {
+            items: 2,
+            status_code: Some(500),
+        });
+
+        let health = ChannelHealth::from(&stats.snapshot());
+
+        assert_eq!(health.status(), HealthStatus::Degraded);
+        assert_eq!(health.last_send_succeeded(), Some(false));
+        assert_eq!(health.last_send_status_code(), Some(500));
+    }
+}
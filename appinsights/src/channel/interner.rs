@@ -0,0 +1,219 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use tokio::time::Instant;
+
+use crate::{channel::envelope::properties_mut, contracts::Envelope};
+
+type PropertyMap = BTreeMap<String, String>;
+
+/// Minimum number of property maps to keep before interner starts to evict maps that are no longer in use.
+const MIN_PURGE_THRESHOLD: usize = 64;
+
+/// A telemetry item that waits in the channel queue to be sent.
+#[derive(Debug)]
+pub struct QueuedItem {
+    envelope: Envelope,
+    properties: Option<Arc<PropertyMap>>,
+    enqueued: Instant,
+}
+
+impl QueuedItem {
+    /// Returns a telemetry item without its properties, which are kept separately when interned.
+    #[cfg(feature = "debug")]
+    pub fn envelope(&self) -> &Envelope {
+        &self.envelope
+    }
+
+    /// Returns a time the item was queued at.
+    pub fn enqueued(&self) -> Instant {
+        self.enqueued
+    }
+
+    /// Restores a telemetry item with its own copy of properties.
+    pub fn into_envelope(self) -> Envelope {
+        let mut envelope = self.envelope;
+        if let Some(properties) = self.properties {
+            if let Some(slot) = properties_mut(&mut envelope) {
+                *slot = Some(Arc::try_unwrap(properties).unwrap_or_else(|properties| (*properties).clone()));
+            }
+        }
+        envelope
+    }
+}
+
+impl From<Envelope> for QueuedItem {
+    fn from(envelope: Envelope) -> Self {
+        Self {
+            envelope,
+            properties: None,
+            enqueued: Instant::now(),
+        }
+    }
+}
+
+/// Shares identical property maps between queued telemetry items to reduce memory footprint of large backlogs.
+#[derive(Debug)]
+pub struct Interner {
+    maps: Mutex<Maps>,
+}
+
+#[derive(Debug)]
+struct Maps {
+    maps: HashSet<Arc<PropertyMap>>,
+    purge_threshold: usize,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self {
+            maps: Mutex::new(Maps {
+                maps: HashSet::new(),
+                purge_threshold: MIN_PURGE_THRESHOLD,
+            }),
+        }
+    }
+
+    /// Replaces properties of the telemetry item with a map shared with other items with the same properties.
+    pub fn intern(&self, mut envelope: Envelope) -> QueuedItem {
+        let properties = match properties_mut(&mut envelope).and_then(Option::take) {
+            Some(properties) => properties,
+            None => return QueuedItem::from(envelope),
+        };
+
+        let mut maps = self.maps.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let properties = match maps.maps.get(&properties) {
+            Some(shared) => shared.clone(),
+            None => {
+                maps.purge();
+
+                let shared = Arc::new(properties);
+                maps.maps.insert(shared.clone());
+                shared
+            }
+        };
+
+        QueuedItem {
+            envelope,
+            properties: Some(properties),
+            enqueued: Instant::now(),
+        }
+    }
+
+    /// Evicts all maps that no queued item refers to and releases memory reserved for new ones.
+    pub fn shrink(&self) {
+        let mut maps = self.maps.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        maps.maps.retain(|map| Arc::strong_count(map) > 1);
+        maps.maps.shrink_to_fit();
+        maps.purge_threshold = MIN_PURGE_THRESHOLD.max(maps.maps.len() * 2);
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.maps.lock().unwrap().maps.len()
+    }
+}
+
+impl Maps {
+    /// Evicts maps that no queued item refers to when there are too many of them.
+    fn purge(&mut self) {
+        if self.maps.len() >= self.purge_threshold {
+            self.maps.retain(|map| Arc::strong_count(map) > 1);
+            self.purge_threshold = MIN_PURGE_THRESHOLD.max(self.maps.len() * 2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contracts::{Base, Data, EventData};
+
+    #[test]
+    fn it_shares_identical_properties() {
+        let interner = Interner::new();
+
+        let first = interner.intern(event("first", &[("tenant", "contoso")]));
+        let second = interner.intern(event("second", &[("tenant", "contoso")]));
+        let third = interner.intern(event("third", &[("tenant", "fabrikam")]));
+
+        assert!(Arc::ptr_eq(
+            first.properties.as_ref().unwrap(),
+            second.properties.as_ref().unwrap()
+        ));
+        assert!(!Arc::ptr_eq(
+            first.properties.as_ref().unwrap(),
+            third.properties.as_ref().unwrap()
+        ));
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    fn it_restores_original_envelope() {
+        let interner = Interner::new();
+        let envelope = event("event", &[("tenant", "contoso")]);
+
+        let item = interner.intern(envelope.clone());
+
+        assert_eq!(item.into_envelope(), envelope);
+    }
+
+    #[test]
+    fn it_keeps_envelope_without_properties_intact() {
+        let interner = Interner::new();
+        let envelope = Envelope {
+            name: "event".into(),
+            data: Some(Base::Data(Data::EventData(EventData::default()))),
+            ..Envelope::default()
+        };
+
+        let item = interner.intern(envelope.clone());
+
+        assert_eq!(item.into_envelope(), envelope);
+        assert_eq!(interner.len(), 0);
+    }
+
+    #[test]
+    fn it_evicts_properties_no_longer_in_use() {
+        let interner = Interner::new();
+
+        let kept = interner.intern(event("kept", &[("id", "kept")]));
+        for i in 0..MIN_PURGE_THRESHOLD {
+            let id = i.to_string();
+            interner.intern(event("dropped", &[("id", &id)])).into_envelope();
+        }
+
+        assert_eq!(interner.len(), 2);
+        assert_eq!(kept.into_envelope(), event("kept", &[("id", "kept")]));
+    }
+
+    #[test]
+    fn it_releases_properties_no_longer_in_use_on_shrink() {
+        let interner = Interner::new();
+
+        let kept = interner.intern(event("kept", &[("id", "kept")]));
+        interner.intern(event("dropped", &[("id", "dropped")])).into_envelope();
+        interner.shrink();
+
+        assert_eq!(interner.len(), 1);
+        assert_eq!(kept.into_envelope(), event("kept", &[("id", "kept")]));
+    }
+
+    fn event(name: &str, properties: &[(&str, &str)]) -> Envelope {
+        let properties = properties
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+
+        Envelope {
+            name: name.to_string().into(),
+            data: Some(Base::Data(Data::EventData(EventData {
+                properties: Some(properties),
+                ..EventData::default()
+            }))),
+            ..Envelope::default()
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+/// An interval between submissions of telemetry items that can be replaced while the worker runs. The worker
+/// reads the interval every time it starts waiting, so a wait in progress completes with the previous one.
+#[derive(Debug, Clone)]
+pub struct Interval(Arc<RwLock<Duration>>);
+
+impl Interval {
+    pub fn new(interval: Duration) -> Self {
+        Self(Arc::new(RwLock::new(interval)))
+    }
+
+    /// Returns a current interval.
+    pub fn get(&self) -> Duration {
+        *self.0.read().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Replaces an interval for all subsequent waits.
+    pub fn set(&self, interval: Duration) {
+        *self.0.write().unwrap_or_else(|err| err.into_inner()) = interval;
+    }
+}
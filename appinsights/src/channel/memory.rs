@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc, time::Duration};
 
 use async_trait::async_trait;
 use crossbeam_queue::SegQueue;
@@ -6,42 +6,161 @@ use futures_channel::mpsc::UnboundedSender;
 use log::{debug, trace, warn};
 use tokio::task::JoinHandle;
 
+#[cfg(feature = "debug")]
+use crate::channel::{
+    snapshot::{self, PendingItems},
+    QueuedItemSnapshot,
+};
 use crate::{
-    channel::{command::Command, state::Worker, TelemetryChannel},
+    channel::{
+        age::ItemAge,
+        batch::BatchSize,
+        capacity::Capacity,
+        command::{send_command, Command},
+        flush::{self, Flushes},
+        interner::{Interner, QueuedItem},
+        interval::Interval,
+        state::Worker,
+        stats::StatsCollector,
+        urgent::UrgentQueue,
+        ChannelControl, ChannelStats, FlushReport, TelemetryChannel,
+    },
+    config::Shared,
     contracts::Envelope,
-    transmitter::Transmitter,
-    TelemetryConfig,
+    diagnostics::Listener,
+    telemetry::TelemetryKind,
+    transmitter::{Endpoint, Transmitter},
+    OverflowPolicy, TelemetryConfig,
 };
 
 /// A telemetry channel that stores events exclusively in memory.
 pub struct InMemoryChannel {
-    items: Arc<SegQueue<Envelope>>,
+    items: Arc<SegQueue<QueuedItem>>,
+    urgent: UrgentQueue,
+    send_immediately: HashSet<TelemetryKind>,
+    capacity: Capacity,
+    overflow_policy: OverflowPolicy,
+    age: ItemAge,
+    batch_size: Option<BatchSize>,
+    endpoint: Endpoint,
+    interval: Interval,
+    interner: Option<Interner>,
+    stats: StatsCollector,
+    #[cfg(feature = "debug")]
+    pending: PendingItems,
+    flushes: Flushes,
     command_sender: Option<UnboundedSender<Command>>,
+    control: ChannelControl,
     join: Option<JoinHandle<()>>,
 }
 
 impl InMemoryChannel {
     /// Creates a new instance of in-memory channel and starts a submission routine.
+    /// The routine is spawned on the runtime configured with [`TelemetryConfig::runtime`] if any,
+    /// or on the current runtime otherwise.
     pub fn new(config: &TelemetryConfig) -> Self {
         let items = Arc::new(SegQueue::new());
+        let urgent = UrgentQueue::default();
+        let capacity = Capacity::new(config.max_queue_capacity());
+        let age = ItemAge::default();
+        let batch_size = config.max_batch_size().map(BatchSize::new);
+        let listener = Listener::new(config.event_listener().cloned());
+        let stats = StatsCollector::new(listener.clone());
+        let listener = listener.with(Shared(Arc::new(stats.clone())));
+        #[cfg(feature = "debug")]
+        let pending = PendingItems::default();
 
         let (command_sender, command_receiver) = futures_channel::mpsc::unbounded();
+        let (flushes, flush_signal) = flush::flushes();
+        let transmitter = Transmitter::from_config(config).listener(listener.clone());
+        let endpoint = transmitter.endpoint();
+        let interval = Interval::new(config.interval());
         let worker = Worker::new(
-            Transmitter::new(config.endpoint()),
+            transmitter,
             items.clone(),
+            urgent.clone(),
+            capacity.clone(),
             command_receiver,
-            config.interval(),
-        );
+            interval.clone(),
+        )
+        .max_item_age(age.clone(), config.max_item_age())
+        .batch_size(batch_size.clone())
+        .record_retry_count(config.record_retry_count())
+        .timer(config.timer())
+        .terminate_sink(config.terminate_sink().cloned())
+        .listener(listener)
+        .stats(stats.clone())
+        .flush_signal(flush_signal);
+        #[cfg(feature = "debug")]
+        let worker = worker.pending(pending.clone());
+        #[cfg(any(test, feature = "test-util"))]
+        let worker = worker.drain_marker(config.drain_marker().cloned());
 
-        let handle = tokio::spawn(worker.run());
+        let (control, routine) = ChannelControl::attach(command_sender.clone(), worker.run());
+        let handle = match config.runtime() {
+            Some(runtime) => runtime.spawn(routine),
+            None => tokio::spawn(routine),
+        };
 
         Self {
             items,
+            urgent,
+            send_immediately: config.send_immediately().clone(),
+            capacity,
+            overflow_policy: config.overflow_policy(),
+            age,
+            batch_size,
+            endpoint,
+            interval,
+            interner: config.intern_properties().then(Interner::new),
+            stats,
+            #[cfg(feature = "debug")]
+            pending,
+            flushes,
             command_sender: Some(command_sender),
+            control,
             join: Some(handle),
         }
     }
 
+    /// Determines whether the item is to be sent right away instead of waiting for a batch.
+    fn is_urgent(&self, envelope: &Envelope) -> bool {
+        !self.send_immediately.is_empty()
+            && TelemetryKind::of(envelope).is_some_and(|kind| self.send_immediately.contains(&kind))
+    }
+
+    /// Makes room for a newly tracked item according to the overflow policy when the channel is over capacity.
+    /// Returns `false` if the item is to be dropped.
+    fn make_room(&self, envelope: &Envelope) -> bool {
+        let queued = || self.items.len() + self.urgent.len();
+        if self.capacity.has_room(queued()) {
+            return true;
+        }
+
+        match self.overflow_policy {
+            OverflowPolicy::DropOldest => {
+                if self.items.pop().or_else(|| self.urgent.pop()).is_some() {
+                    warn!(
+                        "Channel capacity exceeded. Dropped the oldest telemetry item to queue {}",
+                        envelope.name
+                    );
+                    self.stats.record_dropped(1);
+                    return true;
+                }
+            }
+            OverflowPolicy::Block(timeout) => {
+                if self.capacity.wait_for_room(queued, timeout) {
+                    return true;
+                }
+            }
+            OverflowPolicy::DropNewest => {}
+        }
+
+        warn!("Channel capacity exceeded. Dropped telemetry item {}", envelope.name);
+        self.stats.record_dropped(1);
+        false
+    }
+
     async fn shutdown(&mut self, command: Command) {
         // send shutdown command
         if let Some(sender) = self.command_sender.take() {
@@ -59,8 +178,28 @@ impl InMemoryChannel {
 #[async_trait]
 impl TelemetryChannel for InMemoryChannel {
     fn send(&self, envelop: Envelope) {
+        if !self.make_room(&envelop) {
+            return;
+        }
+
         trace!("Sending telemetry to channel");
-        self.items.push(envelop);
+        let urgent = self.is_urgent(&envelop);
+        let item = match &self.interner {
+            Some(interner) => interner.intern(envelop),
+            None => QueuedItem::from(envelop),
+        };
+
+        if urgent {
+            self.urgent.push(item);
+        } else {
+            self.items.push(item);
+
+            self.age.arrived();
+
+            if let Some(batch_size) = &self.batch_size {
+                batch_size.arrived(self.items.len());
+            }
+        }
     }
 
     fn flush(&self) {
@@ -69,6 +208,49 @@ impl TelemetryChannel for InMemoryChannel {
         }
     }
 
+    async fn flush_and_wait(&self) -> FlushReport {
+        self.flushes.wait(|| self.flush()).await
+    }
+
+    fn shrink(&self) {
+        if let Some(interner) = &self.interner {
+            interner.shrink();
+        }
+
+        if let Some(sender) = &self.command_sender {
+            send_command(sender, Command::Shrink);
+        }
+    }
+
+    fn set_endpoint(&self, endpoint: &str) {
+        debug!("Switching to endpoint {}", endpoint);
+        self.endpoint.set(endpoint);
+    }
+
+    fn set_interval(&self, interval: Duration) {
+        debug!("Switching to interval {:?}", interval);
+        self.interval.set(interval);
+    }
+
+    #[cfg(feature = "debug")]
+    fn debug_snapshot(&self) -> Vec<QueuedItemSnapshot> {
+        let mut snapshot = self.pending.get();
+        snapshot.extend(self.urgent.snapshot());
+        snapshot.extend(snapshot::queued(&self.items));
+        snapshot
+    }
+
+    fn stats(&self) -> ChannelStats {
+        let mut stats = self.stats.snapshot();
+        stats.set_queued_items(self.items.len() + self.urgent.len());
+        stats.set_processing_lag(self.age.elapsed());
+        stats
+    }
+
+    fn control(&self) -> ChannelControl {
+        self.control.clone()
+    }
+
     async fn close(&mut self) {
         self.shutdown(Command::Close).await
     }
@@ -77,10 +259,3 @@ impl TelemetryChannel for InMemoryChannel {
         self.shutdown(Command::Terminate).await;
     }
 }
-
-fn send_command(sender: &UnboundedSender<Command>, command: Command) {
-    debug!("Sending {} command to channel", command);
-    if let Err(err) = sender.unbounded_send(command.clone()) {
-        warn!("Unable to send {} command to channel: {}", command, err);
-    }
-}
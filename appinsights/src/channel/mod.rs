@@ -1,18 +1,88 @@
+#[cfg(feature = "runtime")]
+mod age;
+
+#[cfg(feature = "runtime")]
+mod batch;
+
+#[cfg(feature = "runtime")]
+mod capacity;
+
+#[cfg(feature = "runtime")]
 mod command;
 
+#[cfg(feature = "runtime")]
+mod control;
+#[cfg(feature = "runtime")]
+pub use control::ChannelControl;
+
+#[cfg(feature = "runtime")]
+mod envelope;
+
+mod flush;
+pub use flush::FlushReport;
+
+#[cfg(feature = "runtime")]
+mod health;
+#[cfg(feature = "runtime")]
+pub use health::{ChannelHealth, HealthStatus};
+
+#[cfg(feature = "runtime")]
+mod interner;
+
+#[cfg(feature = "runtime")]
+mod interval;
+
+#[cfg(feature = "runtime")]
 mod memory;
+#[cfg(feature = "runtime")]
 pub use memory::InMemoryChannel;
 
+#[cfg(not(feature = "disabled"))]
+mod multicast;
+#[cfg(not(feature = "disabled"))]
+pub use multicast::MulticastChannel;
+
+#[cfg(not(feature = "disabled"))]
+mod persistent;
+#[cfg(not(feature = "disabled"))]
+pub use persistent::PersistentChannel;
+
+#[cfg(feature = "runtime")]
 mod retry;
 
+#[cfg(all(feature = "debug", feature = "runtime"))]
+mod snapshot;
+#[cfg(all(feature = "debug", feature = "runtime"))]
+pub use snapshot::QueuedItemSnapshot;
+
+#[cfg(feature = "runtime")]
 mod state;
 
+#[cfg(feature = "runtime")]
+mod stats;
+#[cfg(feature = "runtime")]
+pub use stats::{ChannelStats, LatencyPercentiles, Transmission};
+
+#[cfg(feature = "runtime")]
+mod urgent;
+
+#[cfg(feature = "runtime")]
+use std::time::Duration;
+
+#[cfg(feature = "runtime")]
 use async_trait::async_trait;
 
+#[cfg(feature = "runtime")]
 use crate::contracts::Envelope;
 
 /// An implementation of [TelemetryChannel](trait.TelemetryChannel.html) is responsible for queueing
 /// and periodically submitting telemetry events.
+///
+/// A custom channel can be installed in the blocking client with
+/// [`blocking::TelemetryClient::with_channel`](blocking/struct.TelemetryClient.html#method.with_channel).
+/// The trait is declared with the [`async_trait`](https://docs.rs/async-trait) macro, so implementations
+/// are annotated with it as well.
+#[cfg(feature = "runtime")]
 #[async_trait]
 pub trait TelemetryChannel: Send + Sync {
     /// Queues a single telemetry item.
@@ -21,6 +91,46 @@ pub trait TelemetryChannel: Send + Sync {
     /// Forces all pending telemetry items to be submitted. The current task will not be blocked.
     fn flush(&self);
 
+    /// Forces all pending telemetry items to be submitted and resolves once the attempt to submit them has
+    /// completed, successfully or not. Channels that cannot tell when items are submitted resolve right away
+    /// with nothing reported.
+    async fn flush_and_wait(&self) -> FlushReport {
+        self.flush();
+        FlushReport::default()
+    }
+
+    /// Forces all pending telemetry items to be submitted and releases memory the channel holds in reserve.
+    /// Intended for hosts under memory pressure. The current task will not be blocked.
+    fn shrink(&self) {
+        self.flush()
+    }
+
+    /// Replaces an URL of the endpoint telemetry items are submitted to. A batch being submitted at the
+    /// moment goes to the previous endpoint, all subsequent batches go to the new one.
+    fn set_endpoint(&self, _endpoint: &str) {}
+
+    /// Replaces an interval between submissions of telemetry items. A wait in progress completes with the
+    /// previous interval, all subsequent ones take the new one.
+    fn set_interval(&self, _interval: Duration) {}
+
+    /// Returns a snapshot of statistics of the channel. Channels that do not collect statistics report none.
+    fn stats(&self) -> ChannelStats {
+        ChannelStats::default()
+    }
+
+    /// Returns a handle to control the submission of telemetry items. Channels that cannot be controlled
+    /// return a handle that does nothing.
+    fn control(&self) -> ChannelControl {
+        ChannelControl::default()
+    }
+
+    /// Returns a snapshot of telemetry items waiting in the channel to diagnose stalled delivery. Channels that
+    /// do not queue items report none. Available with the `debug` feature only.
+    #[cfg(feature = "debug")]
+    fn debug_snapshot(&self) -> Vec<QueuedItemSnapshot> {
+        Vec::new()
+    }
+
     /// Flushes and tears down the submission flow and closes internal channels.
     /// It blocks the current task until all pending telemetry items have been submitted and it is safe to
     /// shutdown without losing telemetry.
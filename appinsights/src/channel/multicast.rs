@@ -0,0 +1,151 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures_util::future::{join, join_all};
+
+#[cfg(feature = "debug")]
+use crate::channel::QueuedItemSnapshot;
+use crate::{
+    channel::{ChannelControl, ChannelStats, FlushReport, TelemetryChannel},
+    contracts::Envelope,
+};
+
+/// A channel that forwards every telemetry item to a primary channel and to channels of mirrors, which
+/// submit it with instrumentation keys of their own.
+pub struct MulticastChannel {
+    primary: Box<dyn TelemetryChannel>,
+    mirrors: Vec<(String, Box<dyn TelemetryChannel>)>,
+}
+
+impl MulticastChannel {
+    /// Creates a new channel that forwards telemetry items to the primary channel as they are and to the
+    /// channels of mirrors with the instrumentation key of the mirror.
+    pub fn new(
+        primary: Box<dyn TelemetryChannel>,
+        mirrors: impl IntoIterator<Item = (String, Box<dyn TelemetryChannel>)>,
+    ) -> Self {
+        Self {
+            primary,
+            mirrors: mirrors.into_iter().collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl TelemetryChannel for MulticastChannel {
+    fn send(&self, envelop: Envelope) {
+        for (i_key, channel) in &self.mirrors {
+            let mut envelop = envelop.clone();
+            envelop.i_key = Some(i_key.clone());
+            channel.send(envelop);
+        }
+        self.primary.send(envelop);
+    }
+
+    fn flush(&self) {
+        self.primary.flush();
+        for (_, channel) in &self.mirrors {
+            channel.flush();
+        }
+    }
+
+    /// Waits for the primary channel and mirrors and reports items of the primary channel only.
+    async fn flush_and_wait(&self) -> FlushReport {
+        let mirrors = join_all(self.mirrors.iter().map(|(_, channel)| channel.flush_and_wait()));
+        join(self.primary.flush_and_wait(), mirrors).await.0
+    }
+
+    fn shrink(&self) {
+        self.primary.shrink();
+        for (_, channel) in &self.mirrors {
+            channel.shrink();
+        }
+    }
+
+    /// Replaces the endpoint of the primary channel only, mirrors keep the endpoints they are configured with.
+    fn set_endpoint(&self, endpoint: &str) {
+        self.primary.set_endpoint(endpoint);
+    }
+
+    /// Replaces the interval of the primary channel only, mirrors keep the intervals they are configured with.
+    fn set_interval(&self, interval: Duration) {
+        self.primary.set_interval(interval);
+    }
+
+    /// Returns statistics of the primary channel.
+    fn stats(&self) -> ChannelStats {
+        self.primary.stats()
+    }
+
+    /// Returns a handle to control the primary channel and mirrors together.
+    fn control(&self) -> ChannelControl {
+        let mirrors = self.mirrors.iter().map(|(_, channel)| channel.control());
+        ChannelControl::all(std::iter::once(self.primary.control()).chain(mirrors))
+    }
+
+    #[cfg(feature = "debug")]
+    fn debug_snapshot(&self) -> Vec<QueuedItemSnapshot> {
+        self.primary.debug_snapshot()
+    }
+
+    async fn close(&mut self) {
+        let mirrors = self.mirrors.iter_mut().map(|(_, channel)| channel.close());
+        join(self.primary.close(), join_all(mirrors)).await;
+    }
+
+    async fn terminate(&mut self) {
+        let mirrors = self.mirrors.iter_mut().map(|(_, channel)| channel.terminate());
+        join(self.primary.terminate(), join_all(mirrors)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crossbeam_queue::SegQueue;
+
+    use super::*;
+    use crate::{channel::InMemoryChannel, client::tests::TestChannel, TelemetryConfig};
+
+    #[test]
+    fn it_forwards_telemetry_to_mirrors_with_their_instrumentation_keys() {
+        let primary = Arc::new(SegQueue::default());
+        let mirror = Arc::new(SegQueue::default());
+        let channel = MulticastChannel::new(
+            Box::new(TestChannel::new(primary.clone())),
+            vec![(
+                "mirror".to_string(),
+                Box::new(TestChannel::new(mirror.clone())) as Box<dyn TelemetryChannel>,
+            )],
+        );
+
+        channel.send(Envelope {
+            i_key: Some("primary".into()),
+            ..Envelope::default()
+        });
+
+        assert_eq!(primary.pop().unwrap().i_key.as_deref(), Some("primary"));
+        assert_eq!(mirror.pop().unwrap().i_key.as_deref(), Some("mirror"));
+    }
+
+    #[tokio::test]
+    async fn it_controls_mirrors_along_with_primary_channel() {
+        let config = TelemetryConfig::new("primary".into());
+        let primary = InMemoryChannel::new(&config);
+        let mirror = InMemoryChannel::new(&config);
+        let (primary_control, mirror_control) = (primary.control(), mirror.control());
+        let channel = MulticastChannel::new(
+            Box::new(primary),
+            vec![("mirror".to_string(), Box::new(mirror) as Box<dyn TelemetryChannel>)],
+        );
+
+        let control = channel.control();
+        assert!(!control.is_closed());
+        control.close().await;
+
+        assert!(control.is_closed());
+        assert!(primary_control.is_closed());
+        assert!(mirror_control.is_closed());
+    }
+}
@@ -0,0 +1,907 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    mem,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use crossbeam_queue::SegQueue;
+use futures_channel::mpsc::{UnboundedReceiver, UnboundedSender};
+use futures_util::StreamExt;
+use log::{debug, trace, warn};
+use serde_json::{json, Value};
+use tokio::{sync::Notify, task::JoinHandle};
+
+use crate::{
+    channel::{
+        command::{send_command, Command},
+        flush::{self, FlushSignal, Flushes},
+        interval::Interval,
+        ChannelControl, FlushReport, TelemetryChannel,
+    },
+    config::Shared,
+    contracts::Envelope,
+    timeout,
+    timer::Timer,
+    transmitter::{Endpoint, Response, Transmitter},
+    TelemetryConfig,
+};
+
+/// An extension of a file telemetry items are being appended to.
+const ACTIVE_EXTENSION: &str = "active";
+
+/// An extension of a file that is complete and waits to be submitted.
+const SEALED_EXTENSION: &str = "ndjson";
+
+/// A name of the on-disk format stated in the header line of every spooled file.
+const FORMAT: &str = "appinsights-spool";
+
+/// A version of the on-disk format this SDK writes. Files of version 0 have no header line.
+const FORMAT_VERSION: u64 = 1;
+
+/// Number of bytes of a batch of telemetry items buffered in memory before they are written to the active file.
+const WRITE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// A telemetry channel that spools events to disk until they have been submitted, so telemetry survives
+/// network outages and process crashes.
+///
+/// Each item is appended to the active newline-delimited JSON file in the configured directory. Tracking an item
+/// never waits for the disk: items are queued in memory and the submission routine appends them in batches,
+/// each of which is written to the file before the next one is taken. Items tracked right before a crash, or
+/// while the routine is busy submitting, may be lost. Every interval the active file is sealed and all sealed files
+/// are submitted one by one, oldest first. A file is removed once its items have been submitted; items that
+/// have to be retried stay on disk until the next interval. Files left by a previous run are submitted on
+/// startup. Once spooled items exceed the [maximum size], the oldest files are removed to make room for new
+/// items.
+///
+/// [maximum size]: ../struct.TelemetryConfigBuilder.html#method.max_spool_size
+///
+/// Every file starts with a header line that states the version of the on-disk format, e.g.
+/// `{"format":"appinsights-spool","version":1}`. Files written by older SDK versions are migrated to the
+/// current format on startup, and files written by newer ones are read as far as items can be understood, so
+/// upgrades and downgrades never strand spooled telemetry.
+pub struct PersistentChannel {
+    pending: Arc<Pending>,
+    endpoint: Endpoint,
+    interval: Interval,
+    flushes: Flushes,
+    command_sender: Option<UnboundedSender<Command>>,
+    control: ChannelControl,
+    join: Option<JoinHandle<()>>,
+}
+
+impl PersistentChannel {
+    /// Creates a new instance of persistent channel that spools events to the given directory and starts a
+    /// submission routine. The routine is spawned on the runtime configured with [`TelemetryConfig::runtime`]
+    /// if any, or on the current runtime otherwise.
+    pub fn new(config: &TelemetryConfig, dir: &Path) -> io::Result<Self> {
+        let spool = Arc::new(Spool::open(dir, config.max_spool_size())?);
+        let pending = Arc::new(Pending::default());
+
+        let (command_sender, command_receiver) = futures_channel::mpsc::unbounded();
+        let (flushes, flush_signal) = flush::flushes();
+        let transmitter = Transmitter::from_config(config);
+        let endpoint = transmitter.endpoint();
+        let interval = Interval::new(config.interval());
+        let worker = Worker {
+            transmitter,
+            spool,
+            pending: pending.clone(),
+            command_receiver,
+            interval: interval.clone(),
+            timer: config.timer(),
+            flush_signal,
+        };
+
+        let (control, routine) = ChannelControl::attach(command_sender.clone(), worker.run());
+        let handle = match config.runtime() {
+            Some(runtime) => runtime.spawn(routine),
+            None => tokio::spawn(routine),
+        };
+
+        Ok(Self {
+            pending,
+            endpoint,
+            interval,
+            flushes,
+            command_sender: Some(command_sender),
+            control,
+            join: Some(handle),
+        })
+    }
+
+    async fn shutdown(&mut self, command: Command) {
+        if let Some(sender) = self.command_sender.take() {
+            send_command(&sender, command);
+        }
+
+        if let Some(handle) = self.join.take() {
+            debug!("Shutting down worker");
+            handle.await.unwrap();
+        }
+    }
+}
+
+#[async_trait]
+impl TelemetryChannel for PersistentChannel {
+    fn send(&self, envelop: Envelope) {
+        trace!("Queueing telemetry to spool to disk");
+        self.pending.items.push(envelop);
+        self.pending.arrived.notify_one();
+    }
+
+    fn flush(&self) {
+        if let Some(sender) = &self.command_sender {
+            send_command(sender, Command::Flush);
+        }
+    }
+
+    async fn flush_and_wait(&self) -> FlushReport {
+        self.flushes.wait(|| self.flush()).await
+    }
+
+    fn set_endpoint(&self, endpoint: &str) {
+        debug!("Switching to endpoint {}", endpoint);
+        self.endpoint.set(endpoint);
+    }
+
+    fn set_interval(&self, interval: Duration) {
+        debug!("Switching to interval {:?}", interval);
+        self.interval.set(interval);
+    }
+
+    fn control(&self) -> ChannelControl {
+        self.control.clone()
+    }
+
+    async fn close(&mut self) {
+        self.shutdown(Command::Close).await
+    }
+
+    /// Tears down the submission flow. Items waiting to be sent stay on disk and are submitted on the next
+    /// startup.
+    async fn terminate(&mut self) {
+        self.shutdown(Command::Terminate).await;
+    }
+}
+
+/// Telemetry items tracked but not written to the spool yet.
+#[derive(Debug, Default)]
+struct Pending {
+    items: SegQueue<Envelope>,
+    arrived: Notify,
+}
+
+/// Writes tracked telemetry items to the spool and submits spooled items every interval or when requested.
+struct Worker {
+    transmitter: Transmitter,
+    spool: Arc<Spool>,
+    pending: Arc<Pending>,
+    command_receiver: UnboundedReceiver<Command>,
+    interval: Interval,
+    timer: Shared<dyn Timer>,
+    flush_signal: FlushSignal,
+}
+
+impl Worker {
+    async fn run(mut self) {
+        // replay files left by a previous run
+        self.submit().await;
+
+        let mut paused = false;
+        let mut flush_deferred = false;
+        let mut interval = timeout::sleep(&*self.timer, self.interval.get());
+        loop {
+            let command = tokio::select! {
+                command = self.command_receiver.next() => command,
+                _ = self.pending.arrived.notified() => {
+                    self.write().await;
+                    continue;
+                }
+                _ = &mut interval, if !paused => Some(Command::Flush),
+            };
+
+            match command {
+                Some(Command::Flush) | Some(Command::Shrink) if paused => flush_deferred = true,
+                Some(Command::Flush) | Some(Command::Shrink) => self.submit().await,
+                Some(Command::Pause) => paused = true,
+                Some(Command::Resume) => {
+                    paused = false;
+                    if mem::take(&mut flush_deferred) {
+                        self.submit().await;
+                    }
+                }
+                Some(Command::Close) => {
+                    self.submit().await;
+                    break;
+                }
+                Some(Command::Terminate) | None => break,
+            }
+            interval = timeout::sleep(&*self.timer, self.interval.get());
+        }
+
+        // items tracked before the channel stopped stay on disk until the next startup
+        self.write().await;
+        self.spool.seal();
+        debug!("Worker stopped");
+    }
+
+    /// Appends items tracked since the last write to the spool.
+    async fn write(&self) {
+        let mut items = Vec::with_capacity(self.pending.items.len());
+        while let Some(item) = self.pending.items.pop() {
+            items.push(item);
+        }
+        if items.is_empty() {
+            return;
+        }
+
+        let spool = self.spool.clone();
+        if let Err(err) = blocking(move || spool.append(&items)).await {
+            warn!("Unable to spool telemetry: {}", err);
+        }
+    }
+
+    /// Submits spooled items and completes flushes requested before.
+    async fn submit(&self) {
+        let ticket = self.flush_signal.ticket();
+        self.write().await;
+        self.submit_files().await;
+        self.flush_signal.complete(ticket);
+    }
+
+    /// Seals the active file and submits all sealed files oldest first. Stops at the first file that could not
+    /// be submitted entirely, so items are sent in order once the endpoint is available again.
+    async fn submit_files(&self) {
+        self.spool.seal();
+
+        let files = match self.spool.sealed() {
+            Ok(files) => files,
+            Err(err) => {
+                warn!("Unable to list spooled telemetry: {}", err);
+                return;
+            }
+        };
+
+        for path in files {
+            let file = path.clone();
+            let items = match blocking(move || read(&file)).await {
+                Ok(items) => items,
+                Err(err) => {
+                    warn!("Unable to read spooled telemetry {}: {}", path.display(), err);
+                    return;
+                }
+            };
+
+            if !items.is_empty() {
+                debug!(
+                    "Submitting {} spooled telemetry items from {}",
+                    items.len(),
+                    path.display()
+                );
+                let retry_items = match self.send(items).await {
+                    Some(retry_items) => retry_items,
+                    None => return,
+                };
+
+                if !retry_items.is_empty() {
+                    let file = path.clone();
+                    if let Err(err) = blocking(move || write(&file, &retry_items)).await {
+                        warn!("Unable to update spooled telemetry {}: {}", path.display(), err);
+                    }
+                    return;
+                }
+            }
+
+            // the file may have been evicted while its items were being sent
+            match fs::remove_file(&path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                    warn!("Unable to remove spooled telemetry {}: {}", path.display(), err);
+                    return;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Sends items of a spooled file and returns those to retry, or `None` if the attempt failed. Items stay on
+    /// disk in that case, so they are retried next interval.
+    async fn send(&self, items: Vec<Envelope>) -> Option<Vec<Envelope>> {
+        let count = items.len();
+        match self.transmitter.send(items).await {
+            Ok(Response::Success) => {
+                self.flush_signal.record(count, 0);
+                Some(Vec::new())
+            }
+            Ok(Response::NoRetry) => {
+                self.flush_signal.record(0, count);
+                Some(Vec::new())
+            }
+            Ok(Response::Retry(retry_items)) | Ok(Response::Throttled(_, retry_items)) => {
+                self.flush_signal.record(count - retry_items.len(), retry_items.len());
+                Some(retry_items)
+            }
+            Err(err) => {
+                warn!("Unable to submit spooled telemetry: {}", err);
+                self.flush_signal.record(0, count);
+                None
+            }
+        }
+    }
+}
+
+/// Runs a file operation on a thread where blocking is acceptable.
+async fn blocking<T, F>(f: F) -> io::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await.map_err(io::Error::other)?
+}
+
+/// A directory of newline-delimited JSON files with telemetry items that take no more than the maximum size.
+#[derive(Debug)]
+struct Spool {
+    dir: PathBuf,
+    max_size: u64,
+    state: Mutex<State>,
+    sequence: AtomicU64,
+}
+
+/// A file items are being appended to and an estimate of bytes sealed files take. The estimate only grows
+/// between evictions, since the worker removes files it submitted without updating it.
+#[derive(Debug, Default)]
+struct State {
+    active: Option<ActiveFile>,
+    sealed_size: u64,
+}
+
+#[derive(Debug)]
+struct ActiveFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    size: u64,
+}
+
+impl Spool {
+    /// Opens a spool in the given directory. Active files left by a previous run are sealed, so they are
+    /// submitted as well, and files of older format versions are migrated to the current one.
+    fn open(dir: &Path, max_size: u64) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if has_extension(&path, ACTIVE_EXTENSION) {
+                fs::rename(&path, path.with_extension(SEALED_EXTENSION))?;
+            }
+        }
+
+        let spool = Self {
+            dir: dir.into(),
+            max_size,
+            state: Mutex::new(State::default()),
+            sequence: AtomicU64::new(0),
+        };
+
+        for path in spool.sealed()? {
+            if let Err(err) = migrate(&path) {
+                warn!("Unable to migrate spooled telemetry {}: {}", path.display(), err);
+            }
+        }
+        spool.lock().sealed_size = size(&spool.sealed()?);
+
+        Ok(spool)
+    }
+
+    /// Appends a batch of items to the active file and writes it to disk. Items that cannot be appended are
+    /// skipped.
+    fn append(&self, envelopes: &[Envelope]) -> io::Result<()> {
+        let mut state = self.lock();
+        for envelope in envelopes {
+            if let Err(err) = self.append_item(&mut state, envelope) {
+                warn!("Unable to spool telemetry item {}: {}", envelope.name, err);
+            }
+        }
+
+        match state.active.as_mut() {
+            Some(active) => active.writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Appends an item to the active file. Creates a new active file if there is none. Evicts the oldest
+    /// sealed files if the item does not fit otherwise, and drops the item if it does not fit even then.
+    fn append_item(&self, state: &mut State, envelope: &Envelope) -> io::Result<()> {
+        let mut line = serde_json::to_vec(envelope)?;
+        line.push(b'\n');
+
+        let header = if state.active.is_none() { header() } else { Vec::new() };
+        let len = (header.len() + line.len()) as u64;
+        if !self.fits(state, len)? {
+            warn!(
+                "Spooled telemetry exceeds maximum size of {} bytes. Dropped telemetry item {}",
+                self.max_size, envelope.name
+            );
+            return Ok(());
+        }
+
+        if state.active.is_none() {
+            let path = self.next_path();
+            let file = OpenOptions::new().create(true).append(true).open(&path)?;
+            state.active = Some(ActiveFile {
+                path,
+                writer: BufWriter::with_capacity(WRITE_BUFFER_SIZE, file),
+                size: 0,
+            });
+        }
+
+        if let Some(active) = state.active.as_mut() {
+            active.writer.write_all(&header)?;
+            active.writer.write_all(&line)?;
+            active.size += len;
+        }
+
+        Ok(())
+    }
+
+    /// Writes buffered items to the active file, closes it and marks it ready to be submitted.
+    fn seal(&self) {
+        let mut state = self.lock();
+        if let Some(active) = state.active.take() {
+            state.sealed_size += active.size;
+            let path = active.path;
+            if let Err(err) = active.writer.into_inner().map_err(|err| err.into_error()) {
+                warn!("Unable to write spooled telemetry {}: {}", path.display(), err);
+            }
+            if let Err(err) = fs::rename(&path, path.with_extension(SEALED_EXTENSION)) {
+                warn!("Unable to seal spooled telemetry {}: {}", path.display(), err);
+            }
+        }
+    }
+
+    /// Determines whether the given number of bytes fits into the spool, removing the oldest sealed files to
+    /// make room if necessary.
+    fn fits(&self, state: &mut State, len: u64) -> io::Result<bool> {
+        let active_size = state.active.as_ref().map_or(0, |active| active.size);
+        if state.sealed_size + active_size + len <= self.max_size {
+            return Ok(true);
+        }
+
+        // the estimate may include files submitted since, so it is taken from disk before evicting anything
+        let mut files = self.sealed()?.into_iter();
+        state.sealed_size = size(files.as_slice());
+        while state.sealed_size + active_size + len > self.max_size {
+            let path = match files.next() {
+                Some(path) => path,
+                None => return Ok(false),
+            };
+
+            let file_size = fs::metadata(&path).map_or(0, |metadata| metadata.len());
+            match fs::remove_file(&path) {
+                Ok(()) => warn!(
+                    "Spooled telemetry exceeds maximum size of {} bytes. Removed oldest file {}",
+                    self.max_size,
+                    path.display()
+                ),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+            state.sealed_size = state.sealed_size.saturating_sub(file_size);
+        }
+        Ok(true)
+    }
+
+    /// Returns paths of files ready to be submitted, oldest first.
+    fn sealed(&self) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if has_extension(&path, SEALED_EXTENSION) {
+                files.push(path);
+            }
+        }
+
+        files.sort();
+        Ok(files)
+    }
+
+    /// Returns a path of a new active file. Names sort in the order files were created.
+    fn next_path(&self) -> PathBuf {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+
+        self.dir
+            .join(format!("{:024}-{:06}", timestamp, sequence))
+            .with_extension(ACTIVE_EXTENSION)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Returns a number of bytes the given files take. Files that are gone already take nothing.
+fn size(files: &[PathBuf]) -> u64 {
+    files
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension().is_some_and(|ext| ext == extension)
+}
+
+/// Returns a header line of the current format version.
+fn header() -> Vec<u8> {
+    let mut line = json!({ "format": FORMAT, "version": FORMAT_VERSION })
+        .to_string()
+        .into_bytes();
+    line.push(b'\n');
+    line
+}
+
+/// Returns a format version stated in the given line if it is a header line.
+fn parse_header(line: &str) -> Option<u64> {
+    let header: Value = serde_json::from_str(line).ok()?;
+    if header.get("format")?.as_str()? == FORMAT {
+        header.get("version")?.as_u64()
+    } else {
+        None
+    }
+}
+
+/// Returns a format version of a file.
+fn version(path: &Path) -> io::Result<u64> {
+    let mut line = String::new();
+    BufReader::new(File::open(path)?).read_line(&mut line)?;
+    Ok(parse_header(line.trim_end()).unwrap_or(0))
+}
+
+/// Rewrites a file of an older format version in the current one. Returns `true` if the file was migrated.
+fn migrate(path: &Path) -> io::Result<bool> {
+    let version = version(path)?;
+    if version >= FORMAT_VERSION {
+        return Ok(false);
+    }
+
+    debug!(
+        "Migrating spooled telemetry {} from format version {} to {}",
+        path.display(),
+        version,
+        FORMAT_VERSION
+    );
+    write(path, &read(path)?)?;
+    Ok(true)
+}
+
+/// Reads items from a file of any format version. Lines that cannot be parsed, e.g. the last one written when
+/// the process crashed or items of a newer format version, are skipped.
+fn read(path: &Path) -> io::Result<Vec<Envelope>> {
+    let mut items = Vec::new();
+    for (index, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if index == 0 {
+            if let Some(version) = parse_header(&line) {
+                if version > FORMAT_VERSION {
+                    warn!(
+                        "Spooled telemetry {} has newer format version {}, reading items it shares with version {}",
+                        path.display(),
+                        version,
+                        FORMAT_VERSION
+                    );
+                }
+                continue;
+            }
+        }
+
+        match serde_json::from_str(&line) {
+            Ok(envelope) => items.push(envelope),
+            Err(err) => warn!(
+                "Skipped malformed spooled telemetry item in {}: {}",
+                path.display(),
+                err
+            ),
+        }
+    }
+
+    Ok(items)
+}
+
+/// Replaces the content of a file with given items in the current format version.
+fn write(path: &Path, items: &[Envelope]) -> io::Result<()> {
+    let mut content = header();
+    for item in items {
+        serde_json::to_writer(&mut content, item)?;
+        content.push(b'\n');
+    }
+
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, content)?;
+    fs::rename(&temp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use super::*;
+    use crate::sink::{SinkError, TelemetrySink};
+
+    #[test]
+    fn it_reads_appended_items_once_sealed() {
+        let dir = temp_dir("sealed");
+        let spool = Spool::open(&dir, u64::MAX).unwrap();
+
+        spool.append(&[envelope("first")]).unwrap();
+        spool.append(&[envelope("second")]).unwrap();
+        assert!(spool.sealed().unwrap().is_empty());
+
+        spool.seal();
+        spool.append(&[envelope("third")]).unwrap();
+        spool.seal();
+
+        let files = spool.sealed().unwrap();
+        let names: Vec<Vec<String>> = files
+            .iter()
+            .map(|path| {
+                read(path)
+                    .unwrap()
+                    .into_iter()
+                    .map(|item| item.name.into_owned())
+                    .collect()
+            })
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            names,
+            vec![
+                vec!["first".to_string(), "second".to_string()],
+                vec!["third".to_string()]
+            ]
+        );
+    }
+
+    #[test]
+    fn it_seals_active_files_left_by_previous_run() {
+        let dir = temp_dir("recover");
+        let spool = Spool::open(&dir, u64::MAX).unwrap();
+        spool.append(&[envelope("first")]).unwrap();
+        drop(spool);
+
+        let spool = Spool::open(&dir, u64::MAX).unwrap();
+
+        let files = spool.sealed().unwrap();
+        let items = read(&files[0]).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(items[0].name, "first");
+    }
+
+    #[test]
+    fn it_skips_malformed_lines() {
+        let dir = temp_dir("malformed");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("items.ndjson");
+        write(&path, &[envelope("first")]).unwrap();
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"name\":\"trunc")
+            .unwrap();
+
+        let items = read(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name, "first");
+    }
+
+    #[test]
+    fn it_writes_header_with_format_version() {
+        let dir = temp_dir("header");
+        let spool = Spool::open(&dir, u64::MAX).unwrap();
+        spool.append(&[envelope("first")]).unwrap();
+        spool.seal();
+
+        let files = spool.sealed().unwrap();
+        let version = version(&files[0]).unwrap();
+        let items = read(&files[0]).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(version, FORMAT_VERSION);
+        assert_eq!(items.len(), 1);
+    }
+
+    #[test]
+    fn it_migrates_files_without_header() {
+        let dir = temp_dir("migrate");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("items.ndjson");
+        let mut legacy = serde_json::to_vec(&envelope("first")).unwrap();
+        legacy.push(b'\n');
+        fs::write(&path, legacy).unwrap();
+        assert_eq!(version(&path).unwrap(), 0);
+
+        Spool::open(&dir, u64::MAX).unwrap();
+
+        let version = version(&path).unwrap();
+        let items = read(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(version, FORMAT_VERSION);
+        assert_eq!(items[0].name, "first");
+    }
+
+    #[test]
+    fn it_reads_files_of_newer_version() {
+        let dir = temp_dir("newer");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("items.ndjson");
+        let content = format!(
+            "{}\n{}\n",
+            json!({ "format": FORMAT, "version": FORMAT_VERSION + 1, "compression": "none" }),
+            json!({ "name": "first", "time": "2019-01-02T03:04:05.600Z", "unknown": true })
+        );
+        fs::write(&path, content).unwrap();
+
+        assert!(!migrate(&path).unwrap());
+        let items = read(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name, "first");
+    }
+
+    #[test]
+    fn it_evicts_oldest_files_beyond_max_size() {
+        let dir = temp_dir("evict");
+        let spool = Spool::open(&dir, file_size("first") + file_size("second")).unwrap();
+
+        for name in ["first", "second", "third"] {
+            spool.append(&[envelope(name)]).unwrap();
+            spool.seal();
+        }
+
+        let names: Vec<String> = spool
+            .sealed()
+            .unwrap()
+            .iter()
+            .flat_map(|path| read(path).unwrap())
+            .map(|item| item.name.into_owned())
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(names, vec!["second".to_string(), "third".to_string()]);
+    }
+
+    #[test]
+    fn it_drops_items_exceeding_max_size() {
+        let dir = temp_dir("drop");
+        let spool = Spool::open(&dir, file_size("first") - 1).unwrap();
+
+        spool.append(&[envelope("first")]).unwrap();
+        spool.seal();
+
+        let files = spool.sealed().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(files.is_empty());
+    }
+
+    #[tokio::test]
+    async fn it_replays_spooled_items_on_startup() {
+        let dir = temp_dir("replay");
+        let spool = Spool::open(&dir, u64::MAX).unwrap();
+        spool.append(&[envelope("first")]).unwrap();
+        drop(spool);
+
+        let sink = TestSink::default();
+        let config = TelemetryConfig::builder().i_key("key").sink(sink.clone()).build();
+        let mut channel = PersistentChannel::new(&config, &dir).unwrap();
+        channel.flush_and_wait().await;
+        channel.close().await;
+
+        let files = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(files, 0);
+        let batches = sink.batches.lock().unwrap();
+        assert_eq!(batches.len(), 1);
+        assert!(batches[0].contains("first"));
+    }
+
+    #[tokio::test]
+    async fn it_removes_spooled_items_once_sent() {
+        let dir = temp_dir("prune");
+        let sink = TestSink::default();
+        sink.available.store(false, Ordering::Relaxed);
+        let config = TelemetryConfig::builder().i_key("key").sink(sink.clone()).build();
+        let mut channel = PersistentChannel::new(&config, &dir).unwrap();
+        // let the routine replay files of a previous run first
+        channel.flush_and_wait().await;
+
+        // items stay on disk while they cannot be sent
+        channel.send(envelope("first"));
+        channel.flush_and_wait().await;
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        sink.available.store(true, Ordering::Relaxed);
+        channel.flush_and_wait().await;
+        channel.close().await;
+
+        let files = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(files, 0);
+        let batches = sink.batches.lock().unwrap();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0], batches[1]);
+    }
+
+    #[tokio::test]
+    async fn it_keeps_tracked_items_on_disk_when_terminated() {
+        let dir = temp_dir("terminate");
+        let sink = TestSink::default();
+        let config = TelemetryConfig::builder().i_key("key").sink(sink.clone()).build();
+        let mut channel = PersistentChannel::new(&config, &dir).unwrap();
+        // let the routine replay files of a previous run first
+        channel.flush_and_wait().await;
+
+        channel.send(envelope("first"));
+        channel.send(envelope("second"));
+        channel.terminate().await;
+
+        let spool = Spool::open(&dir, u64::MAX).unwrap();
+        let items = read(&spool.sealed().unwrap()[0]).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(sink.batches.lock().unwrap().is_empty());
+        assert_eq!(items.len(), 2);
+    }
+
+    #[derive(Clone)]
+    struct TestSink {
+        batches: Arc<Mutex<Vec<String>>>,
+        available: Arc<AtomicBool>,
+    }
+
+    impl Default for TestSink {
+        fn default() -> Self {
+            Self {
+                batches: Arc::default(),
+                available: Arc::new(AtomicBool::new(true)),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl TelemetrySink for TestSink {
+        async fn write(&self, batch: Vec<u8>) -> Result<(), SinkError> {
+            self.batches.lock().unwrap().push(String::from_utf8(batch).unwrap());
+            if self.available.load(Ordering::Relaxed) {
+                Ok(())
+            } else {
+                Err("unavailable".into())
+            }
+        }
+    }
+
+    /// Returns a number of bytes a spooled file with a single item of the given name takes.
+    fn file_size(name: &str) -> u64 {
+        (header().len() + serde_json::to_vec(&envelope(name)).unwrap().len() + 1) as u64
+    }
+
+    fn envelope(name: &str) -> Envelope {
+        Envelope {
+            name: name.to_string().into(),
+            ..Envelope::default()
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("appinsights-spool-{}-{}", name, std::process::id()))
+    }
+}
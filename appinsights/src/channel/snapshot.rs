@@ -0,0 +1,72 @@
+use std::sync::{Arc, Mutex};
+
+use crossbeam_queue::SegQueue;
+
+use crate::{channel::interner::QueuedItem, contracts::Envelope};
+
+/// A summary of a telemetry item waiting in the channel, reported by
+/// [`TelemetryClient::debug_snapshot`](../struct.TelemetryClient.html#method.debug_snapshot).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedItemSnapshot {
+    name: String,
+    time: String,
+    pending: bool,
+}
+
+impl QueuedItemSnapshot {
+    /// Returns a name of the telemetry item, e.g. `Microsoft.ApplicationInsights.Event`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the time the telemetry item was tracked at.
+    pub fn time(&self) -> &str {
+        &self.time
+    }
+
+    /// Returns `true` if the item has been taken from the queue to be sent or retried, and `false` if it still
+    /// waits in the queue.
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+}
+
+impl From<&Envelope> for QueuedItemSnapshot {
+    fn from(envelope: &Envelope) -> Self {
+        Self {
+            name: envelope.name.to_string(),
+            time: envelope.time.clone(),
+            pending: true,
+        }
+    }
+}
+
+/// Items the worker took from the queue and is sending or waiting to retry.
+#[derive(Debug, Clone, Default)]
+pub struct PendingItems(Arc<Mutex<Vec<QueuedItemSnapshot>>>);
+
+impl PendingItems {
+    /// Replaces pending items with the given ones.
+    pub fn set(&self, items: &[Envelope]) {
+        *self.0.lock().unwrap_or_else(|err| err.into_inner()) = items.iter().map(QueuedItemSnapshot::from).collect();
+    }
+
+    pub fn get(&self) -> Vec<QueuedItemSnapshot> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner()).clone()
+    }
+}
+
+/// Takes a snapshot of items waiting in the queue. A queue cannot be inspected in place, so items are taken out
+/// and put back, which moves items queued concurrently ahead of them.
+pub fn queued(items: &SegQueue<QueuedItem>) -> Vec<QueuedItemSnapshot> {
+    let taken: Vec<_> = std::iter::from_fn(|| items.pop()).collect();
+    let snapshot = taken
+        .iter()
+        .map(|item| QueuedItemSnapshot {
+            pending: false,
+            ..QueuedItemSnapshot::from(item.envelope())
+        })
+        .collect();
+    taken.into_iter().for_each(|item| items.push(item));
+    snapshot
+}
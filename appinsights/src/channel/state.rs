@@ -2,16 +2,34 @@ use std::{mem, sync::Arc, time::Duration};
 
 use crossbeam_queue::SegQueue;
 use futures_channel::mpsc::UnboundedReceiver;
-use futures_util::{Future, Stream, StreamExt};
+use futures_util::{future, Future, Stream, StreamExt};
 use log::{debug, error, trace};
 use sm::{sm, Event};
 
+#[cfg(feature = "debug")]
+use crate::channel::snapshot::PendingItems;
+#[cfg(any(test, feature = "test-util"))]
+use crate::test_util::DrainMarker;
 use crate::{
+    callback,
+    channel::age::ItemAge,
+    channel::batch::BatchSize,
+    channel::capacity::Capacity,
     channel::command::Command,
+    channel::envelope,
+    channel::flush::FlushSignal,
+    channel::interner::QueuedItem,
+    channel::interval::Interval,
     channel::retry::Retry,
     channel::state::worker::{Variant::*, *},
+    channel::stats::{QueueLatency, StatsCollector},
+    channel::urgent::UrgentQueue,
+    config::Shared,
     contracts::Envelope,
+    diagnostics::{ChannelEvent, Listener},
+    sink::TelemetrySink,
     timeout,
+    timer::{Timer, TokioTimer},
     transmitter::{Response, Transmitter},
 };
 
@@ -28,6 +46,18 @@ sm! {
             Receiving => Sending
         }
 
+        MaxAgeExceeded {
+            Receiving => Sending
+        }
+
+        UrgentItemsArrived {
+            Receiving => Sending
+        }
+
+        BatchSizeReached {
+            Receiving => Sending
+        }
+
         CloseRequested {
             Receiving => Sending,
             Waiting => Stopped
@@ -59,26 +89,124 @@ sm! {
 
 pub struct Worker {
     transmitter: Transmitter,
-    items: Arc<SegQueue<Envelope>>,
+    items: Arc<SegQueue<QueuedItem>>,
+    urgent: UrgentQueue,
+    capacity: Capacity,
     command_receiver: UnboundedReceiver<Command>,
-    interval: Duration,
+    interval: Interval,
+    age: ItemAge,
+    max_item_age: Option<Duration>,
+    batch_size: Option<BatchSize>,
+    record_retry_count: bool,
+    timer: Shared<dyn Timer>,
+    listener: Listener,
+    terminate_sink: Option<Shared<dyn TelemetrySink>>,
+    shrink_requested: bool,
+    urgent_only: bool,
+    paused: bool,
+    flush_deferred: bool,
+    deferred_command: Option<Command>,
+    queue_latency: QueueLatency,
+    stats: StatsCollector,
+    flush_signal: FlushSignal,
+    #[cfg(feature = "debug")]
+    pending: PendingItems,
+    #[cfg(any(test, feature = "test-util"))]
+    drain_marker: Option<DrainMarker>,
 }
 
 impl Worker {
     pub fn new(
         transmitter: Transmitter,
-        items: Arc<SegQueue<Envelope>>,
+        items: Arc<SegQueue<QueuedItem>>,
+        urgent: UrgentQueue,
+        capacity: Capacity,
         command_receiver: UnboundedReceiver<Command>,
-        interval: Duration,
+        interval: Interval,
     ) -> Self {
         Self {
             transmitter,
             items,
+            urgent,
+            capacity,
             command_receiver,
             interval,
+            age: ItemAge::default(),
+            max_item_age: None,
+            batch_size: None,
+            record_retry_count: false,
+            timer: Shared(Arc::new(TokioTimer)),
+            listener: Listener::default(),
+            terminate_sink: None,
+            shrink_requested: false,
+            urgent_only: false,
+            paused: false,
+            flush_deferred: false,
+            deferred_command: None,
+            queue_latency: QueueLatency::default(),
+            stats: StatsCollector::default(),
+            flush_signal: FlushSignal::default(),
+            #[cfg(feature = "debug")]
+            pending: PendingItems::default(),
+            #[cfg(any(test, feature = "test-util"))]
+            drain_marker: None,
         }
     }
 
+    pub fn max_item_age(mut self, age: ItemAge, max_item_age: Option<Duration>) -> Self {
+        self.age = age;
+        self.max_item_age = max_item_age;
+        self
+    }
+
+    pub fn batch_size(mut self, batch_size: Option<BatchSize>) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn record_retry_count(mut self, record_retry_count: bool) -> Self {
+        self.record_retry_count = record_retry_count;
+        self
+    }
+
+    pub fn timer(mut self, timer: Shared<dyn Timer>) -> Self {
+        self.timer = timer;
+        self
+    }
+
+    pub fn listener(mut self, listener: Listener) -> Self {
+        self.listener = listener;
+        self
+    }
+
+    pub fn terminate_sink(mut self, terminate_sink: Option<Shared<dyn TelemetrySink>>) -> Self {
+        self.terminate_sink = terminate_sink;
+        self
+    }
+
+    pub fn stats(mut self, stats: StatsCollector) -> Self {
+        self.queue_latency = QueueLatency::new(stats.clone());
+        self.stats = stats;
+        self
+    }
+
+    pub fn flush_signal(mut self, flush_signal: FlushSignal) -> Self {
+        self.flush_signal = flush_signal;
+        self
+    }
+
+    #[cfg(feature = "debug")]
+    pub fn pending(mut self, pending: PendingItems) -> Self {
+        self.pending = pending;
+        self
+    }
+
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn drain_marker(mut self, drain_marker: Option<DrainMarker>) -> Self {
+        self.drain_marker = drain_marker;
+        self
+    }
+
     pub async fn run(mut self) {
         let mut state = Machine::new(Receiving).as_enum();
 
@@ -92,11 +220,17 @@ impl Worker {
                 ReceivingByRetryExhausted(m) => self.handle_receiving(m, &mut items).await,
                 SendingByTimeoutExpired(m) => self.handle_sending_with_retry(m, &mut items, &mut retry).await,
                 SendingByFlushRequested(m) => self.handle_sending_with_retry(m, &mut items, &mut retry).await,
+                SendingByMaxAgeExceeded(m) => self.handle_sending_with_retry(m, &mut items, &mut retry).await,
+                SendingByUrgentItemsArrived(m) => self.handle_sending_with_retry(m, &mut items, &mut retry).await,
+                SendingByBatchSizeReached(m) => self.handle_sending_with_retry(m, &mut items, &mut retry).await,
                 SendingByCloseRequested(m) => self.handle_sending_once_and_terminate(m, &mut items, &mut retry).await,
-                WaitingByRetryRequested(m) => self.handle_waiting(m, &mut retry).await,
+                WaitingByRetryRequested(m) => self.handle_waiting(m, &mut retry, items.len()).await,
                 StoppedByItemsSentAndStop(_) => break,
                 StoppedByCloseRequested(_) => break,
-                StoppedByTerminateRequested(_) => break,
+                StoppedByTerminateRequested(_) => {
+                    self.export(&mut items).await;
+                    break;
+                }
             }
         }
     }
@@ -104,30 +238,74 @@ impl Worker {
     async fn handle_receiving<E: Event>(&mut self, m: Machine<Receiving, E>, items: &mut Vec<Envelope>) -> Variant {
         debug!("Receiving messages triggered by {:?}", m.trigger());
 
-        let timeout = timeout::sleep(self.interval);
+        let timeout = timeout::sleep(&*self.timer, self.interval.get());
         items.clear();
+        self.queue_latency.truncate(0);
+        if mem::take(&mut self.shrink_requested) {
+            debug!("Releasing {} reserved items", items.capacity());
+            items.shrink_to_fit();
+            self.transmitter.shrink();
+        }
+        self.capacity.release();
+
+        tokio::pin!(timeout);
 
         loop {
             tokio::select! {
-                command = self.command_receiver.next() => {
+                command = next_command(&mut self.deferred_command, &mut self.command_receiver) => {
                     match command {
                         Some(command) => {
                             trace!("Command received: {}", command);
                             match command {
-                                Command::Flush => return m.transition(FlushRequested).as_enum(),
-                                Command::Terminate => return m.transition(TerminateRequested).as_enum(),
-                                Command::Close => return m.transition(CloseRequested).as_enum(),
+                                Command::Flush | Command::Shrink if self.paused => {
+                                    debug!("Submission paused. Deferring {} until resumed", command);
+                                    self.shrink_requested |= command == Command::Shrink;
+                                    self.flush_deferred = true;
+                                }
+                                Command::Flush => {
+                                    self.shrink_requested |=
+                                        coalesce_flushes(&mut self.command_receiver, &mut self.deferred_command);
+                                    break m.transition(FlushRequested).as_enum();
+                                }
+                                Command::Shrink => {
+                                    self.shrink_requested = true;
+                                    self.shrink_requested |=
+                                        coalesce_flushes(&mut self.command_receiver, &mut self.deferred_command);
+                                    break m.transition(FlushRequested).as_enum();
+                                }
+                                Command::Pause => self.paused = true,
+                                Command::Resume => {
+                                    self.paused = false;
+                                    if mem::take(&mut self.flush_deferred) {
+                                        break m.transition(FlushRequested).as_enum();
+                                    }
+                                }
+                                Command::Terminate => break m.transition(TerminateRequested).as_enum(),
+                                Command::Close => break m.transition(CloseRequested).as_enum(),
                             }
                         },
                         None => {
                             error!("commands channel closed");
-                            return m.transition(TerminateRequested).as_enum()
+                            break m.transition(TerminateRequested).as_enum();
                         },
                     }
                 },
-                _ = timeout => {
+                _ = &mut timeout, if !self.paused => {
                     debug!("Timeout expired");
-                    return m.transition(TimeoutExpired).as_enum()
+                    break m.transition(TimeoutExpired).as_enum();
+                },
+                _ = expired(&self.age, &*self.timer, self.max_item_age), if !self.paused => {
+                    debug!("Oldest telemetry item exceeded max age");
+                    break m.transition(MaxAgeExceeded).as_enum();
+                },
+                _ = reached(&self.batch_size, &self.items), if !self.paused => {
+                    debug!("Telemetry items make up a full batch");
+                    break m.transition(BatchSizeReached).as_enum();
+                },
+                _ = self.urgent.arrived(), if !self.paused => {
+                    debug!("Telemetry items to send immediately arrived");
+                    self.urgent_only = true;
+                    break m.transition(UrgentItemsArrived).as_enum();
                 },
             }
         }
@@ -156,11 +334,32 @@ impl Worker {
     }
 
     async fn handle_sending<E: Event>(&mut self, m: Machine<Sending, E>, items: &mut Vec<Envelope>) -> Variant {
-        // read pending items from a channel
-        while let Some(item) = self.items.pop() {
-            items.push(item);
+        // an attempt that sends only items to send immediately leaves other queued items behind
+        let ticket = (!self.urgent_only).then(|| self.flush_signal.ticket());
+        let next = self.send(m, items).await;
+        if let Some(ticket) = ticket {
+            self.flush_signal.complete(ticket);
+        }
+
+        #[cfg(any(test, feature = "test-util"))]
+        if let Some(marker) = &self.drain_marker {
+            marker.mark();
+        }
+
+        next
+    }
+
+    async fn send<E: Event>(&mut self, m: Machine<Sending, E>, items: &mut Vec<Envelope>) -> Variant {
+        // read pending items from a channel, only items to send immediately if nothing else is due
+        if mem::take(&mut self.urgent_only) {
+            self.drain_urgent(items);
+        } else {
+            self.drain(items);
         }
 
+        // items being transmitted are not counted against the channel capacity
+        self.capacity.release();
+
         debug!(
             "Sending {} telemetry items triggered by {:?}",
             items.len(),
@@ -173,53 +372,150 @@ impl Worker {
             m.transition(ItemsSentAndContinue).as_enum()
         } else {
             // attempt to send items
-            match self.transmitter.send(mem::take(items)).await {
-                Ok(Response::Success) => m.transition(ItemsSentAndContinue).as_enum(),
+            let count = items.len();
+            #[cfg(feature = "debug")]
+            self.pending.set(items);
+            let next = match self.transmitter.send(mem::take(items)).await {
+                Ok(Response::Success) => {
+                    self.flush_signal.record(count, 0);
+                    self.queue_latency.sent(count);
+                    m.transition(ItemsSentAndContinue).as_enum()
+                }
                 Ok(Response::Retry(retry_items)) => {
-                    *items = retry_items;
+                    self.flush_signal.record(count - retry_items.len(), retry_items.len());
+                    self.queue_latency.sent(count.saturating_sub(retry_items.len()));
+                    self.retain(items, retry_items);
                     m.transition(RetryRequested).as_enum()
                 }
-                Ok(Response::Throttled(_retry_after, retry_items)) => {
-                    *items = retry_items;
+                Ok(Response::Throttled(retry_after, retry_items)) => {
+                    self.stats.record_throttled(retry_after);
+                    self.flush_signal.record(count - retry_items.len(), retry_items.len());
+                    self.queue_latency.sent(count.saturating_sub(retry_items.len()));
+                    self.retain(items, retry_items);
                     // TODO implement throttling instead
                     m.transition(RetryRequested).as_enum()
                 }
-                Ok(Response::NoRetry) => m.transition(ItemsSentAndContinue).as_enum(),
+                Ok(Response::NoRetry) => {
+                    self.flush_signal.record(0, count);
+                    self.queue_latency.truncate(0);
+                    m.transition(ItemsSentAndContinue).as_enum()
+                }
                 Err(err) => {
                     debug!("Error occurred during sending telemetry items: {}", err);
+                    self.flush_signal.record(0, count);
+                    self.queue_latency.truncate(0);
                     m.transition(RetryRequested).as_enum()
                 }
+            };
+
+            // items left are retried
+            #[cfg(feature = "debug")]
+            self.pending.set(items);
+            next
+        }
+    }
+
+    fn drain(&mut self, items: &mut Vec<Envelope>) {
+        self.age.reset();
+
+        self.drain_urgent(items);
+        while let Some(item) = self.items.pop() {
+            self.queue_latency.drained(item.enqueued());
+            items.push(item.into_envelope());
+        }
+    }
+
+    /// Drains items to send immediately.
+    fn drain_urgent(&mut self, items: &mut Vec<Envelope>) {
+        while let Some(item) = self.urgent.pop() {
+            self.queue_latency.drained(item.enqueued());
+            items.push(item.into_envelope());
+        }
+    }
+
+    /// Writes items that are about to be discarded on termination to the terminate sink if configured.
+    async fn export(&mut self, items: &mut Vec<Envelope>) {
+        if let Some(sink) = self.terminate_sink.clone() {
+            self.drain(items);
+            if items.is_empty() {
+                return;
+            }
+
+            let batch = match serde_json::to_vec(items) {
+                Ok(batch) => batch,
+                Err(err) => {
+                    error!("Unable to serialize {} telemetry items to export: {}", items.len(), err);
+                    return;
+                }
+            };
+            match callback::call_async("Terminate sink", sink.write(batch)).await {
+                Some(Ok(())) => debug!("Exported {} pending telemetry items", items.len()),
+                Some(Err(err)) => error!("Unable to export {} pending telemetry items: {}", items.len(), err),
+                None => {}
             }
         }
     }
 
-    async fn handle_waiting<E: Event>(&mut self, m: Machine<Waiting, E>, retry: &mut Retry) -> Variant {
+    /// Keeps items to retry along with items queued in the meantime, limited by the channel capacity.
+    fn retain(&mut self, items: &mut Vec<Envelope>, mut retry_items: Vec<Envelope>) {
+        if self.record_retry_count {
+            retry_items.iter_mut().for_each(envelope::record_retry);
+        }
+
+        *items = retry_items;
+        self.drain(items);
+        let dropped = self.capacity.retain(items);
+        self.stats.record_dropped(dropped);
+        self.queue_latency.truncate(items.len());
+    }
+
+    async fn handle_waiting<E: Event>(&mut self, m: Machine<Waiting, E>, retry: &mut Retry, count: usize) -> Variant {
         if let Some(timeout) = retry.next() {
             debug!(
                 "Waiting for retry timeout {:?} or stop command triggered by {:?}",
                 timeout,
                 m.state()
             );
+            self.listener.emit(ChannelEvent::RetryScheduled {
+                items: count,
+                after: timeout,
+            });
             // sleep until next sending attempt
-            let timeout = timeout::sleep(timeout);
+            let timeout = timeout::sleep(&*self.timer, timeout);
+            tokio::pin!(timeout);
+            let mut expired = false;
 
-            // wait for either retry timeout expired or stop command received
-            tokio::select! {
-                command = skip_flush(&mut self.command_receiver) => {
-                    match command {
-                        Some(Command::Terminate) => m.transition(TerminateRequested).as_enum(),
-                        Some(Command::Close) => m.transition(CloseRequested).as_enum(),
-                        Some(Command::Flush) => panic!("whoops Flush is not supported here"),
-                        None => {
-                            error!("commands channel closed");
-                            m.transition(TerminateRequested).as_enum()
+            // wait for either retry timeout expired or stop command received, and for resume if paused
+            loop {
+                tokio::select! {
+                    command = skip_flush(&mut self.deferred_command, &mut self.command_receiver) => {
+                        match command {
+                            Some(Command::Terminate) => break m.transition(TerminateRequested).as_enum(),
+                            Some(Command::Close) => break m.transition(CloseRequested).as_enum(),
+                            Some(Command::Pause) => self.paused = true,
+                            Some(Command::Resume) => {
+                                self.paused = false;
+                                if expired {
+                                    break m.transition(TimeoutExpired).as_enum();
+                                }
+                            }
+                            Some(Command::Flush) | Some(Command::Shrink) => panic!("whoops Flush is not supported here"),
+                            None => {
+                                error!("commands channel closed");
+                                break m.transition(TerminateRequested).as_enum();
+                            }
                         }
-                    }
-                },
-                _ = timeout => {
-                    debug!("Retry timeout expired");
-                    m.transition(TimeoutExpired).as_enum()
-                },
+                    },
+                    _ = &mut timeout, if !expired => {
+                        if self.paused {
+                            debug!("Retry timeout expired. Submission paused, retrying once resumed");
+                            expired = true;
+                        } else {
+                            debug!("Retry timeout expired");
+                            break m.transition(TimeoutExpired).as_enum();
+                        }
+                    },
+                }
             }
         } else {
             debug!("All retries exhausted by {:?}", m.state());
@@ -228,11 +524,59 @@ impl Worker {
     }
 }
 
-fn skip_flush<St>(stream: &mut St) -> SkipFlush<'_, St> {
-    SkipFlush { stream }
+/// Drops flush commands queued right behind the one being handled, so rapid repeated flushes result in a single
+/// submission instead of a number of small ones. Stops at the first command of another kind, which is kept to be
+/// handled next. Returns `true` if a shrink was requested among dropped commands.
+fn coalesce_flushes(receiver: &mut UnboundedReceiver<Command>, deferred: &mut Option<Command>) -> bool {
+    let mut coalesced = 0;
+    let mut shrink_requested = false;
+    while deferred.is_none() {
+        match receiver.try_next() {
+            Ok(Some(Command::Flush)) => coalesced += 1,
+            Ok(Some(Command::Shrink)) => {
+                shrink_requested = true;
+                coalesced += 1;
+            }
+            Ok(Some(command)) => *deferred = Some(command),
+            Ok(None) | Err(_) => break,
+        }
+    }
+
+    if coalesced > 0 {
+        debug!("Coalesced {} pending flush commands", coalesced);
+    }
+    shrink_requested
+}
+
+/// Resolves with a command kept to be handled next if any, or with the next received command.
+async fn next_command(deferred: &mut Option<Command>, receiver: &mut UnboundedReceiver<Command>) -> Option<Command> {
+    match deferred.take() {
+        Some(command) => Some(command),
+        None => receiver.next().await,
+    }
+}
+
+/// Resolves once the oldest queued item exceeds max age if configured, never resolves otherwise.
+async fn expired(age: &ItemAge, timer: &dyn Timer, max_item_age: Option<Duration>) {
+    match max_item_age {
+        Some(max_item_age) => age.expired(timer, max_item_age).await,
+        None => future::pending().await,
+    }
+}
+
+async fn reached(batch_size: &Option<BatchSize>, items: &SegQueue<QueuedItem>) {
+    match batch_size {
+        Some(batch_size) => batch_size.reached(items).await,
+        None => future::pending().await,
+    }
+}
+
+fn skip_flush<'a, St>(deferred: &'a mut Option<Command>, stream: &'a mut St) -> SkipFlush<'a, St> {
+    SkipFlush { deferred, stream }
 }
 
 struct SkipFlush<'a, St: ?Sized> {
+    deferred: &'a mut Option<Command>,
     stream: &'a mut St,
 }
 
@@ -240,10 +584,18 @@ impl<St: ?Sized + Stream<Item = Command> + Unpin> Future for SkipFlush<'_, St> {
     type Output = Option<St::Item>;
 
     fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
-        match self.stream.poll_next_unpin(cx) {
-            std::task::Poll::Ready(Some(Command::Flush)) => std::task::Poll::Pending,
-            std::task::Poll::Ready(command) => std::task::Poll::Ready(command),
-            std::task::Poll::Pending => std::task::Poll::Pending,
+        if let Some(command) = self.deferred.take() {
+            return std::task::Poll::Ready(Some(command));
+        }
+
+        loop {
+            match self.stream.poll_next_unpin(cx) {
+                std::task::Poll::Ready(Some(Command::Flush)) | std::task::Poll::Ready(Some(Command::Shrink)) => {
+                    continue
+                }
+                std::task::Poll::Ready(command) => return std::task::Poll::Ready(command),
+                std::task::Poll::Pending => return std::task::Poll::Pending,
+            }
         }
     }
 }
@@ -0,0 +1,429 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use tokio::time::Instant;
+
+use crate::{
+    diagnostics::{ChannelEvent, EventListener, Listener},
+    time,
+};
+
+/// Number of the most recently sent telemetry items queue latency percentiles are calculated over.
+const LATENCY_WINDOW: usize = 1024;
+
+/// A snapshot of statistics of the telemetry channel.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct ChannelStats {
+    queue_latency: Option<LatencyPercentiles>,
+    dropped_items: u64,
+    queued_items: u64,
+    sent_items: u64,
+    failed_items: u64,
+    last_transmission: Option<Transmission>,
+    retrying: bool,
+    processing_lag: Option<Duration>,
+    throttled_until: Option<DateTime<Utc>>,
+}
+
+impl ChannelStats {
+    /// Returns percentiles of time it took recently sent telemetry items to get from the queue to the ingestion
+    /// endpoint, including time spent waiting for retries. Returns `None` if nothing has been sent yet or the
+    /// channel does not track latency.
+    pub fn queue_latency(&self) -> Option<LatencyPercentiles> {
+        self.queue_latency
+    }
+
+    /// Returns a number of telemetry items dropped because the channel was over capacity or they exceeded the
+    /// maximum envelope size.
+    pub fn dropped_items(&self) -> u64 {
+        self.dropped_items
+    }
+
+    /// Returns a number of telemetry items waiting in the queue to be sent, not counting items waiting for
+    /// a retry.
+    pub fn queued_items(&self) -> u64 {
+        self.queued_items
+    }
+
+    /// Returns a number of telemetry items the ingestion endpoint or a sink accepted.
+    pub fn sent_items(&self) -> u64 {
+        self.sent_items
+    }
+
+    /// Returns a number of telemetry items in batches that failed or that the ingestion endpoint did not accept,
+    /// including items that were retried later.
+    pub fn failed_items(&self) -> u64 {
+        self.failed_items
+    }
+
+    /// Returns an outcome of the most recent attempt to send a batch, if any.
+    pub fn last_transmission(&self) -> Option<Transmission> {
+        self.last_transmission
+    }
+
+    /// Returns `true` if the channel holds telemetry items it failed to send and is going to retry.
+    pub fn is_retrying(&self) -> bool {
+        self.retrying
+    }
+
+    /// Returns how long the oldest telemetry item waiting in the queue has been there, zero if the queue is
+    /// empty, or `None` if the channel does not track lag. The lag grows beyond the submission interval when
+    /// the worker falls behind or gets stuck.
+    pub fn processing_lag(&self) -> Option<Duration> {
+        self.processing_lag
+    }
+
+    /// Returns time until which the ingestion endpoint asked the channel to hold off sending, if that time has
+    /// not passed yet.
+    pub fn throttled_until(&self) -> Option<DateTime<Utc>> {
+        self.throttled_until
+    }
+
+    pub(crate) fn set_processing_lag(&mut self, lag: Duration) {
+        self.processing_lag = Some(lag);
+    }
+
+    pub(crate) fn set_queued_items(&mut self, queued_items: usize) {
+        self.queued_items = queued_items as u64;
+    }
+}
+
+/// An outcome of an attempt to send a batch of telemetry items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transmission {
+    time: DateTime<Utc>,
+    status_code: Option<u16>,
+    succeeded: bool,
+}
+
+impl Transmission {
+    /// Returns time the attempt completed at.
+    pub fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    /// Returns a status code of the response, or `None` if no response has been received or the batch has been
+    /// written to a sink.
+    pub fn status_code(&self) -> Option<u16> {
+        self.status_code
+    }
+
+    /// Returns `true` if the batch has been sent, even if the ingestion endpoint accepted some of its items
+    /// only.
+    pub fn succeeded(&self) -> bool {
+        self.succeeded
+    }
+}
+
+/// Percentiles of time between a telemetry item was queued and the ingestion endpoint accepted it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyPercentiles {
+    p50: Duration,
+    p95: Duration,
+    p99: Duration,
+}
+
+impl LatencyPercentiles {
+    /// Returns the median latency.
+    pub fn p50(&self) -> Duration {
+        self.p50
+    }
+
+    /// Returns the latency 95% of items did not exceed.
+    pub fn p95(&self) -> Duration {
+        self.p95
+    }
+
+    /// Returns the latency 99% of items did not exceed.
+    pub fn p99(&self) -> Duration {
+        self.p99
+    }
+}
+
+/// Collects statistics the worker maintains and the channel reports.
+#[derive(Debug, Clone, Default)]
+pub struct StatsCollector {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    latencies: Mutex<VecDeque<Duration>>,
+    dropped: AtomicU64,
+    sent: AtomicU64,
+    failed: AtomicU64,
+    last_transmission: Mutex<Option<Transmission>>,
+    retrying: AtomicBool,
+    throttled_until: Mutex<Option<DateTime<Utc>>>,
+    listener: Listener,
+}
+
+impl StatsCollector {
+    /// Creates a collector that notifies the given listener about telemetry items dropped.
+    pub fn new(listener: Listener) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                listener,
+                ..Inner::default()
+            }),
+        }
+    }
+
+    /// Records telemetry items dropped because the channel was over capacity.
+    pub fn record_dropped(&self, count: usize) {
+        if count > 0 {
+            self.inner.dropped.fetch_add(count as u64, Ordering::Relaxed);
+            self.inner.listener.emit(ChannelEvent::ItemsDropped { count });
+        }
+    }
+
+    /// Records time until which the ingestion endpoint asked to hold off sending.
+    pub fn record_throttled(&self, until: DateTime<Utc>) {
+        *self.throttled_until() = Some(until);
+    }
+
+    /// Records queue latency of an item that has been sent.
+    fn record_latency(&self, latency: Duration) {
+        let mut latencies = self.lock();
+        if latencies.len() == LATENCY_WINDOW {
+            latencies.pop_front();
+        }
+        latencies.push_back(latency);
+    }
+
+    /// Returns a snapshot of statistics collected so far.
+    pub fn snapshot(&self) -> ChannelStats {
+        let mut latencies: Vec<_> = self.lock().iter().copied().collect();
+        latencies.sort_unstable();
+
+        ChannelStats {
+            queue_latency: (!latencies.is_empty()).then(|| LatencyPercentiles {
+                p50: percentile(&latencies, 50),
+                p95: percentile(&latencies, 95),
+                p99: percentile(&latencies, 99),
+            }),
+            dropped_items: self.inner.dropped.load(Ordering::Relaxed),
+            queued_items: 0,
+            sent_items: self.inner.sent.load(Ordering::Relaxed),
+            failed_items: self.inner.failed.load(Ordering::Relaxed),
+            last_transmission: *self.last_transmission(),
+            retrying: self.inner.retrying.load(Ordering::Relaxed),
+            processing_lag: None,
+            throttled_until: self.throttled_until().filter(|until| *until > time::now()),
+        }
+    }
+
+    /// Records an outcome of an attempt to send a batch.
+    fn record_transmission(&self, status_code: Option<u16>, succeeded: bool) {
+        *self.last_transmission() = Some(Transmission {
+            time: time::now(),
+            status_code,
+            succeeded,
+        });
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<Duration>> {
+        self.inner.latencies.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn throttled_until(&self) -> MutexGuard<'_, Option<DateTime<Utc>>> {
+        self.inner.throttled_until.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn last_transmission(&self) -> MutexGuard<'_, Option<Transmission>> {
+        self.inner
+            .last_transmission
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Collects statistics of batches the transmitter sends and retries the worker schedules.
+impl EventListener for StatsCollector {
+    fn on_event(&self, event: &ChannelEvent) {
+        match *event {
+            ChannelEvent::BatchSent {
+                items,
+                accepted,
+                status_code,
+            } => {
+                self.inner.sent.fetch_add(accepted as u64, Ordering::Relaxed);
+                self.inner
+                    .failed
+                    .fetch_add(items.saturating_sub(accepted) as u64, Ordering::Relaxed);
+                if accepted == items {
+                    self.inner.retrying.store(false, Ordering::Relaxed);
+                }
+                self.record_transmission(status_code, true);
+            }
+            ChannelEvent::BatchFailed { items, status_code } => {
+                self.inner.failed.fetch_add(items as u64, Ordering::Relaxed);
+                self.record_transmission(status_code, false);
+            }
+            ChannelEvent::RetryScheduled { .. } => self.inner.retrying.store(true, Ordering::Relaxed),
+            ChannelEvent::ItemsDropped { count } => {
+                self.inner.dropped.fetch_add(count as u64, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Returns a value at the given percentile of sorted values using the nearest-rank method.
+fn percentile(sorted: &[Duration], percentile: usize) -> Duration {
+    let rank = (sorted.len() * percentile).div_ceil(100);
+    sorted[rank.saturating_sub(1)]
+}
+
+/// Tracks when items the worker holds were queued, so their queue latency is known once they have been sent.
+///
+/// The worker holds the same number of enqueue times as items, in the order items were drained. The endpoint
+/// reports which items to retry but the worker cannot match them to the items it sent, so items to retry are
+/// assumed to keep the earliest enqueue times, which reports latency of accepted items as low as possible and
+/// accounts the whole wait to items that are retried.
+#[derive(Debug, Default)]
+pub struct QueueLatency {
+    enqueued: VecDeque<Instant>,
+    stats: StatsCollector,
+}
+
+impl QueueLatency {
+    pub fn new(stats: StatsCollector) -> Self {
+        Self {
+            enqueued: VecDeque::new(),
+            stats,
+        }
+    }
+
+    /// Accounts an item drained from the queue.
+    pub fn drained(&mut self, enqueued: Instant) {
+        self.enqueued.push_back(enqueued);
+    }
+
+    /// Records latency of `accepted` items out of the items held that have been sent.
+    pub fn sent(&mut self, accepted: usize) {
+        let now = Instant::now();
+        let retried = self.enqueued.len().saturating_sub(accepted);
+        for enqueued in self.enqueued.drain(retried..) {
+            self.stats.record_latency(now.saturating_duration_since(enqueued));
+        }
+    }
+
+    /// Forgets enqueue times of the oldest items, so no more than `len` items are accounted.
+    pub fn truncate(&mut self, len: usize) {
+        let excess = self.enqueued.len().saturating_sub(len);
+        self.enqueued.drain(..excess);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use matches::assert_matches;
+
+    use super::*;
+
+    #[test]
+    fn it_reports_no_latency_before_items_sent() {
+        let stats = StatsCollector::default();
+
+        assert_eq!(stats.snapshot().queue_latency(), None);
+    }
+
+    #[test]
+    fn it_counts_dropped_items() {
+        let stats = StatsCollector::default();
+
+        stats.record_dropped(2);
+        stats.clone().record_dropped(1);
+        stats.on_event(&ChannelEvent::ItemsDropped { count: 4 });
+
+        assert_eq!(stats.snapshot().dropped_items(), 7);
+    }
+
+    #[test]
+    fn it_counts_sent_and_failed_items() {
+        let stats = StatsCollector::default();
+
+        stats.on_event(&ChannelEvent::BatchFailed {
+            items: 10,
+            status_code: Some(500),
+        });
+        stats.on_event(&ChannelEvent::RetryScheduled {
+            items: 10,
+            after: Duration::from_secs(1),
+        });
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.failed_items(), 10);
+        assert!(snapshot.is_retrying());
+        assert_matches!(
+            snapshot.last_transmission(),
+            Some(transmission) if transmission.status_code() == Some(500) && !transmission.succeeded()
+        );
+
+        stats.on_event(&ChannelEvent::BatchSent {
+            items: 10,
+            accepted: 10,
+            status_code: Some(200),
+        });
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.sent_items(), 10);
+        assert_eq!(snapshot.failed_items(), 10);
+        assert!(!snapshot.is_retrying());
+        assert_matches!(snapshot.last_transmission(), Some(transmission) if transmission.succeeded());
+    }
+
+    #[test]
+    fn it_calculates_percentiles() {
+        let stats = StatsCollector::default();
+        for millis in 1..=100 {
+            stats.record_latency(Duration::from_millis(millis));
+        }
+
+        let latency = stats.snapshot().queue_latency().unwrap();
+
+        assert_eq!(latency.p50(), Duration::from_millis(50));
+        assert_eq!(latency.p95(), Duration::from_millis(95));
+        assert_eq!(latency.p99(), Duration::from_millis(99));
+    }
+
+    #[test]
+    fn it_keeps_most_recent_latencies() {
+        let stats = StatsCollector::default();
+        stats.record_latency(Duration::from_secs(60));
+        for _ in 0..LATENCY_WINDOW {
+            stats.record_latency(Duration::from_millis(1));
+        }
+
+        let latency = stats.snapshot().queue_latency().unwrap();
+
+        assert_eq!(latency.p99(), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn it_records_latency_of_accepted_items() {
+        let stats = StatsCollector::default();
+        let mut latency = QueueLatency::new(stats.clone());
+        let now = Instant::now();
+
+        latency.drained(now - Duration::from_secs(60));
+        latency.drained(now - Duration::from_secs(1));
+
+        // the oldest item is retried
+        latency.sent(1);
+        let p99 = stats.snapshot().queue_latency().unwrap().p99();
+        assert!((Duration::from_secs(1)..Duration::from_secs(60)).contains(&p99));
+
+        latency.sent(1);
+        let p99 = stats.snapshot().queue_latency().unwrap().p99();
+        assert!(p99 >= Duration::from_secs(60));
+    }
+}
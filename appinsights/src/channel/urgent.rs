@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use crossbeam_queue::SegQueue;
+use tokio::sync::Notify;
+
+use crate::channel::interner::QueuedItem;
+
+/// A queue of telemetry items to send right away instead of waiting for a batch.
+#[derive(Debug, Clone, Default)]
+pub struct UrgentQueue {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    items: SegQueue<QueuedItem>,
+    notify: Notify,
+}
+
+impl UrgentQueue {
+    /// Enqueues an item and wakes up the worker.
+    pub fn push(&self, item: QueuedItem) {
+        self.inner.items.push(item);
+        self.inner.notify.notify_one();
+    }
+
+    pub fn pop(&self) -> Option<QueuedItem> {
+        self.inner.items.pop()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.items.len()
+    }
+
+    #[cfg(feature = "debug")]
+    pub fn snapshot(&self) -> Vec<crate::channel::QueuedItemSnapshot> {
+        crate::channel::snapshot::queued(&self.inner.items)
+    }
+
+    /// Resolves once there are items in the queue.
+    pub async fn arrived(&self) {
+        // a permit stored by an item pushed in the meantime wakes up the worker immediately
+        while self.inner.items.is_empty() {
+            self.inner.notify.notified().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::contracts::Envelope;
+
+    #[tokio::test]
+    async fn it_resolves_when_items_arrived() {
+        let queue = UrgentQueue::default();
+
+        let arrived = tokio::time::timeout(Duration::from_millis(10), queue.arrived()).await;
+        assert!(arrived.is_err());
+
+        queue.push(QueuedItem::from(Envelope::default()));
+        let arrived = tokio::time::timeout(Duration::from_millis(10), queue.arrived()).await;
+        assert!(arrived.is_ok());
+    }
+
+    #[tokio::test]
+    async fn it_waits_again_when_items_taken() {
+        let queue = UrgentQueue::default();
+
+        queue.push(QueuedItem::from(Envelope::default()));
+        assert!(queue.pop().is_some());
+
+        let arrived = tokio::time::timeout(Duration::from_millis(10), queue.arrived()).await;
+        assert!(arrived.is_err());
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    telemetry::{OperationResult, RemoteDependencyTelemetry, Timestamp},
+    time, TelemetryClient,
+};
+
+/// Measures duration of a dependency call and tracks it as a telemetry item when dropped, so the call is
+/// reported with a correct duration on every path out of the code that made it, including early returns and
+/// the `?` operator. The call is considered successful unless it is marked as failed.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # fn query() -> Result<Vec<String>, std::io::Error> { Ok(Vec::new()) }
+/// # fn load(client: &TelemetryClient) -> Result<Vec<String>, std::io::Error> {
+/// let mut dependency = client.start_dependency("SELECT * FROM users", "SQL", "db.example.com");
+/// let users = query().inspect_err(|_| dependency.mark_failed())?;
+/// if users.is_empty() {
+///     // the dependency is tracked here as well
+///     return Ok(users);
+/// }
+/// dependency.set_result_code("00000");
+/// Ok(users)
+/// # }
+/// ```
+pub struct DependencyTracker<'a> {
+    client: &'a TelemetryClient,
+    started: Instant,
+    timestamp: Timestamp,
+    name: String,
+    dependency_type: String,
+    target: String,
+    result_code: Option<String>,
+    success: bool,
+    result: Option<OperationResult>,
+}
+
+impl<'a> DependencyTracker<'a> {
+    /// Starts measuring a dependency call with the given name, type and target site.
+    pub(crate) fn start(
+        client: &'a TelemetryClient,
+        name: impl Into<String>,
+        dependency_type: impl Into<String>,
+        target: impl Into<String>,
+    ) -> Self {
+        Self {
+            client,
+            started: Instant::now(),
+            timestamp: time::timestamp(),
+            name: name.into(),
+            dependency_type: dependency_type.into(),
+            target: target.into(),
+            result_code: None,
+            success: true,
+            result: None,
+        }
+    }
+
+    /// Returns the time when the dependency call started.
+    pub fn timestamp(&self) -> Timestamp {
+        self.timestamp
+    }
+
+    /// Returns the time elapsed since the dependency call started.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Marks the dependency call as failed.
+    pub fn mark_failed(&mut self) {
+        self.success = false;
+    }
+
+    /// Sets an outcome of the dependency call, which determines whether the call succeeded and why it did not.
+    /// Takes precedence over [`mark_failed`](#method.mark_failed).
+    pub fn set_result(&mut self, result: OperationResult) {
+        self.result = Some(result);
+    }
+
+    /// Sets a result code of the dependency call.
+    pub fn set_result_code(&mut self, result_code: impl Into<String>) {
+        self.result_code = Some(result_code.into());
+    }
+
+    /// Tracks the dependency call right away. It is the same as dropping the tracker, but reads better at the
+    /// end of a block.
+    pub fn complete(self) {}
+
+    /// Tracks the dependency call right away with the given success status.
+    pub fn finish(mut self, success: bool) {
+        self.success = success;
+    }
+}
+
+impl Drop for DependencyTracker<'_> {
+    fn drop(&mut self) {
+        let mut telemetry = RemoteDependencyTelemetry::new(
+            std::mem::take(&mut self.name),
+            std::mem::take(&mut self.dependency_type),
+            self.elapsed(),
+            std::mem::take(&mut self.target),
+            self.success,
+        );
+        telemetry.set_timestamp(self.timestamp);
+        if let Some(result) = self.result.take() {
+            telemetry.set_result(result);
+        }
+        if let Some(result_code) = self.result_code.take() {
+            telemetry.set_result_code(result_code);
+        }
+        self.client.track(telemetry)
+    }
+}
+
+impl fmt::Debug for DependencyTracker<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DependencyTracker")
+            .field("name", &self.name)
+            .field("dependency_type", &self.dependency_type)
+            .field("target", &self.target)
+            .field("timestamp", &self.timestamp)
+            .field("elapsed", &self.elapsed())
+            .field("success", &self.success)
+            .field("result", &self.result)
+            .finish()
+    }
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::{TimeZone, Utc};
+    use crossbeam_queue::SegQueue;
+
+    use super::*;
+    use crate::{
+        client::tests::create_client,
+        contracts::{Base, Data, Envelope, RemoteDependencyData},
+    };
+
+    #[tokio::test]
+    async fn it_tracks_dependency_when_dropped() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 800));
+        {
+            let mut dependency = client.start_dependency("SELECT", "SQL", "db.example.com");
+            dependency.set_result_code("00000");
+        }
+        time::reset();
+
+        let envelope = events.pop().expect("dependency");
+        assert_eq!(envelope.time, "2019-01-02T03:04:05.800Z");
+        let data = dependency(envelope);
+        assert_eq!(data.name, "SELECT");
+        assert_eq!(data.type_, Some("SQL".into()));
+        assert_eq!(data.target, Some("db.example.com".into()));
+        assert_eq!(data.result_code, Some("00000".into()));
+        assert_eq!(data.success, Some(true));
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn it_tracks_failed_dependency_on_early_return() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        fn query(client: &TelemetryClient) -> Result<(), &'static str> {
+            let mut dependency = client.start_dependency("GET /users", "HTTP", "api.example.com");
+            std::thread::sleep(Duration::from_millis(5));
+            Err("unavailable").inspect_err(|_| dependency.mark_failed())?;
+            dependency.complete();
+            Ok(())
+        }
+        assert!(query(&client).is_err());
+
+        let data = dependency(events.pop().expect("dependency"));
+        assert_eq!(data.success, Some(false));
+        assert!(!data.duration.starts_with("0.00:00:00.000"));
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn it_tracks_dependency_with_success_status_once_finished() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        client.start_dependency("GET", "Redis", "cache:6379").finish(false);
+
+        let data = dependency(events.pop().expect("dependency"));
+        assert_eq!(data.success, Some(false));
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn it_tracks_dependency_with_result() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let mut tracker = client.start_dependency("GET", "Redis", "cache:6379");
+        tracker.set_result(OperationResult::Timeout);
+        tracker.complete();
+
+        let data = dependency(events.pop().expect("dependency"));
+        assert_eq!(data.success, Some(false));
+        assert_eq!(data.properties.unwrap()["result.reason"], "timeout");
+    }
+
+    fn dependency(envelope: Envelope) -> RemoteDependencyData {
+        match envelope.data {
+            Some(Base::Data(Data::RemoteDependencyData(data))) => data,
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+}
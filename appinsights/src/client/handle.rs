@@ -0,0 +1,95 @@
+use crate::{
+    contracts::Envelope,
+    telemetry::{EventTelemetry, MetricTelemetry, Telemetry, ToSeverityLevel, TraceTelemetry},
+    TelemetryClient, TelemetryContext, Tracker,
+};
+
+/// A lightweight view of a telemetry client that stamps telemetry with a context of its own.
+///
+/// Handles share the channel, processors and sampling of the client, so components of one binary can report
+/// telemetry with different default tags and properties, e.g. `component=ingest` and `component=api`, without
+/// creating a client per component.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// let mut ingest = client.with_tags([("ai.cloud.role", "ingest")]);
+/// ingest.context_mut().properties_mut().insert("component".into(), "ingest".into());
+///
+/// ingest.track_event("batch received");
+/// ```
+pub struct TelemetryHandle<'a> {
+    client: &'a TelemetryClient,
+    context: TelemetryContext,
+}
+
+impl<'a> TelemetryHandle<'a> {
+    /// Creates a handle with the given context.
+    pub(crate) fn new(client: &'a TelemetryClient, context: TelemetryContext) -> Self {
+        Self { client, context }
+    }
+
+    /// Returns a client the handle tracks telemetry with.
+    pub fn client(&self) -> &'a TelemetryClient {
+        self.client
+    }
+
+    /// Returns a context the handle attaches to telemetry items.
+    pub fn context(&self) -> &TelemetryContext {
+        &self.context
+    }
+
+    /// Returns a mutable reference to a context the handle attaches to telemetry items.
+    pub fn context_mut(&mut self) -> &mut TelemetryContext {
+        &mut self.context
+    }
+
+    /// Creates another handle with tags of this handle overridden by the given ones.
+    pub fn with_tags<K, V>(&self, tags: impl IntoIterator<Item = (K, V)>) -> TelemetryHandle<'a>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        TelemetryHandle::new(self.client, with_tags(&self.context, tags))
+    }
+
+    /// Logs a user action with the specified name.
+    pub fn track_event(&self, name: impl Into<String>) {
+        self.track(EventTelemetry::new(name))
+    }
+
+    /// Logs a trace message with a specified severity level.
+    pub fn track_trace(&self, message: impl Into<String>, severity: impl ToSeverityLevel) {
+        self.track(TraceTelemetry::new(message, severity))
+    }
+
+    /// Logs a numeric value that is not specified with a specific event.
+    pub fn track_metric(&self, name: impl Into<String>, value: f64) {
+        self.track(MetricTelemetry::new(name, value))
+    }
+}
+
+impl Tracker for TelemetryHandle<'_> {
+    fn track<E>(&self, event: E)
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        self.client.track_with_context(&self.context, event)
+    }
+}
+
+/// Clones the context and overrides its tags with the given ones.
+pub(crate) fn with_tags<K, V>(context: &TelemetryContext, tags: impl IntoIterator<Item = (K, V)>) -> TelemetryContext
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    let mut context = context.clone();
+    for (key, value) in tags {
+        context.tags_mut().insert(key.into(), value.into());
+    }
+    context
+}
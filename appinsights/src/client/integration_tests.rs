@@ -12,27 +12,29 @@ use hyper::{
     service::{make_service_fn, service_fn},
     Body, Request, Response, Server, StatusCode,
 };
-use lazy_static::lazy_static;
 use matches::assert_matches;
 use parking_lot::Mutex;
 use serde_json::json;
 use tokio::sync::{
     mpsc::{self, Receiver},
-    oneshot,
+    oneshot, Notify,
 };
 
-use crate::{timeout, TelemetryClient, TelemetryConfig};
-
-lazy_static! {
-    /// A global lock since most tests need to run in serial.
-    static ref SERIAL_TEST_MUTEX: Mutex<()> = Mutex::new(());
-}
+use crate::{
+    diagnostics::ChannelEvent,
+    sink::FileSink,
+    telemetry::TelemetryKind,
+    test_util::DrainMarker,
+    timeout,
+    timer::{Sleep, Timer},
+    OverflowPolicy, Proxy, TelemetryClient, TelemetryConfig,
+};
 
 macro_rules! manual_timeout_test {
     (async fn $name: ident() $body: block) => {
         #[test]
         fn $name() {
-            let _guard = SERIAL_TEST_MUTEX.lock();
+            let _guard = timeout::SERIAL_TEST_MUTEX.lock();
 
             let rt = tokio::runtime::Runtime::new().expect("runtime");
             rt.block_on(async {
@@ -67,20 +69,102 @@ manual_timeout_test! {
     async fn it_does_not_resend_submitted_telemetry_items() {
         let mut server = server().status(StatusCode::OK).create();
 
-        let client = create_client(server.url());
+        let (client, marker) = create_client_with_marker(server.url());
         client.track_event("--event--");
 
         // verify 1 items is sent after first interval expired
 
         // "wait" until interval expired
         timeout::expire();
+        marker.wait(1).await;
         assert_matches!(server.next_request_timeout().await, Ok(_));
 
         // verify no items is sent after next interval expired
         timeout::expire();
+        marker.wait(2).await;
+        assert_matches!(server.try_next_request(), None);
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_reports_queue_latency_of_sent_telemetry_items() {
+        let mut server = server().status(StatusCode::OK).create();
+
+        let (client, marker) = create_client_with_marker(server.url());
+        assert_eq!(client.channel_stats().queue_latency(), None);
+
+        client.track_event("--event--");
+
+        timeout::expire();
+        marker.wait(1).await;
+        assert_matches!(server.next_request_timeout().await, Ok(_));
+
+        let latency = client.channel_stats().queue_latency().expect("queue latency");
+        assert!(latency.p50() <= latency.p95() && latency.p95() <= latency.p99());
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_reports_channel_stats_of_sent_telemetry_items() {
+        let mut server = server()
+            .response(StatusCode::INTERNAL_SERVER_ERROR, json!({}), None)
+            .status(StatusCode::OK)
+            .create();
+
+        let (retries, mut retry_scheduled) = mpsc::unbounded_channel();
+        let marker = DrainMarker::new();
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .drain_marker(marker.clone())
+            .event_listener(move |event: &ChannelEvent| {
+                if let ChannelEvent::RetryScheduled { .. } = event {
+                    let _ = retries.send(());
+                }
+            })
+            .build();
+        let client = TelemetryClient::from_config(config);
+        assert_eq!(client.channel_stats().last_transmission(), None);
+        assert_eq!(client.channel_stats().processing_lag(), Some(Duration::ZERO));
+
+        client.track_event("--event 1--");
+        client.track_event("--event 2--");
+        assert_eq!(client.channel_stats().queued_items(), 2);
+
+        // "wait" until interval expired and the retry scheduled
+        timeout::expire();
+        marker.wait(1).await;
+        assert_matches!(server.next_request_timeout().await, Ok(_));
+        retry_scheduled.recv().await;
+
+        let stats = client.channel_stats();
+        assert_eq!(stats.queued_items(), 0);
+        assert_eq!(stats.failed_items(), 2);
+        assert!(stats.is_retrying());
+        assert_eq!(stats.processing_lag(), Some(Duration::ZERO));
         assert_matches!(
-            server.next_request_timeout().await,
-            Err(RecvTimeoutError::Timeout)
+            stats.last_transmission(),
+            Some(transmission) if transmission.status_code() == Some(500) && !transmission.succeeded()
+        );
+
+        // "wait" until retry timeout expired
+        timeout::expire();
+        marker.wait(2).await;
+        assert_matches!(server.next_request_timeout().await, Ok(_));
+
+        let stats = client.channel_stats();
+        assert_eq!(stats.sent_items(), 2);
+        assert!(!stats.is_retrying());
+        assert_matches!(
+            stats.last_transmission(),
+            Some(transmission) if transmission.status_code() == Some(200) && transmission.succeeded()
         );
 
         // terminate server
@@ -92,15 +176,16 @@ manual_timeout_test! {
     async fn it_sends_telemetry_items_in_several_batches() {
         let mut server = server().status(StatusCode::OK).status(StatusCode::OK).create();
 
-        let client = create_client(server.url());
+        let (client, marker) = create_client_with_marker(server.url());
 
         // send 10 items and then interval expired
         for i in 0..10 {
             client.track_event(format!("--event {}--", i));
         }
 
-        // "wait" until interval expired
+        // "wait" until interval expired and first batch is sent
         timeout::expire();
+        marker.wait(1).await;
 
         // send next 5 items and then interval expired
         for i in 10..15 {
@@ -126,6 +211,47 @@ manual_timeout_test! {
     }
 }
 
+#[test]
+fn it_coalesces_pending_flush_commands() {
+    let _guard = timeout::SERIAL_TEST_MUTEX.lock();
+
+    // the worker runs on the same thread, so it handles flush commands only after all of them are queued
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("runtime");
+    rt.block_on(async {
+        timeout::init();
+
+        let mut server = server().status(StatusCode::OK).status(StatusCode::OK).create();
+        let (client, marker) = create_client_with_marker(server.url());
+
+        for i in 0..5 {
+            client.track_event(format!("--event {}--", i));
+            client.flush_channel();
+        }
+
+        // assert that all items are sent at once
+        let requests = server.wait_for_requests(1).await;
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            (0..5)
+                .filter(|i| requests[0].contains(&format!("--event {}--", i)))
+                .count(),
+            5
+        );
+
+        // verify that remaining flush commands did not trigger any submission
+        let requests = server.wait_for_requests(1).await;
+        assert!(requests.is_empty());
+        assert_eq!(marker.count(), 1);
+
+        server.terminate().await;
+
+        timeout::reset();
+    });
+}
+
 manual_timeout_test! {
     async fn it_flushes_all_pending_telemetry_items() {
         let mut server = server().status(StatusCode::OK).status(StatusCode::OK).create();
@@ -160,6 +286,486 @@ manual_timeout_test! {
     }
 }
 
+manual_timeout_test! {
+    async fn it_waits_until_flushed_telemetry_items_sent() {
+        let mut server = server().status(StatusCode::OK).create();
+
+        let client = create_client(server.url());
+        for i in 0..15 {
+            client.track_event(format!("--event {}--", i));
+        }
+
+        // NOTE no timeout expired
+        let report = client.flush_and_wait().await;
+        assert_eq!(report.sent(), 15);
+        assert!(report.is_success());
+
+        let requests = server.wait_for_requests(1).await;
+        assert!(requests[0].contains("--event 14--"));
+
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_reports_failed_telemetry_items_when_flush_failed() {
+        let server = server().status(StatusCode::INTERNAL_SERVER_ERROR).create();
+
+        let client = create_client(server.url());
+        for i in 0..15 {
+            client.track_event(format!("--event {}--", i));
+        }
+
+        let report = client.flush_and_wait().await;
+        assert_eq!(report.sent(), 0);
+        assert_eq!(report.failed(), 15);
+
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_sends_telemetry_items_when_oldest_item_exceeds_max_age() {
+        let mut server = server().status(StatusCode::OK).status(StatusCode::OK).create();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_secs(3600))
+            .max_item_age(Duration::from_millis(20))
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        client.track_event("--event 1--");
+        client.track_event("--event 2--");
+
+        // NOTE no timeout expired
+        let requests = server.wait_for_requests(1).await;
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("--event 1--") && requests[0].contains("--event 2--"));
+
+        // next item is tracked against its own max age
+        client.track_event("--event 3--");
+        let requests = server.wait_for_requests(1).await;
+        assert!(requests[0].contains("--event 3--"));
+
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_sends_telemetry_items_when_batch_size_reached() {
+        let mut server = server().status(StatusCode::OK).status(StatusCode::OK).create();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_secs(3600))
+            .max_batch_size(2)
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        client.track_event("--event 1--");
+        client.track_event("--event 2--");
+
+        // NOTE no timeout expired
+        let requests = server.wait_for_requests(1).await;
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("--event 1--") && requests[0].contains("--event 2--"));
+
+        // an incomplete batch waits for the interval
+        client.track_event("--event 3--");
+        assert_matches!(server.next_request_timeout().await, Err(_));
+        timeout::expire();
+        let requests = server.wait_for_requests(1).await;
+        assert!(requests[0].contains("--event 3--"));
+
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_drops_oldest_telemetry_items_when_over_capacity() {
+        let mut server = server().status(StatusCode::OK).create();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_secs(3600))
+            .max_queue_capacity(2)
+            .overflow_policy(OverflowPolicy::DropOldest)
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        for i in 1..=3 {
+            client.track_event(format!("--event {}--", i));
+        }
+        assert_eq!(client.channel_stats().dropped_items(), 1);
+
+        timeout::expire();
+        let requests = server.wait_for_requests(1).await;
+        assert!(!requests[0].contains("--event 1--"));
+        assert!(requests[0].contains("--event 2--") && requests[0].contains("--event 3--"));
+
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_sends_telemetry_items_of_configured_type_immediately() {
+        let mut server = server().status(StatusCode::OK).status(StatusCode::OK).create();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_secs(3600))
+            .send_immediately(TelemetryKind::Availability)
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        client.track_event("--event--");
+        client.track_availability("--availability--", Duration::from_secs(1), true);
+
+        // NOTE no timeout expired
+        // expect availability result sent in a dedicated batch
+        let requests = server.wait_for_requests(1).await;
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("--availability--"));
+        assert!(!requests[0].contains("--event--"));
+
+        // other items wait for a batch
+        assert_matches!(server.next_request_timeout().await, Err(_));
+        timeout::expire();
+        let requests = server.wait_for_requests(1).await;
+        assert!(requests[0].contains("--event--"));
+
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_flushes_pending_telemetry_items_when_shrink_requested() {
+        let mut server = server().status(StatusCode::OK).status(StatusCode::OK).create();
+
+        let client = create_client(server.url());
+        for i in 0..5 {
+            client.track_event(format!("--event {}--", i));
+        }
+
+        // force client to send all items and release buffers without timeout expired
+        client.shrink_channel();
+        let requests = server.wait_for_requests(1).await;
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("--event 4--"));
+
+        // client keeps sending items afterwards
+        client.track_event("--event after shrink--");
+        client.flush_channel();
+        let requests = server.wait_for_requests(1).await;
+        assert!(requests[0].contains("--event after shrink--"));
+
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_sends_telemetry_items_once_channel_resumed() {
+        let mut server = server().status(StatusCode::OK).create();
+
+        let client = create_client(server.url());
+        let control = client.channel_control();
+        control.pause();
+
+        for i in 0..5 {
+            client.track_event(format!("--event {}--", i));
+        }
+
+        // neither flush nor timeout submit anything while paused
+        client.flush_channel();
+        timeout::expire();
+        assert_matches!(server.next_request_timeout().await, Err(_));
+
+        // deferred flush happens on resume
+        control.resume();
+        let requests = server.wait_for_requests(1).await;
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("--event 4--"));
+
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_reports_processing_lag_while_worker_does_not_drain_queue() {
+        let server = server().status(StatusCode::OK).create();
+
+        let client = create_client(server.url());
+        client.channel_control().pause();
+
+        client.track_event("--event--");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // the lag grows without the worker picking up the item
+        let lag = client.channel_stats().processing_lag().unwrap();
+        assert!(lag >= Duration::from_millis(50));
+
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_closes_channel_with_control_handle() {
+        let mut server = server().status(StatusCode::OK).create();
+
+        let client = create_client(server.url());
+        let control = client.channel_control();
+        for i in 0..5 {
+            client.track_event(format!("--event {}--", i));
+        }
+
+        // the handle closes the channel without owning the client
+        tokio::spawn(async move { control.close().await }).await.unwrap();
+        let requests = server.wait_for_requests(1).await;
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("--event 4--"));
+        assert!(client.channel_control().is_closed());
+
+        // items tracked afterwards are not submitted
+        client.track_event("--event after close--");
+        client.flush_channel();
+        assert_matches!(server.next_request_timeout().await, Err(_));
+
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_sends_telemetry_items_to_new_endpoint_when_connection_string_changed() {
+        let mut old_server = server().status(StatusCode::OK).create();
+        let mut new_server = server().status(StatusCode::OK).create();
+
+        let client = create_client(old_server.url());
+        client.track_event("--event before--");
+        client.flush_channel();
+        let requests = old_server.wait_for_requests(1).await;
+        assert!(requests[0].contains("--event before--"));
+
+        let connection_string = format!("InstrumentationKey=new key;IngestionEndpoint={}", new_server.url());
+        client.set_connection_string(&connection_string).unwrap();
+        client.track_event("--event after--");
+        client.flush_channel();
+
+        // expect next batch sent to the new endpoint with the new instrumentation key
+        let requests = new_server.wait_for_requests(1).await;
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("--event after--"));
+        assert!(requests[0].contains(r#""iKey":"new key""#));
+        assert_matches!(old_server.next_request_timeout().await, Err(_));
+
+        old_server.terminate().await;
+        new_server.terminate().await;
+    }
+}
+
+#[test]
+fn it_sends_telemetry_items_when_configured_timer_expires() {
+    // tests that emulate timeout expiration replace any timer, so wait until they are done
+    let _guard = timeout::SERIAL_TEST_MUTEX.lock();
+
+    let rt = tokio::runtime::Runtime::new().expect("runtime");
+    rt.block_on(async {
+        let mut server = server().status(StatusCode::OK).create();
+
+        let timer = FakeTimer::default();
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_secs(3600))
+            .timer(timer.clone())
+            .build();
+        let client = TelemetryClient::from_config(config);
+        client.track_event("--event--");
+
+        // the interval is not going to expire unless the timer says so
+        assert_matches!(server.next_request_timeout().await, Err(_));
+
+        timer.0.notify_one();
+        let requests = server.wait_for_requests(1).await;
+        assert!(requests[0].contains("--event--"));
+
+        server.terminate().await;
+    });
+}
+
+#[test]
+fn it_sends_telemetry_items_with_new_settings_when_reconfigured() {
+    // tests that emulate timeout expiration replace any timer, so wait until they are done
+    let _guard = timeout::SERIAL_TEST_MUTEX.lock();
+
+    let rt = tokio::runtime::Runtime::new().expect("runtime");
+    rt.block_on(async {
+        let mut old_server = server().status(StatusCode::OK).create();
+        let mut new_server = server().status(StatusCode::OK).create();
+
+        let timer = FakeTimer::default();
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(old_server.url())
+            .interval(Duration::from_secs(3600))
+            .timer(timer.clone())
+            .build();
+        let client = TelemetryClient::from_config(config);
+        client.track_event("--event before--");
+        timer.0.notify_one();
+        let requests = old_server.wait_for_requests(1).await;
+        assert!(requests[0].contains("--event before--"));
+
+        let config = TelemetryConfig::builder()
+            .i_key("new key")
+            .endpoint(new_server.url())
+            .interval(Duration::from_secs(5))
+            .build();
+        client.reconfigure(config);
+        client.track_event("--event after--");
+        client.flush_and_wait().await;
+
+        // expect the item sent to the new endpoint with the new key
+        let requests = new_server.wait_for_requests(1).await;
+        assert!(requests[0].contains("--event after--"));
+        assert!(requests[0].contains(r#""iKey":"new key""#));
+
+        // the worker handles another flush only after it has started waiting for the next batch again
+        client.flush_and_wait().await;
+        assert_eq!(timer.1.lock().last(), Some(&Duration::from_secs(5)));
+        assert_matches!(old_server.next_request_timeout().await, Err(_));
+
+        old_server.terminate().await;
+        new_server.terminate().await;
+    });
+}
+
+manual_timeout_test! {
+    async fn it_sends_telemetry_items_to_mirrors() {
+        let mut primary_server = server().status(StatusCode::OK).create();
+        let mut mirror_server = server().status(StatusCode::OK).create();
+
+        let mirror = TelemetryConfig::builder()
+            .i_key("mirror key")
+            .endpoint(mirror_server.url())
+            .build();
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(primary_server.url())
+            .mirror(mirror)
+            .build();
+        let client = TelemetryClient::from_config(config);
+        client.track_event("--event--");
+        client.flush_channel();
+
+        // expect the item sent to both resources with their own instrumentation keys
+        let requests = primary_server.wait_for_requests(1).await;
+        assert!(requests[0].contains("--event--"));
+        assert!(requests[0].contains(r#""iKey":"instrumentation key""#));
+
+        let requests = mirror_server.wait_for_requests(1).await;
+        assert!(requests[0].contains("--event--"));
+        assert!(requests[0].contains(r#""iKey":"mirror key""#));
+
+        primary_server.terminate().await;
+        mirror_server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_sends_telemetry_items_through_proxy() {
+        let mut proxy_server = server().status(StatusCode::OK).create();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint("http://ingestion.invalid/v2/track")
+            .proxy(Proxy::new(proxy_server.url()).basic_auth("user", "password"))
+            .build();
+        let client = TelemetryClient::from_config(config);
+        client.track_event("--event--");
+        client.flush_channel();
+
+        // expect the proxy to receive the item addressed to the unresolvable endpoint
+        let requests = proxy_server.wait_for_requests(1).await;
+        assert!(requests[0].contains("--event--"));
+
+        proxy_server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_retries_telemetry_items_when_sending_exceeds_deadline() {
+        // the listener accepts connections but never responds
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let hanging_url = format!("http://{}", listener.local_addr().unwrap());
+        let mut server = server().status(StatusCode::OK).create();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(hanging_url)
+            .interval(Duration::from_millis(300))
+            .send_deadline(Duration::from_millis(100))
+            .build();
+        let client = TelemetryClient::from_config(config);
+        client.track_event("--event--");
+
+        // "wait" until interval expired and let the watchdog cancel the hanging attempt
+        timeout::expire();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let connection_string = format!("InstrumentationKey=instrumentation key;IngestionEndpoint={}", server.url());
+        client.set_connection_string(&connection_string).unwrap();
+
+        // "wait" until retry logic handled
+        timeout::expire();
+
+        // verify the cancelled item has been retried
+        let requests = server.wait_for_requests(1).await;
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("--event--"));
+
+        server.terminate().await;
+    }
+}
+
+#[cfg(feature = "debug")]
+manual_timeout_test! {
+    async fn it_reports_snapshot_of_queued_and_pending_items() {
+        let mut server = server().status(StatusCode::SERVICE_UNAVAILABLE).create();
+
+        let client = create_client(server.url());
+        client.track_event("--event 0--");
+
+        // "wait" until interval expired and the item is to be retried
+        timeout::expire();
+        let requests = server.wait_for_requests(1).await;
+        assert_eq!(requests.len(), 1);
+
+        client.track_event("--event 1--");
+
+        let mut snapshot = client.debug_snapshot();
+        for _ in 0..10 {
+            if snapshot.first().is_some_and(|item| item.is_pending()) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            snapshot = client.debug_snapshot();
+        }
+
+        let pending: Vec<_> = snapshot.iter().map(|item| item.is_pending()).collect();
+        assert_eq!(pending, vec![true, false]);
+        assert!(snapshot
+            .iter()
+            .all(|item| item.name() == "Microsoft.ApplicationInsights.Event"));
+
+        server.terminate().await;
+    }
+}
+
 manual_timeout_test! {
     async fn it_does_not_send_any_pending_telemetry_items_when_drop_client() {
         let mut server = server().status(StatusCode::OK).status(StatusCode::OK).create();
@@ -244,6 +850,78 @@ manual_timeout_test! {
     }
 }
 
+manual_timeout_test! {
+    async fn it_exports_pending_telemetry_items_when_client_terminated() {
+        let mut server = server().status(StatusCode::SERVICE_UNAVAILABLE).create();
+        let path = std::env::temp_dir().join(format!("appinsights-terminate-{}.jsonl", std::process::id()));
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .terminate_sink(FileSink::new(&path))
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        // submission failed and item waits for retry
+        client.track_event("--event retry--");
+        client.flush_channel();
+        let requests = server.wait_for_requests(1).await;
+        assert_eq!(requests.len(), 1);
+
+        // another item waits in the queue
+        client.track_event("--event queued--");
+        client.terminate().await;
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(content.lines().count(), 1);
+        assert!(content.contains("--event retry--"));
+        assert!(content.contains("--event queued--"));
+
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_replays_spooled_telemetry_items_on_startup() {
+        let mut server = server()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .status(StatusCode::OK)
+            .create();
+        let dir = std::env::temp_dir().join(format!("appinsights-persistence-{}", std::process::id()));
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .persistence_dir(&dir)
+            .build();
+
+        // submission failed and item stays on disk
+        let client = TelemetryClient::from_config(config.clone());
+        client.track_event("--event spooled--");
+        client.flush_channel();
+        let requests = server.wait_for_requests(1).await;
+        assert_eq!(requests.len(), 1);
+        client.terminate().await;
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        // next run submits item left on disk and removes it
+        let client = TelemetryClient::from_config(config);
+        let requests = server.wait_for_requests(1).await;
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("--event spooled--"));
+        client.close_channel().await;
+
+        let files = std::fs::read_dir(&dir).unwrap().count();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(files, 0);
+
+        server.terminate().await;
+    }
+}
+
 manual_timeout_test! {
     async fn it_retries_when_previous_submission_failed() {
         let mut server = server()
@@ -283,6 +961,61 @@ manual_timeout_test! {
     }
 }
 
+manual_timeout_test! {
+    async fn it_notifies_event_listener_about_failed_and_retried_batches() {
+        let mut server = server()
+            .response(StatusCode::INTERNAL_SERVER_ERROR, json!({}), None)
+            .status(StatusCode::OK)
+            .create();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let marker = DrainMarker::new();
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .drain_marker(marker.clone())
+            .event_listener({
+                let events = events.clone();
+                move |event: &ChannelEvent| events.lock().push(event.clone())
+            })
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        for i in 0..15 {
+            client.track_event(format!("--event {}--", i));
+        }
+
+        // "wait" until interval expired and then retry timeout expired
+        timeout::expire();
+        marker.wait(1).await;
+        timeout::expire();
+        marker.wait(2).await;
+        server.wait_for_requests(2).await;
+
+        let events = events.lock().clone();
+        assert_eq!(events.len(), 3);
+        assert_eq!(
+            events[0],
+            ChannelEvent::BatchFailed {
+                items: 15,
+                status_code: Some(500)
+            }
+        );
+        assert_matches!(events[1], ChannelEvent::RetryScheduled { items: 15, .. });
+        assert_eq!(
+            events[2],
+            ChannelEvent::BatchSent {
+                items: 15,
+                accepted: 15,
+                status_code: Some(200)
+            }
+        );
+
+        server.terminate().await;
+    }
+}
+
 manual_timeout_test! {
     async fn it_retries_when_partial_content() {
         let mut server = server()
@@ -369,8 +1102,82 @@ manual_timeout_test! {
     }
 }
 
+manual_timeout_test! {
+    async fn it_records_retry_count_when_enabled() {
+        let mut server = server()
+            .response(
+                StatusCode::PARTIAL_CONTENT,
+                json!(
+                {
+                    "itemsAccepted": 1,
+                    "itemsReceived": 2,
+                    "errors": [
+                        {
+                            "index": 1,
+                            "statusCode": StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                            "message": "Internal Server Error"
+                        }
+                    ],
+                }),
+                None,
+            )
+            .response(
+                StatusCode::OK,
+                json!(
+                {
+                    "itemsAccepted": 1,
+                    "itemsReceived": 1,
+                    "errors": [],
+                }),
+                None,
+            )
+            .create();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .record_retry_count(true)
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        client.track_event("--event 0--");
+        client.track_event("--event 1--");
+
+        // "wait" until interval expired
+        timeout::expire();
+
+        // "wait" until retry logic handled
+        timeout::expire();
+
+        // verify the first attempt does not carry retry count
+        let requests = server.wait_for_requests(1).await;
+        assert!(!requests[0].contains("ai.internal.retryCount"));
+
+        // verify the retried item carries retry count
+        let requests = server.wait_for_requests(1).await;
+        assert!(requests[0].contains("--event 1--"));
+        assert!(requests[0].contains(r#""ai.internal.retryCount":"1""#));
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
 // TODO Check case when all retries exhausted. Pending items should not be lost
 
+/// A timer that expires only when a test asks it to. It records durations it was asked to wait for.
+#[derive(Clone, Default)]
+struct FakeTimer(Arc<Notify>, Arc<Mutex<Vec<Duration>>>);
+
+impl Timer for FakeTimer {
+    fn sleep(&self, duration: Duration) -> Sleep {
+        self.1.lock().push(duration);
+        let notify = self.0.clone();
+        Box::pin(async move { notify.notified().await })
+    }
+}
+
 fn create_client(endpoint: &str) -> TelemetryClient {
     let config = TelemetryConfig::builder()
         .i_key("instrumentation key")
@@ -381,6 +1188,18 @@ fn create_client(endpoint: &str) -> TelemetryClient {
     TelemetryClient::from_config(config)
 }
 
+fn create_client_with_marker(endpoint: &str) -> (TelemetryClient, DrainMarker) {
+    let marker = DrainMarker::new();
+    let config = TelemetryConfig::builder()
+        .i_key("instrumentation key")
+        .endpoint(endpoint)
+        .interval(Duration::from_millis(300))
+        .drain_marker(marker.clone())
+        .build();
+
+    (TelemetryClient::from_config(config), marker)
+}
+
 fn server() -> Builder {
     Builder { responses: Vec::new() }
 }
@@ -404,6 +1223,10 @@ impl HyperTestServer {
         }
     }
 
+    fn try_next_request(&mut self) -> Option<String> {
+        self.request_recv.try_recv().ok()
+    }
+
     async fn wait_for_requests(&mut self, count: usize) -> Vec<String> {
         let mut requests = Vec::new();
 
@@ -1,22 +1,74 @@
-use std::time::Duration;
+mod dependency;
+pub use dependency::DependencyTracker;
+mod handle;
+pub use handle::TelemetryHandle;
+mod operation;
+pub use operation::Operation;
+mod request;
+pub use request::RequestTracker;
+mod stopwatch;
+pub use stopwatch::Stopwatch;
+
+use std::{
+    collections::HashMap,
+    hash::BuildHasher,
+    ops::Deref,
+    sync::{RwLock, RwLockReadGuard},
+    time::Duration,
+};
+#[cfg(feature = "runtime")]
+use std::{
+    future::{self, Future},
+    sync::Arc,
+};
 
 use http::{Method, Uri};
+#[cfg(not(feature = "disabled"))]
+use log::warn;
+#[cfg(feature = "runtime")]
+use tokio::task::JoinHandle;
 
 use crate::{
-    channel::{InMemoryChannel, TelemetryChannel},
-    context::TelemetryContext,
+    callback,
+    channel::FlushReport,
+    config::{RequestNameNormalizer, Shared},
+    connectivity::{ConnectivityError, EndpointInfo},
     contracts::Envelope,
+    correlation::CorrelationContext,
+    processor::{Pipeline, TelemetryProcessor},
+    sampling::{self, Sampler},
     telemetry::{
-        AvailabilityTelemetry, EventTelemetry, MetricTelemetry, RemoteDependencyTelemetry, RequestTelemetry,
-        SeverityLevel, Telemetry, TraceTelemetry,
+        tag_keys, AvailabilityTelemetry, EventTelemetry, ExceptionTelemetry, MetricTelemetry, PageViewTelemetry,
+        RemoteDependencyTelemetry, RequestTelemetry, SeverityLevel, Telemetry, ToSeverityLevel, TraceTelemetry,
     },
-    TelemetryConfig,
+    ConnectionString, ConnectionStringError, TelemetryConfig, TelemetryContext, Tracker,
+};
+#[cfg(feature = "runtime")]
+use crate::{
+    channel::{ChannelControl, ChannelStats, TelemetryChannel},
+    timeout,
+};
+#[cfg(not(feature = "disabled"))]
+use crate::{
+    channel::{InMemoryChannel, MulticastChannel, PersistentChannel},
+    connectivity,
+    overload::{self, OverloadGuard},
+    scope, startup,
 };
 
+/// Determines whether telemetry is compiled out with the `disabled` feature.
+pub(crate) const DISABLED: bool = cfg!(feature = "disabled");
+
 /// Application Insights telemetry client provides an interface to track telemetry items.
 pub struct TelemetryClient {
     enabled: bool,
-    context: TelemetryContext,
+    config: RwLock<TelemetryConfig>,
+    context: RwLock<TelemetryContext>,
+    processors: Pipeline,
+    sampler: Sampler,
+    #[cfg(not(feature = "disabled"))]
+    overload: Option<OverloadGuard>,
+    #[cfg(not(feature = "disabled"))]
     channel: Box<dyn TelemetryChannel>,
 }
 
@@ -27,20 +79,60 @@ impl TelemetryClient {
     }
 
     /// Creates a new telemetry client configured with specified configuration.
-    pub fn from_config(config: TelemetryConfig) -> Self {
-        Self::create(&config, InMemoryChannel::new(&config))
+    pub fn from_config(mut config: TelemetryConfig) -> Self {
+        let sampler = Sampler::new(&mut config);
+        Self {
+            enabled: true,
+            context: RwLock::new(TelemetryContext::from(&config)),
+            processors: Pipeline::default(),
+            sampler,
+            #[cfg(not(feature = "disabled"))]
+            overload: OverloadGuard::new(config.overload_threshold()),
+            #[cfg(not(feature = "disabled"))]
+            channel: channel(&config),
+            config: RwLock::new(config),
+        }
+        .started()
     }
 
     /// Creates a new telemetry client with custom telemetry channel.
+    #[cfg(all(any(test, feature = "test-util"), feature = "runtime"))]
     pub(crate) fn create<C: TelemetryChannel + 'static>(config: &TelemetryConfig, channel: C) -> Self {
+        let mut config = config.clone();
+        #[cfg(feature = "disabled")]
+        drop(channel);
         Self {
             enabled: true,
-            context: TelemetryContext::from_config(config),
+            context: RwLock::new(TelemetryContext::from(&config)),
+            processors: Pipeline::default(),
+            sampler: Sampler::new(&mut config),
+            #[cfg(not(feature = "disabled"))]
+            overload: OverloadGuard::new(config.overload_threshold()),
+            #[cfg(not(feature = "disabled"))]
             channel: Box::new(channel),
+            config: RwLock::new(config),
+        }
+        .started()
+    }
+
+    /// Submits a startup event if it is enabled in the configuration.
+    fn started(self) -> Self {
+        #[cfg(not(feature = "disabled"))]
+        if self.config().startup_event() {
+            let event = startup::event(&self.config());
+            self.track(event);
         }
+        self
+    }
+
+    /// Returns a configuration of the client, including settings applied with
+    /// [`reconfigure`](#method.reconfigure).
+    pub(crate) fn config(&self) -> RwLockReadGuard<'_, TelemetryConfig> {
+        self.config.read().unwrap_or_else(|err| err.into_inner())
     }
 
-    /// Determines whether this client is enabled and will accept telemetry.
+    /// Determines whether this client is enabled and will accept telemetry. Always returns `false` when the
+    /// crate is compiled with the `disabled` feature.
     ///
     /// # Examples
     ///
@@ -50,7 +142,7 @@ impl TelemetryClient {
     /// assert!(client.is_enabled());
     /// ```
     pub fn is_enabled(&self) -> bool {
-        self.enabled
+        self.enabled && !DISABLED
     }
 
     /// Enables or disables telemetry client. When disabled, telemetry is silently swallowed by the client. Defaults to enabled.
@@ -69,7 +161,138 @@ impl TelemetryClient {
         self.enabled = enabled;
     }
 
-    /// Returns an immutable reference to a collection of tag data to attach to the telemetry item.
+    /// Registers a processor that can modify or drop telemetry items before they are queued. Processors run in
+    /// the order they were added. See [`processor`](processor/index.html) for details.
+    pub fn add_processor(&mut self, processor: impl TelemetryProcessor + 'static) {
+        self.processors.add(processor);
+    }
+
+    /// Determines whether telemetry items of the operation with the given id are kept according to the
+    /// configured [`sampling_percentage`](struct.TelemetryConfig.html#method.sampling_percentage), reduced while
+    /// the ingestion endpoint is overloaded if sampling feedback is enabled. Applications can skip expensive work,
+    /// e.g. local logging, for operations that are sampled out anyway.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryClient;
+    /// let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// assert!(client.is_sampled_in("0f6f6bd0-c05c-4e92-a0a8-ad0e7c6a7b1e"));
+    /// ```
+    pub fn is_sampled_in(&self, operation_id: &str) -> bool {
+        sampling::is_sampled_in(operation_id, self.sampler.percentage())
+    }
+
+    /// Checks that telemetry can be submitted to the configured ingestion endpoint.
+    ///
+    /// Telemetry is sent in the background, so DNS, TLS or authorization problems otherwise surface only in
+    /// debug logs. Call this method once at startup to fail fast on misconfiguration. It sends a single
+    /// probe item that the endpoint validates and discards, and does not affect telemetry in the channel.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// match client.verify_connectivity().await {
+    ///     Ok(info) => println!("{} responded in {:?}", info.endpoint(), info.latency()),
+    ///     Err(err) => eprintln!("telemetry will not be submitted: {}", err),
+    /// }
+    /// # }
+    /// ```
+    pub async fn verify_connectivity(&self) -> Result<EndpointInfo, ConnectivityError> {
+        #[cfg(not(feature = "disabled"))]
+        {
+            let config = self.config().clone();
+            connectivity::verify(&config).await
+        }
+        #[cfg(feature = "disabled")]
+        {
+            Err(ConnectivityError::Disabled)
+        }
+    }
+
+    /// Switches the client to another Application Insights resource, e.g. when a key is rotated. Telemetry
+    /// items tracked afterwards carry the new instrumentation key. A batch being submitted at the moment goes
+    /// to the previous endpoint, all subsequent batches go to the new one.
+    ///
+    /// Telemetry items queued before the switch keep the previous instrumentation key but are submitted to
+    /// the new endpoint. Call [`flush_and_wait`](#method.flush_and_wait) first to submit them to the previous
+    /// endpoint when the resources live in different regions.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryClient;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// client
+    ///     .set_connection_string("InstrumentationKey=<new key>;IngestionEndpoint=https://westeurope-5.in.applicationinsights.azure.com/")
+    ///     .expect("valid connection string");
+    ///
+    /// assert_eq!(client.context().i_key(), "<new key>");
+    /// # }
+    /// ```
+    pub fn set_connection_string(&self, connection_string: &str) -> Result<(), ConnectionStringError> {
+        let connection_string: ConnectionString = connection_string.parse()?;
+
+        let mut config = self.config().clone();
+        config.set_connection_string(&connection_string);
+        self.reconfigure(config);
+
+        Ok(())
+    }
+
+    /// Applies a configuration reloaded at runtime, e.g. from a control plane, without recreating the client.
+    /// The client can be shared, e.g. with periodic tasks, while it is reconfigured.
+    ///
+    /// The client switches to the instrumentation key, the endpoint, the interval and the sampling percentage
+    /// of the given configuration. Telemetry items queued at the moment are kept and submitted to the new
+    /// endpoint. A batch being submitted goes to the previous endpoint, and a wait for the next batch in progress
+    /// completes with the previous interval. Settings the channel is created with, such as the capacity, the
+    /// runtime, the persistence directory or mirrors, cannot change while the client is running and keep their
+    /// values. Create a new client to change them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use std::time::Duration;
+    /// # use appinsights::{TelemetryClient, TelemetryConfig};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let client = TelemetryClient::new("<instrumentation key>".to_string());
+    ///
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<new key>")
+    ///     .interval(Duration::from_secs(10))
+    ///     .build();
+    /// client.reconfigure(config);
+    ///
+    /// assert_eq!(client.context().i_key(), "<new key>");
+    /// # }
+    /// ```
+    pub fn reconfigure(&self, config: TelemetryConfig) {
+        self.context
+            .write()
+            .unwrap_or_else(|err| err.into_inner())
+            .set_i_key(config.i_key());
+        self.sampler.set_percentage(config.sampling_percentage());
+        #[cfg(not(feature = "disabled"))]
+        {
+            self.channel.set_endpoint(config.endpoint());
+            self.channel.set_interval(config.interval());
+        }
+        self.config
+            .write()
+            .unwrap_or_else(|err| err.into_inner())
+            .update(&config);
+    }
+
+    /// Returns a collection of tag data to attach to the telemetry item. The context stays locked while the
+    /// returned value is alive, so [`reconfigure`](#method.reconfigure) waits until it is dropped.
     ///
     /// # Examples
     ///
@@ -80,8 +303,8 @@ impl TelemetryClient {
     ///
     /// assert_eq!(client.context().tags().cloud().role(), Some("rust_server"));
     /// ```
-    pub fn context(&self) -> &TelemetryContext {
-        &self.context
+    pub fn context(&self) -> impl Deref<Target = TelemetryContext> + '_ {
+        self.context.read().unwrap_or_else(|err| err.into_inner())
     }
 
     /// Returns a mutable reference to a collection of tag data to attach to the telemetry item.
@@ -98,7 +321,37 @@ impl TelemetryClient {
     /// assert_eq!(client.context().properties().get("Resource Group"), Some(&"my-rg".to_string()));
     /// ```
     pub fn context_mut(&mut self) -> &mut TelemetryContext {
-        &mut self.context
+        self.context.get_mut().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns a context to hand off to another process, such as a spawned worker process, along with
+    /// the operation the current task runs within, if any. The process can restore the context with
+    /// [`TelemetryContext::deserialize`](struct.TelemetryContext.html#method.deserialize) to report telemetry
+    /// under the same operation.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use std::process::Command;
+    /// # use appinsights::{TelemetryClient, TelemetryContext};
+    /// # let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// // parent process
+    /// let context = client.handoff_context().serialize();
+    /// Command::new("worker").env("APPINSIGHTS_CONTEXT", context).spawn();
+    ///
+    /// // worker process
+    /// if let Ok(context) = std::env::var("APPINSIGHTS_CONTEXT") {
+    ///     *client.context_mut() = TelemetryContext::deserialize(&context).unwrap();
+    /// }
+    /// ```
+    pub fn handoff_context(&self) -> TelemetryContext {
+        let mut context = self.context().clone();
+        if let Some(correlation) = CorrelationContext::current() {
+            let tags = context.tags_mut();
+            tags.insert(tag_keys::OPERATION_ID.into(), correlation.operation_id().into());
+            tags.insert(tag_keys::OPERATION_PARENT_ID.into(), correlation.parent_id().into());
+        }
+        context
     }
 
     /// Logs a user action with the specified name.
@@ -115,7 +368,24 @@ impl TelemetryClient {
         self.track(event)
     }
 
-    /// Logs a trace message with a specified severity level.
+    /// Logs a user action with the specified static name. Unlike [`track_event`](#method.track_event), the
+    /// name is not copied on its way to the channel, which makes it a cheaper choice for events tracked on hot
+    /// paths.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// client.track_event_static("cache hit");
+    /// ```
+    pub fn track_event_static(&self, name: &'static str) {
+        let event = EventTelemetry::from_static(name);
+        self.track(event)
+    }
+
+    /// Logs a trace message with a specified severity level. Levels of logging frameworks, such as
+    /// `log::Level`, are converted with [`ToSeverityLevel`](telemetry/trait.ToSeverityLevel.html).
     ///
     /// # Examples
     ///
@@ -124,8 +394,9 @@ impl TelemetryClient {
     /// # use appinsights::telemetry::SeverityLevel;
     /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
     /// client.track_trace("Unable to connect to a gateway", SeverityLevel::Warning);
+    /// client.track_trace("Retrying connection to a gateway", log::Level::Debug);
     /// ```
-    pub fn track_trace(&self, message: impl Into<String>, severity: SeverityLevel) {
+    pub fn track_trace(&self, message: impl Into<String>, severity: impl ToSeverityLevel) {
         let event = TraceTelemetry::new(message, severity);
         self.track(event)
     }
@@ -146,22 +417,46 @@ impl TelemetryClient {
         self.track(event)
     }
 
-    /// Logs a HTTP request with the specified method, URL, duration and response code.
+    /// Logs a HTTP request with the specified method, URL, duration and response code. Returns a generated id
+    /// of the request that child telemetry can refer to as its operation parent id. Use
+    /// [`RequestTelemetry::set_id`](telemetry/struct.RequestTelemetry.html#method.set_id) and
+    /// [`track`](#method.track) to submit a request with a pre-generated id instead.
     ///
     /// # Examples
     ///
     /// ```rust, no_run
     /// # use appinsights::TelemetryClient;
     /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
-    /// use http::{Method, Uri};
+    /// use appinsights::ext::{Method, Uri};
     /// use std::time::Duration;
     ///
     /// let uri: Uri = "https://api.github.com/dmolokanov/appinsights-rs".parse().unwrap();
-    /// client.track_request(Method::GET, uri, Duration::from_millis(100), "200");
+    /// let request_id = client.track_request(Method::GET, uri, Duration::from_millis(100), "200");
+    ///
+    /// use appinsights::telemetry::{RemoteDependencyTelemetry, Telemetry};
+    ///
+    /// let mut dependency =
+    ///     RemoteDependencyTelemetry::new("SELECT", "SQL", Duration::from_millis(20), "db.example.com", true);
+    /// dependency.tags_mut().operation_mut().set_parent_id(request_id);
+    /// client.track(dependency);
     /// ```
-    pub fn track_request(&self, method: Method, uri: Uri, duration: Duration, response_code: impl Into<String>) {
-        let event = RequestTelemetry::new(method, uri, duration, response_code);
-        self.track(event)
+    pub fn track_request(
+        &self,
+        method: Method,
+        uri: Uri,
+        duration: Duration,
+        response_code: impl Into<String>,
+    ) -> String {
+        let event = request(
+            self.config().request_name_normalizer(),
+            method,
+            uri,
+            duration,
+            response_code,
+        );
+        let id = event.id().unwrap_or_default().to_string();
+        self.track(event);
+        id
     }
 
     /// Logs a dependency with the specified name, type, target, and success status.
@@ -189,6 +484,37 @@ impl TelemetryClient {
         self.track(event)
     }
 
+    /// Logs an outcome of a fallible operation with the specified name and duration. A successful operation is
+    /// tracked as an in-process dependency, and a failed one as an exception with the chain of errors, see
+    /// [`ExceptionTelemetry::from_error`](telemetry/struct.ExceptionTelemetry.html#method.from_error).
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use std::time::Instant;
+    ///
+    /// let started = Instant::now();
+    /// let result = std::fs::read_to_string("settings.json");
+    /// client.track_result("load settings", &result, started.elapsed());
+    /// ```
+    pub fn track_result<T, E>(&self, name: impl Into<String>, result: &Result<T, E>, duration: Duration)
+    where
+        E: std::error::Error,
+    {
+        match result {
+            Ok(_) => self.track(RemoteDependencyTelemetry::new(
+                name,
+                IN_PROC_DEPENDENCY_TYPE,
+                duration,
+                "",
+                true,
+            )),
+            Err(err) => self.track(result_exception(name, err, duration)),
+        }
+    }
+
     /// Logs an availability test result with the specified test name, duration, and success status.
     ///
     /// # Examples
@@ -209,6 +535,152 @@ impl TelemetryClient {
         self.track(event)
     }
 
+    /// Logs an availability test result with the specified test name, duration, and success status, and
+    /// details set by the given function, such as where the test ran and why it failed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use std::time::Duration;
+    ///
+    /// client.track_availability_with("GET https://api.example.com/health", Duration::from_secs(10), false, |test| {
+    ///     test.set_run_location("westeurope");
+    ///     test.set_message("request timed out");
+    /// });
+    /// ```
+    pub fn track_availability_with(
+        &self,
+        name: impl Into<String>,
+        duration: Duration,
+        success: bool,
+        f: impl FnOnce(&mut AvailabilityTelemetry),
+    ) {
+        let mut event = AvailabilityTelemetry::new(name, duration, success);
+        f(&mut event);
+        self.track(event)
+    }
+
+    /// Logs a page view with the specified name and URL.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use appinsights::ext::Uri;
+    ///
+    /// let uri: Uri = "https://example.com/products/42".parse().unwrap();
+    /// client.track_page_view("Product details", uri);
+    /// ```
+    pub fn track_page_view(&self, name: impl Into<String>, uri: Uri) {
+        let event = PageViewTelemetry::new(name, uri);
+        self.track(event)
+    }
+
+    /// Starts a stopwatch to measure duration of an operation. The stopwatch tracks the operation as a telemetry
+    /// item with duration measured by a monotonic clock and the time stamp when the operation started.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// let stopwatch = client.start_stopwatch();
+    /// // query database
+    /// stopwatch.complete_dependency("SELECT * FROM users", "SQL", "db.example.com", true);
+    /// ```
+    pub fn start_stopwatch(&self) -> Stopwatch<'_> {
+        Stopwatch::start(self)
+    }
+
+    /// Starts measuring a dependency call with the specified name, type and target. The returned guard tracks
+    /// the call when it goes out of scope, so the call is reported on early returns as well. The call is
+    /// considered successful unless the guard is marked as failed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// let mut dependency = client.start_dependency("GET /users", "HTTP", "api.example.com");
+    /// // call the API and mark the dependency as failed if the call fails
+    /// dependency.mark_failed();
+    /// ```
+    pub fn start_dependency(
+        &self,
+        name: impl Into<String>,
+        dependency_type: impl Into<String>,
+        target: impl Into<String>,
+    ) -> DependencyTracker<'_> {
+        DependencyTracker::start(self, name, dependency_type, target)
+    }
+
+    /// Starts measuring a HTTP request with the specified method and URL. The returned guard tracks the request
+    /// when it goes out of scope, so the request is reported on early returns as well. The response code
+    /// defaults to `200` unless it is set.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use appinsights::ext::{Method, Uri};
+    ///
+    /// let uri: Uri = "https://api.github.com/dmolokanov/appinsights-rs".parse().unwrap();
+    /// let request = client.start_request(Method::GET, uri);
+    /// // handle request
+    /// request.finish("404");
+    /// ```
+    pub fn start_request(&self, method: Method, uri: Uri) -> RequestTracker<'_> {
+        RequestTracker::start(self, method, uri)
+    }
+
+    /// Starts an operation with the specified name. Telemetry tracked within the operation is correlated with it,
+    /// and the returned guard tracks the operation as a request when it goes out of scope. An operation started
+    /// within another operation joins it as a child.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::{TelemetryClient, Tracker};
+    /// # use appinsights::telemetry::{SeverityLevel, TraceTelemetry};
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// let operation = client.start_operation("process order");
+    /// // the trace refers to the request the operation is tracked as
+    /// operation.track(TraceTelemetry::new("order received", SeverityLevel::Information));
+    /// ```
+    pub fn start_operation(&self, name: impl Into<String>) -> Operation<'_> {
+        Operation::start(self, name)
+    }
+
+    /// Starts an operation with the specified name that continues an operation propagated in message metadata
+    /// by a producer, so processing of the message is tracked as a request that refers to the producer as its
+    /// parent. Starts the operation within the current scope, if any, when metadata carries no operation. See
+    /// [`correlation`](correlation/index.html) for details.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use std::collections::HashMap;
+    /// # use appinsights::TelemetryClient;
+    /// # async fn process(client: &TelemetryClient, metadata: HashMap<String, String>) {
+    /// let operation = client.continue_operation("process order", &metadata);
+    /// operation.run(async { client.track_event("order processed") }).await;
+    /// # }
+    /// ```
+    pub fn continue_operation<S: BuildHasher>(
+        &self,
+        name: impl Into<String>,
+        metadata: &HashMap<String, String, S>,
+    ) -> Operation<'_> {
+        match CorrelationContext::extract(metadata) {
+            Some(context) => Operation::start_with_parent(self, name, Some(context.to_scope())),
+            None => Operation::start(self, name),
+        }
+    }
+
     /// Submits a specific telemetry event.
     ///
     /// # Examples
@@ -228,13 +700,89 @@ impl TelemetryClient {
         E: Telemetry,
         (TelemetryContext, E): Into<Envelope>,
     {
+        let context = self.context().clone();
+        self.submit(context, event)
+    }
+
+    /// Submits a specific telemetry event with the given context instead of the context of the client.
+    /// Frameworks that handle requests of many tenants or users concurrently can supply request-scoped
+    /// contexts, e.g. with a different user id or instrumentation key, without mutating the shared client.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use appinsights::telemetry::EventTelemetry;
+    ///
+    /// let mut context = client.context().clone();
+    /// context.tags_mut().user_mut().set_id("alice".into());
+    ///
+    /// client.track_with_context(&context, EventTelemetry::new("order placed"));
+    /// ```
+    pub fn track_with_context<E>(&self, context: &TelemetryContext, event: E)
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        self.submit(context.clone(), event)
+    }
+
+    /// Stamps a telemetry item with the given context and queues it unless it is shed, filtered out or sampled
+    /// out.
+    fn submit<E>(&self, context: TelemetryContext, event: E)
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        #[cfg(feature = "disabled")]
+        let _ = (context, event);
+        #[cfg(not(feature = "disabled"))]
         if self.is_enabled() {
-            let envelop = (self.context.clone(), event).into();
-            self.channel.send(envelop);
+            let mut envelop = (context, event).into();
+            scope::stamp(&mut envelop);
+            let overloaded = self
+                .overload
+                .as_ref()
+                .is_some_and(|overload| overload.is_overloaded(self.channel.as_ref()));
+            if overloaded && overload::is_shed(&envelop) {
+                return;
+            }
+
+            let percentage = overload::percentage(self.sampler.percentage(), overloaded);
+            if self.processors.process(&mut envelop) && sampling::sample(&mut envelop, percentage) {
+                self.channel.send(envelop);
+            }
         }
     }
 
+    /// Creates a lightweight handle that tracks telemetry with the client, stamping it with a copy of the client
+    /// context whose tags are overridden by the given ones. Handles share the channel of the client.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use appinsights::telemetry::tag_keys;
+    ///
+    /// let ingest = client.with_tags([(tag_keys::CLOUD_ROLE, "ingest")]);
+    /// let api = client.with_tags([(tag_keys::CLOUD_ROLE, "api")]);
+    ///
+    /// ingest.track_event("batch received");
+    /// api.track_event("request received");
+    /// ```
+    pub fn with_tags<K, V>(&self, tags: impl IntoIterator<Item = (K, V)>) -> TelemetryHandle<'_>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        TelemetryHandle::new(self, handle::with_tags(&self.context(), tags))
+    }
+
     /// Forces all pending telemetry items to be submitted. The current task will not be blocked.
+    /// Flush requests that pile up before the channel gets to handle them are coalesced into a single
+    /// submission, so calling this method often does not result in a number of small posts.
     ///
     /// # Examples
     ///
@@ -259,9 +807,172 @@ impl TelemetryClient {
     /// }
     /// ```
     pub fn flush_channel(&self) {
+        #[cfg(not(feature = "disabled"))]
         self.channel.flush();
     }
 
+    /// Forces all pending telemetry items to be submitted and waits until the attempt to submit them has
+    /// completed, successfully or not. Returns numbers of items the ingestion endpoint accepted and did not
+    /// accept. Items that failed are retried later, unless the endpoint rejected them for good. A flush
+    /// requested while submission is [paused](struct.ChannelControl.html#method.pause) completes once it
+    /// is resumed.
+    ///
+    /// Tests and batch jobs can make sure telemetry has been submitted before they check or exit.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// client.track_event("job completed");
+    ///
+    /// let report = client.flush_and_wait().await;
+    /// println!("{} items sent, {} failed", report.sent(), report.failed());
+    /// # }
+    /// ```
+    pub async fn flush_and_wait(&self) -> FlushReport {
+        #[cfg(not(feature = "disabled"))]
+        {
+            self.channel.flush_and_wait().await
+        }
+        #[cfg(feature = "disabled")]
+        {
+            FlushReport::default()
+        }
+    }
+
+    /// Forces all pending telemetry items to be submitted and releases memory the channel keeps in reserve
+    /// once they are sent. The current task will not be blocked.
+    ///
+    /// Hosts can call this method when the process is under memory pressure, e.g. from an allocator callback
+    /// or on a cgroup memory event, to bound memory held by telemetry.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// # let memory_pressure = || true;
+    /// if memory_pressure() {
+    ///     client.shrink_channel();
+    /// }
+    /// ```
+    pub fn shrink_channel(&self) {
+        #[cfg(not(feature = "disabled"))]
+        self.channel.shrink();
+    }
+
+    /// Returns names and timestamps of telemetry items waiting in the channel, oldest first, to diagnose what
+    /// kinds of items are stuck when delivery stalls. Items taken from the queue to be sent or retried are
+    /// reported as pending. Taking a snapshot briefly disturbs the order of items queued concurrently, so it is
+    /// meant for debugging only. Available with the `debug` feature only. Always empty with the `disabled` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// for item in client.debug_snapshot() {
+    ///     println!("{} {} pending: {}", item.time(), item.name(), item.is_pending());
+    /// }
+    /// ```
+    #[cfg(all(feature = "debug", feature = "runtime"))]
+    pub fn debug_snapshot(&self) -> Vec<crate::QueuedItemSnapshot> {
+        #[cfg(not(feature = "disabled"))]
+        {
+            self.channel.debug_snapshot()
+        }
+        #[cfg(feature = "disabled")]
+        {
+            Vec::new()
+        }
+    }
+
+    /// Returns a snapshot of statistics of the telemetry channel, such as percentiles of time telemetry items
+    /// spend in the queue before the ingestion endpoint accepts them. Always empty with the `disabled` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// if let Some(latency) = client.channel_stats().queue_latency() {
+    ///     println!("p99 delivery latency: {:?}", latency.p99());
+    /// }
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn channel_stats(&self) -> ChannelStats {
+        #[cfg(not(feature = "disabled"))]
+        {
+            self.channel.stats()
+        }
+        #[cfg(feature = "disabled")]
+        {
+            ChannelStats::default()
+        }
+    }
+
+    /// Returns a handle to control the submission of telemetry items, which can be used independently of the
+    /// client, e.g. by a signal handler or a health endpoint that do not own the client. The handle does nothing
+    /// with the `disabled` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// let control = client.channel_control();
+    ///
+    /// // stop submitting telemetry while the network is under maintenance
+    /// control.pause();
+    /// // ...
+    /// control.resume();
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn channel_control(&self) -> ChannelControl {
+        #[cfg(not(feature = "disabled"))]
+        {
+            self.channel.control()
+        }
+        #[cfg(feature = "disabled")]
+        {
+            ChannelControl::default()
+        }
+    }
+
+    /// Submits statistics of the telemetry channel as metrics, so delivery of telemetry can be monitored and
+    /// alerted on like any other metric. Queue latency percentiles are submitted in milliseconds as
+    /// `appinsights_queue_latency_p50_ms`, `appinsights_queue_latency_p95_ms` and
+    /// `appinsights_queue_latency_p99_ms` once at least one item has been sent.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # use std::time::Duration;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// let mut interval = tokio::time::interval(Duration::from_secs(60));
+    /// loop {
+    ///     interval.tick().await;
+    ///     client.track_channel_stats();
+    /// }
+    /// # }
+    /// ```
+    #[cfg(feature = "runtime")]
+    pub fn track_channel_stats(&self) {
+        if let Some(latency) = self.channel_stats().queue_latency() {
+            let percentiles = [("p50", latency.p50()), ("p95", latency.p95()), ("p99", latency.p99())];
+            for (percentile, value) in percentiles {
+                let name = format!("appinsights_queue_latency_{}_ms", percentile);
+                self.track_metric(name, value.as_secs_f64() * 1000.0);
+            }
+        }
+    }
+
     /// Flushes and tears down the submission flow and closes internal channels.
     /// It blocks the current task until all pending telemetry items have been submitted and it is safe to
     /// shutdown without losing telemetry.
@@ -285,12 +996,16 @@ impl TelemetryClient {
     /// // unable to sent any telemetry after client closes its channel
     /// // client.track_event("app is stopped".to_string());
     /// ```
+    #[cfg_attr(feature = "disabled", allow(unused_mut))]
     pub async fn close_channel(mut self) {
+        #[cfg(not(feature = "disabled"))]
         self.channel.close().await;
     }
 
     /// Tears down the submission flow and closes internal channels.
     /// Any telemetry waiting to be sent is discarded. This is a more abrupt version of [`close_channel`](#method.close_channel).
+    /// Configure a [`terminate_sink`](struct.TelemetryConfigBuilder.html#method.terminate_sink) to preserve
+    /// discarded telemetry for post-mortem analysis.
     /// This method consumes the value of client so it makes impossible to use a client with close
     /// channel.
     ///
@@ -314,32 +1029,164 @@ impl TelemetryClient {
     /// // unable to sent any telemetry after client closes its channel
     /// // client.track_event("app is stopped".to_string());
     /// ```
+    #[cfg_attr(feature = "disabled", allow(unused_mut))]
     pub async fn terminate(mut self) {
+        #[cfg(not(feature = "disabled"))]
         self.channel.terminate().await;
     }
 }
 
+impl Tracker for TelemetryClient {
+    fn track<E>(&self, event: E)
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        TelemetryClient::track(self, event)
+    }
+}
+
 impl From<(TelemetryConfig, TelemetryContext)> for TelemetryClient {
-    fn from((config, context): (TelemetryConfig, TelemetryContext)) -> Self {
+    fn from((mut config, context): (TelemetryConfig, TelemetryContext)) -> Self {
         Self {
             enabled: true,
-            context,
-            channel: Box::new(InMemoryChannel::new(&config)),
+            sampler: Sampler::new(&mut config),
+            #[cfg(not(feature = "disabled"))]
+            overload: OverloadGuard::new(config.overload_threshold()),
+            #[cfg(not(feature = "disabled"))]
+            channel: channel(&config),
+            config: RwLock::new(config),
+            context: RwLock::new(context),
+            processors: Pipeline::default(),
         }
     }
 }
 
-#[cfg(test)]
-pub(crate) mod tests {
-    use std::sync::Arc;
+/// Creates a request telemetry item with a random id. Its name is derived with the normalizer, if configured.
+pub(crate) fn request(
+    normalizer: Option<&Shared<RequestNameNormalizer>>,
+    method: Method,
+    uri: Uri,
+    duration: Duration,
+    response_code: impl Into<String>,
+) -> RequestTelemetry {
+    let name = normalizer.and_then(|normalize| callback::call("request name normalizer", || normalize(&method, &uri)));
+
+    let mut telemetry = RequestTelemetry::new(method, uri, duration, response_code);
+    telemetry.set_id(appinsights_core::uuid::new().as_hyphenated().to_string());
+    if let Some(name) = name {
+        telemetry.set_name(name);
+    }
+    telemetry
+}
+
+/// A type of dependencies that run within the process, such as operations tracked with `track_result`.
+pub(crate) const IN_PROC_DEPENDENCY_TYPE: &str = "InProc";
+
+/// Creates an exception telemetry item for a failed operation tracked with `track_result`.
+pub(crate) fn result_exception<E>(name: impl Into<String>, error: &E, duration: Duration) -> ExceptionTelemetry
+where
+    E: std::error::Error,
+{
+    let mut event = ExceptionTelemetry::from_error(error, SeverityLevel::Error);
+    event.properties_mut().insert("operation".into(), name.into());
+    event
+        .measurements_mut()
+        .insert("durationMs".into(), duration.as_secs_f64() * 1000.0);
+    event
+}
+
+/// Creates a telemetry channel according to the configuration, which forwards telemetry to mirrors if any
+/// configured.
+#[cfg(not(feature = "disabled"))]
+pub(crate) fn channel(config: &TelemetryConfig) -> Box<dyn TelemetryChannel> {
+    let primary = single_channel(config);
+    if config.mirrors().is_empty() {
+        return primary;
+    }
+
+    let mirrors = config
+        .mirrors()
+        .iter()
+        .map(|mirror| (mirror.i_key().to_string(), single_channel(mirror)));
+    Box::new(MulticastChannel::new(primary, mirrors))
+}
+
+/// Creates a channel that submits telemetry to a single resource. Falls back to the in-memory channel when the
+/// persistence directory cannot be used.
+#[cfg(not(feature = "disabled"))]
+fn single_channel(config: &TelemetryConfig) -> Box<dyn TelemetryChannel> {
+    if let Some(dir) = config.persistence_dir() {
+        match PersistentChannel::new(config, dir) {
+            Ok(channel) => return Box::new(channel),
+            Err(err) => warn!(
+                "Unable to spool telemetry to {}: {}. Keeping it in memory",
+                dir.display(),
+                err
+            ),
+        }
+    }
+
+    Box::new(InMemoryChannel::new(config))
+}
+
+/// Spawns a task that calls `f` with the client every `interval`, as described in the "Periodic tasks" section
+/// of the crate documentation. With the `disabled` feature the task completes right away.
+#[cfg(feature = "runtime")]
+pub(crate) fn spawn_periodic<F>(client: &Arc<TelemetryClient>, interval: Duration, mut f: F) -> JoinHandle<()>
+where
+    F: FnMut(&TelemetryClient) + Send + 'static,
+{
+    spawn_periodic_async(client, interval, move |client| {
+        f(&client);
+        future::ready(())
+    })
+}
+
+/// Spawns a task that awaits a future `f` returns for the client every `interval`, like
+/// [`spawn_periodic`]. The client is kept alive while the future runs.
+#[cfg(feature = "runtime")]
+pub(crate) fn spawn_periodic_async<F, Fut>(
+    client: &Arc<TelemetryClient>,
+    interval: Duration,
+    mut f: F,
+) -> JoinHandle<()>
+where
+    F: FnMut(Arc<TelemetryClient>) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    let timer = client.config().timer();
+    let runtime = client.config().runtime().cloned();
+    let control = client.channel_control();
+    let controlled = control.is_attached();
+    let client = Arc::downgrade(client);
 
+    let task = async move {
+        if DISABLED {
+            return;
+        }
+        loop {
+            timeout::sleep(&*timer, interval).await;
+            match client.upgrade() {
+                Some(client) if !(controlled && control.is_closed()) => f(client).await,
+                _ => break,
+            }
+        }
+    };
+    match runtime {
+        Some(runtime) => runtime.spawn(task),
+        None => tokio::spawn(task),
+    }
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+pub(crate) mod tests {
     use async_trait::async_trait;
-    use chrono::{DateTime, Utc};
     use crossbeam_queue::SegQueue;
     use matches::assert_matches;
 
     use super::*;
-    use crate::telemetry::{ContextTags, Properties};
+    use crate::contracts::{Base, Data, MetricData};
 
     #[tokio::test]
     async fn it_enabled_by_default() {
@@ -361,27 +1208,214 @@ pub(crate) mod tests {
         let events = Arc::new(SegQueue::default());
         let client = create_client(events.clone());
 
-        client.track(TestTelemetry {});
+        client.track(EventTelemetry::new("test"));
 
         assert_eq!(events.len(), 1)
     }
 
+    #[tokio::test]
+    async fn it_submits_outcome_of_operation() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let ok: Result<(), std::io::Error> = Ok(());
+        client.track_result("load settings", &ok, Duration::from_millis(1500));
+        let err: Result<(), _> = Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no such file"));
+        client.track_result("load settings", &err, Duration::from_millis(1500));
+
+        match events.pop().unwrap().data {
+            Some(Base::Data(Data::RemoteDependencyData(data))) => {
+                assert_eq!(data.name, "load settings");
+                assert_eq!(data.type_.as_deref(), Some("InProc"));
+                assert_eq!(data.duration, "0.00:00:01.5000000");
+                assert_eq!(data.success, Some(true));
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+        match events.pop().unwrap().data {
+            Some(Base::Data(Data::ExceptionData(data))) => {
+                assert_eq!(data.exceptions[0].message, "no such file");
+                assert_eq!(data.properties.unwrap()["operation"], "load settings");
+                assert_eq!(data.measurements.unwrap()["durationMs"], 1500.0);
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_submits_telemetry_with_given_context() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let mut context = TelemetryContext::new("tenant".into(), Default::default(), Default::default());
+        context.tags_mut().user_mut().set_id("alice".into());
+        client.track_with_context(&context, EventTelemetry::new("test"));
+        client.track(EventTelemetry::new("test"));
+
+        let envelope = events.pop().unwrap();
+        assert_eq!(envelope.i_key.as_deref(), Some("tenant"));
+        assert_eq!(envelope.tags.unwrap()["ai.user.id"], "alice");
+
+        let envelope = events.pop().unwrap();
+        assert_eq!(envelope.i_key.as_deref(), Some("instrumentation"));
+        assert!(!envelope.tags.unwrap().contains_key("ai.user.id"));
+    }
+
+    #[tokio::test]
+    async fn it_submits_telemetry_with_tags_of_handle() {
+        let events = Arc::new(SegQueue::default());
+        let mut client = create_client(events.clone());
+        client.context_mut().tags_mut().cloud_mut().set_role("server".into());
+        client.context_mut().tags_mut().user_mut().set_id("alice".into());
+
+        let ingest = client.with_tags([("ai.cloud.role", "ingest")]);
+        let api = ingest.with_tags([("ai.cloud.role", "api")]);
+        ingest.track_event("test");
+        api.track_event("test");
+        client.track_event("test");
+
+        let roles: Vec<_> = (0..3)
+            .map(|_| {
+                let tags = events.pop().unwrap().tags.unwrap();
+                assert_eq!(tags["ai.user.id"], "alice");
+                tags["ai.cloud.role"].clone()
+            })
+            .collect();
+        assert_eq!(roles, vec!["ingest", "api", "server"]);
+    }
+
     #[tokio::test]
     async fn it_swallows_telemetry_when_disabled() {
         let events = Arc::new(SegQueue::default());
         let mut client = create_client(events.clone());
         client.enabled(false);
 
-        client.track(TestTelemetry {});
+        client.track(EventTelemetry::new("test"));
 
         assert!(events.is_empty())
     }
 
+    #[tokio::test]
+    async fn it_swallows_sampled_out_telemetry() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder().i_key("key").sampling_percentage(0.0).build();
+        let client = TelemetryClient::create(&config, TestChannel::new(events.clone()));
+
+        client.track(EventTelemetry::new("test"));
+        client.track(MetricTelemetry::new("test", 1.0));
+
+        assert_eq!(events.len(), 1);
+        assert!(!client.is_sampled_in("operation"));
+    }
+
+    #[tokio::test]
+    async fn it_applies_sampling_percentage_when_reconfigured() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::new("key".into());
+        let client = TelemetryClient::create(&config, TestChannel::new(events.clone()));
+        assert!(client.is_sampled_in("operation"));
+
+        client.reconfigure(TelemetryConfig::builder().i_key("key").sampling_percentage(0.0).build());
+
+        client.track(EventTelemetry::new("test"));
+        assert!(events.is_empty());
+        assert!(!client.is_sampled_in("operation"));
+    }
+
+    #[tokio::test]
+    async fn it_names_request_with_configured_normalizer() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder()
+            .i_key("key")
+            .request_name_normalizer(crate::telemetry::normalize_request_name)
+            .build();
+        let client = TelemetryClient::create(&config, TestChannel::new(events.clone()));
+
+        let uri = "https://example.com/users/42?verbose=true".parse().unwrap();
+        client.track_request(Method::GET, uri, Duration::from_millis(10), "200");
+
+        let envelope = events.pop().unwrap();
+        assert_eq!(envelope.tags.unwrap()["ai.operation.name"], "GET /users/{id}");
+        assert_matches!(
+            envelope.data,
+            Some(Base::Data(Data::RequestData(data)))
+                if data.name.as_deref() == Some("GET /users/{id}")
+        );
+    }
+
+    #[tokio::test]
+    async fn it_returns_id_of_tracked_request() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let uri = "https://example.com/users".parse().unwrap();
+        let id = client.track_request(Method::GET, uri, Duration::from_millis(10), "200");
+
+        assert!(!id.is_empty());
+        assert_matches!(
+            events.pop().unwrap().data,
+            Some(Base::Data(Data::RequestData(data))) if data.id == id
+        );
+    }
+
+    #[tokio::test]
+    async fn it_tracks_page_view() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        client.track_page_view("Product details", "https://example.com/products/42".parse().unwrap());
+
+        assert_matches!(
+            events.pop().unwrap().data,
+            Some(Base::Data(Data::PageViewData(data)))
+                if data.name == "Product details" && data.url.as_deref() == Some("https://example.com/products/42")
+        );
+    }
+
+    #[tokio::test]
+    async fn it_tracks_availability_with_details() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        client.track_availability_with("health", Duration::from_secs(1), false, |test| {
+            test.set_run_location("westeurope");
+            test.set_message("request timed out");
+        });
+
+        assert_matches!(
+            events.pop().unwrap().data,
+            Some(Base::Data(Data::AvailabilityData(data)))
+                if data.run_location.as_deref() == Some("westeurope")
+                    && data.message.as_deref() == Some("request timed out")
+                    && !data.success
+        );
+    }
+
+    #[tokio::test]
+    async fn it_runs_telemetry_through_processors() {
+        let events = Arc::new(SegQueue::default());
+        let mut client = create_client(events.clone());
+        client.add_processor(|envelope: &mut Envelope| {
+            envelope.i_key = Some("processed".into());
+            true
+        });
+        client.add_processor(|envelope: &mut Envelope| {
+            !matches!(&envelope.data, Some(Base::Data(Data::EventData(data))) if data.name == "health")
+        });
+
+        client.track(EventTelemetry::new("health"));
+        client.track(EventTelemetry::new("test"));
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events.pop().unwrap().i_key, Some("processed".into()));
+    }
+
     #[tokio::test]
     async fn it_creates_client_with_default_tags() {
         let client = TelemetryClient::new("instrumentation".into());
 
-        let tags = client.context().tags();
+        let context = client.context();
+        let tags = context.tags();
         assert_matches!(tags.internal().sdk_version(), Some(version) if version.starts_with("rust"));
         assert_matches!(tags.device().os_version(), Some(_))
     }
@@ -392,41 +1426,63 @@ pub(crate) mod tests {
         assert!(client.is_enabled())
     }
 
-    fn create_client(events: Arc<SegQueue<Envelope>>) -> TelemetryClient {
-        let config = TelemetryConfig::new("instrumentation".into());
-        TelemetryClient::create(&config, TestChannel::new(events))
+    #[test]
+    fn it_submits_telemetry_on_configured_runtime() {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .unwrap();
+
+        // no runtime context here, so the worker can be spawned on the configured runtime only
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .runtime(rt.handle().clone())
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        rt.block_on(client.close_channel());
     }
 
-    pub(crate) struct TestTelemetry {}
+    #[test]
+    fn it_calls_periodically_until_client_dropped() {
+        let _guard = timeout::SERIAL_TEST_MUTEX.lock();
 
-    impl Telemetry for TestTelemetry {
-        fn timestamp(&self) -> DateTime<Utc> {
-            unimplemented!()
-        }
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        rt.block_on(async {
+            timeout::init();
 
-        fn properties(&self) -> &Properties {
-            unimplemented!()
-        }
+            let events = Arc::new(SegQueue::default());
+            let client = Arc::new(create_client(events.clone()));
+            let (ticks, mut ticked) = tokio::sync::mpsc::unbounded_channel();
+            let task = spawn_periodic(&client, Duration::from_secs(60), move |client| {
+                client.track_event("tick");
+                let _ = ticks.send(());
+            });
 
-        fn properties_mut(&mut self) -> &mut Properties {
-            unimplemented!()
-        }
+            timeout::expire();
+            ticked.recv().await;
+            assert_eq!(events.len(), 1);
 
-        fn tags(&self) -> &ContextTags {
-            unimplemented!()
-        }
+            // the task does not keep the client alive
+            drop(client);
+            timeout::expire();
+            task.await.unwrap();
 
-        fn tags_mut(&mut self) -> &mut ContextTags {
-            unimplemented!()
-        }
+            timeout::reset();
+        });
     }
 
-    #[derive(Clone)]
-    pub(crate) struct TestData;
+    pub(crate) fn create_client(events: Arc<SegQueue<Envelope>>) -> TelemetryClient {
+        let config = TelemetryConfig::new("instrumentation".into());
+        TelemetryClient::create(&config, TestChannel::new(events))
+    }
 
-    impl From<(TelemetryContext, TestTelemetry)> for Envelope {
-        fn from((_, _): (TelemetryContext, TestTelemetry)) -> Self {
-            Envelope::default()
+    /// Returns data of a metric telemetry item, panicking if the item is missing or is not a metric.
+    pub(crate) fn metric_data(envelope: Option<Envelope>) -> MetricData {
+        match envelope.and_then(|envelope| envelope.data) {
+            Some(Base::Data(Data::MetricData(data))) => data,
+            data => panic!("unexpected data: {:?}", data),
         }
     }
 
@@ -450,13 +1506,11 @@ pub(crate) mod tests {
             unimplemented!()
         }
 
-        async fn close(&mut self) {
-            unimplemented!()
-        }
+        async fn close(&mut self) {}
 
         async fn terminate(&mut self) {}
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, not(feature = "disabled")))]
 mod integration_tests;
@@ -0,0 +1,341 @@
+use std::{
+    fmt,
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    contracts::Envelope,
+    correlation::CorrelationContext,
+    scope::{self, Scope},
+    telemetry::{OperationResult, RequestTelemetry, Telemetry, Timestamp},
+    time, TelemetryClient, TelemetryContext, Tracker,
+};
+
+/// An operation that correlates all telemetry tracked within it and tracks itself as a request when dropped.
+///
+/// An operation started within another operation or an [operation scope](../scope/index.html) joins the
+/// operation of the enclosing scope and refers to it as its parent. Otherwise it starts a new operation with a
+/// random id. Telemetry tracked with the operation as a [`Tracker`](trait.Tracker.html), or with the client
+/// within a future [run](#method.run) by the operation, is stamped with the operation id and refers to the
+/// operation as its parent, unless an item belongs to an operation of its own.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # use appinsights::{TelemetryClient, Tracker};
+/// # use appinsights::telemetry::{SeverityLevel, TraceTelemetry};
+/// # async fn process(client: &TelemetryClient) {
+/// let mut operation = client.start_operation("process order");
+/// operation.track(TraceTelemetry::new("order received", SeverityLevel::Information));
+///
+/// // telemetry tracked with the client within the future is correlated with the operation too
+/// operation.run(async { client.track_event("payment captured") }).await;
+///
+/// operation.set_response_code("201");
+/// // the request is tracked here
+/// # }
+/// ```
+pub struct Operation<'a> {
+    client: &'a TelemetryClient,
+    scope: Scope,
+    parent: Option<Scope>,
+    started: Instant,
+    timestamp: Timestamp,
+    name: String,
+    response_code: String,
+    result: Option<OperationResult>,
+}
+
+impl<'a> Operation<'a> {
+    /// Starts an operation with the given name within the current scope, if any.
+    pub(crate) fn start(client: &'a TelemetryClient, name: impl Into<String>) -> Self {
+        Self::start_with_parent(client, name, Scope::current())
+    }
+
+    /// Starts an operation with the given name within the given parent scope, if any.
+    pub(crate) fn start_with_parent(
+        client: &'a TelemetryClient,
+        name: impl Into<String>,
+        parent: Option<Scope>,
+    ) -> Self {
+        let scope = Scope::child(parent.as_ref());
+        Self::resume(client, name, scope, parent, Instant::now(), time::timestamp())
+    }
+
+    /// Resumes an operation with the given scope that started at the given time, e.g. by middleware that
+    /// needs the scope before the operation itself is created.
+    pub(crate) fn resume(
+        client: &'a TelemetryClient,
+        name: impl Into<String>,
+        scope: Scope,
+        parent: Option<Scope>,
+        started: Instant,
+        timestamp: Timestamp,
+    ) -> Self {
+        Self {
+            client,
+            scope,
+            parent,
+            started,
+            timestamp,
+            name: name.into(),
+            response_code: "200".into(),
+            result: None,
+        }
+    }
+
+    /// Returns a scope telemetry tracked within the operation belongs to.
+    pub fn scope(&self) -> &Scope {
+        &self.scope
+    }
+
+    /// Returns an id of the operation.
+    pub fn operation_id(&self) -> &str {
+        self.scope.operation_id()
+    }
+
+    /// Returns an id of the request the operation is tracked as, which telemetry tracked within the operation
+    /// refers to as its parent.
+    pub fn id(&self) -> &str {
+        self.scope.id()
+    }
+
+    /// Returns a context of the client with tags of the operation, which can be used to correlate telemetry
+    /// tracked by another client with the operation.
+    pub fn context(&self) -> TelemetryContext {
+        let mut context = self.client.context().clone();
+        let mut operation = context.tags_mut().operation_mut();
+        operation.set_id(self.operation_id().into());
+        operation.set_parent_id(self.id().into());
+        operation.set_name(self.name.clone());
+        context
+    }
+
+    /// Returns a context to propagate the operation to consumers of messages the operation produces, so they
+    /// continue the operation with
+    /// [`TelemetryClient::continue_operation`](struct.TelemetryClient.html#method.continue_operation).
+    pub fn correlation(&self) -> CorrelationContext {
+        CorrelationContext::from_scope(&self.scope)
+    }
+
+    /// Runs a future within the operation, so telemetry tracked by the client within the future is correlated
+    /// with the operation. The scope follows the future across `.await` points but not into spawned tasks.
+    pub async fn run<F: Future>(&self, future: F) -> F::Output {
+        scope::within(self.scope.clone(), future).await
+    }
+
+    /// Returns the time when the operation started.
+    pub fn timestamp(&self) -> Timestamp {
+        self.timestamp
+    }
+
+    /// Returns the time elapsed since the operation started.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Sets a response code of the request the operation is tracked as, which determines whether the operation
+    /// succeeded. Defaults to `200`.
+    pub fn set_response_code(&mut self, response_code: impl Into<String>) {
+        self.response_code = response_code.into();
+    }
+
+    /// Sets an outcome of the operation, which determines whether the operation succeeded and why it did not
+    /// regardless of the response code.
+    pub fn set_result(&mut self, result: OperationResult) {
+        self.result = Some(result);
+    }
+
+    /// Tracks the operation right away. It is the same as dropping the operation, but reads better at the end
+    /// of a block.
+    pub fn complete(self) {}
+}
+
+impl Tracker for Operation<'_> {
+    fn track<E>(&self, event: E)
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        scope::within_sync(self.scope.clone(), || self.client.track(event))
+    }
+}
+
+impl Drop for Operation<'_> {
+    fn drop(&mut self) {
+        let mut telemetry = RequestTelemetry::with_name(
+            std::mem::take(&mut self.name),
+            self.elapsed(),
+            std::mem::take(&mut self.response_code),
+        );
+        telemetry.set_id(self.scope.id());
+        telemetry.set_timestamp(self.timestamp);
+        if let Some(result) = self.result.take() {
+            telemetry.set_result(result);
+        }
+
+        let mut operation = telemetry.tags_mut().operation_mut();
+        operation.set_id(self.scope.operation_id().into());
+        if let Some(parent) = &self.parent {
+            operation.set_parent_id(parent.id().into());
+        }
+        self.client.track(telemetry)
+    }
+}
+
+impl fmt::Debug for Operation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Operation")
+            .field("name", &self.name)
+            .field("operation_id", &self.operation_id())
+            .field("id", &self.id())
+            .field("timestamp", &self.timestamp)
+            .field("elapsed", &self.elapsed())
+            .field("response_code", &self.response_code)
+            .field("result", &self.result)
+            .finish()
+    }
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        sync::Arc,
+    };
+
+    use crossbeam_queue::SegQueue;
+
+    use super::*;
+    use crate::{
+        client::tests::create_client,
+        contracts::{Base, Data},
+        telemetry::{tag_keys, SeverityLevel, TraceTelemetry},
+    };
+
+    #[tokio::test]
+    async fn it_correlates_telemetry_tracked_within_operation() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let (operation_id, id) = {
+            let mut operation = client.start_operation("process order");
+            operation.track(TraceTelemetry::new("received", SeverityLevel::Information));
+            operation.run(async { client.track_event("captured") }).await;
+            client.track_event("outside");
+            operation.set_response_code("500");
+            (operation.operation_id().to_string(), operation.id().to_string())
+        };
+
+        for _ in 0..2 {
+            let tags = events.pop().unwrap().tags.unwrap();
+            assert_eq!(tags.get(tag_keys::OPERATION_ID), Some(&operation_id));
+            assert_eq!(tags.get(tag_keys::OPERATION_PARENT_ID), Some(&id));
+        }
+
+        let outside = events.pop().unwrap().tags.unwrap();
+        assert_eq!(outside.get(tag_keys::OPERATION_ID), None);
+
+        let request = events.pop().unwrap();
+        let tags = request.tags.unwrap();
+        assert_eq!(tags.get(tag_keys::OPERATION_ID), Some(&operation_id));
+        assert_eq!(tags.get(tag_keys::OPERATION_PARENT_ID), None);
+        assert_eq!(tags.get(tag_keys::OPERATION_NAME), Some(&"process order".to_string()));
+        match request.data {
+            Some(Base::Data(Data::RequestData(data))) => {
+                assert_eq!(data.id, id);
+                assert_eq!(data.name, Some("process order".into()));
+                assert_eq!(data.response_code, "500");
+                assert!(!data.success);
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn it_nests_operation_in_current_scope() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let parent = client.start_operation("parent");
+        let (parent_operation_id, parent_id) = (parent.operation_id().to_string(), parent.id().to_string());
+        parent.run(async { client.start_operation("child").complete() }).await;
+
+        let tags: BTreeMap<_, _> = events.pop().unwrap().tags.unwrap();
+        assert_eq!(tags.get(tag_keys::OPERATION_ID), Some(&parent_operation_id));
+        assert_eq!(tags.get(tag_keys::OPERATION_PARENT_ID), Some(&parent_id));
+
+        let context = parent.context();
+        assert_eq!(context.tags().operation().id(), Some(parent_operation_id.as_str()));
+        assert_eq!(context.tags().operation().parent_id(), Some(parent_id.as_str()));
+    }
+
+    #[tokio::test]
+    async fn it_continues_operation_propagated_in_message_metadata() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let producer = client.start_operation("place order");
+        let mut metadata = HashMap::new();
+        producer.correlation().inject(&mut metadata);
+
+        let consumer = client.continue_operation("process order", &metadata);
+        assert_eq!(consumer.operation_id(), producer.operation_id());
+        let consumer_id = consumer.id().to_string();
+        consumer.run(async { client.track_event("processed") }).await;
+        consumer.complete();
+
+        let tags = events.pop().unwrap().tags.unwrap();
+        assert_eq!(tags.get(tag_keys::OPERATION_PARENT_ID), Some(&consumer_id));
+
+        let tags = events.pop().unwrap().tags.unwrap();
+        assert_eq!(
+            tags.get(tag_keys::OPERATION_ID).map(String::as_str),
+            Some(producer.operation_id())
+        );
+        assert_eq!(
+            tags.get(tag_keys::OPERATION_PARENT_ID).map(String::as_str),
+            Some(producer.id())
+        );
+    }
+
+    #[tokio::test]
+    async fn it_starts_new_operation_without_propagated_one() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let operation = client.continue_operation("process order", &HashMap::new());
+        let operation_id = operation.operation_id().to_string();
+        operation.complete();
+
+        let tags = events.pop().unwrap().tags.unwrap();
+        assert_eq!(tags.get(tag_keys::OPERATION_ID), Some(&operation_id));
+        assert_eq!(tags.get(tag_keys::OPERATION_PARENT_ID), None);
+    }
+
+    #[tokio::test]
+    async fn it_hands_off_context_of_current_operation() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let operation = client.start_operation("spawn worker");
+        let context = operation.run(async { client.handoff_context().serialize() }).await;
+        assert!(client.handoff_context().tags().operation().id().is_none());
+
+        let mut worker = create_client(events.clone());
+        *worker.context_mut() = TelemetryContext::deserialize(&context).unwrap();
+        worker.track_event("work done");
+
+        let tags = events.pop().unwrap().tags.unwrap();
+        assert_eq!(
+            tags.get(tag_keys::OPERATION_ID).map(String::as_str),
+            Some(operation.operation_id())
+        );
+        assert_eq!(
+            tags.get(tag_keys::OPERATION_PARENT_ID).map(String::as_str),
+            Some(operation.id())
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use http::{Method, Uri};
+
+use crate::{telemetry::Timestamp, time, TelemetryClient};
+
+/// Measures duration of a HTTP request and tracks it as a telemetry item when dropped, so the request is
+/// reported with a correct duration on every path out of the code that handles it, including early returns and
+/// the `?` operator. The response code is `200` unless it is set.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # fn find_user(id: &str) -> Result<Option<String>, std::io::Error> { Ok(None) }
+/// # fn handle(client: &TelemetryClient) -> Result<String, std::io::Error> {
+/// use appinsights::ext::{Method, Uri};
+///
+/// let uri: Uri = "https://example.com/users/42".parse().unwrap();
+/// let mut request = client.start_request(Method::GET, uri);
+/// let user = find_user("42").inspect_err(|_| request.set_response_code("500"))?;
+/// match user {
+///     Some(user) => Ok(user),
+///     None => {
+///         request.finish("404");
+///         Ok(String::new())
+///     }
+/// }
+/// # }
+/// ```
+pub struct RequestTracker<'a> {
+    client: &'a TelemetryClient,
+    started: Instant,
+    timestamp: Timestamp,
+    id: String,
+    method: Method,
+    uri: Uri,
+    response_code: String,
+}
+
+impl<'a> RequestTracker<'a> {
+    /// Starts measuring a request with the given method and URL.
+    pub(crate) fn start(client: &'a TelemetryClient, method: Method, uri: Uri) -> Self {
+        Self {
+            client,
+            started: Instant::now(),
+            timestamp: time::timestamp(),
+            id: appinsights_core::uuid::new().as_hyphenated().to_string(),
+            method,
+            uri,
+            response_code: "200".into(),
+        }
+    }
+
+    /// Returns an id of the request, which telemetry tracked while handling the request can refer to as its
+    /// parent.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the time when the request started.
+    pub fn timestamp(&self) -> Timestamp {
+        self.timestamp
+    }
+
+    /// Returns the time elapsed since the request started.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Sets a response code of the request, which determines whether the request succeeded.
+    pub fn set_response_code(&mut self, response_code: impl Into<String>) {
+        self.response_code = response_code.into();
+    }
+
+    /// Tracks the request right away with the given response code.
+    pub fn finish(mut self, response_code: impl Into<String>) {
+        self.set_response_code(response_code);
+    }
+}
+
+impl Drop for RequestTracker<'_> {
+    fn drop(&mut self) {
+        let mut telemetry = super::request(
+            self.client.config().request_name_normalizer(),
+            std::mem::take(&mut self.method),
+            std::mem::take(&mut self.uri),
+            self.elapsed(),
+            std::mem::take(&mut self.response_code),
+        );
+        telemetry.set_id(std::mem::take(&mut self.id));
+        telemetry.set_timestamp(self.timestamp);
+        self.client.track(telemetry)
+    }
+}
+
+impl fmt::Debug for RequestTracker<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestTracker")
+            .field("id", &self.id)
+            .field("method", &self.method)
+            .field("uri", &self.uri)
+            .field("timestamp", &self.timestamp)
+            .field("elapsed", &self.elapsed())
+            .field("response_code", &self.response_code)
+            .finish()
+    }
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::{TimeZone, Utc};
+    use crossbeam_queue::SegQueue;
+
+    use super::*;
+    use crate::{
+        client::tests::create_client,
+        contracts::{Base, Data, Envelope, RequestData},
+    };
+
+    #[tokio::test]
+    async fn it_tracks_request_when_dropped() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 800));
+        let id = {
+            let uri: Uri = "https://example.com/users".parse().unwrap();
+            let request = client.start_request(Method::GET, uri);
+            std::thread::sleep(Duration::from_millis(5));
+            request.id().to_string()
+        };
+        time::reset();
+
+        let envelope = events.pop().expect("request");
+        assert_eq!(envelope.time, "2019-01-02T03:04:05.800Z");
+        let data = request(envelope);
+        assert_eq!(data.id, id);
+        assert_eq!(data.name.as_deref(), Some("GET https://example.com/users"));
+        assert_eq!(data.response_code, "200");
+        assert!(data.success);
+        assert!(!data.duration.starts_with("0.00:00:00.000"));
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn it_tracks_request_with_response_code_once_finished() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let uri: Uri = "https://example.com/users/42".parse().unwrap();
+        client.start_request(Method::DELETE, uri).finish("404");
+
+        let data = request(events.pop().expect("request"));
+        assert_eq!(data.response_code, "404");
+        assert!(!data.success);
+        assert!(events.is_empty());
+    }
+
+    fn request(envelope: Envelope) -> RequestData {
+        match envelope.data {
+            Some(Base::Data(Data::RequestData(data))) => data,
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use http::{Method, Uri};
+
+use crate::{
+    telemetry::{AvailabilityTelemetry, RemoteDependencyTelemetry, RequestTelemetry, Timestamp},
+    time, TelemetryClient,
+};
+
+/// Measures duration of an operation and tracks it as a telemetry item once the operation completes.
+///
+/// Duration is measured with a monotonic clock, so it is never negative and does not jump when system time
+/// is adjusted. The wall clock is read only once to record the time when the operation started.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// use appinsights::ext::{Method, Uri};
+///
+/// let stopwatch = client.start_stopwatch();
+/// // handle request
+/// let uri: Uri = "https://api.github.com/dmolokanov/appinsights-rs".parse().unwrap();
+/// stopwatch.complete_request(Method::GET, uri, "200");
+/// ```
+pub struct Stopwatch<'a> {
+    client: &'a TelemetryClient,
+    started: Instant,
+    timestamp: Timestamp,
+}
+
+impl<'a> Stopwatch<'a> {
+    /// Starts a new stopwatch that tracks telemetry with the given client.
+    pub(crate) fn start(client: &'a TelemetryClient) -> Self {
+        Self {
+            client,
+            started: Instant::now(),
+            timestamp: time::timestamp(),
+        }
+    }
+
+    /// Returns the time when the stopwatch was started.
+    pub fn timestamp(&self) -> Timestamp {
+        self.timestamp
+    }
+
+    /// Returns the time elapsed since the stopwatch was started.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Logs a HTTP request with the specified method, URL and response code that took the elapsed time.
+    pub fn complete_request(self, method: Method, uri: Uri, response_code: impl Into<String>) {
+        let mut telemetry = RequestTelemetry::new(method, uri, self.elapsed(), response_code);
+        telemetry.set_timestamp(self.timestamp);
+        self.client.track(telemetry)
+    }
+
+    /// Logs a dependency with the specified name, type, target, and success status that took the elapsed time.
+    pub fn complete_dependency(
+        self,
+        name: impl Into<String>,
+        dependency_type: impl Into<String>,
+        target: impl Into<String>,
+        success: bool,
+    ) {
+        let mut telemetry = RemoteDependencyTelemetry::new(name, dependency_type, self.elapsed(), target, success);
+        telemetry.set_timestamp(self.timestamp);
+        self.client.track(telemetry)
+    }
+
+    /// Logs an availability test result with the specified test name and success status that took the
+    /// elapsed time.
+    pub fn complete_availability(self, name: impl Into<String>, success: bool) {
+        let mut telemetry = AvailabilityTelemetry::new(name, self.elapsed(), success);
+        telemetry.set_timestamp(self.timestamp);
+        self.client.track(telemetry)
+    }
+}
+
+impl fmt::Debug for Stopwatch<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Stopwatch")
+            .field("timestamp", &self.timestamp)
+            .field("elapsed", &self.elapsed())
+            .finish()
+    }
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::{TimeZone, Utc};
+    use crossbeam_queue::SegQueue;
+
+    use super::*;
+    use crate::{
+        client::tests::create_client,
+        contracts::{Base, Data},
+    };
+
+    #[tokio::test]
+    async fn it_tracks_request_with_start_time() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 800));
+        let stopwatch = client.start_stopwatch();
+
+        // wall clock moves backwards, but it must not affect the duration
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 100));
+        let uri: Uri = "https://example.com/main.html".parse().unwrap();
+        stopwatch.complete_request(Method::GET, uri, "200");
+        time::reset();
+
+        let envelope = events.pop().expect("request");
+        assert_eq!(envelope.time, "2019-01-02T03:04:05.800Z");
+        match envelope.data {
+            Some(Base::Data(Data::RequestData(data))) => {
+                assert_eq!(data.name, Some("GET https://example.com/main.html".into()));
+                assert_eq!(data.response_code, "200");
+                assert!(data.duration.starts_with("0.00:00:00"));
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_tracks_dependency_and_availability() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        client
+            .start_stopwatch()
+            .complete_dependency("SELECT", "SQL", "db.example.com", true);
+        client.start_stopwatch().complete_availability("ping", false);
+
+        assert!(matches!(
+            events.pop().and_then(|envelope| envelope.data),
+            Some(Base::Data(Data::RemoteDependencyData(data))) if data.target == Some("db.example.com".into())
+        ));
+        assert!(matches!(
+            events.pop().and_then(|envelope| envelope.data),
+            Some(Base::Data(Data::AvailabilityData(data))) if !data.success
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_measures_elapsed_time() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events);
+
+        let stopwatch = client.start_stopwatch();
+        std::thread::sleep(Duration::from_millis(5));
+
+        assert!(stopwatch.elapsed() >= Duration::from_millis(5));
+    }
+}
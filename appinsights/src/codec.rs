@@ -0,0 +1,78 @@
+use std::io::{self, Write};
+
+use flate2::{
+    write::{GzEncoder, ZlibEncoder},
+    Compression as Level,
+};
+
+use crate::Compression;
+
+/// Encodes request bodies with one of the content codings a server accepts.
+pub(crate) trait Codec: Send + Sync {
+    /// Returns a value of the `Content-Encoding` header that tells the server how the body is encoded.
+    fn content_encoding(&self) -> &'static str;
+
+    /// Encodes a serialized payload.
+    fn encode(&self, payload: &[u8]) -> io::Result<Vec<u8>>;
+}
+
+/// Returns a codec that implements the given compression, or `None` if requests are sent as is.
+pub(crate) fn codec(compression: Compression) -> Option<&'static dyn Codec> {
+    match compression {
+        Compression::None => None,
+        Compression::Gzip => Some(&Gzip),
+        Compression::Deflate => Some(&Deflate),
+        #[cfg(feature = "brotli")]
+        Compression::Brotli => Some(&Brotli),
+    }
+}
+
+/// A gzip coding of RFC 1952.
+struct Gzip;
+
+impl Codec for Gzip {
+    fn content_encoding(&self) -> &'static str {
+        "gzip"
+    }
+
+    fn encode(&self, payload: &[u8]) -> io::Result<Vec<u8>> {
+        let mut encoder = GzEncoder::new(Vec::with_capacity(payload.len() / 4), Level::default());
+        encoder.write_all(payload)?;
+        encoder.finish()
+    }
+}
+
+/// A deflate coding, i.e. the zlib format of RFC 1950 as HTTP defines it.
+struct Deflate;
+
+impl Codec for Deflate {
+    fn content_encoding(&self) -> &'static str {
+        "deflate"
+    }
+
+    fn encode(&self, payload: &[u8]) -> io::Result<Vec<u8>> {
+        // HTTP deflate is a zlib stream rather than a raw deflate one
+        let mut encoder = ZlibEncoder::new(Vec::with_capacity(payload.len() / 4), Level::default());
+        encoder.write_all(payload)?;
+        encoder.finish()
+    }
+}
+
+/// A Brotli coding of RFC 7932.
+#[cfg(feature = "brotli")]
+struct Brotli;
+
+#[cfg(feature = "brotli")]
+impl Codec for Brotli {
+    fn content_encoding(&self) -> &'static str {
+        "br"
+    }
+
+    fn encode(&self, payload: &[u8]) -> io::Result<Vec<u8>> {
+        // a moderate quality compresses telemetry almost as well as the maximum one at a fraction of the cost
+        let mut encoder = brotli::CompressorWriter::new(Vec::with_capacity(payload.len() / 4), 4096, 5, 22);
+        encoder.write_all(payload)?;
+        encoder.flush()?;
+        Ok(encoder.into_inner())
+    }
+}
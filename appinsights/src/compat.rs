@@ -0,0 +1,191 @@
+//! Names and signatures of the 0.1 API implemented over the current pipeline.
+//!
+//! The 0.1 client blocked the calling thread instead of relying on a Tokio runtime, used a `Config` type and
+//! reported through a `Result` whether a telemetry item has been accepted. This module keeps these names and
+//! signatures, so a large codebase can switch to the current version of the crate by replacing imports first
+//! and migrate to [`blocking::TelemetryClient`](../blocking/struct.TelemetryClient.html) or the async
+//! [`TelemetryClient`](../struct.TelemetryClient.html) call site by call site later. The compatibility client
+//! is a thin wrapper over the blocking one, so it follows the same rules: it must not be used within a
+//! runtime. Available with the `compat` feature only.
+//!
+//! ```rust, no_run
+//! use appinsights::compat::{Config, TelemetryClient};
+//!
+//! let config = Config::new("<instrumentation key>".to_string());
+//! let client = TelemetryClient::from_config(config);
+//!
+//! client.track_event("application started").expect("telemetry accepted");
+//! client.close_channel().expect("telemetry submitted");
+//! ```
+use std::time::Duration;
+
+use http::{Method, Uri};
+
+pub use crate::blocking::Error;
+use crate::{
+    blocking,
+    contracts::Envelope,
+    telemetry::{
+        AvailabilityTelemetry, EventTelemetry, MetricTelemetry, PageViewTelemetry, RemoteDependencyTelemetry,
+        Telemetry, ToSeverityLevel, TraceTelemetry,
+    },
+    TelemetryConfig, TelemetryContext, Tracker,
+};
+
+/// Configuration of a telemetry client as it was named in 0.1.
+pub type Config = TelemetryConfig;
+
+/// A telemetry client with the 0.1 API, where every tracking method reports whether a telemetry item has been
+/// accepted.
+pub struct TelemetryClient(blocking::TelemetryClient);
+
+impl TelemetryClient {
+    /// Creates a new telemetry client that submits telemetry with specified instrumentation key.
+    pub fn new(i_key: String) -> Self {
+        Self(blocking::TelemetryClient::new(i_key))
+    }
+
+    /// Creates a new telemetry client configured with specified configuration.
+    pub fn from_config(config: Config) -> Self {
+        Self(blocking::TelemetryClient::from_config(config))
+    }
+
+    /// Determines whether this client is enabled and will accept telemetry.
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_enabled()
+    }
+
+    /// Enables or disables telemetry client. When disabled, telemetry is silently swallowed by the client.
+    pub fn enabled(&mut self, enabled: bool) {
+        self.0.enabled(enabled)
+    }
+
+    /// Returns an immutable reference to a collection of tag data to attach to the telemetry item.
+    pub fn context(&self) -> &TelemetryContext {
+        self.0.context()
+    }
+
+    /// Returns a mutable reference to a collection of tag data to attach to the telemetry item.
+    pub fn context_mut(&mut self) -> &mut TelemetryContext {
+        self.0.context_mut()
+    }
+
+    /// Logs a user action with the specified name.
+    pub fn track_event(&self, name: impl Into<String>) -> Result<(), Error> {
+        self.track(EventTelemetry::new(name))
+    }
+
+    /// Logs a trace message with a specified severity level.
+    pub fn track_trace(&self, message: impl Into<String>, severity: impl ToSeverityLevel) -> Result<(), Error> {
+        self.track(TraceTelemetry::new(message, severity))
+    }
+
+    /// Logs a numeric value that is not specified with a specific event.
+    pub fn track_metric(&self, name: impl Into<String>, value: f64) -> Result<(), Error> {
+        self.track(MetricTelemetry::new(name, value))
+    }
+
+    /// Logs a HTTP request with the specified method, URL, duration and response code.
+    pub fn track_request(
+        &self,
+        method: Method,
+        uri: Uri,
+        duration: Duration,
+        response_code: impl Into<String>,
+    ) -> Result<(), Error> {
+        self.track(self.0.request(method, uri, duration, response_code))
+    }
+
+    /// Logs a dependency with the specified name, type, target, and success status.
+    pub fn track_remote_dependency(
+        &self,
+        name: impl Into<String>,
+        dependency_type: impl Into<String>,
+        target: impl Into<String>,
+        success: bool,
+    ) -> Result<(), Error> {
+        let event = RemoteDependencyTelemetry::new(name, dependency_type, Default::default(), target, success);
+        self.track(event)
+    }
+
+    /// Logs an availability test result with the specified test name, duration, and success status.
+    pub fn track_availability(&self, name: impl Into<String>, duration: Duration, success: bool) -> Result<(), Error> {
+        self.track(AvailabilityTelemetry::new(name, duration, success))
+    }
+
+    /// Logs a page view with the specified name and URL.
+    pub fn track_page_view(&self, name: impl Into<String>, uri: Uri) -> Result<(), Error> {
+        self.track(PageViewTelemetry::new(name, uri))
+    }
+
+    /// Submits a specific telemetry event and returns an error when the background thread is no longer running.
+    pub fn track<E>(&self, event: E) -> Result<(), Error>
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        self.0.try_track(event)
+    }
+
+    /// Forces all pending telemetry items to be submitted. The current thread will not be blocked.
+    pub fn flush_channel(&self) -> Result<(), Error> {
+        self.0.flush_channel()
+    }
+
+    /// Flushes and tears down the submission flow and blocks the current thread until all pending telemetry
+    /// items have been submitted.
+    pub fn close_channel(self) -> Result<(), Error> {
+        self.0.close_channel()
+    }
+
+    /// Tears down the submission flow. Any telemetry waiting to be sent is discarded.
+    pub fn terminate(self) -> Result<(), Error> {
+        self.0.terminate()
+    }
+
+    /// Returns the blocking client the compatibility client wraps, to migrate a call site to the current API.
+    pub fn into_inner(self) -> blocking::TelemetryClient {
+        self.0
+    }
+}
+
+impl From<blocking::TelemetryClient> for TelemetryClient {
+    fn from(client: blocking::TelemetryClient) -> Self {
+        Self(client)
+    }
+}
+
+impl Tracker for TelemetryClient {
+    fn track<E>(&self, event: E)
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        Tracker::track(&self.0, event)
+    }
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use std::sync::Arc;
+
+    use crossbeam_queue::SegQueue;
+
+    use super::*;
+    use crate::client::tests::TestChannel;
+
+    #[test]
+    fn it_tracks_telemetry_with_result() {
+        let events = Arc::new(SegQueue::default());
+        let config = Config::new("instrumentation".into());
+        let client = TelemetryClient::from(blocking::TelemetryClient::with_channel(config, {
+            let events = events.clone();
+            |_| TestChannel::new(events)
+        }));
+
+        assert_eq!(client.track_event("started"), Ok(()));
+        assert_eq!(client.track_metric("queue_length", 42.0), Ok(()));
+        assert_eq!(client.close_channel(), Ok(()));
+        assert_eq!(events.len(), 2);
+    }
+}
@@ -1,5 +1,38 @@
 //! Module for telemetry client configuration.
-use std::time::Duration;
+use std::{
+    collections::HashSet,
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use http::{header::HeaderName, HeaderMap, HeaderValue, Method, Uri};
+#[cfg(feature = "runtime")]
+use tokio::runtime::Handle;
+
+use crate::{
+    connection_string::{ConnectionString, DEFAULT_ENDPOINT},
+    diagnostics::EventListener,
+    sink::TelemetrySink,
+    telemetry::TelemetryKind,
+    timer::Timer,
+};
+
+#[cfg(all(any(test, feature = "test-util"), feature = "runtime"))]
+use crate::test_util::DrainMarker;
+
+/// Maximum size of a serialized telemetry item the ingestion endpoint accepts.
+const DEFAULT_MAX_ENVELOPE_SIZE: usize = 64 * 1024;
+
+/// Maximum number of bytes of serialized telemetry items sent in one request by default.
+const DEFAULT_MAX_REQUEST_SIZE: usize = 2 * 1024 * 1024;
+
+/// Maximum number of bytes telemetry items spooled to disk take by default.
+const DEFAULT_MAX_SPOOL_SIZE: u64 = 50 * 1024 * 1024;
+
+/// A function that derives a name of a request from its method and URI.
+pub(crate) type RequestNameNormalizer = dyn Fn(&Method, &Uri) -> String + Send + Sync;
 
 /// Configuration data used to initialize a new [`TelemetryClient`](../struct.TelemetryClient.html) with.
 ///
@@ -20,6 +53,27 @@ use std::time::Duration;
 ///     .interval(Duration::from_secs(5))
 ///     .build();
 /// ```
+///
+/// Naming the background thread of a [blocking](../blocking/index.html) client and adjusting it once started
+/// ```rust
+/// # use appinsights::TelemetryConfig;
+/// let config = TelemetryConfig::builder()
+///     .i_key("<instrumentation key>")
+///     .thread_name("appinsights-tenant-42")
+///     .on_thread_start(|| {
+///         // set thread priority or CPU affinity here with a platform specific crate
+///     })
+///     .build();
+/// ```
+///
+/// Running several [blocking](../blocking/index.html) clients on one runtime instead of a thread per client
+/// ```rust
+/// # use appinsights::{blocking, TelemetryConfig};
+/// let config = TelemetryConfig::builder()
+///     .i_key("<instrumentation key>")
+///     .runtime(blocking::shared_runtime())
+///     .build();
+/// ```
 #[derive(Clone, Debug, PartialEq)]
 pub struct TelemetryConfig {
     /// Instrumentation key for the client.
@@ -30,6 +84,107 @@ pub struct TelemetryConfig {
 
     /// Maximum time to wait until send a batch of telemetry.
     interval: Duration,
+
+    /// Name of the background thread that submits telemetry.
+    thread_name: Option<String>,
+
+    /// A callback invoked on the background thread right after it started.
+    on_thread_start: Option<Shared<dyn Fn() + Send + Sync>>,
+
+    /// A runtime to submit telemetry on.
+    #[cfg(feature = "runtime")]
+    runtime: Option<Shared<Handle>>,
+
+    /// Maximum number of telemetry items the channel holds, including items waiting for retry.
+    max_queue_capacity: Option<usize>,
+
+    /// Determines whether queued telemetry items share identical property maps.
+    intern_properties: bool,
+
+    /// Determines whether telemetry items record how many times their submission was retried.
+    record_retry_count: bool,
+
+    /// Custom HTTP headers to add to every request that submits telemetry.
+    headers: HeaderMap,
+
+    /// An application-specific suffix of the `User-Agent` header of requests that submit telemetry.
+    user_agent_suffix: Option<String>,
+
+    /// A marker to append to the SDK version reported in the `ai.internal.sdkVersion` tag.
+    sdk_version_suffix: Option<String>,
+
+    /// A compression of requests that submit telemetry.
+    compression: Compression,
+
+    /// A destination to write telemetry batches to instead of the ingestion endpoint.
+    sink: Option<Shared<dyn TelemetrySink>>,
+
+    /// Maximum time a telemetry item waits in the channel before a batch is sent regardless of the interval.
+    max_item_age: Option<Duration>,
+
+    /// Types of telemetry items to send right away instead of waiting for a batch.
+    send_immediately: HashSet<TelemetryKind>,
+
+    /// A destination to write telemetry items discarded on termination to.
+    terminate_sink: Option<Shared<dyn TelemetrySink>>,
+
+    /// A directory to spool telemetry items to until they have been submitted.
+    persistence_dir: Option<PathBuf>,
+
+    /// Maximum number of bytes telemetry items spooled to disk take.
+    max_spool_size: u64,
+
+    /// A percentage of operations to keep telemetry items of.
+    sampling_percentage: f64,
+
+    /// A function that derives names of requests tracked from a method and a URI.
+    request_name_normalizer: Option<Shared<RequestNameNormalizer>>,
+
+    /// Number of queued telemetry items that triggers submission before the interval expires.
+    max_batch_size: Option<usize>,
+
+    /// Maximum number of bytes a serialized telemetry item can take.
+    max_envelope_size: usize,
+
+    /// Determines what happens to a telemetry item tracked when the channel is at its maximum capacity.
+    overflow_policy: OverflowPolicy,
+
+    /// Maximum number of bytes of serialized telemetry items sent in one request.
+    max_request_size: usize,
+
+    /// Maximum time an attempt to send a batch takes before it is cancelled, if configured.
+    send_deadline: Option<Duration>,
+
+    /// Configurations of additional resources every telemetry item is forwarded to.
+    mirrors: Vec<TelemetryConfig>,
+
+    /// A timer to wait for intervals and retry timeouts with.
+    timer: Option<Shared<dyn Timer>>,
+
+    /// A listener to notify about events of the channel.
+    event_listener: Option<Shared<dyn EventListener>>,
+
+    /// Determines whether sampling is reduced while the ingestion endpoint is overloaded.
+    sampling_feedback: bool,
+
+    /// An HTTP proxy to send requests that submit telemetry through.
+    proxy: Option<Proxy>,
+
+    /// Processing lag of the channel worker that triggers shedding of telemetry items, if configured.
+    overload_threshold: Option<Duration>,
+
+    /// Determines whether cloud role and role instance are detected from environment variables.
+    detect_environment: bool,
+
+    /// A version of the application reported in the `ai.application.ver` tag.
+    application_version: Option<String>,
+
+    /// Determines whether a client submits an event with SDK and application metadata once created.
+    startup_event: bool,
+
+    /// A marker to notify each time the submission routine drained the queue.
+    #[cfg(all(any(test, feature = "test-util"), feature = "runtime"))]
+    drain_marker: Option<DrainMarker>,
 }
 
 impl TelemetryConfig {
@@ -40,7 +195,22 @@ impl TelemetryConfig {
 
     /// Creates a new telemetry configuration builder with default parameters.
     pub fn builder() -> DefaultTelemetryConfigBuilder {
-        DefaultTelemetryConfigBuilder::default()
+        DefaultTelemetryConfigBuilder
+    }
+
+    /// Replaces an instrumentation key and an endpoint with ones from the connection string.
+    pub(crate) fn set_connection_string(&mut self, connection_string: &ConnectionString) {
+        self.i_key = connection_string.i_key().into();
+        self.endpoint = connection_string.endpoint().into();
+    }
+
+    /// Takes settings a running client can switch to from another configuration: an instrumentation key, an
+    /// endpoint, an interval and a sampling percentage.
+    pub(crate) fn update(&mut self, config: &TelemetryConfig) {
+        self.i_key = config.i_key.clone();
+        self.endpoint = config.endpoint.clone();
+        self.interval = config.interval;
+        self.sampling_percentage = config.sampling_percentage;
     }
 
     /// Returns an instrumentation key for the client.
@@ -57,6 +227,185 @@ impl TelemetryConfig {
     pub fn interval(&self) -> Duration {
         self.interval
     }
+
+    /// Returns a name of the background thread that submits telemetry, if configured.
+    pub fn thread_name(&self) -> Option<&str> {
+        self.thread_name.as_deref()
+    }
+
+    /// Returns a callback to invoke on the background thread right after it started.
+    #[cfg_attr(not(feature = "blocking"), allow(dead_code))]
+    #[cfg(not(feature = "disabled"))]
+    pub(crate) fn on_thread_start(&self) -> Option<&Shared<dyn Fn() + Send + Sync>> {
+        self.on_thread_start.as_ref()
+    }
+
+    /// Returns a handle of the runtime to submit telemetry on, if configured.
+    #[cfg(feature = "runtime")]
+    pub fn runtime(&self) -> Option<&Handle> {
+        self.runtime.as_deref()
+    }
+
+    /// Returns maximum number of telemetry items the channel holds, if configured.
+    pub fn max_queue_capacity(&self) -> Option<usize> {
+        self.max_queue_capacity
+    }
+
+    /// Returns whether queued telemetry items share identical property maps.
+    pub fn intern_properties(&self) -> bool {
+        self.intern_properties
+    }
+
+    /// Returns whether telemetry items record how many times their submission was retried.
+    pub fn record_retry_count(&self) -> bool {
+        self.record_retry_count
+    }
+
+    /// Returns custom HTTP headers to add to every request that submits telemetry.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Returns an application-specific suffix of the `User-Agent` header of requests that submit telemetry.
+    pub fn user_agent_suffix(&self) -> Option<&str> {
+        self.user_agent_suffix.as_deref()
+    }
+
+    /// Returns a marker to append to the SDK version reported in the `ai.internal.sdkVersion` tag.
+    pub fn sdk_version_suffix(&self) -> Option<&str> {
+        self.sdk_version_suffix.as_deref()
+    }
+
+    /// Returns a compression of requests that submit telemetry.
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Returns a destination to write telemetry batches to instead of the ingestion endpoint, if configured.
+    #[cfg(feature = "runtime")]
+    pub(crate) fn sink(&self) -> Option<&Shared<dyn TelemetrySink>> {
+        self.sink.as_ref()
+    }
+
+    /// Returns maximum time a telemetry item waits in the channel before a batch is sent, if configured.
+    pub fn max_item_age(&self) -> Option<Duration> {
+        self.max_item_age
+    }
+
+    /// Returns types of telemetry items to send right away instead of waiting for a batch.
+    pub fn send_immediately(&self) -> &HashSet<TelemetryKind> {
+        &self.send_immediately
+    }
+
+    /// Returns a destination to write telemetry items discarded on termination to, if configured.
+    #[cfg(feature = "runtime")]
+    pub(crate) fn terminate_sink(&self) -> Option<&Shared<dyn TelemetrySink>> {
+        self.terminate_sink.as_ref()
+    }
+
+    /// Returns a directory telemetry items are spooled to until they have been submitted, if configured.
+    pub fn persistence_dir(&self) -> Option<&Path> {
+        self.persistence_dir.as_deref()
+    }
+
+    /// Returns maximum number of bytes telemetry items spooled to disk take.
+    pub fn max_spool_size(&self) -> u64 {
+        self.max_spool_size
+    }
+
+    /// Returns a percentage of operations telemetry items are kept of.
+    pub fn sampling_percentage(&self) -> f64 {
+        self.sampling_percentage
+    }
+
+    /// Returns a function that derives names of requests tracked from a method and a URI, if configured.
+    pub(crate) fn request_name_normalizer(&self) -> Option<&Shared<RequestNameNormalizer>> {
+        self.request_name_normalizer.as_ref()
+    }
+
+    /// Returns a number of queued telemetry items that triggers submission before the interval expires, if configured.
+    pub fn max_batch_size(&self) -> Option<usize> {
+        self.max_batch_size
+    }
+
+    /// Returns maximum number of bytes a serialized telemetry item can take.
+    pub fn max_envelope_size(&self) -> usize {
+        self.max_envelope_size
+    }
+
+    /// Returns what happens to a telemetry item tracked when the channel is at its maximum capacity.
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow_policy
+    }
+
+    /// Returns maximum number of bytes of serialized telemetry items sent in one request.
+    pub fn max_request_size(&self) -> usize {
+        self.max_request_size
+    }
+
+    /// Returns maximum time an attempt to send a batch takes before it is cancelled, if configured.
+    pub fn send_deadline(&self) -> Option<Duration> {
+        self.send_deadline
+    }
+
+    /// Returns configurations of additional resources every telemetry item is forwarded to.
+    pub fn mirrors(&self) -> &[TelemetryConfig] {
+        &self.mirrors
+    }
+
+    /// Returns a timer to wait for intervals and retry timeouts with. Defaults to [`TokioTimer`].
+    #[cfg(feature = "runtime")]
+    pub(crate) fn timer(&self) -> Shared<dyn Timer> {
+        self.timer
+            .clone()
+            .unwrap_or_else(|| Shared(Arc::new(crate::timer::TokioTimer)))
+    }
+
+    /// Returns a listener to notify about events of the channel, if configured.
+    pub(crate) fn event_listener(&self) -> Option<&Shared<dyn EventListener>> {
+        self.event_listener.as_ref()
+    }
+
+    /// Returns `true` if sampling is reduced temporarily while the ingestion endpoint is overloaded.
+    pub fn sampling_feedback(&self) -> bool {
+        self.sampling_feedback
+    }
+
+    /// Replaces a listener to notify about events of the channel.
+    pub(crate) fn set_event_listener(&mut self, listener: Shared<dyn EventListener>) {
+        self.event_listener = Some(listener);
+    }
+
+    /// Returns an HTTP proxy to send requests that submit telemetry through, if any.
+    pub fn proxy(&self) -> Option<&Proxy> {
+        self.proxy.as_ref()
+    }
+
+    /// Returns processing lag of the channel worker that triggers shedding of telemetry items, if configured.
+    pub fn overload_threshold(&self) -> Option<Duration> {
+        self.overload_threshold
+    }
+
+    /// Returns whether cloud role and role instance are detected from environment variables.
+    pub fn detect_environment(&self) -> bool {
+        self.detect_environment
+    }
+
+    /// Returns a version of the application reported with telemetry, if any.
+    pub fn application_version(&self) -> Option<&str> {
+        self.application_version.as_deref()
+    }
+
+    /// Returns whether a client submits an event with SDK and application metadata once created.
+    pub fn startup_event(&self) -> bool {
+        self.startup_event
+    }
+
+    /// Returns a marker to notify each time the submission routine drained the queue, if configured.
+    #[cfg(all(any(test, feature = "test-util"), feature = "runtime"))]
+    pub(crate) fn drain_marker(&self) -> Option<&DrainMarker> {
+        self.drain_marker.as_ref()
+    }
 }
 
 /// Constructs a new instance of a [`TelemetryConfig`](struct.TelemetryConfig.html) with required
@@ -72,8 +421,43 @@ impl DefaultTelemetryConfigBuilder {
     {
         TelemetryConfigBuilder {
             i_key: i_key.into(),
-            endpoint: "https://dc.services.visualstudio.com/v2/track".into(),
+            endpoint: DEFAULT_ENDPOINT.into(),
             interval: Duration::from_secs(2),
+            thread_name: None,
+            on_thread_start: None,
+            #[cfg(feature = "runtime")]
+            runtime: None,
+            max_queue_capacity: None,
+            intern_properties: false,
+            record_retry_count: false,
+            headers: HeaderMap::new(),
+            user_agent_suffix: None,
+            sdk_version_suffix: None,
+            compression: Compression::None,
+            sink: None,
+            max_item_age: None,
+            send_immediately: HashSet::new(),
+            terminate_sink: None,
+            persistence_dir: None,
+            max_spool_size: DEFAULT_MAX_SPOOL_SIZE,
+            sampling_percentage: 100.0,
+            request_name_normalizer: None,
+            max_batch_size: None,
+            max_envelope_size: DEFAULT_MAX_ENVELOPE_SIZE,
+            overflow_policy: OverflowPolicy::DropNewest,
+            max_request_size: DEFAULT_MAX_REQUEST_SIZE,
+            send_deadline: None,
+            mirrors: Vec::new(),
+            timer: None,
+            event_listener: None,
+            sampling_feedback: false,
+            proxy: None,
+            overload_threshold: None,
+            detect_environment: true,
+            application_version: None,
+            startup_event: false,
+            #[cfg(all(any(test, feature = "test-util"), feature = "runtime"))]
+            drain_marker: None,
         }
     }
 }
@@ -83,6 +467,41 @@ pub struct TelemetryConfigBuilder {
     i_key: String,
     endpoint: String,
     interval: Duration,
+    thread_name: Option<String>,
+    on_thread_start: Option<Shared<dyn Fn() + Send + Sync>>,
+    #[cfg(feature = "runtime")]
+    runtime: Option<Shared<Handle>>,
+    max_queue_capacity: Option<usize>,
+    intern_properties: bool,
+    record_retry_count: bool,
+    headers: HeaderMap,
+    user_agent_suffix: Option<String>,
+    sdk_version_suffix: Option<String>,
+    compression: Compression,
+    sink: Option<Shared<dyn TelemetrySink>>,
+    max_item_age: Option<Duration>,
+    send_immediately: HashSet<TelemetryKind>,
+    terminate_sink: Option<Shared<dyn TelemetrySink>>,
+    persistence_dir: Option<PathBuf>,
+    max_spool_size: u64,
+    sampling_percentage: f64,
+    request_name_normalizer: Option<Shared<RequestNameNormalizer>>,
+    max_batch_size: Option<usize>,
+    max_envelope_size: usize,
+    overflow_policy: OverflowPolicy,
+    max_request_size: usize,
+    send_deadline: Option<Duration>,
+    mirrors: Vec<TelemetryConfig>,
+    timer: Option<Shared<dyn Timer>>,
+    event_listener: Option<Shared<dyn EventListener>>,
+    sampling_feedback: bool,
+    proxy: Option<Proxy>,
+    overload_threshold: Option<Duration>,
+    detect_environment: bool,
+    application_version: Option<String>,
+    startup_event: bool,
+    #[cfg(all(any(test, feature = "test-util"), feature = "runtime"))]
+    drain_marker: Option<DrainMarker>,
 }
 
 impl TelemetryConfigBuilder {
@@ -110,18 +529,550 @@ impl TelemetryConfigBuilder {
         self
     }
 
+    /// Initializes a builder with a name of the background thread that submits telemetry. It helps to
+    /// distinguish threads of several clients in profilers and debuggers.
+    /// Applies to the [`blocking`](../blocking/index.html) client only.
+    /// Defaults to `appinsights-internal-sync-runtime`.
+    pub fn thread_name<N>(mut self, thread_name: N) -> Self
+    where
+        N: Into<String>,
+    {
+        self.thread_name = Some(thread_name.into());
+        self
+    }
+
+    /// Initializes a builder with a callback invoked on the background thread right after it started.
+    /// Since thread priority and CPU affinity are platform specific, use this callback to apply them.
+    /// Applies to the [`blocking`](../blocking/index.html) client only.
+    pub fn on_thread_start<F>(mut self, on_thread_start: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.on_thread_start = Some(Shared(Arc::new(on_thread_start)));
+        self
+    }
+
+    /// Initializes a builder with a handle of the runtime to submit telemetry on.
+    /// A [`TelemetryClient`](../struct.TelemetryClient.html) spawns its submission routine on this runtime
+    /// instead of the current one, so it can be created outside of a runtime context.
+    /// A [blocking](../blocking/index.html) client spawns its background task on this runtime instead of
+    /// starting a dedicated thread, so thread settings do not apply. Use
+    /// [`blocking::shared_runtime`](../blocking/fn.shared_runtime.html) to share one runtime between clients.
+    #[cfg(feature = "runtime")]
+    pub fn runtime(mut self, runtime: Handle) -> Self {
+        self.runtime = Some(Shared(Arc::new(runtime)));
+        self
+    }
+
+    /// Initializes a builder with a maximum number of telemetry items the channel holds. Items waiting
+    /// for retry count against this limit too. When the limit is reached, newly tracked items are handled
+    /// according to the [`overflow_policy`](#method.overflow_policy), and when items returned for retry do not fit, items with the lowest priority are dropped first:
+    /// traces, then metrics, events and page views, then dependencies, then requests, and exceptions and
+    /// availability results last. Defaults to unbounded.
+    pub fn max_queue_capacity(mut self, max_queue_capacity: usize) -> Self {
+        self.max_queue_capacity = Some(max_queue_capacity);
+        self
+    }
+
+    /// Initializes a builder with a flag that makes queued telemetry items with identical properties share
+    /// a single property map until they are sent. It cuts memory for large backlogs of items that carry the
+    /// same properties at the cost of hashing properties of every tracked item. Defaults to `false`.
+    pub fn intern_properties(mut self, intern_properties: bool) -> Self {
+        self.intern_properties = intern_properties;
+        self
+    }
+
+    /// Initializes a builder with a flag that makes telemetry items record how many times their submission was
+    /// retried in the `ai.internal.retryCount` custom property. Items delivered on the first attempt do not
+    /// get the property. It helps to analyze ingestion latency and reliability in the portal. Defaults to `false`.
+    pub fn record_retry_count(mut self, record_retry_count: bool) -> Self {
+        self.record_retry_count = record_retry_count;
+        self
+    }
+
+    /// Initializes a builder with a custom HTTP header to add to every request that submits telemetry, such as
+    /// `x-ms-client-request-id` or a routing header of a forwarding proxy. A header with the same name set
+    /// before is replaced.
+    ///
+    /// ```rust
+    /// use appinsights::TelemetryConfig;
+    /// use appinsights::ext::{HeaderName, HeaderValue};
+    ///
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .header(HeaderName::from_static("x-forwarder-route"), HeaderValue::from_static("eu"))
+    ///     .build();
+    ///
+    /// assert_eq!(config.headers()["x-forwarder-route"], "eu");
+    /// ```
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Initializes a builder with an application-specific suffix to append to the `User-Agent` header of requests
+    /// that submit telemetry. The header contains the SDK version, such as `rust:0.2.3`, followed by the suffix
+    /// separated with a space. Gateways may use it for attribution and debugging.
+    pub fn user_agent_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.user_agent_suffix = Some(suffix.into());
+        self
+    }
+
+    /// Initializes a builder with a marker to append to the SDK version reported in the `ai.internal.sdkVersion`
+    /// tag of every telemetry item. Libraries that wrap this SDK can use it to identify themselves.
+    ///
+    /// ```rust
+    /// use appinsights::{TelemetryConfig, TelemetryContext};
+    ///
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .sdk_version_suffix("-via-mylib:1.4")
+    ///     .build();
+    ///
+    /// let context = TelemetryContext::from(&config);
+    /// assert!(context.tags().internal().sdk_version().unwrap().ends_with("-via-mylib:1.4"));
+    /// ```
+    pub fn sdk_version_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.sdk_version_suffix = Some(suffix.into());
+        self
+    }
+
+    /// Initializes a builder with a compression of requests that submit telemetry. Defaults to
+    /// [`Compression::None`](enum.Compression.html#variant.None).
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Initializes a builder with a destination to write telemetry batches to instead of the ingestion
+    /// endpoint. See [`sink`](../sink/index.html) for details.
+    pub fn sink(mut self, sink: impl TelemetrySink) -> Self {
+        self.sink = Some(Shared(Arc::new(sink)));
+        self
+    }
+
+    /// Initializes a builder with maximum time a telemetry item waits in the channel. Once the oldest queued
+    /// item gets older, a batch is sent without waiting for the interval to expire. It bounds the latency of
+    /// telemetry when the interval is long but an item arrived right after a batch has been sent.
+    pub fn max_item_age(mut self, max_item_age: Duration) -> Self {
+        self.max_item_age = Some(max_item_age);
+        self
+    }
+
+    /// Initializes a builder with a type of telemetry items to send right away instead of waiting for the
+    /// interval to expire, e.g. availability results that alerts depend on. Such items are sent in a small
+    /// dedicated batch as soon as the channel is not busy sending another batch. Call it several times to
+    /// send items of several types immediately. Defaults to none.
+    pub fn send_immediately(mut self, kind: TelemetryKind) -> Self {
+        self.send_immediately.insert(kind);
+        self
+    }
+
+    /// Initializes a builder with a destination to write telemetry items to when
+    /// [`terminate`](../struct.TelemetryClient.html#method.terminate) discards them, so even an abrupt shutdown
+    /// preserves data for post-mortem analysis. Pending items are written as a single batch before
+    /// `terminate` returns. Use [`FileSink`](../sink/struct.FileSink.html) to append them to a file.
+    pub fn terminate_sink(mut self, sink: impl TelemetrySink) -> Self {
+        self.terminate_sink = Some(Shared(Arc::new(sink)));
+        self
+    }
+
+    /// Initializes a builder with a directory to spool telemetry items to until they have been submitted, so
+    /// telemetry survives network outages and process crashes. Items are appended to newline-delimited JSON
+    /// files in the directory, files left by a previous run are replayed on startup and each file is removed
+    /// once its items have been submitted. The directory is created if it does not exist and must not be shared
+    /// between processes. Defaults to none, which keeps telemetry in memory only.
+    pub fn persistence_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.persistence_dir = Some(dir.as_ref().into());
+        self
+    }
+
+    /// Initializes a builder with maximum number of bytes telemetry items spooled to the
+    /// [`persistence_dir`](#method.persistence_dir) take. Once spooled items exceed it, the oldest files are
+    /// removed to make room for new items, and items that do not fit even then are dropped. Default is 50 MiB.
+    pub fn max_spool_size(mut self, max_spool_size: u64) -> Self {
+        self.max_spool_size = max_spool_size;
+        self
+    }
+
+    /// Initializes a builder with a percentage of operations to keep telemetry items of, between 0 and 100.
+    /// Items of other operations are dropped before they are queued. See [`sampling`](../sampling/index.html)
+    /// for details. Defaults to 100, which keeps all telemetry items.
+    pub fn sampling_percentage(mut self, percentage: f64) -> Self {
+        self.sampling_percentage = percentage.clamp(0.0, 100.0);
+        self
+    }
+
+    /// Initializes a builder with a function that derives names of requests tracked with
+    /// [`track_request`](../struct.TelemetryClient.html#method.track_request) from their method and URI. By
+    /// default a name consists of the method and the whole URL, so each user or order id ends up in a separate
+    /// group in the portal. Use [`normalize_request_name`](../telemetry/fn.normalize_request_name.html) to
+    /// replace ids in the path with `{id}`, e.g. `GET /users/{id}`, or provide a function that knows routes of
+    /// the application.
+    pub fn request_name_normalizer<F>(mut self, normalizer: F) -> Self
+    where
+        F: Fn(&Method, &Uri) -> String + Send + Sync + 'static,
+    {
+        self.request_name_normalizer = Some(Shared(Arc::new(normalizer)));
+        self
+    }
+
+    /// Initializes a builder with a number of queued telemetry items that triggers submission right away
+    /// instead of waiting for the interval to expire, which lowers latency of bursty workloads. It also bounds
+    /// a number of items sent in one request: a larger backlog, e.g. after an outage, is sent in several requests.
+    /// The size is at least 1.
+    pub fn max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = Some(max_batch_size.max(1));
+        self
+    }
+
+    /// Initializes a builder with maximum number of bytes a serialized telemetry item can take. The ingestion
+    /// endpoint rejects a whole batch that contains an oversized item, so such items are dropped before they are
+    /// sent and reported to the [`event_listener`](#method.event_listener). Default is 64 KiB, the limit of the
+    /// ingestion endpoint.
+    pub fn max_envelope_size(mut self, max_envelope_size: usize) -> Self {
+        self.max_envelope_size = max_envelope_size;
+        self
+    }
+
+    /// Initializes a builder with a policy that determines what happens to a telemetry item tracked when the
+    /// channel is at its [maximum capacity](#method.max_queue_capacity). Defaults to
+    /// [`OverflowPolicy::DropNewest`](enum.OverflowPolicy.html#variant.DropNewest).
+    pub fn overflow_policy(mut self, overflow_policy: OverflowPolicy) -> Self {
+        self.overflow_policy = overflow_policy;
+        self
+    }
+
+    /// Initializes a builder with a maximum number of bytes of serialized telemetry items sent in one request.
+    /// Items are split into several requests as they are serialized, so requests stay under the limit of the
+    /// ingestion endpoint regardless of the size of items. A request always contains at least one item. The size
+    /// is measured before compression. Default is 2 MiB.
+    pub fn max_request_size(mut self, max_request_size: usize) -> Self {
+        self.max_request_size = max_request_size;
+        self
+    }
+
+    /// Initializes a builder with maximum time an attempt to send a batch takes. An attempt that hangs longer,
+    /// e.g. on a stalled DNS lookup the HTTP client timeouts do not cover, is cancelled and reported as a failed
+    /// batch, and its items are retried, so the channel keeps moving. The deadline covers all requests a batch is
+    /// split into; items of requests completed before the deadline are not sent again.
+    pub fn send_deadline(mut self, send_deadline: Duration) -> Self {
+        self.send_deadline = Some(send_deadline);
+        self
+    }
+
+    /// Initializes a builder with a configuration of an additional resource to forward every telemetry item to,
+    /// e.g. to fan out telemetry to an old and a new Application Insights resource during a migration. Items are
+    /// submitted with the instrumentation key of the mirror by a channel of its own, which follows the mirror
+    /// configuration, so a mirror can be sent to a different endpoint or spooled to a different directory.
+    /// Mirrors of the mirror configuration are ignored. Call it several times to forward telemetry to several
+    /// resources. Defaults to none.
+    pub fn mirror(mut self, config: TelemetryConfig) -> Self {
+        self.mirrors.push(config);
+        self
+    }
+
+    /// Initializes a builder with a timer to wait for submission intervals, retry timeouts and intervals of
+    /// periodic tasks with, e.g. a fake timer that lets tests decide when an interval expires. See
+    /// [`timer`](../timer/index.html) for details. Defaults to [`TokioTimer`](../timer/struct.TokioTimer.html).
+    pub fn timer(mut self, timer: impl Timer) -> Self {
+        self.timer = Some(Shared(Arc::new(timer)));
+        self
+    }
+
+    /// Initializes a builder with a listener to notify about batches sent and failed, telemetry items dropped and
+    /// retries scheduled by the channel. See [`diagnostics`](../diagnostics/index.html) for details.
+    pub fn event_listener(mut self, listener: impl EventListener) -> Self {
+        self.event_listener = Some(Shared(Arc::new(listener)));
+        self
+    }
+
+    /// Initializes a builder with a flag that enables reducing the sampling percentage temporarily while the
+    /// ingestion endpoint throttles or rejects most telemetry items, which protects both the endpoint and the
+    /// application. See [`sampling`](../sampling/index.html) for details. Defaults to `false`.
+    pub fn sampling_feedback(mut self, sampling_feedback: bool) -> Self {
+        self.sampling_feedback = sampling_feedback;
+        self
+    }
+
+    /// Initializes a builder with an HTTP proxy to send requests that submit telemetry through, such as
+    /// `http://proxy.example.com:8080`. By default requests are sent directly, or through a proxy set with
+    /// the `HTTP_PROXY` and `HTTPS_PROXY` environment variables. See [`Proxy`](struct.Proxy.html) for
+    /// authentication and hosts that bypass the proxy.
+    pub fn proxy(mut self, proxy: impl Into<Proxy>) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    /// Initializes a builder with processing lag of the channel worker that triggers shedding of telemetry items.
+    /// While the worker falls behind by more than the threshold, the client drops verbose traces and samples
+    /// telemetry items at a tenth of the sampling percentage, which keeps CPU usage of the SDK bounded during
+    /// traffic spikes. See [`overload`](../overload/index.html) for details. Disabled by default. Only the async
+    /// client sheds telemetry items; the blocking client ignores the threshold.
+    pub fn overload_threshold(mut self, overload_threshold: Duration) -> Self {
+        self.overload_threshold = Some(overload_threshold);
+        self
+    }
+
+    /// Initializes a builder with a flag that detects cloud role, role instance and details of the hosting
+    /// environment, such as Azure App Service or Kubernetes, from environment variables when the telemetry
+    /// context is created. Applications that set these tags themselves can opt out. Defaults to `true`.
+    pub fn detect_environment(mut self, detect_environment: bool) -> Self {
+        self.detect_environment = detect_environment;
+        self
+    }
+
+    /// Initializes a builder with a version of the application reported in the `ai.application.ver` tag of
+    /// every telemetry item, so telemetry can be filtered by a release.
+    pub fn application_version(mut self, version: impl Into<String>) -> Self {
+        self.application_version = Some(version.into());
+        self
+    }
+
+    /// Initializes a builder with a flag that makes every client submit an `ApplicationStarted` event with SDK
+    /// and application metadata and a redacted summary of the configuration once it has been created. See
+    /// [`startup`](startup/index.html) for details. Defaults to `false`.
+    pub fn startup_event(mut self, startup_event: bool) -> Self {
+        self.startup_event = startup_event;
+        self
+    }
+
+    /// Initializes a builder with a marker to notify each time the submission routine drained the queue.
+    /// Tests can wait for the marker instead of sleeping. Available with the `test-util` feature only.
+    #[cfg(all(any(test, feature = "test-util"), feature = "runtime"))]
+    pub fn drain_marker(mut self, drain_marker: DrainMarker) -> Self {
+        self.drain_marker = Some(drain_marker);
+        self
+    }
+
     /// Constructs a new instance of a [`TelemetryConfig`](struct.TelemetryConfig.html) with custom settings.
     pub fn build(self) -> TelemetryConfig {
         TelemetryConfig {
             i_key: self.i_key,
             endpoint: self.endpoint,
             interval: self.interval,
+            thread_name: self.thread_name,
+            on_thread_start: self.on_thread_start,
+            #[cfg(feature = "runtime")]
+            runtime: self.runtime,
+            max_queue_capacity: self.max_queue_capacity,
+            intern_properties: self.intern_properties,
+            record_retry_count: self.record_retry_count,
+            headers: self.headers,
+            user_agent_suffix: self.user_agent_suffix,
+            sdk_version_suffix: self.sdk_version_suffix,
+            compression: self.compression,
+            sink: self.sink,
+            max_item_age: self.max_item_age,
+            send_immediately: self.send_immediately,
+            terminate_sink: self.terminate_sink,
+            persistence_dir: self.persistence_dir,
+            max_spool_size: self.max_spool_size,
+            sampling_percentage: self.sampling_percentage,
+            request_name_normalizer: self.request_name_normalizer,
+            max_batch_size: self.max_batch_size,
+            max_envelope_size: self.max_envelope_size,
+            overflow_policy: self.overflow_policy,
+            max_request_size: self.max_request_size,
+            send_deadline: self.send_deadline,
+            mirrors: self.mirrors,
+            timer: self.timer,
+            event_listener: self.event_listener,
+            sampling_feedback: self.sampling_feedback,
+            proxy: self.proxy,
+            overload_threshold: self.overload_threshold,
+            detect_environment: self.detect_environment,
+            application_version: self.application_version,
+            startup_event: self.startup_event,
+            #[cfg(all(any(test, feature = "test-util"), feature = "runtime"))]
+            drain_marker: self.drain_marker,
         }
     }
 }
 
-#[cfg(test)]
+/// A compression of requests that submit telemetry.
+///
+/// Every telemetry item carries its own copy of common properties and context tags, because the ingestion
+/// protocol has no way to share them between items of a batch. Large and repetitive payloads compress well,
+/// so compression cuts the size of requests considerably when common properties are large. The ingestion
+/// endpoint accepts gzip, while proxies and forwarders in between may accept other encodings only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+    /// Requests are sent as is.
+    None,
+
+    /// Requests are compressed with gzip and sent with `Content-Encoding: gzip` header.
+    Gzip,
+
+    /// Requests are compressed with zlib and sent with `Content-Encoding: deflate` header.
+    Deflate,
+
+    /// Requests are compressed with Brotli and sent with `Content-Encoding: br` header. Available with the
+    /// `brotli` feature only.
+    #[cfg(feature = "brotli")]
+    Brotli,
+}
+
+/// Determines what happens to a telemetry item tracked when the channel is at its
+/// [maximum capacity](struct.TelemetryConfigBuilder.html#method.max_queue_capacity).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OverflowPolicy {
+    /// The newly tracked item is dropped.
+    DropNewest,
+
+    /// The oldest item waiting in the queue is dropped to make room for the newly tracked one. Items the
+    /// channel retains for retry are not dropped this way.
+    DropOldest,
+
+    /// The thread that tracks the item is blocked until the channel submits a batch and there is room for the
+    /// item, but no longer than the given timeout. The item is dropped if there is still no room after that.
+    /// Blocking stalls the runtime when telemetry is tracked from async code, so keep the timeout short.
+    Block(Duration),
+}
+
+/// An HTTP proxy to send requests that submit telemetry through.
+///
+/// ```rust
+/// use appinsights::{Proxy, TelemetryConfig};
+///
+/// let proxy = Proxy::new("http://proxy.corp.example.com:3128")
+///     .basic_auth("user", "password")
+///     .no_proxy("localhost")
+///     .no_proxy(".internal.example.com");
+///
+/// let config = TelemetryConfig::builder()
+///     .i_key("<instrumentation key>")
+///     .proxy(proxy)
+///     .build();
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct Proxy {
+    url: String,
+    credentials: Option<(String, String)>,
+    no_proxy: Vec<String>,
+}
+
+impl Proxy {
+    /// Creates a proxy with the given URL, such as `http://proxy.example.com:8080`.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            credentials: None,
+            no_proxy: Vec::new(),
+        }
+    }
+
+    /// Authenticates requests to the proxy with the given user name and password using basic authentication.
+    pub fn basic_auth(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.credentials = Some((username.into(), password.into()));
+        self
+    }
+
+    /// Sends requests to the given host directly, bypassing the proxy. A host that starts with a dot, such as
+    /// `.example.com`, matches all subdomains of the domain, and any other host matches itself and its
+    /// subdomains.
+    pub fn no_proxy(mut self, host: impl Into<String>) -> Self {
+        self.no_proxy.push(host.into());
+        self
+    }
+
+    /// Returns a URL of the proxy.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns a user name to authenticate to the proxy with, if any.
+    pub fn username(&self) -> Option<&str> {
+        self.credentials.as_ref().map(|(username, _)| username.as_str())
+    }
+
+    /// Returns hosts requests to which bypass the proxy.
+    pub fn no_proxy_hosts(&self) -> &[String] {
+        &self.no_proxy
+    }
+
+    /// Returns a password to authenticate to the proxy with, if any.
+    #[cfg(feature = "runtime")]
+    pub(crate) fn password(&self) -> Option<&str> {
+        self.credentials.as_ref().map(|(_, password)| password.as_str())
+    }
+
+    /// Determines whether requests to the given host bypass the proxy.
+    #[cfg(feature = "runtime")]
+    pub(crate) fn bypasses(&self, host: &str) -> bool {
+        self.no_proxy.iter().any(|pattern| match pattern.strip_prefix('.') {
+            Some(domain) => host
+                .strip_suffix(domain)
+                .is_some_and(|subdomain| subdomain.ends_with('.')),
+            None => {
+                host == pattern
+                    || host
+                        .strip_suffix(pattern.as_str())
+                        .is_some_and(|subdomain| subdomain.ends_with('.'))
+            }
+        })
+    }
+}
+
+impl From<&str> for Proxy {
+    fn from(url: &str) -> Self {
+        Self::new(url)
+    }
+}
+
+impl From<String> for Proxy {
+    fn from(url: String) -> Self {
+        Self::new(url)
+    }
+}
+
+/// Hides the password.
+impl fmt::Debug for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Proxy")
+            .field("url", &self.url)
+            .field("username", &self.username())
+            .field("no_proxy", &self.no_proxy)
+            .finish()
+    }
+}
+
+/// A value shared between clones of the configuration, such as a user-provided callback.
+/// Two values are equal only when they point to the same allocation.
+pub(crate) struct Shared<T: ?Sized>(pub(crate) Arc<T>);
+
+impl<T: ?Sized> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: ?Sized> std::ops::Deref for Shared<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: ?Sized> PartialEq for Shared<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for Shared<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Shared(..)")
+    }
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
 mod tests {
+    use test_case::test_case;
+
     use super::*;
 
     #[test]
@@ -132,7 +1083,40 @@ mod tests {
             TelemetryConfig {
                 i_key: "instrumentation key".into(),
                 endpoint: "https://dc.services.visualstudio.com/v2/track".into(),
-                interval: Duration::from_secs(2)
+                interval: Duration::from_secs(2),
+                thread_name: None,
+                on_thread_start: None,
+                runtime: None,
+                max_queue_capacity: None,
+                intern_properties: false,
+                record_retry_count: false,
+                headers: HeaderMap::new(),
+                user_agent_suffix: None,
+                sdk_version_suffix: None,
+                compression: Compression::None,
+                sink: None,
+                max_item_age: None,
+                send_immediately: HashSet::new(),
+                terminate_sink: None,
+                persistence_dir: None,
+                max_spool_size: DEFAULT_MAX_SPOOL_SIZE,
+                sampling_percentage: 100.0,
+                request_name_normalizer: None,
+                max_batch_size: None,
+                max_envelope_size: DEFAULT_MAX_ENVELOPE_SIZE,
+                overflow_policy: OverflowPolicy::DropNewest,
+                max_request_size: DEFAULT_MAX_REQUEST_SIZE,
+                send_deadline: None,
+                mirrors: Vec::new(),
+                timer: None,
+                event_listener: None,
+                sampling_feedback: false,
+                proxy: None,
+                overload_threshold: None,
+                detect_environment: true,
+                application_version: None,
+                startup_event: false,
+                drain_marker: None,
             },
             config
         )
@@ -144,15 +1128,126 @@ mod tests {
             .i_key("instrumentation key")
             .endpoint("https://google.com")
             .interval(Duration::from_micros(100))
+            .max_queue_capacity(1000)
+            .intern_properties(true)
+            .record_retry_count(true)
+            .header(
+                HeaderName::from_static("x-ms-client-request-id"),
+                HeaderValue::from_static("42"),
+            )
+            .user_agent_suffix("orders/1.4")
+            .sdk_version_suffix("-via-mylib:1.4")
+            .compression(Compression::Gzip)
+            .max_item_age(Duration::from_secs(10))
+            .send_immediately(TelemetryKind::Availability)
+            .persistence_dir("/var/lib/app/telemetry")
+            .max_spool_size(1024)
+            .sampling_percentage(25.0)
+            .max_batch_size(500)
+            .max_envelope_size(1024)
+            .overflow_policy(OverflowPolicy::DropOldest)
+            .max_request_size(1024)
+            .send_deadline(Duration::from_secs(30))
+            .mirror(TelemetryConfig::new("mirror".into()))
+            .sampling_feedback(true)
+            .proxy("http://proxy:3128")
+            .overload_threshold(Duration::from_secs(30))
+            .detect_environment(false)
+            .application_version("1.4.2")
+            .startup_event(true)
             .build();
 
         assert_eq!(
             TelemetryConfig {
                 i_key: "instrumentation key".into(),
                 endpoint: "https://google.com".into(),
-                interval: Duration::from_micros(100)
+                interval: Duration::from_micros(100),
+                thread_name: None,
+                on_thread_start: None,
+                runtime: None,
+                max_queue_capacity: Some(1000),
+                intern_properties: true,
+                record_retry_count: true,
+                headers: {
+                    let mut headers = HeaderMap::new();
+                    headers.insert("x-ms-client-request-id", HeaderValue::from_static("42"));
+                    headers
+                },
+                user_agent_suffix: Some("orders/1.4".into()),
+                sdk_version_suffix: Some("-via-mylib:1.4".into()),
+                compression: Compression::Gzip,
+                sink: None,
+                max_item_age: Some(Duration::from_secs(10)),
+                send_immediately: vec![TelemetryKind::Availability].into_iter().collect(),
+                terminate_sink: None,
+                persistence_dir: Some("/var/lib/app/telemetry".into()),
+                max_spool_size: 1024,
+                sampling_percentage: 25.0,
+                request_name_normalizer: None,
+                max_batch_size: Some(500),
+                max_envelope_size: 1024,
+                overflow_policy: OverflowPolicy::DropOldest,
+                max_request_size: 1024,
+                send_deadline: Some(Duration::from_secs(30)),
+                mirrors: vec![TelemetryConfig::new("mirror".into())],
+                timer: None,
+                event_listener: None,
+                sampling_feedback: true,
+                proxy: Some(Proxy::new("http://proxy:3128")),
+                overload_threshold: Some(Duration::from_secs(30)),
+                detect_environment: false,
+                application_version: Some("1.4.2".into()),
+                startup_event: true,
+                drain_marker: None,
             },
             config
         );
     }
+
+    #[test]
+    fn it_builds_config_with_thread_settings() {
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .thread_name("appinsights-tenant")
+            .on_thread_start(|| {})
+            .build();
+
+        assert_eq!(config.thread_name(), Some("appinsights-tenant"));
+        assert!(config.on_thread_start().is_some());
+        assert_eq!(config.clone(), config);
+    }
+
+    #[tokio::test]
+    async fn it_builds_config_with_runtime() {
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .runtime(Handle::current())
+            .build();
+
+        assert!(config.runtime().is_some());
+        assert_eq!(config.clone(), config);
+    }
+
+    #[test_case("localhost", true; "exact host")]
+    #[test_case("v1.api.example.com", true; "subdomain of host")]
+    #[test_case("example.com", false; "domain of dotted pattern")]
+    #[test_case("db.internal.example.com", true; "subdomain of dotted pattern")]
+    #[test_case("badapi.example.com", false; "host with same suffix")]
+    #[test_case("dc.services.visualstudio.com", false; "other host")]
+    fn it_bypasses_proxy_for_no_proxy_hosts(host: &str, bypasses: bool) {
+        let proxy = Proxy::new("http://proxy:3128")
+            .no_proxy("localhost")
+            .no_proxy("api.example.com")
+            .no_proxy(".internal.example.com");
+
+        assert_eq!(proxy.bypasses(host), bypasses);
+    }
+
+    #[test]
+    fn it_hides_proxy_password() {
+        let proxy = Proxy::new("http://proxy:3128").basic_auth("user", "secret");
+
+        assert_eq!(proxy.username(), Some("user"));
+        assert!(!format!("{:?}", proxy).contains("secret"));
+    }
 }
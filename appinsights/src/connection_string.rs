@@ -0,0 +1,124 @@
+use std::{fmt, str::FromStr};
+
+/// An endpoint to send telemetry to when a connection string does not specify one.
+pub(crate) const DEFAULT_ENDPOINT: &str = "https://dc.services.visualstudio.com/v2/track";
+
+/// A path of the ingestion API relative to the ingestion endpoint.
+const TRACK_PATH: &str = "v2/track";
+
+/// Settings to connect to an Application Insights resource, parsed from a connection string like
+/// `InstrumentationKey=<key>;IngestionEndpoint=https://westeurope-5.in.applicationinsights.azure.com/`.
+///
+/// # Examples
+///
+/// ```rust
+/// use appinsights::ConnectionString;
+///
+/// let connection_string: ConnectionString =
+///     "InstrumentationKey=00000000-0000-0000-0000-000000000000;IngestionEndpoint=https://westeurope-5.in.applicationinsights.azure.com/"
+///         .parse()
+///         .unwrap();
+///
+/// assert_eq!(connection_string.i_key(), "00000000-0000-0000-0000-000000000000");
+/// assert_eq!(connection_string.endpoint(), "https://westeurope-5.in.applicationinsights.azure.com/v2/track");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionString {
+    i_key: String,
+    endpoint: String,
+}
+
+impl ConnectionString {
+    /// Returns an instrumentation key.
+    pub fn i_key(&self) -> &str {
+        &self.i_key
+    }
+
+    /// Returns an URL of the ingestion API to send telemetry to.
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+}
+
+impl FromStr for ConnectionString {
+    type Err = ConnectionStringError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut i_key = None;
+        let mut endpoint = None;
+
+        for part in s.split(';').map(str::trim).filter(|part| !part.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| ConnectionStringError::Malformed(part.into()))?;
+
+            // keys are case insensitive, unknown ones are reserved for other SDK features
+            let value = value.trim();
+            if key.trim().eq_ignore_ascii_case("InstrumentationKey") {
+                i_key = Some(value);
+            } else if key.trim().eq_ignore_ascii_case("IngestionEndpoint") {
+                endpoint = Some(value);
+            }
+        }
+
+        let i_key = i_key
+            .filter(|i_key| !i_key.is_empty())
+            .ok_or(ConnectionStringError::MissingInstrumentationKey)?;
+        let endpoint = match endpoint.filter(|endpoint| !endpoint.is_empty()) {
+            Some(endpoint) => format!("{}/{}", endpoint.trim_end_matches('/'), TRACK_PATH),
+            None => DEFAULT_ENDPOINT.into(),
+        };
+
+        Ok(Self {
+            i_key: i_key.into(),
+            endpoint,
+        })
+    }
+}
+
+/// An error returned when a connection string cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectionStringError {
+    /// A connection string does not contain an instrumentation key.
+    MissingInstrumentationKey,
+
+    /// A part of a connection string is not a `key=value` pair.
+    Malformed(String),
+}
+
+impl fmt::Display for ConnectionStringError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionStringError::MissingInstrumentationKey => write!(f, "instrumentation key is missing"),
+            ConnectionStringError::Malformed(part) => write!(f, "malformed connection string part: {}", part),
+        }
+    }
+}
+
+impl std::error::Error for ConnectionStringError {}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("InstrumentationKey=key",                                                   "key", DEFAULT_ENDPOINT                                  ; "key only")]
+    #[test_case("InstrumentationKey=key;IngestionEndpoint=https://example.com/",            "key", "https://example.com/v2/track"                    ; "ingestion endpoint")]
+    #[test_case("instrumentationkey=key; ingestionendpoint = https://example.com ;",        "key", "https://example.com/v2/track"                    ; "case insensitive keys")]
+    #[test_case("InstrumentationKey=key;LiveEndpoint=https://live.example.com/;Authorization=ikey", "key", DEFAULT_ENDPOINT                          ; "unknown keys")]
+    fn it_parses_connection_string(value: &str, i_key: &str, endpoint: &str) {
+        let connection_string: ConnectionString = value.parse().unwrap();
+
+        assert_eq!(connection_string.i_key(), i_key);
+        assert_eq!(connection_string.endpoint(), endpoint);
+    }
+
+    #[test_case("IngestionEndpoint=https://example.com/",   ConnectionStringError::MissingInstrumentationKey ; "no key")]
+    #[test_case("InstrumentationKey=",                      ConnectionStringError::MissingInstrumentationKey ; "empty key")]
+    #[test_case("InstrumentationKey=key;endpoint",          ConnectionStringError::Malformed("endpoint".into()) ; "malformed part")]
+    fn it_rejects_invalid_connection_string(value: &str, expected: ConnectionStringError) {
+        assert_eq!(value.parse::<ConnectionString>(), Err(expected));
+    }
+}
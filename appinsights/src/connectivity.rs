@@ -0,0 +1,242 @@
+//! Preflight check of the connection to the ingestion endpoint.
+//!
+//! Telemetry is submitted in the background, so a misconfigured endpoint, a firewall or an invalid
+//! instrumentation key show up only as debug logs while telemetry quietly piles up in the channel.
+//! [`TelemetryClient::verify_connectivity`](../struct.TelemetryClient.html#method.verify_connectivity) reports
+//! these problems right away, so an application can check its configuration at startup.
+#[cfg(not(feature = "disabled"))]
+use std::time::Instant;
+use std::{error::Error as StdError, fmt, time::Duration};
+
+use http::StatusCode;
+#[cfg(not(feature = "disabled"))]
+use log::debug;
+
+#[cfg(not(feature = "disabled"))]
+use crate::{
+    contracts::{Envelope, Transmission},
+    time,
+    transmitter::{snippet, Transmitter},
+    TelemetryConfig,
+};
+
+/// Maximum time to wait for the ingestion endpoint to respond.
+#[cfg(not(feature = "disabled"))]
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A name of the telemetry item sent to the ingestion endpoint to check connectivity.
+#[cfg(not(feature = "disabled"))]
+const PROBE_NAME: &str = "Microsoft.ApplicationInsights.Event";
+
+/// Details of the ingestion endpoint that responded to a connectivity check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EndpointInfo {
+    endpoint: String,
+    status_code: StatusCode,
+    latency: Duration,
+}
+
+impl EndpointInfo {
+    /// Returns a URL of the ingestion endpoint.
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Returns a status code the ingestion endpoint responded with.
+    pub fn status_code(&self) -> StatusCode {
+        self.status_code
+    }
+
+    /// Returns a time it took the ingestion endpoint to respond, including DNS resolution and TLS handshake.
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+}
+
+/// An error returned when telemetry cannot be submitted to the ingestion endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectivityError {
+    /// The endpoint cannot be reached because of a DNS, connection or TLS failure, or it did not respond in time.
+    Unreachable(String),
+
+    /// The endpoint rejected the request as not authorized.
+    Unauthorized(StatusCode),
+
+    /// The endpoint does not know the instrumentation key.
+    InvalidInstrumentationKey(String),
+
+    /// The endpoint responded with a status code that does not come from the ingestion service, for example
+    /// from a proxy or a wrong URL.
+    UnexpectedStatus(StatusCode, String),
+
+    /// Telemetry is compiled out with the `disabled` feature, so the endpoint is never contacted.
+    Disabled,
+}
+
+impl fmt::Display for ConnectivityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectivityError::Unreachable(err) => write!(f, "ingestion endpoint is unreachable: {}", err),
+            ConnectivityError::Unauthorized(status_code) => {
+                write!(f, "ingestion endpoint rejected request: {}", status_code)
+            }
+            ConnectivityError::InvalidInstrumentationKey(message) => {
+                write!(f, "invalid instrumentation key: {}", message)
+            }
+            ConnectivityError::UnexpectedStatus(status_code, body) => {
+                write!(f, "unexpected response {}: {}", status_code, body)
+            }
+            ConnectivityError::Disabled => write!(f, "telemetry is disabled"),
+        }
+    }
+}
+
+impl StdError for ConnectivityError {}
+
+/// Sends a probe to the ingestion endpoint and classifies the outcome.
+///
+/// The probe is a single telemetry item without data. The endpoint validates the instrumentation key
+/// before anything else and rejects the item afterwards, so the probe never shows up in the Application
+/// Insights resource.
+#[cfg(not(feature = "disabled"))]
+pub(crate) async fn verify(config: &TelemetryConfig) -> Result<EndpointInfo, ConnectivityError> {
+    let transmitter = Transmitter::new(config.endpoint(), config.headers().clone())
+        .user_agent_suffix(config.user_agent_suffix())
+        .compression(config.compression());
+
+    let probe = Envelope {
+        ver: None,
+        name: PROBE_NAME.into(),
+        time: time::timestamp().to_string(),
+        sample_rate: None,
+        seq: None,
+        i_key: Some(config.i_key().into()),
+        flags: None,
+        tags: None,
+        data: None,
+    };
+
+    let started = Instant::now();
+    let (status_code, body) = transmitter
+        .probe(&[probe], PROBE_TIMEOUT)
+        .await
+        .map_err(|err| ConnectivityError::Unreachable(describe(err.as_ref())))?;
+    let latency = started.elapsed();
+    debug!("Ingestion endpoint responded {} in {:?}", status_code, latency);
+
+    classify(status_code, &body)?;
+
+    Ok(EndpointInfo {
+        endpoint: config.endpoint().into(),
+        status_code,
+        latency,
+    })
+}
+
+/// Determines whether a response means the endpoint will accept telemetry.
+#[cfg(not(feature = "disabled"))]
+fn classify(status_code: StatusCode, body: &str) -> Result<(), ConnectivityError> {
+    match status_code {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(ConnectivityError::Unauthorized(status_code)),
+        StatusCode::OK | StatusCode::PARTIAL_CONTENT | StatusCode::BAD_REQUEST => {
+            match serde_json::from_str::<Transmission>(body) {
+                Ok(content) => match content
+                    .errors
+                    .into_iter()
+                    .find(|error| error.message.to_lowercase().contains("instrumentation key"))
+                {
+                    Some(error) => Err(ConnectivityError::InvalidInstrumentationKey(error.message)),
+                    None => Ok(()),
+                },
+                // the ingestion service always describes rejected items, so anything else comes from elsewhere
+                Err(_) if status_code == StatusCode::BAD_REQUEST => {
+                    Err(ConnectivityError::UnexpectedStatus(status_code, snippet(body).into()))
+                }
+                Err(_) => Ok(()),
+            }
+        }
+        _ => Err(ConnectivityError::UnexpectedStatus(status_code, snippet(body).into())),
+    }
+}
+
+/// Joins an error with all its sources, since DNS and TLS details are usually found in the innermost one.
+#[cfg(not(feature = "disabled"))]
+fn describe(err: &(dyn StdError + 'static)) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+    message
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use http::Request;
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Server,
+    };
+    use serde_json::json;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(StatusCode::BAD_REQUEST, errors("Field 'data' on type 'Envelope' is required."), Ok(()); "probe rejected")]
+    #[test_case(StatusCode::OK, json!({"itemsReceived": 1, "itemsAccepted": 1, "errors": []}).to_string(), Ok(()); "probe accepted")]
+    #[test_case(StatusCode::BAD_REQUEST, errors("Invalid instrumentation key"), Err(ConnectivityError::InvalidInstrumentationKey("Invalid instrumentation key".into())); "invalid key")]
+    #[test_case(StatusCode::UNAUTHORIZED, String::new(), Err(ConnectivityError::Unauthorized(StatusCode::UNAUTHORIZED)); "unauthorized")]
+    #[test_case(StatusCode::FORBIDDEN, String::new(), Err(ConnectivityError::Unauthorized(StatusCode::FORBIDDEN)); "forbidden")]
+    #[test_case(StatusCode::NOT_FOUND, "<html></html>".into(), Err(ConnectivityError::UnexpectedStatus(StatusCode::NOT_FOUND, "<html></html>".into())); "not found")]
+    #[test_case(StatusCode::BAD_REQUEST, "<html></html>".into(), Err(ConnectivityError::UnexpectedStatus(StatusCode::BAD_REQUEST, "<html></html>".into())); "bad request from proxy")]
+    fn it_classifies_response(status_code: StatusCode, body: String, expected: Result<(), ConnectivityError>) {
+        assert_eq!(classify(status_code, &body), expected);
+    }
+
+    #[tokio::test]
+    async fn it_reports_endpoint_info() {
+        let make_service = make_service_fn(|_| async {
+            Ok::<_, hyper::Error>(service_fn(|_: Request<Body>| async {
+                hyper::Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(Body::from(errors("Field 'data' on type 'Envelope' is required.")))
+            }))
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let endpoint = format!("http://{}/v2/track", server.local_addr());
+        tokio::spawn(server);
+
+        let config = TelemetryConfig::builder().i_key("key").endpoint(&endpoint).build();
+        let info = verify(&config).await.unwrap();
+
+        assert_eq!(info.endpoint(), endpoint);
+        assert_eq!(info.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn it_reports_unreachable_endpoint() {
+        // bind and release a port, so nothing listens on it
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap()
+            .port();
+        let endpoint = format!("http://127.0.0.1:{}/v2/track", port);
+
+        let config = TelemetryConfig::builder().i_key("key").endpoint(endpoint).build();
+        let result = verify(&config).await;
+
+        assert!(matches!(result, Err(ConnectivityError::Unreachable(_))));
+    }
+
+    fn errors(message: &str) -> String {
+        json!({
+            "itemsReceived": 1,
+            "itemsAccepted": 0,
+            "errors": [{ "index": 0, "statusCode": 400, "message": message }]
+        })
+        .to_string()
+    }
+}
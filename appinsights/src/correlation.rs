@@ -0,0 +1,172 @@
+//! Propagation of operations through message metadata.
+//!
+//! Message brokers such as AMQP or Kafka deliver messages along with metadata, like application properties or
+//! headers. A producer can [inject](struct.CorrelationContext.html#method.inject) the operation it runs in into
+//! the metadata of a message it sends as a [W3C `traceparent`](https://www.w3.org/TR/trace-context/) entry,
+//! and a consumer can continue the operation with
+//! [`TelemetryClient::continue_operation`](../struct.TelemetryClient.html#method.continue_operation). The
+//! consumer tracks processing of the message as a request that refers to the producer as its parent, so the
+//! end-to-end transaction view shows both sides of the queue.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! use std::collections::HashMap;
+//! use appinsights::{correlation::CorrelationContext, TelemetryClient};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! # let client = TelemetryClient::new("<instrumentation key>".to_string());
+//! // producer
+//! let operation = client.start_operation("place order");
+//! let mut metadata = HashMap::new();
+//! operation.correlation().inject(&mut metadata);
+//! // send the message with metadata
+//! # drop(operation);
+//!
+//! // consumer
+//! let operation = client.continue_operation("process order", &metadata);
+//! operation.run(async { client.track_event("order processed") }).await;
+//! // the request is tracked here
+//! # }
+//! ```
+use std::{collections::HashMap, fmt, hash::BuildHasher};
+
+use crate::scope::Scope;
+
+/// A name of the metadata entry that carries the operation.
+pub const TRACEPARENT: &str = "traceparent";
+
+/// An operation and a parent telemetry item to correlate telemetry of another process with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorrelationContext {
+    operation_id: String,
+    parent_id: String,
+}
+
+impl CorrelationContext {
+    /// Creates a context of the given operation id, which must be 32 lowercase hex characters, and parent id,
+    /// which must be 16 lowercase hex characters. Returns `None` if ids are malformed.
+    pub fn new(operation_id: impl Into<String>, parent_id: impl Into<String>) -> Option<Self> {
+        let operation_id = operation_id.into();
+        let parent_id = parent_id.into();
+        if is_id(&operation_id, 32) && is_id(&parent_id, 16) {
+            Some(Self {
+                operation_id,
+                parent_id,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a context of the scope the current task runs within, if any.
+    pub fn current() -> Option<Self> {
+        Scope::current().map(|scope| Self::from_scope(&scope))
+    }
+
+    /// Returns a context that refers to the given scope as a parent. Ids of scopes are valid W3C ids.
+    pub fn from_scope(scope: &Scope) -> Self {
+        Self {
+            operation_id: scope.operation_id().into(),
+            parent_id: scope.id().into(),
+        }
+    }
+
+    /// Parses a value of a `traceparent` entry, such as `00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.trim().split('-');
+        let version = parts.next()?;
+        let operation_id = parts.next()?;
+        let parent_id = parts.next()?;
+        let flags = parts.next()?;
+
+        // future versions may append fields, the version 00 may not
+        let valid =
+            is_hex(version, 2) && version != "ff" && is_hex(flags, 2) && (version != "00" || parts.next().is_none());
+        if valid {
+            Self::new(operation_id, parent_id)
+        } else {
+            None
+        }
+    }
+
+    /// Reads a context from the `traceparent` entry of message metadata, if any. Entry names are matched
+    /// case-insensitively.
+    pub fn extract<S: BuildHasher>(metadata: &HashMap<String, String, S>) -> Option<Self> {
+        metadata
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(TRACEPARENT))
+            .and_then(|(_, value)| Self::parse(value))
+    }
+
+    /// Writes the context to the `traceparent` entry of message metadata.
+    pub fn inject<S: BuildHasher>(&self, metadata: &mut HashMap<String, String, S>) {
+        metadata.insert(TRACEPARENT.into(), self.to_string());
+    }
+
+    /// Returns an id of the operation.
+    pub fn operation_id(&self) -> &str {
+        &self.operation_id
+    }
+
+    /// Returns an id of the telemetry item to refer to as a parent.
+    pub fn parent_id(&self) -> &str {
+        &self.parent_id
+    }
+
+    /// Returns a scope that stands for the parent.
+    pub(crate) fn to_scope(&self) -> Scope {
+        Scope::remote(self.operation_id.clone(), self.parent_id.clone())
+    }
+}
+
+/// Formats the context as a value of a `traceparent` entry of a sampled operation.
+impl fmt::Display for CorrelationContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "00-{}-{}-01", self.operation_id, self.parent_id)
+    }
+}
+
+fn is_hex(value: &str, len: usize) -> bool {
+    value.len() == len && value.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn is_id(value: &str, len: usize) -> bool {
+    is_hex(value, len) && value.bytes().any(|b| b != b'0')
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01", true; "valid")]
+    #[test_case("01-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-00-extra", true; "future version")]
+    #[test_case("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-extra", false; "extra field")]
+    #[test_case("ff-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01", false; "invalid version")]
+    #[test_case("00-00000000000000000000000000000000-b7ad6b7169203331-01", false; "zero operation id")]
+    #[test_case("00-0AF7651916CD43DD8448EB211C80319C-b7ad6b7169203331-01", false; "uppercase operation id")]
+    #[test_case("00-0af7651916cd43dd8448eb211c80319c-b7ad6b71-01", false; "short parent id")]
+    #[test_case("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331", false; "missing flags")]
+    fn it_parses_traceparent(value: &str, valid: bool) {
+        assert_eq!(CorrelationContext::parse(value).is_some(), valid);
+    }
+
+    #[test]
+    fn it_injects_and_extracts_context() {
+        let context = CorrelationContext::new("0af7651916cd43dd8448eb211c80319c", "b7ad6b7169203331").unwrap();
+
+        let mut metadata = HashMap::new();
+        context.inject(&mut metadata);
+        assert_eq!(
+            metadata[TRACEPARENT],
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"
+        );
+
+        let value = metadata.remove(TRACEPARENT).unwrap();
+        metadata.insert("TraceParent".into(), value);
+        assert_eq!(CorrelationContext::extract(&metadata), Some(context));
+    }
+}
@@ -0,0 +1,138 @@
+//! Observing what telemetry channels are doing.
+//!
+//! An [`EventListener`] configured with [`TelemetryConfigBuilder::event_listener`] is notified about batches
+//! sent to the ingestion endpoint or a sink, batches that failed, telemetry items dropped and retries scheduled,
+//! so an application can expose health metrics of the SDK along with its own. Events are emitted on the task
+//! that submits telemetry, so a listener should be cheap, e.g. increment counters. A listener that panics is
+//! skipped for the event.
+//!
+//! [`TelemetryConfigBuilder::event_listener`]: ../struct.TelemetryConfigBuilder.html#method.event_listener
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//!
+//! use appinsights::{diagnostics::ChannelEvent, TelemetryClient, TelemetryConfig};
+//!
+//! static FAILED_BATCHES: AtomicUsize = AtomicUsize::new(0);
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .event_listener(|event: &ChannelEvent| {
+//!         if let ChannelEvent::BatchFailed { .. } = event {
+//!             FAILED_BATCHES.fetch_add(1, Ordering::Relaxed);
+//!         }
+//!     })
+//!     .build();
+//! let client = TelemetryClient::from_config(config);
+//! # }
+//! ```
+use std::time::Duration;
+
+#[cfg(feature = "runtime")]
+use crate::{callback, config::Shared};
+
+/// An event that happened in a telemetry channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ChannelEvent {
+    /// A batch of telemetry items has been sent. The ingestion endpoint may have accepted some of them only.
+    BatchSent {
+        /// Number of telemetry items in the batch.
+        items: usize,
+        /// Number of telemetry items accepted.
+        accepted: usize,
+        /// A status code of the response, or `None` if the batch has been written to a sink.
+        status_code: Option<u16>,
+    },
+    /// A batch of telemetry items could not be sent.
+    BatchFailed {
+        /// Number of telemetry items in the batch.
+        items: usize,
+        /// A status code of the response, or `None` if no response has been received or the batch could not
+        /// be written to a sink.
+        status_code: Option<u16>,
+    },
+    /// Telemetry items have been dropped because the channel was over capacity or they exceeded the maximum
+    /// envelope size.
+    ItemsDropped {
+        /// Number of telemetry items dropped.
+        count: usize,
+    },
+    /// Sending telemetry items is going to be retried after a timeout.
+    RetryScheduled {
+        /// Number of telemetry items to retry.
+        items: usize,
+        /// Time to wait before the next attempt.
+        after: Duration,
+    },
+}
+
+/// Receives events of telemetry channels.
+pub trait EventListener: Send + Sync + 'static {
+    /// Handles an event.
+    fn on_event(&self, event: &ChannelEvent);
+}
+
+impl<F> EventListener for F
+where
+    F: Fn(&ChannelEvent) + Send + Sync + 'static,
+{
+    fn on_event(&self, event: &ChannelEvent) {
+        self(event)
+    }
+}
+
+/// Notifies a configured listener, if any, and internal listeners about events.
+#[cfg(feature = "runtime")]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Listener(Vec<Shared<dyn EventListener>>);
+
+#[cfg(feature = "runtime")]
+impl Listener {
+    pub(crate) fn new(listener: Option<Shared<dyn EventListener>>) -> Self {
+        Self(listener.into_iter().collect())
+    }
+
+    /// Adds an internal listener to notify ahead of the configured one, so it observes state the internal
+    /// listener updates.
+    pub(crate) fn with(mut self, listener: Shared<dyn EventListener>) -> Self {
+        self.0.insert(0, listener);
+        self
+    }
+
+    pub(crate) fn emit(&self, event: ChannelEvent) {
+        for listener in &self.0 {
+            callback::call("Event listener", || listener.on_event(&event));
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[test]
+    fn it_notifies_listener_and_survives_panic() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let listener = Listener::new(Some(Shared(Arc::new({
+            let events = events.clone();
+            move |event: &ChannelEvent| {
+                if let ChannelEvent::ItemsDropped { count: 0 } = event {
+                    panic!("nothing dropped");
+                }
+                events.lock().unwrap().push(event.clone());
+            }
+        }))));
+
+        listener.emit(ChannelEvent::ItemsDropped { count: 0 });
+        listener.emit(ChannelEvent::ItemsDropped { count: 1 });
+
+        assert_eq!(*events.lock().unwrap(), vec![ChannelEvent::ItemsDropped { count: 1 }]);
+    }
+}
@@ -0,0 +1,190 @@
+//! Detection of the hosting environment an application runs in.
+use crate::telemetry::{ContextTags, Properties};
+
+/// A property that contains the type of Azure resource an application runs in.
+const AZURE_RESOURCE_TYPE: &str = "azure.resource.type";
+
+/// A property that contains the name of Azure App Service site or Azure Functions app.
+const AZURE_RESOURCE_NAME: &str = "azure.resource.name";
+
+/// A property that contains the region of Azure resource.
+const AZURE_RESOURCE_REGION: &str = "azure.resource.region";
+
+/// A property that contains the language worker runtime of Azure Functions app.
+const AZURE_RESOURCE_FUNCTIONS_RUNTIME: &str = "azure.resource.functions_runtime";
+
+/// Populates cloud tags and properties with details of the environment read from environment variables.
+pub(crate) fn detect(tags: &mut ContextTags, properties: &mut Properties) {
+    detect_with(|name| std::env::var(name).ok(), tags, properties)
+}
+
+fn detect_with<F>(var: F, tags: &mut ContextTags, properties: &mut Properties)
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let var = |name: &str| var(name).filter(|value| !value.is_empty());
+
+    // Azure App Service and Azure Functions
+    if let Some(site_name) = var("WEBSITE_SITE_NAME") {
+        tags.cloud_mut().set_role(site_name.clone());
+        properties.insert(AZURE_RESOURCE_NAME.into(), site_name);
+
+        let functions_runtime = var("FUNCTIONS_WORKER_RUNTIME");
+        let resource_type = if functions_runtime.is_some() {
+            "functions"
+        } else {
+            "appservice"
+        };
+        properties.insert(AZURE_RESOURCE_TYPE.into(), resource_type.into());
+
+        if let Some(runtime) = functions_runtime {
+            properties.insert(AZURE_RESOURCE_FUNCTIONS_RUNTIME.into(), runtime);
+        }
+
+        if let Some(region) = var("REGION_NAME") {
+            tags.cloud_mut().set_location(region.clone());
+            properties.insert(AZURE_RESOURCE_REGION.into(), region);
+        }
+
+        if let Some(instance_id) = var("WEBSITE_INSTANCE_ID") {
+            tags.cloud_mut().set_role_instance(instance_id);
+        }
+    } else if var("KUBERNETES_SERVICE_HOST").is_some() {
+        // a pod name exposed via the downward API, or the host name Kubernetes sets to the pod name
+        if let Some(pod_name) = var("POD_NAME").or_else(|| var("HOSTNAME")) {
+            tags.cloud_mut().set_role(workload_name(&pod_name).into());
+            tags.cloud_mut().set_role_instance(pod_name);
+        }
+    }
+}
+
+/// Characters Kubernetes generates suffixes of pod and replica set names from. It has no vowels, so generated
+/// suffixes do not form words.
+const GENERATED_ALPHABET: &str = "bcdfghjklmnpqrstvwxz2456789";
+
+/// Returns a name of the workload a pod belongs to by stripping suffixes Kubernetes appends to pod names, e.g.
+/// `orders-api` for `orders-api-7d9f8c6b5-x2k4p` of a deployment or `orders-db` for `orders-db-0` of a stateful
+/// set.
+fn workload_name(pod_name: &str) -> &str {
+    match pod_name.rsplit_once('-') {
+        // a pod of a stateful set
+        Some((name, ordinal))
+            if !name.is_empty() && !ordinal.is_empty() && ordinal.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            name
+        }
+        // a pod of a replica set, which in turn may belong to a deployment and end with a pod template hash
+        _ => strip_generated_suffix(strip_generated_suffix(pod_name)),
+    }
+}
+
+fn strip_generated_suffix(name: &str) -> &str {
+    match name.rsplit_once('-') {
+        Some((prefix, suffix))
+            if !prefix.is_empty()
+                && (5..=10).contains(&suffix.len())
+                && suffix.chars().all(|c| GENERATED_ALPHABET.contains(c)) =>
+        {
+            prefix
+        }
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn it_detects_azure_functions() {
+        let (tags, properties) = detect_from(&[
+            ("WEBSITE_SITE_NAME", "orders-func"),
+            ("REGION_NAME", "West Europe"),
+            ("FUNCTIONS_WORKER_RUNTIME", "custom"),
+        ]);
+
+        assert_eq!(tags.cloud().role(), Some("orders-func"));
+        assert_eq!(tags.cloud().location(), Some("West Europe"));
+        assert_eq!(properties.get(AZURE_RESOURCE_TYPE), Some(&"functions".to_string()));
+        assert_eq!(properties.get(AZURE_RESOURCE_NAME), Some(&"orders-func".to_string()));
+        assert_eq!(properties.get(AZURE_RESOURCE_REGION), Some(&"West Europe".to_string()));
+        assert_eq!(
+            properties.get(AZURE_RESOURCE_FUNCTIONS_RUNTIME),
+            Some(&"custom".to_string())
+        );
+    }
+
+    #[test]
+    fn it_detects_azure_app_service() {
+        let (tags, properties) = detect_from(&[("WEBSITE_SITE_NAME", "orders-web")]);
+
+        assert_eq!(tags.cloud().role(), Some("orders-web"));
+        assert_eq!(tags.cloud().location(), None);
+        assert_eq!(properties.get(AZURE_RESOURCE_TYPE), Some(&"appservice".to_string()));
+        assert_eq!(properties.get(AZURE_RESOURCE_REGION), None);
+        assert_eq!(properties.get(AZURE_RESOURCE_FUNCTIONS_RUNTIME), None);
+    }
+
+    #[test]
+    fn it_detects_azure_app_service_instance() {
+        let (tags, _) = detect_from(&[("WEBSITE_SITE_NAME", "orders-web"), ("WEBSITE_INSTANCE_ID", "a1b2c3")]);
+
+        assert_eq!(tags.cloud().role_instance(), Some("a1b2c3"));
+    }
+
+    #[test]
+    fn it_detects_kubernetes_pod() {
+        let (tags, properties) = detect_from(&[
+            ("KUBERNETES_SERVICE_HOST", "10.0.0.1"),
+            ("HOSTNAME", "orders-api-7d9f8c6b5-x2k4p"),
+        ]);
+
+        assert_eq!(tags.cloud().role(), Some("orders-api"));
+        assert_eq!(tags.cloud().role_instance(), Some("orders-api-7d9f8c6b5-x2k4p"));
+        assert!(properties.is_empty());
+
+        let (tags, _) = detect_from(&[
+            ("KUBERNETES_SERVICE_HOST", "10.0.0.1"),
+            ("HOSTNAME", "ignored"),
+            ("POD_NAME", "orders-db-0"),
+        ]);
+
+        assert_eq!(tags.cloud().role(), Some("orders-db"));
+        assert_eq!(tags.cloud().role_instance(), Some("orders-db-0"));
+    }
+
+    #[test_case("orders-api-7d9f8c6b5-x2k4p", "orders-api"; "deployment")]
+    #[test_case("orders-job-x2k4p", "orders-job"; "replica set")]
+    #[test_case("orders-db-12", "orders-db"; "stateful set")]
+    #[test_case("payments-service", "payments-service"; "pod")]
+    fn it_derives_workload_name(pod_name: &str, expected: &str) {
+        assert_eq!(workload_name(pod_name), expected);
+    }
+
+    #[test]
+    fn it_does_not_detect_anything_outside_of_cloud() {
+        let (tags, properties) = detect_from(&[("REGION_NAME", "West Europe"), ("WEBSITE_SITE_NAME", "")]);
+
+        assert_eq!(tags.cloud().role(), None);
+        assert_eq!(tags.cloud().location(), None);
+        assert!(properties.is_empty());
+    }
+
+    fn detect_from(vars: &[(&str, &str)]) -> (ContextTags, Properties) {
+        let vars: HashMap<_, _> = vars.iter().cloned().collect();
+
+        let mut tags = ContextTags::default();
+        let mut properties = Properties::default();
+        detect_with(
+            |name| vars.get(name).map(ToString::to_string),
+            &mut tags,
+            &mut properties,
+        );
+
+        (tags, properties)
+    }
+}
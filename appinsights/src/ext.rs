@@ -0,0 +1,18 @@
+//! Re-exports of third-party types that appear in public APIs of this crate.
+//!
+//! Use these types instead of depending on `http` and `chrono` crates directly, so an application
+//! doesn't have to keep versions of these dependencies aligned with the ones used by the SDK. [`DateTime`] and
+//! [`Utc`] are available with the `runtime` feature only, as are the APIs they appear in.
+//!
+//! ```rust, no_run
+//! # use appinsights::TelemetryClient;
+//! # let client = TelemetryClient::new("<instrumentation key>".to_string());
+//! use appinsights::ext::{Method, Uri};
+//! use std::time::Duration;
+//!
+//! let uri: Uri = "https://api.github.com/dmolokanov/appinsights-rs".parse().unwrap();
+//! client.track_request(Method::GET, uri, Duration::from_millis(100), "200");
+//! ```
+#[cfg(feature = "runtime")]
+pub use chrono::{DateTime, Utc};
+pub use http::{header::HeaderName, HeaderMap, HeaderValue, Method, StatusCode, Uri};
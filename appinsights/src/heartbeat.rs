@@ -0,0 +1,135 @@
+//! Periodic heartbeats that report the state of an application and the SDK.
+//!
+//! Like other Application Insights SDKs, [`Heartbeat`] submits a `HeartbeatState` aggregate metric on a
+//! regular basis, so an application that stopped sending telemetry can be told apart from an application
+//! that has nothing to report. Heartbeats carry the following properties:
+//! * `sdkVersion` - a version of the SDK,
+//! * `osType` - an operating system the application runs on,
+//! * `osArch` - a CPU architecture the application is compiled for,
+//! * `rustcVersion` - a version of the compiler the SDK is built with,
+//! * `processSessionId` - a random id generated when a heartbeat is created, so it changes every time the
+//!   application restarts.
+//!
+//! Custom fields can be added to heartbeats with [`Heartbeat::properties_mut`].
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # use std::{sync::Arc, time::Duration};
+//! use appinsights::{heartbeat::Heartbeat, TelemetryClient};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = Arc::new(TelemetryClient::new("<instrumentation key>".to_string()));
+//!
+//! let mut heartbeat = Heartbeat::new();
+//! heartbeat.properties_mut().insert("deployment".into(), "blue".into());
+//! heartbeat.spawn(&client, Duration::from_secs(15 * 60));
+//! # }
+//! ```
+#[cfg(feature = "runtime")]
+use std::{sync::Arc, time::Duration};
+
+#[cfg(feature = "runtime")]
+use tokio::task::JoinHandle;
+
+#[cfg(feature = "runtime")]
+use crate::{client, TelemetryClient};
+use crate::{
+    context::SDK_VERSION,
+    telemetry::{AggregateMetricTelemetry, Properties, Telemetry},
+    Tracker,
+};
+
+/// A name of the metric heartbeats are submitted as.
+pub const HEARTBEAT_METRIC_NAME: &str = "HeartbeatState";
+
+/// Submits heartbeats with SDK and host metadata and custom fields.
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    properties: Properties,
+}
+
+impl Heartbeat {
+    /// Creates a new heartbeat with default properties.
+    pub fn new() -> Self {
+        let mut properties = Properties::default();
+        properties.insert("sdkVersion".into(), SDK_VERSION.into());
+        properties.insert("osType".into(), std::env::consts::OS.into());
+        properties.insert("osArch".into(), std::env::consts::ARCH.into());
+        properties.insert("rustcVersion".into(), env!("APPINSIGHTS_RUSTC_VERSION").into());
+        properties.insert(
+            "processSessionId".into(),
+            appinsights_core::uuid::new().as_hyphenated().to_string(),
+        );
+        Self { properties }
+    }
+
+    /// Returns properties submitted with every heartbeat.
+    pub fn properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    /// Returns mutable reference to properties submitted with every heartbeat, which custom fields can be
+    /// added to.
+    pub fn properties_mut(&mut self) -> &mut Properties {
+        &mut self.properties
+    }
+
+    /// Submits a heartbeat with the given tracker.
+    pub fn submit<T: Tracker>(&self, tracker: &T) {
+        let mut telemetry = AggregateMetricTelemetry::new(HEARTBEAT_METRIC_NAME);
+        telemetry.stats_mut().add_data(&[0.0]);
+        *telemetry.properties_mut() = self.properties.clone();
+        tracker.track(telemetry);
+    }
+
+    /// Spawns a [periodic task](../index.html#periodic-tasks) that submits a heartbeat with the given client every
+    /// `interval`.
+    #[cfg(feature = "runtime")]
+    pub fn spawn(self, client: &Arc<TelemetryClient>, interval: Duration) -> JoinHandle<()> {
+        client::spawn_periodic(client, interval, move |client| self.submit(client))
+    }
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use crossbeam_queue::SegQueue;
+
+    use super::*;
+    use crate::client::tests::{create_client, metric_data};
+
+    #[tokio::test]
+    async fn it_submits_heartbeat_with_custom_fields() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let mut heartbeat = Heartbeat::new();
+        heartbeat.properties_mut().insert("deployment".into(), "blue".into());
+        heartbeat.submit(&client);
+        heartbeat.submit(&client);
+
+        let first = metric_data(events.pop());
+        let second = metric_data(events.pop());
+        assert_eq!(first.metrics[0].name, HEARTBEAT_METRIC_NAME);
+        assert_eq!(first.metrics[0].count, Some(1));
+
+        let properties = first.properties.unwrap();
+        assert_eq!(properties.get("sdkVersion"), Some(&SDK_VERSION.to_string()));
+        assert_eq!(properties.get("osType"), Some(&std::env::consts::OS.to_string()));
+        assert_eq!(properties.get("deployment"), Some(&"blue".to_string()));
+        assert!(properties
+            .get("rustcVersion")
+            .is_some_and(|version| version.starts_with("rustc")));
+        assert_eq!(
+            properties.get("processSessionId"),
+            second.properties.unwrap().get("processSessionId")
+        );
+    }
+}
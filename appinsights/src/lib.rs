@@ -17,7 +17,7 @@
 //! Eventually all telemetry items that Application Insights supports will be implemented.
 //!
 //! ## Requirements
-//! Add appinsights crate to your project
+//! The crate requires Rust 1.82 or newer. Add appinsights crate to your project
 //!
 //! ```bash
 //! $ cargo add appinsights
@@ -29,8 +29,8 @@
 //! ## Examples
 //!
 //! 1. Create an new instance of [`TelemetryClient`](struct.TelemetryClient.html) with an
-//! Instrumentation Key and default settings. To get more control over client behavior please visit
-//! [`TelemetryConfig`](struct.TelemetryConfig.html).
+//!    Instrumentation Key and default settings. To get more control over client behavior please visit
+//!    [`TelemetryConfig`](struct.TelemetryConfig.html).
 //! 2. Send an event telemetry to the Application Insights service.
 //!
 //! ```rust
@@ -83,6 +83,7 @@
 //! * [track_request](struct.TelemetryClient.html#method.track_request) to log a HTTP request with the specified method, URL, duration and response code.
 //! * [track_remote_dependency](struct.TelemetryClient.html#method.track_remote_dependency) to log a dependency with the specified name, type, target, and success status.
 //! * [track_availability](struct.TelemetryClient.html#method.track_availability) to log an availability test result with the specified test name, duration, and success status.
+//! * [track_page_view](struct.TelemetryClient.html#method.track_page_view) to log a page view with the specified name and URL.
 //!
 //! But they provide the very basic set of parameters telemetry types can represent. For example all
 //! telemetry items support [`properties`](telemetry/trait.Telemetry.html#method.properties) and
@@ -99,11 +100,15 @@
 //! A [`TelemetryContext`](struct.TelemetryContext.html) also have
 //! [`tags`](struct.TelemetryContext.html#method.tags). These tags will be applied to all telemetry
 //! items submitted to a server. In case when some tags exists in both telemetry client context tags
-//! and telemetry item tags, later will be sent to the server.
+//! and telemetry item tags, later will be sent to the server. A request-scoped context can be supplied
+//! instead of the context of the client with
+//! [`track_with_context`](struct.TelemetryClient.html#method.track_with_context), and components of one
+//! binary can share a client through handles with tags of their own created with
+//! [`with_tags`](struct.TelemetryClient.html#method.with_tags).
 //!
 //! ```rust
 //! use std::time::Duration;
-//! use http::Method;
+//! use appinsights::ext::Method;
 //! use appinsights::TelemetryClient;
 //! use appinsights::telemetry::{RequestTelemetry, Telemetry};
 //!
@@ -160,38 +165,180 @@
 //! worker stores it in memory, so when application crashes the data will be lost. Luckily SDK
 //! provides several convenient methods to deal with this issue.
 //! * [`flush_channel`](struct.TelemetryClient.html#method.flush_channel) will trigger telemetry submission
-//! as soon as possible. It returns immediately and telemetry is no guaranteed to be sent.
+//!   as soon as possible. It returns immediately and telemetry is no guaranteed to be sent. Repeated flush
+//!   requests pending at the same time are coalesced into a single submission.
 //! * [`close_channel`](struct.TelemetryClient.html#method.close_channel) will cause the channel to
-//! stop accepting any new telemetry items, submit all pending ones, block current task and
-//! wait until data will be sent at most once. If telemetry submission fails, it will not retry.
-//! This method consumes the value of client so it makes impossible to use a client with close channel.
+//!   stop accepting any new telemetry items, submit all pending ones, block current task and
+//!   wait until data will be sent at most once. If telemetry submission fails, it will not retry.
+//!   This method consumes the value of client so it makes impossible to use a client with close channel.
 //! * [`terminate`](struct.TelemetryClient.html#method.terminate) will trigger termination of submission flow, all pending items discarded and
-//! current task will be blocked until all resources freed.
+//!   current task will be blocked until all resources freed.
+//!
+//! ## Panics
+//!
+//! Tracking telemetry never panics. Callbacks supplied by an application, such as a
+//! [telemetry sink](sink/index.html), a thread start callback or a Prometheus name mapping, run under
+//! `catch_unwind`: a panic is logged as an error and treated as a failure of the callback, so a batch
+//! written to a panicking sink is retried later and the worker keeps running. A panic in the worker itself
+//! stops telemetry submission. The [blocking](blocking/index.html) client reports it as
+//! `Error::Disconnected` afterwards.
+//!
+//! Panics of the application itself can be reported as exceptions with a hook installed by
+//! [`panics::register_panic_hook`](panics/fn.register_panic_hook.html).
+//!
+//! ## Processing telemetry
+//! Telemetry items can be modified or dropped before they are queued by processors registered with
+//! [`TelemetryClient::add_processor`](struct.TelemetryClient.html#method.add_processor). See
+//! [`processor`](processor/index.html) for details.
+//!
+//! ## Sampling
+//! Services with a lot of traffic can keep telemetry of a fixed percentage of operations only with
+//! [`TelemetryConfig::sampling_percentage`](struct.TelemetryConfig.html#method.sampling_percentage). See
+//! [`sampling`](sampling/index.html) for details. To keep the CPU footprint of the SDK bounded during traffic
+//! spikes, the client can also shed telemetry items temporarily while the channel falls behind. See
+//! [`overload`](overload/index.html) for details.
+//!
+//! ## Operation scopes
+//! Telemetry tracked within an async call chain can be correlated by running it within a
+//! [`scope`](scope/index.html). With the `macros` feature enabled, the `instrument_ai` attribute tracks
+//! every call of an async function as a dependency and runs its body within a scope of its own.
+//! [`TelemetryClient::start_operation`](struct.TelemetryClient.html#method.start_operation) starts an operation
+//! that correlates telemetry tracked within it and tracks itself as a request once it completes. Consumers of
+//! message queues can continue operations of producers propagated in message metadata with
+//! [`correlation`](correlation/index.html). With the `tower` feature enabled, a
+//! [`RequestTrackingLayer`](middleware/struct.RequestTrackingLayer.html) tracks every request a hyper or axum
+//! server handles as such an operation.
+//!
+//! ## Diagnostics
+//! Applications can observe batches sent and failed, telemetry items dropped and retries scheduled by the
+//! channel with an [`EventListener`](diagnostics/trait.EventListener.html) to expose health metrics of the SDK.
+//! A [`ChannelHealth`](struct.ChannelHealth.html) snapshot serializes the queue depth, the outcome of the most
+//! recent submission and throttling into a response of a health endpoint.
+//!
+//! ## Service level objectives
+//! A rolling success rate of requests and a remaining error budget can be submitted as metrics with
+//! a [`SuccessRate`](slo/struct.SuccessRate.html) to chart SLO compliance.
+//!
+//! ## Heartbeats
+//! Applications can opt in to submitting a periodic `HeartbeatState` metric with SDK and host metadata and
+//! custom fields with a [`Heartbeat`](heartbeat/struct.Heartbeat.html).
+//!
+//! ## Performance counters
+//! CPU usage, memory, threads and open handles of the process, as well as custom counters, can be submitted
+//! periodically as standard performance counters with
+//! [`PerformanceCounters`](performance/struct.PerformanceCounters.html).
+//!
+//! ## Periodic tasks
+//! Collectors such as heartbeats, performance counters, metric aggregators and success rates submit their
+//! telemetry from a task their `spawn` method starts. The task runs on the runtime configured with
+//! [`TelemetryConfig::runtime`](struct.TelemetryConfig.html#method.runtime) if any, or on the current runtime
+//! otherwise. It holds only a weak reference to the client, so it does not keep the client alive: the task stops
+//! once the last `Arc` of the client is dropped or the channel of the client is closed. The returned `JoinHandle` can be
+//! awaited or aborted to stop it earlier.
+//!
+//! ## Migrating from 0.1
+//! With the `compat` feature enabled, the [`compat`](compat/index.html) module provides the 0.1 names and
+//! signatures, such as `Config` and tracking methods that return a `Result`, on top of the blocking client.
+//!
+//! ## Disabled telemetry
+//! Libraries can instrument their code unconditionally and let the final binary decide whether telemetry
+//! is collected. When the crate is compiled with the `disabled` feature, telemetry clients swallow all
+//! telemetry items, never spawn a background task or thread and never connect to the network, so no
+//! Tokio runtime is needed. Cargo features are additive, so enabling `disabled` anywhere in the
+//! dependency graph turns telemetry off for the whole binary.
+//!
+//! The API stays the same, so instrumented code compiles either way: statistics of the channel are empty,
+//! connectivity checks fail with [`ConnectivityError::Disabled`](connectivity/enum.ConnectivityError.html)
+//! and periodic tasks complete right away.
+//!
+//! The default `runtime` feature brings in the `tokio`, `reqwest` and `chrono` dependencies. APIs that take or
+//! return their types, such as [`TelemetryChannel`](trait.TelemetryChannel.html), [`ChannelStats`](struct.ChannelStats.html) or `spawn` methods of periodic
+//! collectors, are available with this feature only. A binary that turns telemetry off and does not use these
+//! APIs anywhere in its dependency graph can leave the feature out to drop the dependencies.
+//!
+//! ```toml
+//! [dependencies]
+//! appinsights = { version = "0.2", default-features = false, features = ["disabled"] }
+//! ```
 #![deny(unused_extern_crates)]
 #![deny(missing_docs)]
 
+#[cfg(not(any(feature = "runtime", feature = "disabled")))]
+compile_error!("either the `runtime` feature, which is enabled by default, or the `disabled` feature is required");
+
+pub mod aggregation;
+
 #[cfg(feature = "blocking")]
 pub mod blocking;
 
+pub mod baggage;
+
+mod callback;
+
 mod channel;
+pub use channel::FlushReport;
+#[cfg(all(feature = "debug", feature = "runtime"))]
+pub use channel::QueuedItemSnapshot;
+#[cfg(feature = "runtime")]
+pub use channel::{
+    ChannelControl, ChannelHealth, ChannelStats, HealthStatus, InMemoryChannel, LatencyPercentiles, TelemetryChannel,
+    Transmission,
+};
+
+#[cfg(feature = "compat")]
+pub mod compat;
 
 mod client;
-pub use client::TelemetryClient;
+#[cfg(feature = "runtime")]
+mod codec;
+pub use client::{DependencyTracker, Operation, RequestTracker, Stopwatch, TelemetryClient, TelemetryHandle};
 
 mod config;
+mod connection_string;
+pub use connection_string::{ConnectionString, ConnectionStringError};
+pub mod connectivity;
 #[doc(inline)]
-pub use config::TelemetryConfig;
+pub use config::{Compression, OverflowPolicy, Proxy, TelemetryConfig};
 
 mod context;
-pub use context::TelemetryContext;
+pub mod correlation;
+pub use appinsights_core::{TelemetryContext, Tracker};
 
-mod contracts;
-pub mod telemetry;
+pub use appinsights_core::contracts;
+pub mod diagnostics;
+mod environment;
+pub mod ext;
+pub mod heartbeat;
+#[cfg(feature = "tower")]
+pub mod middleware;
+pub mod overload;
+pub mod panics;
+pub mod performance;
+pub mod processor;
+#[cfg(feature = "metrics")]
+pub mod recorder;
+pub mod sampling;
+pub mod scope;
+#[cfg(feature = "macros")]
+pub use appinsights_macros::instrument_ai;
+#[cfg(feature = "prometheus")]
+pub mod scrape;
+pub mod sink;
+pub mod slo;
+pub mod startup;
+pub use appinsights_core::telemetry;
+pub use appinsights_core::validation;
+#[cfg(all(any(test, feature = "test-util"), feature = "runtime"))]
+pub mod test_util;
 mod time;
+#[cfg(feature = "runtime")]
 mod timeout;
+pub mod timer;
+#[cfg(feature = "runtime")]
 mod transmitter;
-mod uuid;
 
+#[cfg(feature = "runtime")]
 use std::error::Error;
 
+#[cfg(feature = "runtime")]
 type Result<T> = std::result::Result<T, Box<dyn Error>>;
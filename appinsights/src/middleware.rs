@@ -0,0 +1,295 @@
+//! Automatic tracking of requests handled by `tower` services, such as hyper or axum servers.
+//!
+//! [`RequestTrackingLayer`] wraps a service and tracks every request it handles as request telemetry with its
+//! duration and response code. A request that carries a [W3C `traceparent`](https://www.w3.org/TR/trace-context/)
+//! header continues the operation of the caller, so the end-to-end transaction view shows both sides of the
+//! call. The wrapped service runs within the [scope](../scope/index.html) of the request, so telemetry tracked by
+//! handlers, including dependencies, refers to the request as its parent. The [`Scope`] of the request is also
+//! added to request extensions, so tasks spawned by handlers can run within it with [`scope::within`].
+//!
+//! Requests are named after the HTTP method and the URI path with identifiers replaced, see
+//! [`normalize_request_name`](../telemetry/fn.normalize_request_name.html). With the `axum` feature enabled,
+//! the route axum matched the request with is used instead of the path. The layer is available with the
+//! `tower` feature only.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! use std::sync::Arc;
+//! use appinsights::{middleware::RequestTrackingLayer, TelemetryClient};
+//! use axum::{routing::get, Router};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = Arc::new(TelemetryClient::new("<instrumentation key>".to_string()));
+//!
+//! let app: Router = Router::new()
+//!     .route("/users/:id", get(|| async { "user" }))
+//!     // a route layer runs after axum matched the route
+//!     .route_layer(RequestTrackingLayer::new(client));
+//! # }
+//! ```
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use http::{Request, Response};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    client::Operation,
+    correlation::{CorrelationContext, TRACEPARENT},
+    scope::{self, Scope},
+    telemetry::{normalize_request_name, OperationResult},
+    time, TelemetryClient,
+};
+
+/// A function that names a request.
+type NameFn = dyn Fn(&http::Method, &http::Uri, &http::Extensions) -> String + Send + Sync;
+
+/// A layer that tracks requests handled by the wrapped service.
+#[derive(Clone)]
+pub struct RequestTrackingLayer {
+    client: Arc<TelemetryClient>,
+    name: Arc<NameFn>,
+}
+
+impl RequestTrackingLayer {
+    /// Creates a layer that tracks requests with the given client.
+    pub fn new(client: Arc<TelemetryClient>) -> Self {
+        Self {
+            client,
+            name: Arc::new(request_name),
+        }
+    }
+
+    /// Names requests with the given function of the HTTP method, the URI and request extensions instead of
+    /// the default name.
+    pub fn name_with<F>(mut self, name: F) -> Self
+    where
+        F: Fn(&http::Method, &http::Uri, &http::Extensions) -> String + Send + Sync + 'static,
+    {
+        self.name = Arc::new(name);
+        self
+    }
+}
+
+impl<S> Layer<S> for RequestTrackingLayer {
+    type Service = RequestTracking<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestTracking {
+            inner,
+            client: self.client.clone(),
+            name: self.name.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for RequestTrackingLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestTrackingLayer").finish_non_exhaustive()
+    }
+}
+
+/// A service that tracks requests handled by the inner service. Created by [`RequestTrackingLayer`].
+#[derive(Clone)]
+pub struct RequestTracking<S> {
+    inner: S,
+    client: Arc<TelemetryClient>,
+    name: Arc<NameFn>,
+}
+
+impl<S, B, R> Service<Request<B>> for RequestTracking<S>
+where
+    S: Service<Request<B>, Response = Response<R>>,
+    S::Future: Send + 'static,
+    S::Error: fmt::Display,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        let started = Instant::now();
+        let timestamp = time::timestamp();
+        let name = (self.name)(request.method(), request.uri(), request.extensions());
+
+        let parent = request
+            .headers()
+            .get(TRACEPARENT)
+            .and_then(|value| value.to_str().ok())
+            .and_then(CorrelationContext::parse)
+            .map(|context| context.to_scope())
+            .or_else(Scope::current);
+        let scope = Scope::child(parent.as_ref());
+        request.extensions_mut().insert(scope.clone());
+
+        let future = scope::within_sync(scope.clone(), || self.inner.call(request));
+        let client = self.client.clone();
+        Box::pin(async move {
+            let mut operation = Operation::resume(&client, name, scope, parent, started, timestamp);
+            let result = operation.run(future).await;
+            match &result {
+                Ok(response) => operation.set_response_code(response.status().as_str()),
+                Err(err) => {
+                    operation.set_response_code("500");
+                    operation.set_result(OperationResult::failure(err.to_string()));
+                }
+            }
+            result
+        })
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for RequestTracking<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestTracking")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Names a request after the route axum matched it with, if any, or the normalized URI path.
+fn request_name(method: &http::Method, uri: &http::Uri, extensions: &http::Extensions) -> String {
+    #[cfg(feature = "axum")]
+    if let Some(path) = extensions.get::<axum::extract::MatchedPath>() {
+        return format!("{} {}", method, path.as_str());
+    }
+    #[cfg(not(feature = "axum"))]
+    let _ = extensions;
+
+    normalize_request_name(method, uri)
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use std::convert::Infallible;
+
+    use crossbeam_queue::SegQueue;
+    use futures_util::future::{self, Ready};
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data, Envelope, RequestData},
+        telemetry::tag_keys,
+        TelemetryConfig,
+    };
+
+    #[tokio::test]
+    async fn it_tracks_request_within_operation_of_caller() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::new("instrumentation".into());
+        let client = Arc::new(TelemetryClient::create(&config, TestChannel::new(events.clone())));
+
+        let mut service = RequestTrackingLayer::new(client.clone()).layer(Handler(client));
+        let request = Request::get("/users/42")
+            .header(TRACEPARENT, "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01")
+            .body(())
+            .unwrap();
+        let response = service.call(request).await.unwrap();
+        assert_eq!(response.status(), 404);
+
+        let event = events.pop().unwrap();
+        let scope = response.extensions().get::<Scope>().unwrap();
+        assert_eq!(tag(&event, tag_keys::OPERATION_ID), "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(tag(&event, tag_keys::OPERATION_PARENT_ID), scope.id());
+
+        let request = events.pop().unwrap();
+        assert_eq!(
+            tag(&request, tag_keys::OPERATION_ID),
+            "0af7651916cd43dd8448eb211c80319c"
+        );
+        assert_eq!(tag(&request, tag_keys::OPERATION_PARENT_ID), "b7ad6b7169203331");
+        let data = request_data(request);
+        assert_eq!(data.id, scope.id());
+        assert_eq!(data.name.as_deref(), Some("GET /users/{id}"));
+        assert_eq!(data.response_code, "404");
+        assert!(!data.success);
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn it_names_request_with_custom_function() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::new("instrumentation".into());
+        let client = Arc::new(TelemetryClient::create(&config, TestChannel::new(events.clone())));
+
+        let mut service = RequestTrackingLayer::new(client.clone())
+            .name_with(|method, _, _| format!("{} users", method))
+            .layer(Handler(client));
+        service.call(Request::get("/users/42").body(()).unwrap()).await.unwrap();
+
+        events.pop();
+        let data = request_data(events.pop().unwrap());
+        assert_eq!(data.name.as_deref(), Some("GET users"));
+    }
+
+    #[cfg(feature = "axum")]
+    #[tokio::test]
+    async fn it_names_request_after_matched_route() {
+        use axum::{body::Body, routing::get, Router};
+
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::new("instrumentation".into());
+        let client = Arc::new(TelemetryClient::create(&config, TestChannel::new(events.clone())));
+
+        let mut app: Router = Router::new()
+            .route("/users/:name", get(|| async { "user" }))
+            .route_layer(RequestTrackingLayer::new(client));
+        let response = app
+            .call(Request::get("/users/alice").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        let data = request_data(events.pop().unwrap());
+        assert_eq!(data.name.as_deref(), Some("GET /users/:name"));
+        assert_eq!(data.response_code, "200");
+    }
+
+    /// Tracks an event and responds with the scope it runs within.
+    struct Handler(Arc<TelemetryClient>);
+
+    impl Service<Request<()>> for Handler {
+        type Response = Response<()>;
+        type Error = Infallible;
+        type Future = Ready<Result<Response<()>, Infallible>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: Request<()>) -> Self::Future {
+            self.0.track_event("user requested");
+
+            let mut response = Response::builder().status(404).body(()).unwrap();
+            if let Some(scope) = request.extensions().get::<Scope>() {
+                response.extensions_mut().insert(scope.clone());
+            }
+            future::ready(Ok(response))
+        }
+    }
+
+    fn tag<'a>(envelope: &'a Envelope, key: &str) -> &'a str {
+        envelope.tags.as_ref().and_then(|tags| tags.get(key)).unwrap()
+    }
+
+    fn request_data(envelope: Envelope) -> RequestData {
+        match envelope.data {
+            Some(Base::Data(Data::RequestData(data))) => data,
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+}
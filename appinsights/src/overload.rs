@@ -0,0 +1,194 @@
+//! Shedding of telemetry items while the channel falls behind.
+//!
+//! During incident-level traffic spikes an application may track telemetry faster than the channel worker
+//! submits it, and the SDK ends up competing with the application for CPU. With
+//! [`TelemetryConfigBuilder::overload_threshold`] configured, the client watches the processing lag of the
+//! worker, i.e. how long the oldest telemetry item waiting in the queue has been there. While
+//! the lag exceeds the threshold, the client drops verbose traces and samples other telemetry items at
+//! [`SAMPLING_FACTOR`] of the sampling percentage. The lag is re-evaluated at most once per [`CHECK_INTERVAL`],
+//! so the guard adds little overhead to tracking a telemetry item, and the client returns to the configured
+//! behavior as soon as the worker catches up.
+//!
+//! The blocking client hands telemetry items over to a channel on its own thread without looking at the lag,
+//! so it does not shed telemetry items.
+//!
+//! [`TelemetryConfigBuilder::overload_threshold`]: ../struct.TelemetryConfigBuilder.html#method.overload_threshold
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! use std::time::Duration;
+//! use appinsights::{TelemetryClient, TelemetryConfig};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .overload_threshold(Duration::from_secs(30))
+//!     .build();
+//! let client = TelemetryClient::from_config(config);
+//! # }
+//! ```
+#[cfg(not(feature = "disabled"))]
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+use std::time::Duration;
+
+#[cfg(not(feature = "disabled"))]
+use log::{debug, warn};
+#[cfg(not(feature = "disabled"))]
+use tokio::time::Instant;
+
+#[cfg(not(feature = "disabled"))]
+use crate::{
+    channel::TelemetryChannel,
+    contracts::{Base, Data, Envelope, SeverityLevel},
+};
+
+/// A fraction of the sampling percentage telemetry items are sampled at while the channel is overloaded.
+pub const SAMPLING_FACTOR: f64 = 0.1;
+
+/// Minimum time between two evaluations of the processing lag.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Watches processing lag of a channel and determines whether telemetry items are to be shed.
+#[cfg(not(feature = "disabled"))]
+#[derive(Debug)]
+pub(crate) struct OverloadGuard {
+    threshold: Duration,
+    overloaded: AtomicBool,
+    checked: Mutex<Option<Instant>>,
+}
+
+#[cfg(not(feature = "disabled"))]
+impl OverloadGuard {
+    /// Creates a guard if the threshold is configured.
+    pub(crate) fn new(threshold: Option<Duration>) -> Option<Self> {
+        threshold.map(|threshold| Self {
+            threshold,
+            overloaded: AtomicBool::new(false),
+            checked: Mutex::new(None),
+        })
+    }
+
+    /// Returns `true` if the processing lag of the channel exceeded the threshold when it was evaluated last.
+    /// Re-evaluates the lag if it has not been evaluated within the check interval.
+    pub(crate) fn is_overloaded(&self, channel: &dyn TelemetryChannel) -> bool {
+        // another thread that holds the lock is evaluating the lag right now
+        if let Ok(mut checked) = self.checked.try_lock() {
+            let now = Instant::now();
+            if checked.is_none_or(|checked| now.duration_since(checked) >= CHECK_INTERVAL) {
+                *checked = Some(now);
+                self.update(channel.stats().processing_lag());
+            }
+        }
+        self.overloaded.load(Ordering::Relaxed)
+    }
+
+    fn update(&self, lag: Option<Duration>) {
+        let overloaded = lag.is_some_and(|lag| lag > self.threshold);
+        if self.overloaded.swap(overloaded, Ordering::Relaxed) != overloaded {
+            if overloaded {
+                warn!(
+                    "Telemetry channel is {:?} behind. Shedding verbose traces and sampling telemetry items at {} of configured percentage",
+                    lag.unwrap_or_default(),
+                    SAMPLING_FACTOR
+                );
+            } else {
+                debug!("Telemetry channel caught up. Stopped shedding telemetry items");
+            }
+        }
+    }
+}
+
+/// Returns `true` if an item is a trace to drop while the channel is overloaded.
+#[cfg(not(feature = "disabled"))]
+pub(crate) fn is_shed(envelope: &Envelope) -> bool {
+    matches!(
+        &envelope.data,
+        Some(Base::Data(Data::MessageData(data))) if data.severity_level == Some(SeverityLevel::Verbose)
+    )
+}
+
+/// Returns a sampling percentage to apply while the channel is overloaded or not.
+#[cfg(not(feature = "disabled"))]
+pub(crate) fn percentage(percentage: f64, overloaded: bool) -> f64 {
+    if overloaded {
+        percentage * SAMPLING_FACTOR
+    } else {
+        percentage
+    }
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::channel::ChannelStats;
+
+    #[tokio::test(start_paused = true)]
+    async fn it_evaluates_lag_once_per_check_interval() {
+        let channel = LaggingChannel::default();
+        let guard = OverloadGuard::new(Some(Duration::from_secs(30))).unwrap();
+        assert!(!guard.is_overloaded(&channel), "not overloaded until drained");
+
+        channel.set(Duration::from_secs(45));
+        assert!(
+            !guard.is_overloaded(&channel),
+            "lag is not evaluated within check interval"
+        );
+
+        tokio::time::advance(CHECK_INTERVAL).await;
+        assert!(guard.is_overloaded(&channel));
+
+        channel.set(Duration::from_secs(2));
+        tokio::time::advance(CHECK_INTERVAL).await;
+        assert!(!guard.is_overloaded(&channel));
+    }
+
+    #[test]
+    fn it_sheds_verbose_traces_only() {
+        let trace = |severity_level| Envelope {
+            data: Some(Base::Data(Data::MessageData(crate::contracts::MessageData {
+                severity_level: Some(severity_level),
+                ..Default::default()
+            }))),
+            ..Envelope::default()
+        };
+
+        assert!(is_shed(&trace(SeverityLevel::Verbose)));
+        assert!(!is_shed(&trace(SeverityLevel::Warning)));
+        assert!(!is_shed(&Envelope::default()));
+    }
+
+    #[derive(Default)]
+    struct LaggingChannel(Mutex<Option<Duration>>);
+
+    impl LaggingChannel {
+        fn set(&self, lag: Duration) {
+            *self.0.lock().unwrap() = Some(lag);
+        }
+    }
+
+    #[async_trait]
+    impl TelemetryChannel for LaggingChannel {
+        fn send(&self, _: Envelope) {}
+
+        fn flush(&self) {}
+
+        fn stats(&self) -> ChannelStats {
+            let mut stats = ChannelStats::default();
+            if let Some(lag) = *self.0.lock().unwrap() {
+                stats.set_processing_lag(lag);
+            }
+            stats
+        }
+
+        async fn close(&mut self) {}
+
+        async fn terminate(&mut self) {}
+    }
+}
@@ -0,0 +1,218 @@
+//! Reporting of unhandled panics.
+//!
+//! A panic usually takes the process down before the channel gets a chance to submit pending telemetry,
+//! so the panic itself is lost together with the telemetry that led to it. [`register_panic_hook`] installs
+//! a panic hook that reports the panic as an exception with critical severity. Its message, location and
+//! backtrace are tracked with the client like any other telemetry item, so telemetry processors and the
+//! ambient scope apply to it. The hook then flushes the channel and blocks the panicking thread until the
+//! channel reported the flush back or [`SUBMIT_TIMEOUT`] expired. The wait happens on a dedicated thread with
+//! its own runtime, so it ends with the timeout at the latest when the channel worker runs on the very thread
+//! that panicked. The previously installed hook runs afterwards, so the panic is still printed as usual.
+//!
+//! With the `backtrace` feature enabled, the backtrace is submitted as structured stack frames rather than
+//! a single string, so the end-to-end transaction view in the portal shows methods, files and lines.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # use std::sync::Arc;
+//! use appinsights::{panics, TelemetryClient};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = Arc::new(TelemetryClient::new("<instrumentation key>".to_string()));
+//! panics::register_panic_hook(client.clone());
+//!
+//! panic!("unrecoverable state");
+//! # }
+//! ```
+use std::{backtrace::Backtrace, panic, panic::Location, sync::Arc, thread, time::Duration};
+
+#[cfg(not(feature = "disabled"))]
+use log::{debug, error};
+
+use crate::{
+    callback,
+    telemetry::{ExceptionTelemetry, SeverityLevel, StackFrame, Telemetry},
+    TelemetryClient,
+};
+
+/// Maximum time the panic hook waits for the exception and pending telemetry to be submitted.
+pub const SUBMIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A type name of exceptions that report panics.
+const PANIC_TYPE_NAME: &str = "panic";
+
+/// Installs a panic hook that reports panics as exceptions with the given client. The previously installed
+/// hook is called after the exception has been submitted.
+pub fn register_panic_hook(client: Arc<TelemetryClient>) {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if client.is_enabled() {
+            let backtrace = Backtrace::force_capture().to_string();
+            client.track(exception(
+                callback::message(info.payload()),
+                info.location(),
+                &backtrace,
+            ));
+            #[cfg(not(feature = "disabled"))]
+            flush(client.clone());
+        }
+
+        previous(info);
+    }));
+}
+
+/// Creates an exception telemetry item that describes a panic.
+fn exception(message: &str, location: Option<&Location<'_>>, backtrace: &str) -> ExceptionTelemetry {
+    let mut telemetry = ExceptionTelemetry::new(PANIC_TYPE_NAME, message);
+    telemetry.set_severity(SeverityLevel::Critical);
+
+    if let Some(location) = location {
+        telemetry
+            .properties_mut()
+            .insert("location".into(), location.to_string());
+    }
+    if cfg!(feature = "backtrace") {
+        telemetry.set_parsed_stack(StackFrame::parse_backtrace(backtrace));
+    } else {
+        let mut stack = String::new();
+        if let Some(location) = location {
+            stack.push_str(&format!("at {}\n", location));
+        }
+        stack.push_str(backtrace);
+        telemetry.set_stack(stack);
+    }
+
+    if let Some(name) = thread::current().name() {
+        telemetry.properties_mut().insert("thread".into(), name.into());
+    }
+
+    telemetry
+}
+
+/// Flushes the channel of the client and blocks until the channel reported the flush back or the timeout
+/// expired. The wait happens on a dedicated thread, since the current thread may run a runtime.
+#[cfg(not(feature = "disabled"))]
+fn flush(client: Arc<TelemetryClient>) {
+    let flushed = thread::Builder::new()
+        .name("appinsights-panic".into())
+        .spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+            let timer = client.config().timer();
+            rt.block_on(async {
+                match timer.timeout(SUBMIT_TIMEOUT, client.flush_and_wait()).await {
+                    Some(report) => debug!(
+                        "Panic submitted: {} telemetry items sent, {} failed",
+                        report.sent(),
+                        report.failed()
+                    ),
+                    None => error!("Unable to submit panic within {:?}", SUBMIT_TIMEOUT),
+                }
+            });
+            Ok::<_, std::io::Error>(())
+        })
+        .map(|handle| handle.join());
+
+    match flushed {
+        Ok(Ok(Ok(()))) => {}
+        Ok(Ok(Err(err))) => error!("Unable to create runtime to submit panic: {}", err),
+        Ok(Err(_)) => error!("Thread submitting panic panicked"),
+        Err(err) => error!("Unable to spawn thread to submit panic: {}", err),
+    }
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use std::sync::Mutex;
+
+    use hyper::{
+        body::Buf,
+        service::{make_service_fn, service_fn},
+        Body, Request, Server,
+    };
+
+    use super::*;
+    use crate::{contracts::Envelope, TelemetryConfig};
+
+    #[cfg(not(feature = "backtrace"))]
+    #[test]
+    fn it_creates_exception_for_panic() {
+        let location = Location::caller();
+
+        let telemetry = exception("unrecoverable state", Some(location), "   0: main\n");
+
+        assert_eq!(telemetry.type_name(), "panic");
+        assert_eq!(telemetry.message(), "unrecoverable state");
+        assert_eq!(telemetry.severity(), SeverityLevel::Critical);
+        assert_eq!(
+            telemetry.stack(),
+            Some(format!("at {}\n   0: main\n", location).as_str())
+        );
+        assert_eq!(telemetry.properties().get("location"), Some(&location.to_string()));
+    }
+
+    #[cfg(feature = "backtrace")]
+    #[test]
+    fn it_creates_exception_with_stack_frames_for_panic() {
+        let location = Location::caller();
+
+        let telemetry = exception(
+            "unrecoverable state",
+            Some(location),
+            "   0: app::main\n             at ./src/main.rs:4:5\n",
+        );
+
+        assert_eq!(telemetry.stack(), None);
+        assert_eq!(telemetry.parsed_stack().len(), 1);
+        assert_eq!(telemetry.parsed_stack()[0].method(), "app::main");
+        assert_eq!(telemetry.parsed_stack()[0].line(), Some(4));
+        assert_eq!(telemetry.properties().get("location"), Some(&location.to_string()));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn it_submits_exception_through_client_synchronously() {
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let make_service = {
+            let bodies = bodies.clone();
+            make_service_fn(move |_| {
+                let bodies = bodies.clone();
+                async move {
+                    Ok::<_, hyper::Error>(service_fn(move |request: Request<Body>| {
+                        let bodies = bodies.clone();
+                        async move {
+                            let body = hyper::body::aggregate(request).await?;
+                            let mut content = String::new();
+                            std::io::Read::read_to_string(&mut body.reader(), &mut content).unwrap();
+                            bodies.lock().unwrap().push(content);
+                            Ok::<_, hyper::Error>(hyper::Response::new(Body::empty()))
+                        }
+                    }))
+                }
+            })
+        };
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let endpoint = format!("http://{}/v2/track", server.local_addr());
+        tokio::spawn(server);
+
+        let config = TelemetryConfig::builder().i_key("key").endpoint(endpoint).build();
+        let mut client = TelemetryClient::from_config(config);
+        client.add_processor(|envelope: &mut Envelope| {
+            envelope
+                .tags
+                .get_or_insert_with(Default::default)
+                .insert("ai.cloud.role".into(), "api".into());
+            true
+        });
+        let client = Arc::new(client);
+
+        // blocks until submitted, as a panic hook does
+        client.track(exception("unrecoverable state", None, ""));
+        tokio::task::block_in_place(|| flush(client.clone()));
+
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 1);
+        assert!(bodies[0].contains("unrecoverable state"));
+        assert!(bodies[0].contains(r#""ai.cloud.role":"api""#));
+    }
+}
@@ -0,0 +1,278 @@
+//! Periodic collection of process performance counters.
+//!
+//! [`PerformanceCounters`] samples resource usage of the current process on a regular basis and submits it as
+//! metrics named after standard Application Insights performance counters, so the values show up in the
+//! performance counters view of the portal next to counters of applications written in other languages:
+//! * [`PROCESS_CPU`] - CPU time the process used as a percentage of one processor,
+//! * [`PROCESS_CPU_NORMALIZED`] - the same percentage divided by the number of processors,
+//! * [`PROCESS_WORKING_SET`] - resident memory of the process in bytes,
+//! * [`PROCESS_THREAD_COUNT`] - number of threads of the process,
+//! * [`PROCESS_HANDLE_COUNT`] - number of file descriptors the process holds open.
+//!
+//! Built-in counters are read from `/proc` and available on Linux only, so no values are submitted for them on
+//! other platforms. Rust has no garbage collector to report statistics of, but custom counters, e.g. allocator
+//! statistics, can be registered with [`PerformanceCounters::register`].
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # use std::{sync::Arc, time::Duration};
+//! use appinsights::{performance::PerformanceCounters, TelemetryClient};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = Arc::new(TelemetryClient::new("<instrumentation key>".to_string()));
+//!
+//! let mut counters = PerformanceCounters::new();
+//! counters.register("Cache entries", || Some(42.0));
+//! counters.spawn(&client, Duration::from_secs(60));
+//! # }
+//! ```
+#[cfg(feature = "runtime")]
+use std::{sync::Arc, time::Duration};
+
+#[cfg(not(feature = "runtime"))]
+use std::time::Instant;
+
+#[cfg(feature = "runtime")]
+use tokio::task::JoinHandle;
+#[cfg(feature = "runtime")]
+use tokio::time::Instant;
+
+#[cfg(feature = "runtime")]
+use crate::{client, TelemetryClient};
+use crate::{
+    telemetry::{MetricTelemetry, Telemetry},
+    Tracker,
+};
+
+/// A name of the counter of CPU usage of the process.
+pub const PROCESS_CPU: &str = r"\Process(??APP_WIN32_PROC??)\% Processor Time";
+
+/// A name of the counter of CPU usage of the process divided by the number of processors.
+pub const PROCESS_CPU_NORMALIZED: &str = r"\Process(??APP_WIN32_PROC??)\% Processor Time Normalized";
+
+/// A name of the counter of resident memory of the process.
+pub const PROCESS_WORKING_SET: &str = r"\Process(??APP_WIN32_PROC??)\Working Set";
+
+/// A name of the counter of threads of the process.
+pub const PROCESS_THREAD_COUNT: &str = r"\Process(??APP_WIN32_PROC??)\Thread Count";
+
+/// A name of the counter of open handles of the process.
+pub const PROCESS_HANDLE_COUNT: &str = r"\Process(??APP_WIN32_PROC??)\Handle Count";
+
+/// A name of the property that marks metrics as performance counters.
+const CUSTOM_PERF_COUNTER: &str = "CustomPerfCounter";
+
+/// Number of clock ticks per second CPU times in `/proc` are measured in.
+const USER_HZ: f64 = 100.0;
+
+/// Samples a value of a performance counter.
+pub trait Counter: Send + 'static {
+    /// Returns a current value of the counter, or `None` if it is not available at the moment.
+    fn sample(&mut self) -> Option<f64>;
+}
+
+impl<F> Counter for F
+where
+    F: FnMut() -> Option<f64> + Send + 'static,
+{
+    fn sample(&mut self) -> Option<f64> {
+        self()
+    }
+}
+
+/// Collects values of built-in and custom performance counters and submits them as metrics.
+pub struct PerformanceCounters {
+    counters: Vec<(String, Box<dyn Counter>)>,
+}
+
+impl PerformanceCounters {
+    /// Creates a collector of built-in counters of the current process.
+    pub fn new() -> Self {
+        let mut counters = Self::empty();
+        counters.register(PROCESS_CPU, ProcessCpu::new(false));
+        counters.register(PROCESS_CPU_NORMALIZED, ProcessCpu::new(true));
+        counters.register(PROCESS_WORKING_SET, || {
+            read("/proc/self/status").and_then(|s| working_set(&s))
+        });
+        counters.register(PROCESS_THREAD_COUNT, || {
+            read("/proc/self/stat")
+                .and_then(|s| ProcessStat::parse(&s))
+                .map(|stat| stat.threads)
+        });
+        counters.register(PROCESS_HANDLE_COUNT, || {
+            std::fs::read_dir("/proc/self/fd").ok().map(|fds| fds.count() as f64)
+        });
+        counters
+    }
+
+    /// Creates a collector without any counters, e.g. to submit custom counters only.
+    pub fn empty() -> Self {
+        Self { counters: Vec::new() }
+    }
+
+    /// Registers a custom counter submitted as a metric with the given name.
+    pub fn register(&mut self, name: impl Into<String>, counter: impl Counter) {
+        self.counters.push((name.into(), Box::new(counter)));
+    }
+
+    /// Samples all counters and submits available values with the given tracker.
+    pub fn submit<T: Tracker>(&mut self, tracker: &T) {
+        for (name, counter) in &mut self.counters {
+            if let Some(value) = counter.sample().filter(|value| value.is_finite()) {
+                let mut telemetry = MetricTelemetry::new(name.as_str(), value);
+                telemetry
+                    .properties_mut()
+                    .insert(CUSTOM_PERF_COUNTER.into(), "true".into());
+                tracker.track(telemetry);
+            }
+        }
+    }
+
+    /// Spawns a [periodic task](../index.html#periodic-tasks) that samples and submits counters with the given client
+    /// every `interval`.
+    #[cfg(feature = "runtime")]
+    pub fn spawn(mut self, client: &Arc<TelemetryClient>, interval: Duration) -> JoinHandle<()> {
+        client::spawn_periodic(client, interval, move |client| self.submit(client))
+    }
+}
+
+impl Default for PerformanceCounters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Samples CPU usage of the process since the previous sample. The first sample has no value.
+struct ProcessCpu {
+    normalized: bool,
+    previous: Option<(Instant, f64)>,
+}
+
+impl ProcessCpu {
+    fn new(normalized: bool) -> Self {
+        Self {
+            normalized,
+            previous: None,
+        }
+    }
+}
+
+impl Counter for ProcessCpu {
+    fn sample(&mut self) -> Option<f64> {
+        let now = Instant::now();
+        let cpu_time = ProcessStat::parse(&read("/proc/self/stat")?)?.cpu_time;
+        let (then, previous) = self.previous.replace((now, cpu_time))?;
+
+        let elapsed = now.duration_since(then).as_secs_f64();
+        if elapsed <= 0.0 {
+            return None;
+        }
+        let mut percentage = (cpu_time - previous) / elapsed * 100.0;
+        if self.normalized {
+            percentage /= std::thread::available_parallelism().map_or(1, |n| n.get()) as f64;
+        }
+        Some(percentage)
+    }
+}
+
+/// Values of `/proc/self/stat` counters are derived from.
+#[derive(Debug, PartialEq)]
+struct ProcessStat {
+    /// CPU time in seconds the process spent in user and kernel mode.
+    cpu_time: f64,
+    threads: f64,
+}
+
+impl ProcessStat {
+    fn parse(stat: &str) -> Option<Self> {
+        // a command name in parentheses may contain spaces, so fields are counted from its end
+        let fields: Vec<_> = stat.get(stat.rfind(')')? + 1..)?.split_whitespace().collect();
+        let ticks = |index: usize| fields.get(index)?.parse::<f64>().ok();
+        Some(Self {
+            cpu_time: (ticks(11)? + ticks(12)?) / USER_HZ,
+            threads: ticks(17)?,
+        })
+    }
+}
+
+/// Reads resident memory in bytes from `/proc/self/status`.
+fn working_set(status: &str) -> Option<f64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: f64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024.0)
+}
+
+fn read(path: &str) -> Option<String> {
+    std::fs::read_to_string(path).ok()
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use crossbeam_queue::SegQueue;
+
+    use super::*;
+    use crate::client::tests::{create_client, metric_data};
+
+    #[test]
+    fn it_submits_custom_counters() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let mut counters = PerformanceCounters::empty();
+        let mut entries = 0.0;
+        counters.register("Cache entries", move || {
+            entries += 10.0;
+            Some(entries)
+        });
+        counters.register("Unavailable", || None);
+        counters.submit(&client);
+        counters.submit(&client);
+
+        let data = metric_data(events.pop());
+        assert_eq!(data.metrics[0].name, "Cache entries");
+        assert_eq!(data.metrics[0].value, 10.0);
+        assert_eq!(data.properties.unwrap()[CUSTOM_PERF_COUNTER], "true");
+        assert_eq!(metric_data(events.pop()).metrics[0].value, 20.0);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn it_parses_process_stat() {
+        let stat = "4242 (my (app) srv) S 1 4242 4242 0 -1 4194560 1205 0 0 0 250 50 0 0 20 0 7 0 1088 \
+                    22556672 1530 18446744073709551615";
+
+        assert_eq!(
+            ProcessStat::parse(stat),
+            Some(ProcessStat {
+                cpu_time: 3.0,
+                threads: 7.0
+            })
+        );
+        assert_eq!(ProcessStat::parse("4242 (app"), None);
+    }
+
+    #[test]
+    fn it_parses_working_set() {
+        let status = "Name:\tapp\nVmPeak:\t  30000 kB\nVmRSS:\t    6120 kB\nThreads:\t7\n";
+
+        assert_eq!(working_set(status), Some(6120.0 * 1024.0));
+        assert_eq!(working_set("Name:\tapp\n"), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn it_samples_process_counters() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        PerformanceCounters::new().submit(&client);
+
+        // CPU usage is not known until the second sample
+        let names: Vec<_> = std::iter::from_fn(|| events.pop())
+            .map(|envelope| metric_data(Some(envelope)).metrics.remove(0).name)
+            .collect();
+        assert_eq!(names, [PROCESS_WORKING_SET, PROCESS_THREAD_COUNT, PROCESS_HANDLE_COUNT]);
+    }
+}
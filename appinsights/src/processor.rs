@@ -0,0 +1,104 @@
+//! Processing of telemetry items before they are queued.
+//!
+//! A [`TelemetryProcessor`] registered with a telemetry client sees every telemetry item right before it is
+//! queued for submission. It can modify the item, e.g. scrub personal data from URLs or add computed properties,
+//! or drop it altogether, e.g. skip requests of health checks. Processors run in the order they were added on
+//! the thread that tracks the item, so they should be cheap. A processor that panics is skipped and the item is
+//! kept as is.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! use appinsights::{contracts::{Base, Data, Envelope}, TelemetryClient};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+//!
+//! // drop requests of health checks
+//! client.add_processor(|envelope: &mut Envelope| match &envelope.data {
+//!     Some(Base::Data(Data::RequestData(data))) => !data.url.as_deref().is_some_and(|url| url.ends_with("/health")),
+//!     _ => true,
+//! });
+//! # }
+//! ```
+use std::sync::Arc;
+
+#[cfg(not(feature = "disabled"))]
+use crate::callback;
+use crate::contracts::Envelope;
+
+/// Modifies or drops telemetry items before they are queued.
+pub trait TelemetryProcessor: Send + Sync {
+    /// Processes a telemetry item. Returns `false` to drop the item.
+    fn process(&self, envelope: &mut Envelope) -> bool;
+}
+
+impl<F> TelemetryProcessor for F
+where
+    F: Fn(&mut Envelope) -> bool + Send + Sync,
+{
+    fn process(&self, envelope: &mut Envelope) -> bool {
+        self(envelope)
+    }
+}
+
+/// A chain of processors each telemetry item passes through.
+#[derive(Clone, Default)]
+pub(crate) struct Pipeline {
+    processors: Vec<Arc<dyn TelemetryProcessor>>,
+}
+
+impl Pipeline {
+    /// Appends a processor to the end of the chain.
+    pub(crate) fn add(&mut self, processor: impl TelemetryProcessor + 'static) {
+        self.processors.push(Arc::new(processor));
+    }
+
+    /// Runs the item through all processors. Returns `false` as soon as a processor drops the item.
+    #[cfg(not(feature = "disabled"))]
+    pub(crate) fn process(&self, envelope: &mut Envelope) -> bool {
+        self.processors
+            .iter()
+            .all(|processor| callback::call("Telemetry processor", || processor.process(envelope)).unwrap_or(true))
+    }
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_runs_processors_in_order() {
+        let mut pipeline = Pipeline::default();
+        pipeline.add(|envelope: &mut Envelope| {
+            envelope.name.to_mut().push('a');
+            true
+        });
+        pipeline.add(|envelope: &mut Envelope| {
+            envelope.name.to_mut().push('b');
+            true
+        });
+
+        let mut envelope = Envelope::default();
+        assert!(pipeline.process(&mut envelope));
+        assert_eq!(envelope.name, "ab");
+    }
+
+    #[test]
+    fn it_stops_when_item_dropped() {
+        let mut pipeline = Pipeline::default();
+        pipeline.add(|_: &mut Envelope| false);
+        pipeline.add(|_: &mut Envelope| -> bool { panic!("item has been dropped already") });
+
+        assert!(!pipeline.process(&mut Envelope::default()));
+    }
+
+    #[test]
+    fn it_keeps_item_when_processor_panicked() {
+        let mut pipeline = Pipeline::default();
+        pipeline.add(|_: &mut Envelope| -> bool { panic!("processor failed") });
+
+        assert!(pipeline.process(&mut Envelope::default()));
+    }
+}
@@ -0,0 +1,320 @@
+//! Integration with the [`metrics`](https://docs.rs/metrics) facade.
+//!
+//! Code instrumented with `metrics` macros reports to Application Insights once [`MetricsRecorder`] is
+//! installed as the global recorder. Values are aggregated in memory and submitted periodically:
+//! * a counter is submitted as a metric with the increase since the previous submission,
+//! * a gauge is submitted as a metric with the last value,
+//! * a histogram is submitted as an aggregated metric of values recorded since the previous submission.
+//!
+//! Metric labels become custom properties of submitted telemetry items.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # use std::{sync::Arc, time::Duration};
+//! use appinsights::{recorder::MetricsRecorder, TelemetryClient};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = Arc::new(TelemetryClient::new("<instrumentation key>".to_string()));
+//! let handle = MetricsRecorder::new().install().expect("no other recorder installed");
+//! handle.spawn(&client, Duration::from_secs(60));
+//!
+//! metrics::increment_counter!("requests_total", "method" => "GET");
+//! metrics::histogram!("request_duration", 0.25);
+//! # }
+//! ```
+#[cfg(feature = "runtime")]
+use std::time::Duration;
+use std::{
+    collections::HashMap,
+    mem,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use metrics::{
+    Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, Recorder, SetRecorderError, SharedString,
+    Unit,
+};
+#[cfg(feature = "runtime")]
+use tokio::task::JoinHandle;
+
+#[cfg(feature = "runtime")]
+use crate::{client, TelemetryClient};
+use crate::{
+    telemetry::{AggregateMetricTelemetry, MetricTelemetry, Properties, Telemetry},
+    Tracker,
+};
+
+/// A recorder for the `metrics` facade that collects values to submit to Application Insights.
+#[derive(Debug, Default)]
+pub struct MetricsRecorder {
+    registry: Arc<Registry>,
+}
+
+impl MetricsRecorder {
+    /// Creates a new recorder without any metrics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a handle to submit metrics collected by this recorder.
+    pub fn handle(&self) -> MetricsHandle {
+        MetricsHandle {
+            registry: self.registry.clone(),
+        }
+    }
+
+    /// Installs this recorder as the global recorder of the `metrics` facade. Fails when another recorder
+    /// has been installed already.
+    pub fn install(self) -> Result<MetricsHandle, SetRecorderError> {
+        let handle = self.handle();
+        metrics::set_boxed_recorder(Box::new(self))?;
+        Ok(handle)
+    }
+}
+
+impl Recorder for MetricsRecorder {
+    fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn register_counter(&self, key: &Key) -> Counter {
+        Counter::from_arc(register(&self.registry.counters, key))
+    }
+
+    fn register_gauge(&self, key: &Key) -> Gauge {
+        Gauge::from_arc(register(&self.registry.gauges, key))
+    }
+
+    fn register_histogram(&self, key: &Key) -> Histogram {
+        Histogram::from_arc(register(&self.registry.histograms, key))
+    }
+}
+
+/// A handle to submit metrics collected by [`MetricsRecorder`] as telemetry items.
+#[derive(Debug, Clone)]
+pub struct MetricsHandle {
+    registry: Arc<Registry>,
+}
+
+impl MetricsHandle {
+    /// Submits values collected since the previous submission with the given tracker.
+    pub fn submit<T: Tracker>(&self, tracker: &T) {
+        for (key, counter) in snapshot(&self.registry.counters) {
+            let mut telemetry = MetricTelemetry::new(key.name(), counter.take() as f64);
+            set_labels(&key, telemetry.properties_mut());
+            tracker.track(telemetry);
+        }
+
+        for (key, gauge) in snapshot(&self.registry.gauges) {
+            let mut telemetry = MetricTelemetry::new(key.name(), gauge.get());
+            set_labels(&key, telemetry.properties_mut());
+            tracker.track(telemetry);
+        }
+
+        for (key, histogram) in snapshot(&self.registry.histograms) {
+            let values = histogram.take();
+            if values.is_empty() {
+                continue;
+            }
+
+            let mut telemetry = AggregateMetricTelemetry::new(key.name());
+            telemetry.stats_mut().add_data(&values);
+            set_labels(&key, telemetry.properties_mut());
+            tracker.track(telemetry);
+        }
+    }
+
+    /// Spawns a [periodic task](../index.html#periodic-tasks) that submits collected values with the given client every
+    /// `interval`.
+    #[cfg(feature = "runtime")]
+    pub fn spawn(self, client: &Arc<TelemetryClient>, interval: Duration) -> JoinHandle<()> {
+        client::spawn_periodic(client, interval, move |client| self.submit(client))
+    }
+}
+
+/// Metrics registered in the recorder.
+#[derive(Debug, Default)]
+struct Registry {
+    counters: Mutex<HashMap<Key, Arc<CounterCell>>>,
+    gauges: Mutex<HashMap<Key, Arc<GaugeCell>>>,
+    histograms: Mutex<HashMap<Key, Arc<HistogramCell>>>,
+}
+
+/// Returns a metric with the given key, registering a new one if necessary.
+fn register<T: Default>(metrics: &Mutex<HashMap<Key, Arc<T>>>, key: &Key) -> Arc<T> {
+    let mut metrics = metrics.lock().unwrap_or_else(|err| err.into_inner());
+    metrics.entry(key.clone()).or_default().clone()
+}
+
+/// Returns all registered metrics without holding a lock while they are submitted.
+fn snapshot<T>(metrics: &Mutex<HashMap<Key, Arc<T>>>) -> Vec<(Key, Arc<T>)> {
+    let metrics = metrics.lock().unwrap_or_else(|err| err.into_inner());
+    metrics
+        .iter()
+        .map(|(key, metric)| (key.clone(), metric.clone()))
+        .collect()
+}
+
+/// Records metric labels as custom properties.
+fn set_labels(key: &Key, properties: &mut Properties) {
+    for label in key.labels() {
+        properties.insert(label.key().into(), label.value().into());
+    }
+}
+
+/// A counter that keeps the total value and the value at the time of the previous submission.
+#[derive(Debug, Default)]
+struct CounterCell {
+    total: AtomicU64,
+    submitted: AtomicU64,
+}
+
+impl CounterCell {
+    /// Returns an increase since the previous call.
+    fn take(&self) -> u64 {
+        let total = self.total.load(Ordering::Acquire);
+        let submitted = self.submitted.swap(total, Ordering::AcqRel);
+        total.saturating_sub(submitted)
+    }
+}
+
+impl CounterFn for CounterCell {
+    fn increment(&self, value: u64) {
+        self.total.fetch_add(value, Ordering::Release);
+    }
+
+    fn absolute(&self, value: u64) {
+        self.total.fetch_max(value, Ordering::AcqRel);
+    }
+}
+
+/// A gauge that keeps bits of the last `f64` value.
+#[derive(Debug, Default)]
+struct GaugeCell {
+    value: AtomicU64,
+}
+
+impl GaugeCell {
+    fn get(&self) -> f64 {
+        f64::from_bits(self.value.load(Ordering::Acquire))
+    }
+
+    fn update(&self, f: impl Fn(f64) -> f64) {
+        let _ = self.value.fetch_update(Ordering::AcqRel, Ordering::Acquire, |bits| {
+            Some(f(f64::from_bits(bits)).to_bits())
+        });
+    }
+}
+
+impl GaugeFn for GaugeCell {
+    fn increment(&self, value: f64) {
+        self.update(|current| current + value)
+    }
+
+    fn decrement(&self, value: f64) {
+        self.update(|current| current - value)
+    }
+
+    fn set(&self, value: f64) {
+        self.value.store(value.to_bits(), Ordering::Release);
+    }
+}
+
+/// A histogram that keeps values recorded since the previous submission.
+#[derive(Debug, Default)]
+struct HistogramCell {
+    values: Mutex<Vec<f64>>,
+}
+
+impl HistogramCell {
+    /// Returns all values recorded since the previous call.
+    fn take(&self) -> Vec<f64> {
+        let mut values = self.values.lock().unwrap_or_else(|err| err.into_inner());
+        mem::take(&mut *values)
+    }
+}
+
+impl HistogramFn for HistogramCell {
+    fn record(&self, value: f64) {
+        let mut values = self.values.lock().unwrap_or_else(|err| err.into_inner());
+        values.push(value);
+    }
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use crossbeam_queue::SegQueue;
+    use metrics::Label;
+
+    use super::*;
+    use crate::{
+        client::tests::{create_client, metric_data},
+        contracts::DataPointType,
+    };
+
+    #[tokio::test]
+    async fn it_submits_counter_increase() {
+        let recorder = MetricsRecorder::new();
+        let counter = recorder.register_counter(&Key::from_parts("requests", vec![Label::new("method", "GET")]));
+
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        counter.increment(3);
+        recorder.handle().submit(&client);
+        counter.increment(2);
+        recorder.handle().submit(&client);
+
+        let data = metric_data(events.pop());
+        assert_eq!(data.metrics[0].name, "requests");
+        assert_eq!(data.metrics[0].value, 3.0);
+        assert_eq!(
+            data.properties.and_then(|properties| properties.get("method").cloned()),
+            Some("GET".into())
+        );
+        assert_eq!(metric_data(events.pop()).metrics[0].value, 2.0);
+    }
+
+    #[tokio::test]
+    async fn it_submits_last_gauge_value() {
+        let recorder = MetricsRecorder::new();
+        let gauge = recorder.register_gauge(&Key::from_name("connections"));
+
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        gauge.set(10.0);
+        gauge.increment(5.0);
+        gauge.decrement(3.0);
+        recorder.handle().submit(&client);
+
+        assert_eq!(metric_data(events.pop()).metrics[0].value, 12.0);
+    }
+
+    #[tokio::test]
+    async fn it_submits_histogram_aggregate() {
+        let recorder = MetricsRecorder::new();
+        let histogram = recorder.register_histogram(&Key::from_name("latency"));
+
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        histogram.record(1.0);
+        histogram.record(3.0);
+        recorder.handle().submit(&client);
+        recorder.handle().submit(&client);
+
+        let data = metric_data(events.pop());
+        assert_eq!(data.metrics[0].kind, Some(DataPointType::Aggregation));
+        assert_eq!(data.metrics[0].count, Some(2));
+        assert_eq!(data.metrics[0].value, 4.0);
+        assert!(events.pop().is_none(), "nothing recorded since previous submission");
+    }
+}
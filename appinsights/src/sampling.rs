@@ -0,0 +1,322 @@
+//! Fixed-rate sampling of telemetry items.
+//!
+//! Sampling keeps only a configured percentage of telemetry items and drops the rest before they are queued,
+//! which reduces traffic and ingestion costs of large services. The decision is derived from a hash of the
+//! operation id, so all items of the same operation are either kept or dropped together and the application
+//! map and end-to-end transaction views stay consistent. The hash is the same one other Application Insights
+//! SDKs use, so a distributed operation is sampled consistently across services written in other languages.
+//! Items that are kept are stamped with the sampling percentage, so the portal upscales counts accordingly.
+//! Metrics are never sampled since they are aggregated already.
+//!
+//! With [`TelemetryConfigBuilder::sampling_feedback`] enabled, the client also uses responses of the ingestion
+//! endpoint as a signal to protect the endpoint and the application. Each time the endpoint throttles a batch or
+//! accepts less than half of it, the sampling percentage is halved, down to a hundredth of the configured one.
+//! Each batch accepted in full restores a tenth of the configured percentage, so the client returns to the
+//! configured percentage once the endpoint recovers.
+//!
+//! [`TelemetryConfigBuilder::sampling_feedback`]: ../struct.TelemetryConfigBuilder.html#method.sampling_feedback
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! use appinsights::{TelemetryClient, TelemetryConfig};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .sampling_percentage(10.0)
+//!     .build();
+//! let client = TelemetryClient::from_config(config);
+//!
+//! // skip expensive local logging of operations that are sampled out anyway
+//! if client.is_sampled_in("0f6f6bd0-c05c-4e92-a0a8-ad0e7c6a7b1e") {
+//!     client.track_event("order placed");
+//! }
+//! # }
+//! ```
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use log::debug;
+
+use crate::{
+    config::Shared,
+    diagnostics::{ChannelEvent, EventListener},
+    TelemetryConfig,
+};
+#[cfg(not(feature = "disabled"))]
+use crate::{
+    contracts::Envelope,
+    telemetry::{tag_keys, TelemetryKind},
+};
+
+/// The lowest fraction of the configured percentage feedback reduces sampling to.
+const MIN_FEEDBACK_FACTOR: f64 = 0.01;
+
+/// A fraction of the configured percentage each batch accepted in full restores.
+const FEEDBACK_RECOVERY: f64 = 0.1;
+
+/// Status codes of responses the ingestion endpoint throttles with.
+const THROTTLING_STATUS_CODES: [u16; 3] = [429, 439, 503];
+
+/// Determines whether telemetry items of the operation with the given id are kept when `percentage` percent of
+/// operations are sampled in.
+pub fn is_sampled_in(operation_id: &str, percentage: f64) -> bool {
+    percentage >= 100.0 || percentage.is_nan() || score(operation_id) < percentage
+}
+
+/// Decides whether an item is kept and stamps the sampling percentage on it if so. Items without an operation
+/// id are sampled at random.
+#[cfg(not(feature = "disabled"))]
+pub(crate) fn sample(envelope: &mut Envelope, percentage: f64) -> bool {
+    if percentage >= 100.0 || percentage.is_nan() || TelemetryKind::of(envelope) == Some(TelemetryKind::Metric) {
+        return true;
+    }
+
+    let operation_id = envelope.tags.as_ref().and_then(|tags| tags.get(tag_keys::OPERATION_ID));
+    let sampled_in = match operation_id {
+        Some(operation_id) => is_sampled_in(operation_id, percentage),
+        None => is_sampled_in(&appinsights_core::uuid::new().to_string(), percentage),
+    };
+
+    if sampled_in {
+        envelope.sample_rate = Some(percentage);
+    }
+    sampled_in
+}
+
+/// A sampling percentage of a client that is temporarily reduced while the ingestion endpoint is overloaded if
+/// sampling feedback is enabled.
+#[derive(Debug)]
+pub(crate) struct Sampler {
+    percentage: AtomicU64,
+    feedback: Option<Feedback>,
+}
+
+impl Sampler {
+    /// Creates a sampler with the configured percentage. If sampling feedback is enabled, it also attaches a
+    /// listener to the configuration, so a channel created with it reports ingestion responses to the sampler.
+    pub(crate) fn new(config: &mut TelemetryConfig) -> Self {
+        let feedback = config.sampling_feedback().then(|| {
+            let feedback = Feedback::default();
+            let listener = FeedbackListener {
+                feedback: feedback.clone(),
+                listener: config.event_listener().cloned(),
+            };
+            config.set_event_listener(Shared(Arc::new(listener)));
+            feedback
+        });
+
+        Self {
+            percentage: AtomicU64::new(config.sampling_percentage().to_bits()),
+            feedback,
+        }
+    }
+
+    /// Returns a percentage of operations to keep telemetry items of at the moment.
+    pub(crate) fn percentage(&self) -> f64 {
+        let percentage = f64::from_bits(self.percentage.load(Ordering::Relaxed));
+        match &self.feedback {
+            Some(feedback) => percentage * feedback.factor(),
+            None => percentage,
+        }
+    }
+
+    /// Replaces the configured percentage, e.g. when the client is reconfigured. A reduction by sampling
+    /// feedback in effect applies to the new percentage.
+    pub(crate) fn set_percentage(&self, percentage: f64) {
+        self.percentage.store(percentage.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// A fraction of the configured sampling percentage to keep, adjusted by ingestion responses.
+#[derive(Debug, Clone)]
+struct Feedback(Arc<AtomicU64>);
+
+impl Default for Feedback {
+    fn default() -> Self {
+        Self(Arc::new(AtomicU64::new(1.0_f64.to_bits())))
+    }
+}
+
+impl Feedback {
+    fn factor(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    fn adjust(&self, f: impl Fn(f64) -> f64) {
+        let _ = self.0.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+            Some(f(f64::from_bits(bits)).to_bits())
+        });
+    }
+
+    fn on_event(&self, event: &ChannelEvent) {
+        match *event {
+            ChannelEvent::BatchFailed {
+                status_code: Some(status_code),
+                ..
+            } if THROTTLING_STATUS_CODES.contains(&status_code) => self.reduce(),
+            ChannelEvent::BatchSent { items, accepted, .. } if accepted * 2 < items => self.reduce(),
+            ChannelEvent::BatchSent { items, accepted, .. } if accepted == items => {
+                self.adjust(|factor| (factor + FEEDBACK_RECOVERY).min(1.0))
+            }
+            _ => {}
+        }
+    }
+
+    fn reduce(&self) {
+        self.adjust(|factor| (factor / 2.0).max(MIN_FEEDBACK_FACTOR));
+        debug!(
+            "Ingestion endpoint is overloaded. Reduced sampling to {:.2} of configured percentage",
+            self.factor()
+        );
+    }
+}
+
+/// Adjusts sampling feedback by channel events and passes them on to a listener configured by the application.
+struct FeedbackListener {
+    feedback: Feedback,
+    listener: Option<Shared<dyn EventListener>>,
+}
+
+impl EventListener for FeedbackListener {
+    fn on_event(&self, event: &ChannelEvent) {
+        self.feedback.on_event(event);
+        if let Some(listener) = &self.listener {
+            listener.on_event(event);
+        }
+    }
+}
+
+/// Maps an operation id to a number in range [0, 100].
+fn score(operation_id: &str) -> f64 {
+    f64::from(hash(operation_id)) / f64::from(i32::MAX) * 100.0
+}
+
+/// Calculates a non-negative djb2 hash of UTF-16 code units of the value repeated to at least 8 characters.
+fn hash(value: &str) -> i32 {
+    if value.is_empty() {
+        return 0;
+    }
+
+    let mut input: Vec<u16> = value.encode_utf16().collect();
+    while input.len() < 8 {
+        input.extend_from_within(..);
+    }
+
+    let hash = input.into_iter().fold(5381_i32, |hash, unit| {
+        (hash << 5).wrapping_add(hash).wrapping_add(i32::from(unit))
+    });
+    if hash == i32::MIN {
+        i32::MAX
+    } else {
+        hash.abs()
+    }
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::contracts::{Base, Data, EventData, MetricData};
+
+    #[test_case("", 0; "empty")]
+    #[test_case("a", 348_946_573; "short")]
+    #[test_case("0123456789", 995_771_986; "long")]
+    fn it_calculates_hash(value: &str, expected: i32) {
+        assert_eq!(hash(value), expected);
+    }
+
+    #[test]
+    fn it_samples_operations_consistently() {
+        let sampled_in = is_sampled_in("operation", 50.0);
+
+        for _ in 0..10 {
+            assert_eq!(is_sampled_in("operation", 50.0), sampled_in);
+        }
+    }
+
+    #[test]
+    fn it_samples_approximately_configured_percentage() {
+        let count = (0..10_000)
+            .filter(|_| is_sampled_in(&appinsights_core::uuid::new().to_string(), 25.0))
+            .count();
+
+        assert!((2000..3000).contains(&count), "sampled in {} operations", count);
+    }
+
+    #[test]
+    fn it_stamps_sample_rate_on_sampled_in_items() {
+        let mut envelope = envelope(Data::EventData(EventData::default()), "operation");
+
+        let sampled_in = sample(&mut envelope, 99.99);
+
+        assert!(sampled_in);
+        assert_eq!(envelope.sample_rate, Some(99.99));
+    }
+
+    #[test]
+    fn it_drops_sampled_out_items() {
+        let mut envelope = envelope(Data::EventData(EventData::default()), "operation");
+
+        assert!(!sample(&mut envelope, 0.0));
+    }
+
+    #[test]
+    fn it_reduces_sampling_while_endpoint_is_overloaded() {
+        let mut config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .sampling_percentage(50.0)
+            .sampling_feedback(true)
+            .build();
+        let sampler = Sampler::new(&mut config);
+        let listener = config.event_listener().unwrap();
+
+        listener.on_event(&ChannelEvent::BatchFailed {
+            items: 10,
+            status_code: Some(429),
+        });
+        assert_eq!(sampler.percentage(), 25.0);
+
+        listener.on_event(&ChannelEvent::BatchSent {
+            items: 10,
+            accepted: 4,
+            status_code: Some(206),
+        });
+        assert_eq!(sampler.percentage(), 12.5);
+
+        for _ in 0..10 {
+            listener.on_event(&ChannelEvent::BatchSent {
+                items: 10,
+                accepted: 10,
+                status_code: Some(200),
+            });
+        }
+        assert_eq!(sampler.percentage(), 50.0);
+    }
+
+    #[test]
+    fn it_does_not_sample_metrics() {
+        let mut envelope = envelope(Data::MetricData(MetricData::default()), "operation");
+
+        assert!(sample(&mut envelope, 0.0));
+        assert_eq!(envelope.sample_rate, Some(100.0));
+    }
+
+    fn envelope(data: Data, operation_id: &str) -> Envelope {
+        let mut tags = BTreeMap::new();
+        tags.insert(tag_keys::OPERATION_ID.to_string(), operation_id.to_string());
+
+        Envelope {
+            tags: Some(tags),
+            data: Some(Base::Data(data)),
+            ..Envelope::default()
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! Operation scopes that correlate telemetry of async call chains.
+//!
+//! [`instrument`] runs a future within a scope of its own and tracks it as an in-process dependency once the
+//! future completes. Telemetry items tracked within the scope are stamped with the operation id and refer to
+//! the scope as their parent, and scopes started within the scope become its children, so the end-to-end
+//! transaction view shows the call chain as a tree. The parent scope is restored as soon as the future
+//! completes. The scope follows the future across `.await` points but not into spawned tasks.
+//!
+//! The [`instrument_ai`](../attr.instrument_ai.html) attribute, available with the `macros` feature, wraps the
+//! body of an async function in such a scope.
+//!
+//! With the `disabled` feature scopes are not kept, so futures run as is and no scope is ever current.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! use appinsights::{scope, TelemetryClient};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//!
+//! let users = scope::instrument(&client, "load users", "InProc", async {
+//!     // the event refers to the "load users" dependency as its parent
+//!     client.track_event("cache miss");
+//!     vec!["alice", "bob"]
+//! }, |_| true)
+//! .await;
+//! # }
+//! ```
+use std::{future::Future, time::Instant};
+
+#[cfg(not(feature = "disabled"))]
+use crate::{contracts::Envelope, telemetry::tag_keys};
+use crate::{
+    telemetry::{RemoteDependencyTelemetry, Telemetry},
+    Tracker,
+};
+
+#[cfg(not(feature = "disabled"))]
+tokio::task_local! {
+    static CURRENT: Scope;
+}
+
+/// Identifies the innermost operation scope telemetry is tracked within.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scope {
+    operation_id: String,
+    id: String,
+}
+
+impl Scope {
+    /// Returns the scope the current task runs within, if any.
+    pub fn current() -> Option<Scope> {
+        #[cfg(not(feature = "disabled"))]
+        {
+            CURRENT.try_with(Clone::clone).ok()
+        }
+        #[cfg(feature = "disabled")]
+        {
+            None
+        }
+    }
+
+    /// Returns an id of the operation the scope belongs to.
+    pub fn operation_id(&self) -> &str {
+        &self.operation_id
+    }
+
+    /// Returns an id of the scope, which telemetry items tracked within the scope refer to as their parent.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Creates a scope nested in the given one, or a scope of a new operation.
+    pub(crate) fn child(parent: Option<&Scope>) -> Self {
+        Self {
+            operation_id: match parent {
+                Some(parent) => parent.operation_id.clone(),
+                None => new_operation_id(),
+            },
+            id: new_id(),
+        }
+    }
+
+    /// Creates a scope that stands for a parent of an operation started by another process, such as a producer
+    /// of a message.
+    pub(crate) fn remote(operation_id: impl Into<String>, id: impl Into<String>) -> Self {
+        Self {
+            operation_id: operation_id.into(),
+            id: id.into(),
+        }
+    }
+
+    /// Stamps the operation id and the parent id on a telemetry item unless the item belongs to an operation
+    /// of its own.
+    #[cfg(not(feature = "disabled"))]
+    pub(crate) fn stamp(&self, envelope: &mut Envelope) {
+        let tags = envelope.tags.get_or_insert_with(Default::default);
+        if !tags.contains_key(tag_keys::OPERATION_ID) {
+            tags.insert(tag_keys::OPERATION_ID.into(), self.operation_id.clone());
+            tags.entry(tag_keys::OPERATION_PARENT_ID.into())
+                .or_insert_with(|| self.id.clone());
+        }
+    }
+}
+
+/// Stamps the current scope, if any, on a telemetry item.
+#[cfg(not(feature = "disabled"))]
+pub(crate) fn stamp(envelope: &mut Envelope) {
+    let _ = CURRENT.try_with(|scope| scope.stamp(envelope));
+}
+
+/// Runs a future within the given scope, e.g. a task spawned to handle a part of a request that runs within the
+/// scope of the request.
+pub async fn within<F: Future>(scope: Scope, future: F) -> F::Output {
+    #[cfg(not(feature = "disabled"))]
+    {
+        CURRENT.scope(scope, future).await
+    }
+    #[cfg(feature = "disabled")]
+    {
+        let _ = scope;
+        future.await
+    }
+}
+
+/// Runs a function within the given scope.
+pub(crate) fn within_sync<R>(scope: Scope, f: impl FnOnce() -> R) -> R {
+    #[cfg(not(feature = "disabled"))]
+    {
+        CURRENT.sync_scope(scope, f)
+    }
+    #[cfg(feature = "disabled")]
+    {
+        let _ = scope;
+        f()
+    }
+}
+
+/// Runs a future within a new scope nested in the current one and tracks it as a dependency with the given
+/// name and type once it completes. `success` determines whether the call succeeded by its output.
+pub async fn instrument<T, F, S>(tracker: &T, name: &str, dependency_type: &str, future: F, success: S) -> F::Output
+where
+    T: Tracker,
+    F: Future,
+    S: FnOnce(&F::Output) -> bool,
+{
+    let parent = Scope::current();
+    let scope = Scope::child(parent.as_ref());
+
+    let started = Instant::now();
+    let output = within(scope.clone(), future).await;
+    let duration = started.elapsed();
+
+    let mut telemetry = RemoteDependencyTelemetry::new(name, dependency_type, duration, "", success(&output));
+    telemetry.set_id(scope.id);
+    let mut operation = telemetry.tags_mut().operation_mut();
+    operation.set_id(scope.operation_id);
+    if let Some(parent) = parent {
+        operation.set_parent_id(parent.id);
+    }
+    tracker.track(telemetry);
+
+    output
+}
+
+/// Generates an operation id that is a valid W3C trace id.
+fn new_operation_id() -> String {
+    appinsights_core::uuid::new().as_simple().to_string()
+}
+
+/// Generates a scope id that is a valid W3C span id.
+fn new_id() -> String {
+    let mut id = new_operation_id();
+    id.truncate(16);
+    id
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use std::{collections::BTreeMap, sync::Mutex};
+
+    use super::*;
+    use crate::{
+        contracts::{Base, Data, RemoteDependencyData},
+        TelemetryContext,
+    };
+
+    #[tokio::test]
+    async fn it_tracks_nested_scopes() {
+        let tracker = TestTracker::default();
+
+        let inner = instrument(
+            &tracker,
+            "outer",
+            "InProc",
+            async {
+                let outer = Scope::current().unwrap();
+                let inner = instrument(&tracker, "inner", "InProc", async { Scope::current() }, |_| false).await;
+
+                // parent scope is restored
+                assert_eq!(Scope::current(), Some(outer));
+                inner
+            },
+            |_| true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(Scope::current(), None);
+
+        let items = tracker.0.into_inner().unwrap();
+        let (inner_tags, inner_data) = dependency(&items[0]);
+        let (outer_tags, outer_data) = dependency(&items[1]);
+
+        assert_eq!(inner_data.name, "inner");
+        assert!(!inner_data.success.unwrap());
+        assert_eq!(inner_data.id.as_deref(), Some(inner.id()));
+        assert_eq!(inner_tags.get(tag_keys::OPERATION_ID), Some(&inner.operation_id));
+        assert_eq!(inner_tags.get(tag_keys::OPERATION_PARENT_ID), outer_data.id.as_ref());
+
+        assert_eq!(outer_data.name, "outer");
+        assert_eq!(outer_tags.get(tag_keys::OPERATION_ID), Some(&inner.operation_id));
+        assert_eq!(outer_tags.get(tag_keys::OPERATION_PARENT_ID), None);
+    }
+
+    #[tokio::test]
+    async fn it_stamps_scope_on_telemetry_without_own_operation() {
+        let scope = Scope::child(None);
+
+        let mut envelope = Envelope::default();
+        CURRENT.scope(scope.clone(), async { stamp(&mut envelope) }).await;
+
+        let tags = envelope.tags.unwrap();
+        assert_eq!(tags.get(tag_keys::OPERATION_ID), Some(&scope.operation_id));
+        assert_eq!(tags.get(tag_keys::OPERATION_PARENT_ID), Some(&scope.id));
+    }
+
+    #[test]
+    fn it_keeps_operation_of_telemetry() {
+        let scope = Scope::child(None);
+
+        let mut envelope = Envelope::default();
+        let mut tags = BTreeMap::new();
+        tags.insert(tag_keys::OPERATION_ID.to_string(), "operation".to_string());
+        envelope.tags = Some(tags);
+        scope.stamp(&mut envelope);
+
+        let tags = envelope.tags.unwrap();
+        assert_eq!(tags.get(tag_keys::OPERATION_ID), Some(&"operation".to_string()));
+        assert_eq!(tags.get(tag_keys::OPERATION_PARENT_ID), None);
+    }
+
+    fn dependency(envelope: &Envelope) -> (&BTreeMap<String, String>, &RemoteDependencyData) {
+        match &envelope.data {
+            Some(Base::Data(Data::RemoteDependencyData(data))) => (envelope.tags.as_ref().unwrap(), data),
+            _ => panic!("dependency expected"),
+        }
+    }
+
+    #[derive(Default)]
+    struct TestTracker(Mutex<Vec<Envelope>>);
+
+    impl Tracker for TestTracker {
+        fn track<E>(&self, event: E)
+        where
+            E: Telemetry,
+            (TelemetryContext, E): Into<Envelope>,
+        {
+            let context = TelemetryContext::new("instrumentation".into(), Default::default(), Default::default());
+            self.0.lock().unwrap().push((context, event).into());
+        }
+    }
+}
@@ -0,0 +1,526 @@
+//! Mirroring of Prometheus metrics into Application Insights.
+//!
+//! [`PrometheusBridge`] periodically scrapes a [`prometheus::Registry`] or an HTTP endpoint that exposes
+//! metrics in the Prometheus text format, and submits every sample as a metric telemetry item.
+//! Sample labels become custom properties of submitted items. Counters, as well as `_sum` and `_count`
+//! series of histograms and summaries, are cumulative in Prometheus, so they are submitted as the increase
+//! since the previous scrape. Histogram buckets and `_created` timestamps are skipped.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # use std::{sync::Arc, time::Duration};
+//! use appinsights::{scrape::PrometheusBridge, TelemetryClient};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = Arc::new(TelemetryClient::new("<instrumentation key>".to_string()));
+//! PrometheusBridge::from_endpoint("http://localhost:9090/metrics")
+//!     .prefix("orders.")
+//!     .map_name(|name| Some(name.trim_end_matches("_total").to_string()))
+//!     .spawn(&client, Duration::from_secs(60));
+//! # }
+//! ```
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::Mutex,
+};
+#[cfg(feature = "runtime")]
+use std::{sync::Arc, time::Duration};
+
+use http::StatusCode;
+use log::debug;
+#[cfg(feature = "runtime")]
+use log::warn;
+#[cfg(feature = "runtime")]
+use reqwest::Client;
+#[cfg(feature = "runtime")]
+use tokio::task::JoinHandle;
+
+use crate::{
+    callback,
+    telemetry::{MetricTelemetry, Telemetry},
+    Tracker,
+};
+#[cfg(feature = "runtime")]
+use crate::{client, TelemetryClient};
+
+/// A function that converts a Prometheus sample name to a metric name or skips the sample.
+type NameMapper = dyn Fn(&str) -> Option<String> + Send + Sync;
+
+/// Periodically converts Prometheus samples into metric telemetry items.
+pub struct PrometheusBridge {
+    source: Source,
+    prefix: String,
+    map_name: Option<Box<NameMapper>>,
+    previous: Mutex<HashMap<SeriesKey, f64>>,
+}
+
+enum Source {
+    Registry(prometheus::Registry),
+    #[cfg(feature = "runtime")]
+    Endpoint(Client, String),
+}
+
+impl PrometheusBridge {
+    /// Creates a bridge that gathers metrics from the given registry.
+    pub fn from_registry(registry: prometheus::Registry) -> Self {
+        Self::new(Source::Registry(registry))
+    }
+
+    /// Creates a bridge that scrapes metrics in the Prometheus text format from the given URL.
+    #[cfg(feature = "runtime")]
+    pub fn from_endpoint(url: impl Into<String>) -> Self {
+        Self::new(Source::Endpoint(Client::new(), url.into()))
+    }
+
+    fn new(source: Source) -> Self {
+        Self {
+            source,
+            prefix: String::default(),
+            map_name: None,
+            previous: Mutex::default(),
+        }
+    }
+
+    /// Prepends the given prefix to names of all submitted metrics.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Converts sample names with the given function before the prefix is applied. A sample is skipped when
+    /// the function returns `None`.
+    pub fn map_name<F>(mut self, map_name: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        self.map_name = Some(Box::new(map_name));
+        self
+    }
+
+    /// Scrapes samples once and submits them with the given tracker. Returns a number of submitted items.
+    pub async fn submit<T: Tracker>(&self, tracker: &T) -> Result<usize, ScrapeError> {
+        let text = self.scrape().await?;
+        let metrics = self.convert(&text);
+
+        let count = metrics.len();
+        for telemetry in metrics {
+            tracker.track(telemetry);
+        }
+        Ok(count)
+    }
+
+    /// Spawns a [periodic task](../index.html#periodic-tasks) that scrapes samples and submits them with the
+    /// given client every `interval`.
+    #[cfg(feature = "runtime")]
+    pub fn spawn(self, client: &Arc<TelemetryClient>, interval: Duration) -> JoinHandle<()> {
+        let bridge = Arc::new(self);
+        client::spawn_periodic_async(client, interval, move |client| {
+            let bridge = bridge.clone();
+            async move {
+                match bridge.submit(client.as_ref()).await {
+                    Ok(count) => debug!("Submitted {} Prometheus samples", count),
+                    Err(err) => warn!("Unable to scrape Prometheus metrics: {}", err),
+                }
+            }
+        })
+    }
+
+    async fn scrape(&self) -> Result<String, ScrapeError> {
+        match &self.source {
+            Source::Registry(registry) => prometheus::TextEncoder::new()
+                .encode_to_string(&registry.gather())
+                .map_err(|err| ScrapeError::Encode(err.to_string())),
+            #[cfg(feature = "runtime")]
+            Source::Endpoint(client, url) => {
+                let response = client
+                    .get(url)
+                    .send()
+                    .await
+                    .map_err(|err| ScrapeError::Request(err.to_string()))?;
+
+                if !response.status().is_success() {
+                    return Err(ScrapeError::Status(response.status()));
+                }
+
+                response
+                    .text()
+                    .await
+                    .map_err(|err| ScrapeError::Request(err.to_string()))
+            }
+        }
+    }
+
+    /// Converts samples in the text format to metric telemetry items.
+    fn convert(&self, text: &str) -> Vec<MetricTelemetry> {
+        let mut previous = self.previous.lock().unwrap_or_else(|err| err.into_inner());
+        let mut metrics = Vec::new();
+
+        for sample in parse(text) {
+            if !sample.value.is_finite() {
+                continue;
+            }
+
+            let value = if sample.cumulative {
+                let key = (sample.name.clone(), sample.labels.clone());
+                match previous.insert(key, sample.value) {
+                    // counter was reset, for example when the scraped process restarted
+                    Some(last) if last > sample.value => sample.value,
+                    Some(last) => sample.value - last,
+                    // there is no baseline to calculate the increase on the first scrape
+                    None => continue,
+                }
+            } else {
+                sample.value
+            };
+
+            let name = match &self.map_name {
+                Some(map_name) => {
+                    match callback::call("Prometheus name mapping", || map_name(&sample.name)).flatten() {
+                        Some(name) => name,
+                        None => continue,
+                    }
+                }
+                None => sample.name,
+            };
+
+            let mut telemetry = MetricTelemetry::new(format!("{}{}", self.prefix, name), value);
+            telemetry.properties_mut().extend(sample.labels);
+            metrics.push(telemetry);
+        }
+
+        metrics
+    }
+}
+
+impl fmt::Debug for PrometheusBridge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = match &self.source {
+            Source::Registry(_) => "registry",
+            #[cfg(feature = "runtime")]
+            Source::Endpoint(_, url) => url,
+        };
+        f.debug_struct("PrometheusBridge")
+            .field("source", &source)
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+/// An error returned when Prometheus metrics cannot be scraped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ScrapeError {
+    /// Metrics endpoint cannot be reached.
+    Request(String),
+
+    /// Metrics endpoint responded with an unsuccessful status code.
+    Status(StatusCode),
+
+    /// Metrics cannot be encoded in the text format.
+    Encode(String),
+}
+
+impl fmt::Display for ScrapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScrapeError::Request(err) => write!(f, "metrics endpoint is unreachable: {}", err),
+            ScrapeError::Status(status_code) => write!(f, "metrics endpoint responded with {}", status_code),
+            ScrapeError::Encode(err) => write!(f, "unable to encode metrics: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ScrapeError {}
+
+/// A sample name together with labels that identifies a time series.
+type SeriesKey = (String, BTreeMap<String, String>);
+
+/// A single sample of the Prometheus text format.
+#[derive(Debug, PartialEq)]
+struct Sample {
+    name: String,
+    labels: BTreeMap<String, String>,
+    value: f64,
+    cumulative: bool,
+}
+
+/// Suffixes of samples that belong to a metric family of the same name without the suffix.
+const FAMILY_SUFFIXES: &[&str] = &["_total", "_bucket", "_sum", "_count", "_created"];
+
+/// Parses samples of the Prometheus text format. Malformed lines, histogram buckets and `_created` samples
+/// are skipped.
+fn parse(text: &str) -> Vec<Sample> {
+    let mut types = HashMap::new();
+    let mut samples = Vec::new();
+
+    for line in text.lines().map(str::trim) {
+        if let Some(comment) = line.strip_prefix('#') {
+            let mut parts = comment.split_whitespace();
+            if let (Some("TYPE"), Some(name), Some(kind)) = (parts.next(), parts.next(), parts.next()) {
+                types.insert(name.to_string(), kind.to_string());
+            }
+            continue;
+        }
+
+        if line.is_empty() {
+            continue;
+        }
+
+        match parse_sample(line) {
+            Some((name, labels, value)) => {
+                let (family, suffix) = family(&types, &name);
+
+                let cumulative = match (types.get(family).map(String::as_str), suffix) {
+                    (_, "_created") | (Some("histogram"), "_bucket") => continue,
+                    (Some("counter"), _) => true,
+                    (Some("histogram"), _) | (Some("summary"), "_sum") | (Some("summary"), "_count") => true,
+                    _ => false,
+                };
+
+                samples.push(Sample {
+                    name,
+                    labels,
+                    value,
+                    cumulative,
+                });
+            }
+            None => debug!("Skipping malformed Prometheus sample: {}", line),
+        }
+    }
+
+    samples
+}
+
+/// Returns a metric family the sample of the given name belongs to along with the suffix of the sample. A type
+/// declared for the exact name wins over a family the name shares a prefix with.
+fn family<'a>(types: &HashMap<String, String>, name: &'a str) -> (&'a str, &'static str) {
+    if !types.contains_key(name) {
+        for suffix in FAMILY_SUFFIXES {
+            match name.strip_suffix(suffix) {
+                Some(family) if types.contains_key(family) => return (family, suffix),
+                _ => {}
+            }
+        }
+    }
+    (name, "")
+}
+
+/// Parses a line like `name{label="value"} 1.5 1395066363000` into a name, labels and a value.
+fn parse_sample(line: &str) -> Option<(String, BTreeMap<String, String>, f64)> {
+    let name_end = line.find(|c: char| c == '{' || c.is_whitespace())?;
+    let name = &line[..name_end];
+    let mut rest = &line[name_end..];
+
+    let mut labels = BTreeMap::new();
+    if let Some(mut chars) = rest.strip_prefix('{') {
+        loop {
+            chars = chars.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+            if let Some(after) = chars.strip_prefix('}') {
+                rest = after;
+                break;
+            }
+
+            let (key, after) = chars.split_once('=')?;
+            let (value, after) = parse_label_value(after.trim_start().strip_prefix('"')?)?;
+            labels.insert(key.trim().to_string(), value);
+            chars = after;
+        }
+    }
+
+    let value = match rest.split_whitespace().next()? {
+        "+Inf" => f64::INFINITY,
+        "-Inf" => f64::NEG_INFINITY,
+        value => value.parse().ok()?,
+    };
+
+    Some((name.to_string(), labels, value))
+}
+
+/// Reads an escaped label value up to the closing quote and returns it with the rest of the line.
+fn parse_label_value(text: &str) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = text.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &text[index + 1..])),
+            '\\' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                c => value.push(c),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use crossbeam_queue::SegQueue;
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Request, Server,
+    };
+    use prometheus::{IntCounterVec, Opts};
+
+    use super::*;
+    use crate::{
+        client::tests::create_client,
+        contracts::{Base, Data},
+    };
+
+    const TEXT: &str = r#"
+# HELP http_requests_total The total number of HTTP requests.
+# TYPE http_requests_total counter
+http_requests_total{method="post",code="200"} 1027 1395066363000
+# TYPE queue_depth gauge
+queue_depth{queue="orders \"eu\""} 12
+# TYPE request_duration_seconds histogram
+request_duration_seconds_bucket{le="0.05"} 24054
+request_duration_seconds_bucket{le="+Inf"} 144320
+request_duration_seconds_sum 53423
+request_duration_seconds_count 144320
+# TYPE temperature untyped
+temperature NaN
+malformed{ 1
+"#;
+
+    #[test]
+    fn it_parses_text_format() {
+        let samples = parse(TEXT);
+
+        let names: Vec<_> = samples.iter().map(|sample| sample.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "http_requests_total",
+                "queue_depth",
+                "request_duration_seconds_sum",
+                "request_duration_seconds_count",
+                "temperature",
+            ]
+        );
+        assert!(samples[0].cumulative);
+        assert_eq!(samples[0].labels.get("code"), Some(&"200".to_string()));
+        assert_eq!(samples[0].value, 1027.0);
+        assert!(!samples[1].cumulative);
+        assert_eq!(samples[1].labels.get("queue"), Some(&"orders \"eu\"".to_string()));
+        assert!(samples[2].cumulative && samples[3].cumulative);
+    }
+
+    #[test]
+    fn it_prefers_exact_type_over_family_prefix() {
+        let samples = parse(
+            r#"
+# TYPE jobs counter
+jobs_total 5
+# TYPE jobs_pending gauge
+jobs_pending 3
+"#,
+        );
+
+        assert_eq!(samples.len(), 2);
+        assert!(samples[0].cumulative);
+        assert_eq!(samples[1].name, "jobs_pending");
+        assert!(!samples[1].cumulative);
+    }
+
+    #[test]
+    fn it_skips_created_samples() {
+        let samples = parse(
+            r#"
+# TYPE jobs counter
+jobs_total 5
+jobs_created 1395066363
+# TYPE latency summary
+latency_sum 12
+latency_count 4
+latency_created 1395066363
+"#,
+        );
+
+        let names: Vec<_> = samples.iter().map(|sample| sample.name.as_str()).collect();
+        assert_eq!(names, vec!["jobs_total", "latency_sum", "latency_count"]);
+        assert!(samples.iter().all(|sample| sample.cumulative));
+    }
+
+    #[test]
+    fn it_submits_increase_of_cumulative_samples() {
+        let bridge = PrometheusBridge::from_endpoint("http://localhost").prefix("svc.");
+
+        let first = bridge.convert(TEXT);
+        assert_eq!(names(&first), vec!["svc.queue_depth"]);
+
+        let second = bridge.convert(&TEXT.replace("1027", "1030"));
+        assert_eq!(
+            names(&second),
+            vec![
+                "svc.http_requests_total",
+                "svc.queue_depth",
+                "svc.request_duration_seconds_sum",
+                "svc.request_duration_seconds_count",
+            ]
+        );
+        assert_eq!(second[0].value(), 3.0);
+        assert_eq!(second[2].value(), 0.0);
+    }
+
+    #[test]
+    fn it_maps_sample_names() {
+        let bridge = PrometheusBridge::from_endpoint("http://localhost")
+            .map_name(|name| name.strip_prefix("queue_").map(|name| format!("Queue {}", name)));
+
+        assert_eq!(names(&bridge.convert(TEXT)), vec!["Queue depth"]);
+    }
+
+    #[tokio::test]
+    async fn it_scrapes_registry() {
+        let registry = prometheus::Registry::new();
+        let counter = IntCounterVec::new(Opts::new("jobs_total", "Jobs processed"), &["queue"]).unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+        let bridge = PrometheusBridge::from_registry(registry);
+
+        counter.with_label_values(&["orders"]).inc_by(5);
+        assert_eq!(bridge.submit(&client).await, Ok(0));
+
+        counter.with_label_values(&["orders"]).inc_by(2);
+        assert_eq!(bridge.submit(&client).await, Ok(1));
+
+        match events.pop().and_then(|envelope| envelope.data) {
+            Some(Base::Data(Data::MetricData(data))) => {
+                assert_eq!(data.metrics[0].name, "jobs_total");
+                assert_eq!(data.metrics[0].value, 2.0);
+                assert_eq!(data.properties.unwrap().get("queue"), Some(&"orders".to_string()));
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_scrapes_endpoint() {
+        let make_service = make_service_fn(|_| async {
+            Ok::<_, hyper::Error>(service_fn(|_: Request<Body>| async {
+                hyper::Response::builder().body(Body::from(TEXT))
+            }))
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = format!("http://{}/metrics", server.local_addr());
+        tokio::spawn(server);
+
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+        let bridge = PrometheusBridge::from_endpoint(url);
+
+        assert_eq!(bridge.submit(&client).await, Ok(1));
+        assert!(events.pop().is_some());
+    }
+
+    fn names(metrics: &[MetricTelemetry]) -> Vec<&str> {
+        metrics.iter().map(MetricTelemetry::name).collect()
+    }
+}
@@ -0,0 +1,196 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use http::header::{AUTHORIZATION, CONTENT_TYPE};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::Client;
+use sha2::Sha256;
+
+use crate::{
+    sink::{SinkError, TelemetrySink},
+    time,
+};
+
+/// Characters to percent-encode in parts of a shared access signature: everything but unreserved ones.
+const ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+
+/// A time a shared access signature stays valid for.
+const TOKEN_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// A sink that sends each batch of telemetry items as a single event to Azure Event Hubs using
+/// its REST API.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// use appinsights::{sink::EventHubsSink, TelemetryConfig};
+///
+/// let sink = EventHubsSink::from_connection_string(
+///     "Endpoint=sb://contoso.servicebus.windows.net/;SharedAccessKeyName=send;SharedAccessKey=<key>;EntityPath=telemetry",
+/// )
+/// .expect("valid connection string");
+///
+/// let config = TelemetryConfig::builder().i_key("<instrumentation key>").sink(sink).build();
+/// ```
+#[derive(Debug)]
+pub struct EventHubsSink {
+    resource: String,
+    key_name: String,
+    key: String,
+    client: Client,
+}
+
+impl EventHubsSink {
+    /// Creates a sink that sends events to the event hub in the given namespace, e.g.
+    /// `contoso.servicebus.windows.net`, authorizing with a shared access key.
+    pub fn new(
+        namespace: impl AsRef<str>,
+        event_hub: impl AsRef<str>,
+        key_name: impl Into<String>,
+        key: impl Into<String>,
+    ) -> Self {
+        let namespace = namespace.as_ref().trim_end_matches('/');
+        let resource = if namespace.contains("://") {
+            format!("{}/{}", namespace, event_hub.as_ref())
+        } else {
+            format!("https://{}/{}", namespace, event_hub.as_ref())
+        };
+
+        Self {
+            resource,
+            key_name: key_name.into(),
+            key: key.into(),
+            client: Client::new(),
+        }
+    }
+
+    /// Creates a sink from a connection string of an event hub, or of a namespace with an `EntityPath` part.
+    /// Returns `None` if the connection string misses an endpoint, a key or an event hub name.
+    pub fn from_connection_string(connection_string: &str) -> Option<Self> {
+        let mut endpoint = None;
+        let mut key_name = None;
+        let mut key = None;
+        let mut event_hub = None;
+
+        for part in connection_string.split(';') {
+            match part.split_once('=') {
+                Some(("Endpoint", value)) => endpoint = Some(value.trim().replacen("sb://", "", 1)),
+                Some(("SharedAccessKeyName", value)) => key_name = Some(value.trim()),
+                Some(("SharedAccessKey", value)) => key = Some(value.trim()),
+                Some(("EntityPath", value)) => event_hub = Some(value.trim()),
+                _ => {}
+            }
+        }
+
+        Some(Self::new(endpoint?, event_hub?, key_name?, key?))
+    }
+
+    /// Creates a shared access signature that expires at the given Unix time.
+    fn token(&self, expiry: i64) -> String {
+        let resource = utf8_percent_encode(&self.resource, ENCODE_SET).to_string();
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.key.as_bytes()).expect("HMAC accepts keys of any size");
+        mac.update(format!("{}\n{}", resource, expiry).as_bytes());
+        let signature = STANDARD.encode(mac.finalize().into_bytes());
+
+        format!(
+            "SharedAccessSignature sr={}&sig={}&se={}&skn={}",
+            resource,
+            utf8_percent_encode(&signature, ENCODE_SET),
+            expiry,
+            utf8_percent_encode(&self.key_name, ENCODE_SET)
+        )
+    }
+}
+
+#[async_trait]
+impl TelemetrySink for EventHubsSink {
+    async fn write(&self, batch: Vec<u8>) -> Result<(), SinkError> {
+        let expiry = time::now().timestamp() + TOKEN_TTL.as_secs() as i64;
+
+        self.client
+            .post(format!("{}/messages", self.resource))
+            .header(AUTHORIZATION, self.token(expiry))
+            .header(CONTENT_TYPE, "application/atom+xml;type=entry;charset=utf-8")
+            .body(batch)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Request, Server, StatusCode,
+    };
+
+    use super::*;
+
+    #[test]
+    fn it_parses_connection_string() {
+        let sink = EventHubsSink::from_connection_string(
+            "Endpoint=sb://contoso.servicebus.windows.net/;SharedAccessKeyName=send;SharedAccessKey=secret;EntityPath=telemetry",
+        )
+        .unwrap();
+
+        assert_eq!(sink.resource, "https://contoso.servicebus.windows.net/telemetry");
+        assert_eq!(sink.key_name, "send");
+        assert_eq!(sink.key, "secret");
+    }
+
+    #[test]
+    fn it_rejects_connection_string_without_event_hub() {
+        let sink = EventHubsSink::from_connection_string(
+            "Endpoint=sb://contoso.servicebus.windows.net/;SharedAccessKeyName=send;SharedAccessKey=secret",
+        );
+
+        assert!(sink.is_none());
+    }
+
+    #[test]
+    fn it_creates_shared_access_signature() {
+        let sink = EventHubsSink::new("contoso.servicebus.windows.net", "telemetry", "send", "secret");
+
+        let token = sink.token(1700000000);
+
+        assert!(
+            token.starts_with("SharedAccessSignature sr=https%3A%2F%2Fcontoso.servicebus.windows.net%2Ftelemetry&sig=")
+        );
+        assert!(token.ends_with("&se=1700000000&skn=send"));
+        assert_eq!(token, sink.token(1700000000));
+        assert_ne!(token, sink.token(1700000001));
+    }
+
+    #[tokio::test]
+    async fn it_sends_batch_as_event() {
+        let make_service = make_service_fn(|_| async {
+            Ok::<_, hyper::Error>(service_fn(|request: Request<Body>| async move {
+                let authorized = request
+                    .headers()
+                    .get(AUTHORIZATION)
+                    .is_some_and(|value| value.as_bytes().starts_with(b"SharedAccessSignature "));
+                let status_code = match (request.uri().path(), authorized) {
+                    ("/telemetry/messages", true) => StatusCode::CREATED,
+                    (_, false) => StatusCode::UNAUTHORIZED,
+                    _ => StatusCode::NOT_FOUND,
+                };
+                hyper::Response::builder().status(status_code).body(Body::empty())
+            }))
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let namespace = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+
+        let sink = EventHubsSink::new(&namespace, "telemetry", "send", "secret");
+        assert!(sink.write(b"[]".to_vec()).await.is_ok());
+
+        let sink = EventHubsSink::new(&namespace, "unknown", "send", "secret");
+        assert!(sink.write(b"[]".to_vec()).await.is_err());
+    }
+}
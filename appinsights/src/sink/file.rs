@@ -0,0 +1,79 @@
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+
+use crate::sink::{SinkError, TelemetrySink};
+
+/// A sink that appends each batch of telemetry items to a file as a separate line, so the file can be
+/// processed line by line as JSON Lines. The file is created if it does not exist.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// use appinsights::{sink::FileSink, TelemetryClient, TelemetryConfig};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let config = TelemetryConfig::builder()
+///     .i_key("<instrumentation key>")
+///     .terminate_sink(FileSink::new("/var/log/app/telemetry.jsonl"))
+///     .build();
+/// let client = TelemetryClient::from_config(config);
+///
+/// // items that have not been sent yet are appended to the file instead of being lost
+/// client.terminate().await;
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileSink {
+    path: PathBuf,
+}
+
+impl FileSink {
+    /// Creates a sink that appends batches to a file at the given path.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().into(),
+        }
+    }
+}
+
+#[async_trait]
+impl TelemetrySink for FileSink {
+    async fn write(&self, mut batch: Vec<u8>) -> Result<(), SinkError> {
+        let path = self.path.clone();
+        batch.push(b'\n');
+
+        tokio::task::spawn_blocking(move || {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            file.write_all(&batch)
+        })
+        .await??;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn it_appends_batches_as_lines() {
+        let path = std::env::temp_dir().join(format!("appinsights-file-sink-{}.jsonl", std::process::id()));
+        let sink = FileSink::new(&path);
+
+        sink.write(b"[1]".to_vec()).await.unwrap();
+        sink.write(b"[2]".to_vec()).await.unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(content, "[1]\n[2]\n");
+    }
+}
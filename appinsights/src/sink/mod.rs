@@ -0,0 +1,62 @@
+//! Destinations of telemetry other than the ingestion endpoint.
+//!
+//! Organizations that centralize telemetry in their own pipeline before forwarding it to Application
+//! Insights can configure a [`TelemetrySink`] with [`TelemetryConfigBuilder::sink`]. The channel then writes
+//! batches of telemetry items to the sink instead of the ingestion endpoint, keeping its batching, retry and
+//! capacity behavior. Each batch is a JSON array of envelopes in the format the ingestion endpoint accepts,
+//! so the pipeline can forward it as is.
+//!
+//! [`FileSink`] appends batches to a file. [`EventHubsSink`] writes batches to Azure Event Hubs when the
+//! crate is compiled with the `eventhubs` feature. Other systems such as Kafka can be supported by implementing [`TelemetrySink`] with a client
+//! of choice.
+//!
+//! [`TelemetryConfigBuilder::sink`]: ../struct.TelemetryConfigBuilder.html#method.sink
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! use appinsights::{sink::{SinkError, TelemetrySink}, TelemetryClient, TelemetryConfig};
+//! use async_trait::async_trait;
+//!
+//! struct KafkaSink {
+//!     // producer: rdkafka::producer::FutureProducer,
+//! }
+//!
+//! #[async_trait]
+//! impl TelemetrySink for KafkaSink {
+//!     async fn write(&self, batch: Vec<u8>) -> Result<(), SinkError> {
+//!         // self.producer.send(FutureRecord::to("telemetry").payload(&batch), timeout).await?;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .sink(KafkaSink {})
+//!     .build();
+//! let client = TelemetryClient::from_config(config);
+//! ```
+#[cfg(all(feature = "eventhubs", feature = "runtime"))]
+mod eventhubs;
+#[cfg(all(feature = "eventhubs", feature = "runtime"))]
+pub use eventhubs::EventHubsSink;
+
+#[cfg(feature = "runtime")]
+mod file;
+#[cfg(feature = "runtime")]
+pub use file::FileSink;
+
+use std::error::Error;
+
+use async_trait::async_trait;
+
+/// An error returned by a sink when a batch cannot be written.
+pub type SinkError = Box<dyn Error + Send + Sync>;
+
+/// A destination that receives batches of telemetry items instead of the ingestion endpoint.
+#[async_trait]
+pub trait TelemetrySink: Send + Sync + 'static {
+    /// Writes a batch of telemetry items serialized as a JSON array. When an error is returned, the
+    /// channel retries the whole batch later.
+    async fn write(&self, batch: Vec<u8>) -> Result<(), SinkError>;
+}
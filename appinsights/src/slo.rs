@@ -0,0 +1,235 @@
+//! Rolling success rate of requests for charting service level objectives.
+//!
+//! [`SuccessRate`] accounts outcomes of requests, or any other calls, within a rolling window and submits
+//! a percentage of successful ones as a metric, so teams can chart SLO compliance without a separate
+//! aggregation job. Outcomes are accounted in a fixed number of buckets per window, so memory usage does not
+//! depend on the rate of requests. When an objective is set, a remaining error budget is submitted as
+//! another metric, which drops below 0 once the window has more failures than the objective allows.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # use std::{sync::Arc, time::Duration};
+//! use appinsights::{slo::SuccessRate, telemetry::RequestTelemetry, TelemetryClient};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = Arc::new(TelemetryClient::new("<instrumentation key>".to_string()));
+//! let success_rate = SuccessRate::new("orders availability", Duration::from_secs(3600)).with_objective(0.999);
+//! success_rate.clone().spawn(&client, Duration::from_secs(60));
+//!
+//! let telemetry = RequestTelemetry::with_name("POST /orders", Duration::from_millis(25), "201");
+//! success_rate.track(client.as_ref(), telemetry);
+//! # }
+//! ```
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+#[cfg(not(feature = "runtime"))]
+use std::time::Instant;
+
+#[cfg(feature = "runtime")]
+use tokio::task::JoinHandle;
+#[cfg(feature = "runtime")]
+use tokio::time::Instant;
+
+#[cfg(feature = "runtime")]
+use crate::{client, TelemetryClient};
+use crate::{
+    telemetry::{MetricTelemetry, RequestTelemetry, Telemetry},
+    Tracker,
+};
+
+/// A number of buckets outcomes within a window are accounted in.
+const BUCKETS: u32 = 60;
+
+/// Maintains a rolling success rate of calls within a window.
+#[derive(Debug, Clone)]
+pub struct SuccessRate {
+    name: String,
+    window: Duration,
+    objective: Option<f64>,
+    buckets: Arc<Mutex<VecDeque<Bucket>>>,
+}
+
+impl SuccessRate {
+    /// Creates a success rate submitted as a metric with the given name that accounts outcomes within the
+    /// given window.
+    pub fn new(name: impl Into<String>, window: Duration) -> Self {
+        Self {
+            name: name.into(),
+            window,
+            objective: None,
+            buckets: Arc::default(),
+        }
+    }
+
+    /// Sets a target success rate between 0 and 1, e.g. `0.999`, to submit a remaining error budget for as
+    /// a metric named `<name> error budget`.
+    pub fn with_objective(mut self, objective: f64) -> Self {
+        self.objective = Some(objective.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Accounts an outcome of a call.
+    pub fn record(&self, success: bool) {
+        let now = Instant::now();
+        let mut buckets = self.lock();
+        self.evict(&mut buckets, now);
+
+        let granularity = self.window / BUCKETS;
+        match buckets.back_mut() {
+            Some(bucket) if now.duration_since(bucket.started) < granularity => bucket.add(success),
+            _ => {
+                let mut bucket = Bucket::new(now);
+                bucket.add(success);
+                buckets.push_back(bucket);
+            }
+        }
+    }
+
+    /// Accounts an outcome of a request and submits the request with the given tracker.
+    pub fn track<T: Tracker>(&self, tracker: &T, telemetry: RequestTelemetry) {
+        self.record(telemetry.is_success());
+        tracker.track(telemetry);
+    }
+
+    /// Returns a fraction of successful calls within the window, or `None` if no calls have been accounted.
+    pub fn success_rate(&self) -> Option<f64> {
+        let mut buckets = self.lock();
+        self.evict(&mut buckets, Instant::now());
+
+        let (total, succeeded) = buckets.iter().fold((0, 0), |(total, succeeded), bucket| {
+            (total + bucket.total, succeeded + bucket.succeeded)
+        });
+        if total == 0 {
+            None
+        } else {
+            Some(succeeded as f64 / total as f64)
+        }
+    }
+
+    /// Returns a fraction of the error budget left within the window, or `None` if no objective is set or no
+    /// calls have been accounted.
+    pub fn error_budget(&self) -> Option<f64> {
+        let objective = self.objective?;
+        let success_rate = self.success_rate()?;
+        let allowed = 1.0 - objective;
+        if allowed > 0.0 {
+            Some(1.0 - (1.0 - success_rate) / allowed)
+        } else if success_rate < 1.0 {
+            Some(f64::NEG_INFINITY)
+        } else {
+            Some(1.0)
+        }
+    }
+
+    /// Submits the success rate in percents and the remaining error budget, if there are any calls within the
+    /// window, with the given tracker.
+    pub fn submit<T: Tracker>(&self, tracker: &T) {
+        if let Some(success_rate) = self.success_rate() {
+            tracker.track(self.metric(&self.name, success_rate * 100.0));
+        }
+        if let Some(error_budget) = self.error_budget().filter(|budget| budget.is_finite()) {
+            tracker.track(self.metric(&format!("{} error budget", self.name), error_budget * 100.0));
+        }
+    }
+
+    /// Spawns a [periodic task](../index.html#periodic-tasks) that submits the success rate with the given client every
+    /// `interval`.
+    #[cfg(feature = "runtime")]
+    pub fn spawn(self, client: &Arc<TelemetryClient>, interval: Duration) -> JoinHandle<()> {
+        client::spawn_periodic(client, interval, move |client| self.submit(client))
+    }
+
+    fn metric(&self, name: &str, value: f64) -> MetricTelemetry {
+        let mut telemetry = MetricTelemetry::new(name, value);
+        telemetry
+            .properties_mut()
+            .insert("window".into(), format!("{}s", self.window.as_secs()));
+        telemetry
+    }
+
+    /// Removes buckets that fell out of the window.
+    fn evict(&self, buckets: &mut VecDeque<Bucket>, now: Instant) {
+        while buckets
+            .front()
+            .is_some_and(|bucket| now.duration_since(bucket.started) >= self.window)
+        {
+            buckets.pop_front();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<Bucket>> {
+        self.buckets.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Outcomes of calls started within a fraction of the window.
+#[derive(Debug)]
+struct Bucket {
+    started: Instant,
+    total: u64,
+    succeeded: u64,
+}
+
+impl Bucket {
+    fn new(started: Instant) -> Self {
+        Self {
+            started,
+            total: 0,
+            succeeded: 0,
+        }
+    }
+
+    fn add(&mut self, success: bool) {
+        self.total += 1;
+        if success {
+            self.succeeded += 1;
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use crossbeam_queue::SegQueue;
+
+    use super::*;
+    use crate::client::tests::{create_client, metric_data};
+
+    #[tokio::test(start_paused = true)]
+    async fn it_submits_success_rate_within_window() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+        let success_rate = SuccessRate::new("availability", Duration::from_secs(60)).with_objective(0.9);
+
+        success_rate.submit(&client);
+        assert!(events.pop().is_none(), "nothing submitted without calls");
+
+        success_rate.record(false);
+        tokio::time::advance(Duration::from_secs(30)).await;
+        for _ in 0..3 {
+            success_rate.record(true);
+        }
+        assert_eq!(success_rate.success_rate(), Some(0.75));
+        assert_eq!(
+            success_rate.error_budget().map(|budget| (budget * 100.0).round()),
+            Some(-150.0)
+        );
+
+        // the failure falls out of the window
+        tokio::time::advance(Duration::from_secs(30)).await;
+        success_rate.submit(&client);
+
+        let data = metric_data(events.pop());
+        assert_eq!(data.metrics[0].name, "availability");
+        assert_eq!(data.metrics[0].value, 100.0);
+        assert_eq!(data.properties.unwrap()["window"], "60s");
+        let data = metric_data(events.pop());
+        assert_eq!(data.metrics[0].name, "availability error budget");
+        assert_eq!(data.metrics[0].value, 100.0);
+    }
+}
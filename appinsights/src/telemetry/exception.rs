@@ -1 +0,0 @@
-// TODO implement exception collection telemetry item
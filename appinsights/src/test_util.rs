@@ -0,0 +1,457 @@
+//! Utilities to synchronize tests with the telemetry submission routine and to verify that the ingestion
+//! endpoint accepts telemetry items.
+//!
+//! Available with the `test-util` feature only.
+//!
+//! ```rust
+//! # async fn run() {
+//! use appinsights::{test_util::DrainMarker, TelemetryClient, TelemetryConfig};
+//!
+//! let marker = DrainMarker::new();
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .drain_marker(marker.clone())
+//!     .build();
+//!
+//! let client = TelemetryClient::from_config(config);
+//! client.track_event("event happened");
+//! client.flush_channel();
+//!
+//! // resolves as soon as the submission routine handled the flush
+//! marker.wait(1).await;
+//! # }
+//! ```
+//!
+//! [`CapturingChannel`] keeps telemetry items in memory instead of submitting them, and reports which
+//! telemetry types and names a test run produced. Comparing the report with an expected [`CoverageManifest`]
+//! ensures new endpoints and jobs are instrumented before they are released.
+//!
+//! ```rust
+//! use appinsights::{
+//!     telemetry::TelemetryKind,
+//!     test_util::{CapturingChannel, CoverageManifest},
+//!     TelemetryConfig,
+//! };
+//!
+//! let channel = CapturingChannel::new();
+//! let client = channel.client(TelemetryConfig::new("<instrumentation key>".to_string()));
+//! client.track_event("order placed");
+//!
+//! let manifest = CoverageManifest::new()
+//!     .item(TelemetryKind::Event, "order placed")
+//!     .kind(TelemetryKind::Request);
+//! let diff = channel.coverage().diff(&manifest);
+//!
+//! assert_eq!(diff.missing(), [(TelemetryKind::Request, None)]);
+//! ```
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fmt,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use tokio::sync::Notify;
+
+use crate::{
+    channel::TelemetryChannel,
+    contracts::{Base, Data, Envelope, Transmission},
+    telemetry::TelemetryKind,
+    transmitter::{snippet, Transmitter},
+    TelemetryClient, TelemetryConfig,
+};
+
+/// Maximum time to wait for the ingestion endpoint to respond to [`submit`].
+pub const SUBMIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Submits telemetry items to the configured endpoint right away, bypassing the channel, and returns the
+/// response of the endpoint: how many items it received and accepted, and why it rejected the rest.
+/// End-to-end tests use it to verify that telemetry is accepted by a real Application Insights resource.
+///
+/// ```rust, no_run
+/// # async fn run() {
+/// use appinsights::{telemetry::EventTelemetry, test_util, TelemetryConfig, TelemetryContext};
+///
+/// let config = TelemetryConfig::new("<instrumentation key>".to_string());
+/// let envelope = (TelemetryContext::from(&config), EventTelemetry::new("event happened")).into();
+///
+/// let response = test_util::submit(&config, vec![envelope]).await.unwrap();
+/// assert_eq!(response.items_accepted, 1);
+/// # }
+/// ```
+pub async fn submit(config: &TelemetryConfig, items: Vec<Envelope>) -> Result<Transmission, Box<dyn Error>> {
+    let transmitter = Transmitter::from_config(config);
+    let (status, body) = transmitter.probe(&items, SUBMIT_TIMEOUT).await?;
+
+    serde_json::from_str(&body)
+        .map_err(|err| format!("unexpected response {}: {} ({})", status, snippet(&body), err).into())
+}
+
+/// Counts how many times the submission routine drained the queue and attempted to send telemetry items.
+/// A marker resolves waiters once the submission attempt is finished, so tests do not need to sleep.
+#[derive(Clone, Default)]
+pub struct DrainMarker {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    count: Mutex<usize>,
+    changed: Condvar,
+    notify: Notify,
+}
+
+impl DrainMarker {
+    /// Creates a new marker.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns how many times the submission routine drained the queue so far.
+    pub fn count(&self) -> usize {
+        *self.lock()
+    }
+
+    /// Waits until the submission routine drained the queue at least `count` times in total.
+    pub async fn wait(&self, count: usize) {
+        loop {
+            let notified = self.inner.notify.notified();
+            if self.count() >= count {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Blocks the current thread until the submission routine drained the queue at least `count` times
+    /// in total or `timeout` elapsed. Returns `true` if the queue was drained enough times.
+    pub fn wait_blocking(&self, count: usize, timeout: Duration) -> bool {
+        let guard = self.lock();
+        let (guard, _) = self
+            .inner
+            .changed
+            .wait_timeout_while(guard, timeout, |current| *current < count)
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *guard >= count
+    }
+
+    /// Records that the submission routine drained the queue once more.
+    pub(crate) fn mark(&self) {
+        *self.lock() += 1;
+        self.inner.changed.notify_all();
+        self.inner.notify.notify_waiters();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, usize> {
+        self.inner.count.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl PartialEq for DrainMarker {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl fmt::Debug for DrainMarker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DrainMarker").field("count", &self.count()).finish()
+    }
+}
+
+/// A channel that keeps telemetry items in memory instead of submitting them. Clones of the channel share
+/// captured items, so a test can inspect items tracked by a client it created.
+#[derive(Clone, Debug, Default)]
+pub struct CapturingChannel {
+    items: Arc<Mutex<Vec<Envelope>>>,
+}
+
+impl CapturingChannel {
+    /// Creates a new channel without any captured items.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a client with the given configuration that sends telemetry items to this channel.
+    pub fn client(&self, config: TelemetryConfig) -> TelemetryClient {
+        TelemetryClient::create(&config, self.clone())
+    }
+
+    /// Returns telemetry items captured so far.
+    pub fn items(&self) -> Vec<Envelope> {
+        self.lock().clone()
+    }
+
+    /// Removes all captured telemetry items.
+    pub fn clear(&self) {
+        self.lock().clear()
+    }
+
+    /// Returns a report of telemetry types and names of items captured so far.
+    pub fn coverage(&self) -> CoverageReport {
+        let mut report = CoverageReport::default();
+        for envelope in self.lock().iter() {
+            if let Some(kind) = TelemetryKind::of(envelope) {
+                let names = report.items.entry(kind).or_default();
+                if let Some(name) = name(envelope) {
+                    names.insert(name.into());
+                }
+            }
+        }
+        report
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Envelope>> {
+        self.items.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl TelemetryChannel for CapturingChannel {
+    fn send(&self, envelop: Envelope) {
+        self.lock().push(envelop);
+    }
+
+    fn flush(&self) {}
+
+    async fn close(&mut self) {}
+
+    async fn terminate(&mut self) {}
+}
+
+/// Returns a name telemetry items of the same type are told apart by: a name of an event, a request, a
+/// dependency, an availability test, a page view or a metric, or a type of an exception. Trace messages are
+/// not names, so traces are covered by their type only.
+fn name(envelope: &Envelope) -> Option<&str> {
+    match envelope.data.as_ref()? {
+        Base::Data(Data::AvailabilityData(data)) => Some(&data.name),
+        Base::Data(Data::EventData(data)) => Some(&data.name),
+        Base::Data(Data::ExceptionData(data)) => data.exceptions.first().map(|details| details.type_name.as_str()),
+        Base::Data(Data::MessageData(_)) => None,
+        Base::Data(Data::MetricData(data)) => data.metrics.first().map(|metric| metric.name.as_str()),
+        Base::Data(Data::PageViewData(data)) => Some(&data.name),
+        Base::Data(Data::RemoteDependencyData(data)) => Some(&data.name),
+        Base::Data(Data::RequestData(data)) => data.name.as_deref(),
+    }
+}
+
+/// Telemetry types and names of items a test run produced.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CoverageReport {
+    items: BTreeMap<TelemetryKind, BTreeSet<String>>,
+}
+
+impl CoverageReport {
+    /// Returns telemetry types of produced items.
+    pub fn kinds(&self) -> impl Iterator<Item = TelemetryKind> + '_ {
+        self.items.keys().copied()
+    }
+
+    /// Returns names of produced items of the given telemetry type.
+    pub fn names(&self, kind: TelemetryKind) -> impl Iterator<Item = &str> {
+        self.items.get(&kind).into_iter().flatten().map(String::as_str)
+    }
+
+    /// Returns `true` if an item of the given telemetry type and name was produced.
+    pub fn contains(&self, kind: TelemetryKind, name: &str) -> bool {
+        self.items.get(&kind).is_some_and(|names| names.contains(name))
+    }
+
+    /// Compares produced items with the expected ones. An item is missing if the manifest lists it but it was
+    /// not produced, and unexpected if it was produced but the manifest lists neither the item nor its type.
+    pub fn diff(&self, manifest: &CoverageManifest) -> CoverageDiff {
+        let mut diff = CoverageDiff::default();
+        for (kind, expected) in &manifest.items {
+            match self.items.get(kind) {
+                None => diff.missing.push((*kind, None)),
+                Some(names) => diff.missing.extend(
+                    expected
+                        .iter()
+                        .filter(|name| !names.contains(*name))
+                        .map(|name| (*kind, Some(name.clone()))),
+                ),
+            }
+        }
+        for (kind, names) in &self.items {
+            match manifest.items.get(kind) {
+                None => diff.unexpected.push((*kind, None)),
+                Some(expected) if !expected.is_empty() => diff.unexpected.extend(
+                    names
+                        .iter()
+                        .filter(|name| !expected.contains(*name))
+                        .map(|name| (*kind, Some(name.clone()))),
+                ),
+                Some(_) => {}
+            }
+        }
+        diff
+    }
+}
+
+/// Telemetry types and names of items a test run is expected to produce.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CoverageManifest {
+    items: BTreeMap<TelemetryKind, BTreeSet<String>>,
+}
+
+impl CoverageManifest {
+    /// Creates a manifest that expects no telemetry items.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expects at least one item of the given telemetry type with any name.
+    pub fn kind(mut self, kind: TelemetryKind) -> Self {
+        self.items.entry(kind).or_default();
+        self
+    }
+
+    /// Expects an item of the given telemetry type and name.
+    pub fn item(mut self, kind: TelemetryKind, name: impl Into<String>) -> Self {
+        self.items.entry(kind).or_default().insert(name.into());
+        self
+    }
+}
+
+/// Differences between telemetry items a test run produced and the expected ones. A name is `None` when a
+/// whole telemetry type is missing or unexpected.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CoverageDiff {
+    missing: Vec<(TelemetryKind, Option<String>)>,
+    unexpected: Vec<(TelemetryKind, Option<String>)>,
+}
+
+impl CoverageDiff {
+    /// Returns expected items that were not produced.
+    pub fn missing(&self) -> &[(TelemetryKind, Option<String>)] {
+        &self.missing
+    }
+
+    /// Returns produced items that were not expected.
+    pub fn unexpected(&self) -> &[(TelemetryKind, Option<String>)] {
+        &self.unexpected
+    }
+
+    /// Returns `true` if all expected items and no others were produced.
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.unexpected.is_empty()
+    }
+}
+
+impl fmt::Display for CoverageDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (label, items) in [("missing", &self.missing), ("unexpected", &self.unexpected)] {
+            for (kind, name) in items {
+                match name {
+                    Some(name) => writeln!(f, "{} {:?} {:?}", label, kind, name)?,
+                    None => writeln!(f, "{} {:?}", label, kind)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_resolves_when_drained_enough_times() {
+        let marker = DrainMarker::new();
+
+        let waiter = tokio::spawn({
+            let marker = marker.clone();
+            async move { marker.wait(2).await }
+        });
+
+        marker.mark();
+        marker.mark();
+
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("marker resolved")
+            .unwrap();
+        assert_eq!(marker.count(), 2);
+    }
+
+    #[test]
+    fn it_blocks_until_drained_enough_times() {
+        let marker = DrainMarker::new();
+
+        let handle = std::thread::spawn({
+            let marker = marker.clone();
+            move || marker.wait_blocking(1, Duration::from_secs(1))
+        });
+        marker.mark();
+
+        assert!(handle.join().unwrap());
+        assert!(!marker.wait_blocking(2, Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn it_reports_coverage_of_captured_items() {
+        let channel = CapturingChannel::new();
+        let client = channel.client(TelemetryConfig::new("instrumentation".into()));
+        client.track_event("order placed");
+        client.track_event("order placed");
+        client.track_metric("queue length", 5.0);
+        client.track_trace("order received", crate::telemetry::SeverityLevel::Information);
+
+        let report = channel.coverage();
+
+        assert_eq!(channel.items().len(), 4);
+        assert_eq!(
+            report.kinds().collect::<Vec<_>>(),
+            [TelemetryKind::Event, TelemetryKind::Metric, TelemetryKind::Trace]
+        );
+        assert!(report.contains(TelemetryKind::Event, "order placed"));
+        assert_eq!(
+            report.names(TelemetryKind::Metric).collect::<Vec<_>>(),
+            ["queue length"]
+        );
+        assert_eq!(report.names(TelemetryKind::Trace).count(), 0);
+
+        channel.clear();
+        assert_eq!(channel.coverage(), CoverageReport::default());
+    }
+
+    #[test]
+    fn it_diffs_coverage_against_manifest() {
+        let channel = CapturingChannel::new();
+        let client = channel.client(TelemetryConfig::new("instrumentation".into()));
+        client.track_event("order placed");
+        client.track_event("order shipped");
+        client.track_metric("queue length", 5.0);
+        client.track_trace("order received", crate::telemetry::SeverityLevel::Information);
+
+        let manifest = CoverageManifest::new()
+            .item(TelemetryKind::Event, "order placed")
+            .item(TelemetryKind::Event, "order canceled")
+            .kind(TelemetryKind::Metric)
+            .kind(TelemetryKind::Request);
+        let diff = channel.coverage().diff(&manifest);
+
+        assert!(!diff.is_empty());
+        assert_eq!(
+            diff.missing(),
+            [
+                (TelemetryKind::Event, Some("order canceled".into())),
+                (TelemetryKind::Request, None)
+            ]
+        );
+        assert_eq!(
+            diff.unexpected(),
+            [
+                (TelemetryKind::Event, Some("order shipped".into())),
+                (TelemetryKind::Trace, None)
+            ]
+        );
+        assert_eq!(
+            diff.to_string(),
+            "missing Event \"order canceled\"\nmissing Request\nunexpected Event \"order shipped\"\nunexpected Trace\n"
+        );
+    }
+}
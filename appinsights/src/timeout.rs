@@ -1,28 +1,32 @@
 pub use imp::*;
 
-#[cfg(not(test))]
+#[cfg(any(not(test), feature = "disabled"))]
 mod imp {
     use std::time::Duration;
 
-    use tokio::time::{self, Instant};
+    use crate::timer::{Sleep, Timer};
 
-    /// Creates a receiver that reliably delivers only one message when given interval expires.
-    pub async fn sleep(duration: Duration) {
-        let timeout = Instant::now() + duration;
-        time::sleep_until(timeout).await;
+    /// Starts waiting until given interval expires according to the timer.
+    pub fn sleep(timer: &dyn Timer, duration: Duration) -> Sleep {
+        timer.sleep(duration)
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, not(feature = "disabled")))]
 mod imp {
     use std::{sync::Arc, time::Duration};
 
     use lazy_static::lazy_static;
     use parking_lot::Mutex;
-    use tokio::{sync::Notify, time::Instant};
+    use tokio::sync::Notify;
+
+    use crate::timer::{Sleep, Timer};
 
     lazy_static! {
         static ref CHANNEL: Mutex<Option<Arc<Notify>>> = Mutex::new(None);
+
+        /// A global lock since tests that emulate timeout expiration share a channel and need to run in serial.
+        pub static ref SERIAL_TEST_MUTEX: Mutex<()> = Mutex::new(());
     }
 
     /// Initializes a channel which emulates timeout expiration event. External code should run
@@ -32,16 +36,14 @@ mod imp {
         *channel = Some(Arc::new(Notify::new()));
     }
 
-    /// Creates a copy of a receiver that delivers a current time stamp in order to emulate
-    /// timeout expiration for tests.
-    pub async fn sleep(duration: Duration) {
+    /// Starts waiting until timeout expiration is emulated if a channel was initialized in advance, or until
+    /// given interval expires according to the timer otherwise.
+    pub fn sleep(timer: &dyn Timer, duration: Duration) -> Sleep {
         let maybe_notify = CHANNEL.lock().clone();
 
-        if let Some(notify) = maybe_notify {
-            notify.notified().await;
-        } else {
-            let timeout = Instant::now() + duration;
-            tokio::time::sleep_until(timeout).await;
+        match maybe_notify {
+            Some(notify) => Box::pin(async move { notify.notified().await }),
+            None => timer.sleep(duration),
         }
     }
 
@@ -0,0 +1,134 @@
+//! Timers the SDK waits with between submissions and periodic tasks.
+//!
+//! Channels wait for the submission interval and retry timeouts, and periodic tasks such as
+//! [`Heartbeat`](../heartbeat/struct.Heartbeat.html) wait for their interval with a [`Timer`]. Requests that
+//! exceed the send deadline, items that exceed the maximum age and the flush of a reported panic are timed
+//! with it as well. By default the SDK relies on [`TokioTimer`]. Any other timer, e.g. a fake one that lets
+//! tests decide when an interval expires, can be configured with [`TelemetryConfigBuilder::timer`].
+//!
+//! [`TelemetryConfigBuilder::timer`]: ../struct.TelemetryConfigBuilder.html#method.timer
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! use std::{sync::Arc, time::Duration};
+//!
+//! use appinsights::{timer::{Sleep, Timer}, TelemetryClient, TelemetryConfig};
+//! use tokio::sync::Notify;
+//!
+//! /// A timer that expires only when a test asks it to.
+//! #[derive(Clone, Default)]
+//! struct FakeTimer(Arc<Notify>);
+//!
+//! impl Timer for FakeTimer {
+//!     fn sleep(&self, _: Duration) -> Sleep {
+//!         let notify = self.0.clone();
+//!         Box::pin(async move { notify.notified().await })
+//!     }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let timer = FakeTimer::default();
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .timer(timer.clone())
+//!     .build();
+//! let client = TelemetryClient::from_config(config);
+//!
+//! client.track_event("test");
+//!
+//! // the submission interval expires right away
+//! timer.0.notify_one();
+//! # }
+//! ```
+use std::{
+    future::Future,
+    pin::Pin,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "runtime")]
+use futures_util::future::{self, Either};
+
+/// A future that completes once a duration a [`Timer`] was asked to wait for has elapsed.
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Waits for durations the SDK needs to wait for.
+pub trait Timer: Send + Sync + 'static {
+    /// Returns a future that completes once the given duration has elapsed.
+    fn sleep(&self, duration: Duration) -> Sleep;
+
+    /// Returns a future that completes once the given deadline has been reached. Sleeps for the duration
+    /// remaining until the deadline by default.
+    fn sleep_until(&self, deadline: Instant) -> Sleep {
+        self.sleep(deadline.saturating_duration_since(Instant::now()))
+    }
+}
+
+#[cfg(feature = "runtime")]
+impl dyn Timer {
+    /// Awaits the given future for at most the given duration. Returns `None` if the duration elapsed first.
+    #[cfg(not(feature = "disabled"))]
+    pub(crate) async fn timeout<F: Future>(&self, duration: Duration, future: F) -> Option<F::Output> {
+        race(future, self.sleep(duration)).await
+    }
+
+    /// Awaits the given future until the given deadline. Returns `None` if the deadline was reached first.
+    #[cfg(feature = "runtime")]
+    pub(crate) async fn timeout_at<F: Future>(&self, deadline: Instant, future: F) -> Option<F::Output> {
+        race(future, self.sleep_until(deadline)).await
+    }
+}
+
+#[cfg(feature = "runtime")]
+async fn race<F: Future>(future: F, sleep: Sleep) -> Option<F::Output> {
+    futures_util::pin_mut!(future);
+    match future::select(future, sleep).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
+}
+
+/// A timer backed by the Tokio runtime the SDK runs on. Used by default.
+#[cfg(feature = "runtime")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TokioTimer;
+
+#[cfg(feature = "runtime")]
+impl Timer for TokioTimer {
+    fn sleep(&self, duration: Duration) -> Sleep {
+        Box::pin(tokio::time::sleep(duration))
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Sleep {
+        Box::pin(tokio::time::sleep_until(deadline.into()))
+    }
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn it_sleeps_with_tokio_timer() {
+        let started = Instant::now();
+
+        TokioTimer.sleep(Duration::from_millis(10)).await;
+
+        assert!(started.elapsed() >= Duration::from_millis(10));
+    }
+
+    #[tokio::test]
+    async fn it_times_out_with_timer() {
+        let timer: &dyn Timer = &TokioTimer;
+
+        assert_eq!(timer.timeout(Duration::from_secs(1), async { 42 }).await, Some(42));
+        assert_eq!(
+            timer.timeout(Duration::from_millis(10), future::pending::<()>()).await,
+            None
+        );
+    }
+}
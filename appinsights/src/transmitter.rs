@@ -1,13 +1,37 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, MutexGuard, RwLock},
+    time::{Duration, Instant},
+};
+
 use chrono::{DateTime, Utc};
-use http::{header::RETRY_AFTER, StatusCode};
-use log::debug;
-use reqwest::Client;
+use futures_util::Future;
+use http::{
+    header::{HeaderValue, CONTENT_ENCODING, RETRY_AFTER, USER_AGENT},
+    HeaderMap, StatusCode,
+};
+use log::{debug, warn};
+use reqwest::{Client, RequestBuilder, Url};
 
 use crate::{
+    callback,
+    codec::{self, Codec},
+    config::Shared,
+    context::SDK_VERSION,
     contracts::{Envelope, Transmission, TransmissionItem},
-    Result,
+    diagnostics::{ChannelEvent, Listener},
+    sink::TelemetrySink,
+    time,
+    timer::{Timer, TokioTimer},
+    Compression, Proxy, Result, TelemetryConfig,
 };
 
+/// Maximum number of characters of a response body kept for diagnostics.
+const BODY_SNIPPET_LEN: usize = 256;
+
+/// Capacity of the serialization buffer that is always kept once allocated.
+const MIN_RETAINED_BUFFER: usize = 64 * 1024;
+
 #[derive(Debug, PartialEq)]
 pub enum Response {
     Success,
@@ -16,79 +40,391 @@ pub enum Response {
     NoRetry,
 }
 
+/// A URL of the server that can be replaced while telemetry is being sent. Each request reads the URL once,
+/// so a batch is never split between an old and a new server.
+#[derive(Debug, Clone)]
+pub struct Endpoint(Arc<RwLock<String>>);
+
+impl Endpoint {
+    fn new(url: &str) -> Self {
+        Self(Arc::new(RwLock::new(url.into())))
+    }
+
+    /// Returns a current URL.
+    pub fn get(&self) -> String {
+        self.0.read().unwrap_or_else(|err| err.into_inner()).clone()
+    }
+
+    /// Replaces a URL for all subsequent requests.
+    pub fn set(&self, url: &str) {
+        *self.0.write().unwrap_or_else(|err| err.into_inner()) = url.into();
+    }
+}
+
 /// Sends telemetry items to the server.
 pub struct Transmitter {
-    url: String,
+    url: Endpoint,
+    headers: HeaderMap,
+    codec: Option<&'static dyn Codec>,
+    sink: Option<Shared<dyn TelemetrySink>>,
+    max_batch_size: Option<usize>,
+    max_envelope_size: usize,
+    max_request_size: usize,
+    send_deadline: Option<Duration>,
+    timer: Shared<dyn Timer>,
+    listener: Listener,
     client: Client,
+    buffer: Buffer,
 }
 
 impl Transmitter {
-    /// Creates a new instance of telemetry items sender.
-    pub fn new(url: &str) -> Self {
+    /// Creates a new instance of telemetry items sender that adds custom headers to every request.
+    /// Requests report the SDK version in the `User-Agent` header unless custom headers contain one.
+    pub fn new(url: &str, mut headers: HeaderMap) -> Self {
+        headers
+            .entry(USER_AGENT)
+            .or_insert_with(|| HeaderValue::from_static(SDK_VERSION));
+
         let client = Client::new();
         Self {
-            url: url.into(),
+            url: Endpoint::new(url),
+            headers,
+            codec: None,
+            sink: None,
+            max_batch_size: None,
+            max_envelope_size: usize::MAX,
+            max_request_size: usize::MAX,
+            send_deadline: None,
+            timer: Shared(Arc::new(TokioTimer)),
+            listener: Listener::default(),
             client,
+            buffer: Buffer::default(),
+        }
+    }
+
+    /// Creates a new instance of telemetry items sender with settings of the given configuration.
+    pub fn from_config(config: &TelemetryConfig) -> Self {
+        Self::new(config.endpoint(), config.headers().clone())
+            .user_agent_suffix(config.user_agent_suffix())
+            .proxy(config.proxy())
+            .compression(config.compression())
+            .sink(config.sink().cloned())
+            .max_batch_size(config.max_batch_size())
+            .max_envelope_size(config.max_envelope_size())
+            .max_request_size(config.max_request_size())
+            .send_deadline(config.send_deadline())
+            .timer(config.timer())
+            .listener(Listener::new(config.event_listener().cloned()))
+    }
+
+    /// Returns a handle to replace the URL of the server.
+    pub fn endpoint(&self) -> Endpoint {
+        self.url.clone()
+    }
+
+    /// Compresses requests with the given compression and tells the server how they are encoded with the
+    /// `Content-Encoding` header.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.codec = codec::codec(compression);
+        match self.codec {
+            Some(codec) => self
+                .headers
+                .insert(CONTENT_ENCODING, HeaderValue::from_static(codec.content_encoding())),
+            None => self.headers.remove(CONTENT_ENCODING),
+        };
+        self
+    }
+
+    /// Writes telemetry items to the given sink instead of the server, if any. Compression and headers
+    /// do not apply to the sink.
+    pub fn sink(mut self, sink: Option<Shared<dyn TelemetrySink>>) -> Self {
+        self.sink = sink;
+        self
+    }
+
+    /// Sends no more than the given number of telemetry items in one request, if any.
+    pub fn max_batch_size(mut self, max_batch_size: Option<usize>) -> Self {
+        self.max_batch_size = max_batch_size;
+        self
+    }
+
+    /// Drops telemetry items that take more than the given number of bytes once serialized.
+    pub fn max_envelope_size(mut self, max_envelope_size: usize) -> Self {
+        self.max_envelope_size = max_envelope_size;
+        self
+    }
+
+    /// Sends no more than the given number of bytes of serialized telemetry items in one request. Sizes are
+    /// measured before compression.
+    pub fn max_request_size(mut self, max_request_size: usize) -> Self {
+        self.max_request_size = max_request_size;
+        self
+    }
+
+    /// Cancels requests that are still in progress when the given time since the start of sending has passed.
+    /// Items of cancelled requests are retried.
+    pub fn send_deadline(mut self, send_deadline: Option<Duration>) -> Self {
+        self.send_deadline = send_deadline;
+        self
+    }
+
+    /// Waits for the send deadline with the given timer.
+    pub fn timer(mut self, timer: Shared<dyn Timer>) -> Self {
+        self.timer = timer;
+        self
+    }
+
+    /// Notifies the given listener about batches sent and failed and about oversized items dropped.
+    pub fn listener(mut self, listener: Listener) -> Self {
+        self.listener = listener;
+        self
+    }
+
+    /// Sends requests through the given HTTP proxy, if any. Requests are sent directly when the proxy cannot
+    /// be configured, e.g. its URL is invalid.
+    pub fn proxy(mut self, proxy: Option<&Proxy>) -> Self {
+        if let Some(proxy) = proxy {
+            match proxy_client(proxy) {
+                Ok(client) => self.client = client,
+                Err(err) => warn!("Unable to configure proxy {}: {}", proxy.url(), err),
+            }
         }
+        self
     }
 
-    /// Sends a telemetry items to the server.
-    pub async fn send(&self, mut items: Vec<Envelope>) -> Result<Response> {
-        let payload = serde_json::to_string(&items)?;
+    /// Appends an application-specific suffix to the `User-Agent` header separated with a space.
+    pub fn user_agent_suffix(mut self, suffix: Option<&str>) -> Self {
+        if let Some(suffix) = suffix {
+            let user_agent = match self.headers.get(USER_AGENT).map(HeaderValue::to_str) {
+                Some(Ok(user_agent)) => format!("{} {}", user_agent, suffix),
+                _ => format!("{} {}", SDK_VERSION, suffix),
+            };
 
-        let response = self.client.post(&self.url).body(payload).send().await?;
-        let response = match response.status() {
+            match HeaderValue::from_str(&user_agent) {
+                Ok(user_agent) => {
+                    self.headers.insert(USER_AGENT, user_agent);
+                }
+                Err(err) => warn!("Unable to append suffix to User-Agent header: {}", err),
+            }
+        }
+        self
+    }
+
+    /// Releases memory reserved for serialization of telemetry items.
+    pub fn shrink(&self) {
+        self.buffer.release();
+    }
+
+    /// Sends a telemetry items to the server. Items with different instrumentation keys, e.g. tracked before
+    /// and after the key has been rotated, are sent in separate requests, so throttling and errors reported
+    /// for one key do not affect items of another. Batches larger than the maximum batch size or the maximum
+    /// request size are split into several requests too.
+    pub async fn send(&self, items: Vec<Envelope>) -> Result<Response> {
+        let mut batches = VecDeque::from(batches(items, self.max_batch_size));
+        if batches.len() > 1 {
+            debug!("Sending telemetry items in {} requests", batches.len());
+        }
+
+        // the sink gets payloads as is
+        let codec = match self.sink {
+            Some(_) => None,
+            None => self.codec,
+        };
+
+        let deadline = self.send_deadline.map(|deadline| Instant::now() + deadline);
+        let mut requests = 0;
+        let mut responses = Vec::with_capacity(batches.len());
+        while let Some(mut batch) = batches.pop_front() {
+            let count = batch.len();
+            let (payload, rest) = self.payload(&mut batch, codec)?;
+            let dropped = count - batch.len() - rest.len();
+            if dropped > 0 {
+                self.listener.emit(ChannelEvent::ItemsDropped { count: dropped });
+            }
+            if !rest.is_empty() {
+                debug!(
+                    "Telemetry items exceed maximum request size of {} bytes. Sending {} items in a separate request",
+                    self.max_request_size,
+                    rest.len()
+                );
+                batches.push_front(rest);
+            }
+
+            if batch.is_empty() {
+                responses.push(Response::NoRetry);
+                continue;
+            }
+
+            requests += 1;
+            match self.send_batch(batch, payload, deadline).await {
+                Ok(response) => responses.push(response),
+                Err(err) if requests == 1 && batches.is_empty() => return Err(err),
+                Err(err) => debug!("Error occurred during sending telemetry items: {}", err),
+            }
+        }
+        Ok(merge(responses))
+    }
+
+    /// Awaits the given future until the deadline, if any. Returns `None` when the deadline passed first.
+    async fn within<F: Future>(&self, deadline: Option<Instant>, future: F) -> Option<F::Output> {
+        match deadline {
+            Some(deadline) => self.timer.timeout_at(deadline, future).await,
+            None => Some(future.await),
+        }
+    }
+
+    /// Sends a serialized batch of telemetry items with the same instrumentation key to the server.
+    async fn send_batch(
+        &self,
+        mut items: Vec<Envelope>,
+        payload: Vec<u8>,
+        deadline: Option<Instant>,
+    ) -> Result<Response> {
+        let count = items.len();
+        if let Some(sink) = &self.sink {
+            let written = match self
+                .within(deadline, callback::call_async("Telemetry sink", sink.write(payload)))
+                .await
+            {
+                Some(written) => written,
+                None => return Ok(self.cancel(items)),
+            };
+            return match written {
+                Some(Ok(())) => {
+                    debug!("Successfully wrote {} items to sink", count);
+                    self.listener.emit(ChannelEvent::BatchSent {
+                        items: count,
+                        accepted: count,
+                        status_code: None,
+                    });
+                    Ok(Response::Success)
+                }
+                Some(Err(err)) => {
+                    debug!("Unable to write items to sink: {}. Retry sending {} items", err, count);
+                    self.listener.emit(ChannelEvent::BatchFailed {
+                        items: count,
+                        status_code: None,
+                    });
+                    Ok(Response::Retry(items))
+                }
+                None => {
+                    self.listener.emit(ChannelEvent::BatchFailed {
+                        items: count,
+                        status_code: None,
+                    });
+                    Ok(Response::Retry(items))
+                }
+            };
+        }
+
+        let response = self
+            .within(deadline, async {
+                let response = self.request(payload).send().await?;
+                let status = response.status();
+                let retry_after = response.headers().get(RETRY_AFTER).cloned();
+
+                // proxies and gateways may respond with HTML error pages instead of JSON, so the body is
+                // read as text first and parsed separately to keep a snippet for diagnostics
+                let body = response.text().await.unwrap_or_default();
+                Ok::<_, reqwest::Error>((status, retry_after, body))
+            })
+            .await;
+        let (status, retry_after, body) = match response {
+            Some(Ok(response)) => response,
+            Some(Err(err)) => {
+                self.listener.emit(ChannelEvent::BatchFailed {
+                    items: count,
+                    status_code: None,
+                });
+                return Err(err.into());
+            }
+            None => return Ok(self.cancel(items)),
+        };
+        let content = serde_json::from_str::<Transmission>(&body);
+
+        self.listener.emit(match status {
+            StatusCode::OK => ChannelEvent::BatchSent {
+                items: count,
+                accepted: count,
+                status_code: Some(status.as_u16()),
+            },
+            StatusCode::PARTIAL_CONTENT => ChannelEvent::BatchSent {
+                items: count,
+                accepted: content.as_ref().map_or(0, |content| content.items_accepted),
+                status_code: Some(status.as_u16()),
+            },
+            _ => ChannelEvent::BatchFailed {
+                items: count,
+                status_code: Some(status.as_u16()),
+            },
+        });
+
+        let response = match status {
             StatusCode::OK => {
                 debug!("Successfully sent {} items", items.len());
                 Response::Success
             }
-            StatusCode::PARTIAL_CONTENT => {
-                let content: Transmission = response.json().await?;
-                let log_prefix = format!(
-                    "Successfully sent {}/{} telemetry items",
-                    content.items_accepted, content.items_received
-                );
-                if content.items_received == content.items_accepted {
-                    debug!("{}", log_prefix);
-                    Response::Success
-                } else {
-                    retain_retry_items(&mut items, content);
-                    if items.is_empty() {
-                        debug!("{}. Nothing to re-send", log_prefix);
-                        Response::NoRetry
+            StatusCode::PARTIAL_CONTENT => match content {
+                Ok(content) => {
+                    let log_prefix = format!(
+                        "Successfully sent {}/{} telemetry items",
+                        content.items_accepted, content.items_received
+                    );
+                    if content.items_received == content.items_accepted {
+                        debug!("{}", log_prefix);
+                        Response::Success
                     } else {
-                        debug!("{}. Retry sending {} items", log_prefix, items.len());
-                        Response::Retry(items)
+                        retain_retry_items(&mut items, content);
+                        if items.is_empty() {
+                            debug!("{}. Nothing to re-send", log_prefix);
+                            Response::NoRetry
+                        } else {
+                            debug!("{}. Retry sending {} items", log_prefix, items.len());
+                            Response::Retry(items)
+                        }
                     }
                 }
-            }
-            StatusCode::TOO_MANY_REQUESTS | StatusCode::REQUEST_TIMEOUT => {
-                let retry_after = response.headers().get(RETRY_AFTER).cloned();
-
-                if let Ok(content) = response.json::<Transmission>().await {
-                    retain_retry_items(&mut items, content);
-                }
-
-                if let Some(retry_after) = retry_after {
-                    let retry_after = retry_after.to_str()?;
-                    let retry_after = DateTime::parse_from_rfc2822(retry_after)?.with_timezone(&Utc);
+                Err(err) => {
                     debug!(
-                        "Some items were discarded. Retry sending {} items after {}",
+                        "Partially sent telemetry items but response is malformed: {}. Retry sending {} items. Response: {}",
+                        err,
                         items.len(),
-                        retry_after
+                        snippet(&body)
                     );
-                    Response::Throttled(retry_after, items)
-                } else {
-                    debug!("Some items were discarded. Retry sending {} items", items.len());
                     Response::Retry(items)
                 }
+            },
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::REQUEST_TIMEOUT => {
+                match content {
+                    Ok(content) => retain_retry_items(&mut items, content),
+                    Err(_) => debug!("Unable to parse response: {}", snippet(&body)),
+                }
+
+                match retry_after.as_ref().and_then(parse_retry_after) {
+                    Some(retry_after) => {
+                        debug!(
+                            "Some items were discarded. Retry sending {} items after {}",
+                            items.len(),
+                            retry_after
+                        );
+                        Response::Throttled(retry_after, items)
+                    }
+                    None => {
+                        debug!("Some items were discarded. Retry sending {} items", items.len());
+                        Response::Retry(items)
+                    }
+                }
             }
             StatusCode::SERVICE_UNAVAILABLE => {
-                debug!("Service unavailable. Retry sending {} items", items.len());
-                Response::Retry(items.to_vec())
+                debug!(
+                    "Service unavailable. Retry sending {} items. Response: {}",
+                    items.len(),
+                    snippet(&body)
+                );
+                Response::Retry(items)
             }
-            StatusCode::INTERNAL_SERVER_ERROR => {
-                if let Ok(content) = response.json::<Transmission>().await {
+            StatusCode::INTERNAL_SERVER_ERROR => match content {
+                Ok(content) => {
                     retain_retry_items(&mut items, content);
                     if items.is_empty() {
                         debug!("Service error. Nothing to re-send");
@@ -97,30 +433,262 @@ impl Transmitter {
                         debug!("Service error. Retry sending {} items", items.len());
                         Response::Retry(items)
                     }
-                } else {
-                    debug!("Service error. Retry sending {} items", items.len());
-                    Response::Retry(items.to_vec())
                 }
-            }
+                Err(_) => {
+                    debug!(
+                        "Service error. Retry sending {} items. Response: {}",
+                        items.len(),
+                        snippet(&body)
+                    );
+                    Response::Retry(items)
+                }
+            },
             _ => {
-                debug!(
-                    "Unknown status: {}. {}. Nothing to re-send",
-                    response.status(),
-                    response.text().await.unwrap_or_default()
-                );
+                debug!("Unknown status: {}. {}. Nothing to re-send", status, snippet(&body));
                 Response::NoRetry
             }
         };
 
         Ok(response)
     }
+
+    /// Sends telemetry items to the server within the given timeout and returns a status code and a body of
+    /// the response as is.
+    pub async fn probe(&self, items: &[Envelope], timeout: Duration) -> Result<(StatusCode, String)> {
+        let (payload, _) = self.payload(&mut items.to_vec(), self.codec)?;
+        let response = self.request(payload).timeout(timeout).send().await?;
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        Ok((status, body))
+    }
+
+    /// Serializes telemetry items to a request body. Oversized items are removed from the batch, so indices
+    /// of items the server reports back match the items sent. Items that do not fit into the request are split
+    /// off the batch and returned.
+    fn payload(&self, items: &mut Vec<Envelope>, codec: Option<&dyn Codec>) -> Result<(Vec<u8>, Vec<Envelope>)> {
+        self.buffer.serialize(
+            items,
+            self.max_envelope_size,
+            self.max_request_size,
+            |payload| match codec {
+                Some(codec) => Ok(codec.encode(payload)?),
+                None => Ok(payload.to_vec()),
+            },
+        )
+    }
+
+    /// Gives up on a batch that has not been sent before the send deadline, so its items are retried.
+    fn cancel(&self, items: Vec<Envelope>) -> Response {
+        warn!(
+            "Sending {} telemetry items exceeded deadline of {:?} and has been cancelled. Retry sending them",
+            items.len(),
+            self.send_deadline.unwrap_or_default()
+        );
+        self.listener.emit(ChannelEvent::BatchFailed {
+            items: items.len(),
+            status_code: None,
+        });
+        Response::Retry(items)
+    }
+
+    fn request(&self, payload: Vec<u8>) -> RequestBuilder {
+        self.client
+            .post(self.url.get())
+            .headers(self.headers.clone())
+            .body(payload)
+    }
+}
+
+/// A growable buffer telemetry items are serialized into, reused across batches to avoid growing a fresh
+/// allocation for every request. Every request still gets its own exactly sized copy of the payload, since
+/// the HTTP client takes ownership of a request body.
+#[derive(Debug, Default)]
+struct Buffer(Mutex<Vec<u8>>);
+
+impl Buffer {
+    /// Serializes telemetry items into the buffer and passes the payload to `f` to make a request body of it.
+    /// Items that take more than `max_item_size` bytes are left out of the payload and removed from `items`,
+    /// since the ingestion endpoint rejects the whole batch otherwise. Serialization stops at the first item
+    /// that would make the payload exceed `max_payload_size` bytes; the item and all items after it are split
+    /// off `items` and returned, so they can be sent in another request. A payload always takes at least one
+    /// item. The buffer gives memory back when it is much larger than batches being sent recently.
+    fn serialize<F>(
+        &self,
+        items: &mut Vec<Envelope>,
+        max_item_size: usize,
+        max_payload_size: usize,
+        f: F,
+    ) -> Result<(Vec<u8>, Vec<Envelope>)>
+    where
+        F: FnOnce(&[u8]) -> Result<Vec<u8>>,
+    {
+        let mut buffer = self.lock();
+        buffer.clear();
+
+        let mut kept = Vec::with_capacity(items.len());
+        let mut split = items.len();
+        buffer.push(b'[');
+        for (index, item) in items.iter().enumerate() {
+            let start = buffer.len();
+            if start > 1 {
+                buffer.push(b',');
+            }
+            let item_start = buffer.len();
+            serde_json::to_writer(&mut *buffer, item)?;
+
+            let size = buffer.len() - item_start;
+            if size > max_item_size {
+                warn!(
+                    "Telemetry item {} of {} bytes exceeds maximum size of {} bytes and has been dropped",
+                    item.name, size, max_item_size
+                );
+                buffer.truncate(start);
+            } else if start > 1 && buffer.len() + 1 > max_payload_size {
+                // one more byte for the closing bracket
+                buffer.truncate(start);
+                split = index;
+                break;
+            }
+            kept.push(size <= max_item_size);
+        }
+        buffer.push(b']');
+
+        let rest = items.split_off(split);
+        let mut kept = kept.into_iter();
+        items.retain(|_| kept.next().unwrap_or(true));
+
+        let body = f(&buffer).map(|body| (body, rest));
+
+        let retained = MIN_RETAINED_BUFFER.max(buffer.len() * 2);
+        if buffer.capacity() > retained * 2 {
+            debug!("Shrinking serialization buffer of {} bytes", buffer.capacity());
+            buffer.clear();
+            buffer.shrink_to(retained);
+        }
+        body
+    }
+
+    /// Releases all memory the buffer holds.
+    fn release(&self) {
+        let mut buffer = self.lock();
+        buffer.clear();
+        buffer.shrink_to_fit();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<u8>> {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    #[cfg(test)]
+    fn capacity(&self) -> usize {
+        self.lock().capacity()
+    }
+}
+
+/// Splits telemetry items into batches to send in separate requests: one batch per instrumentation key, each
+/// one split further into batches of no more than `max_batch_size` items, if any.
+fn batches(items: Vec<Envelope>, max_batch_size: Option<usize>) -> Vec<Vec<Envelope>> {
+    let groups = group_by_i_key(items);
+    let max = match max_batch_size {
+        Some(max) => max,
+        None => return groups,
+    };
+
+    let mut batches = Vec::with_capacity(groups.len());
+    for mut group in groups {
+        while group.len() > max {
+            let rest = group.split_off(max);
+            batches.push(group);
+            group = rest;
+        }
+        batches.push(group);
+    }
+    batches
+}
+
+/// Splits telemetry items into groups with the same instrumentation key, keeping the order of items
+/// within each group.
+fn group_by_i_key(items: Vec<Envelope>) -> Vec<Vec<Envelope>> {
+    let mut groups: Vec<Vec<Envelope>> = Vec::new();
+    for item in items {
+        match groups.iter_mut().find(|group| group[0].i_key == item.i_key) {
+            Some(group) => group.push(item),
+            None => groups.push(vec![item]),
+        }
+    }
+
+    if groups.is_empty() {
+        groups.push(Vec::new());
+    }
+    groups
+}
+
+/// Combines responses to requests for several instrumentation keys: items to retry are collected from
+/// all responses and the latest throttling time applies to all of them.
+fn merge(responses: Vec<Response>) -> Response {
+    let mut success = false;
+    let mut throttled = None;
+    let mut retry_items = Vec::new();
+
+    for response in responses {
+        match response {
+            Response::Success => success = true,
+            Response::Retry(items) => retry_items.extend(items),
+            Response::Throttled(retry_after, items) => {
+                throttled = throttled.max(Some(retry_after));
+                retry_items.extend(items);
+            }
+            Response::NoRetry => {}
+        }
+    }
+
+    match throttled {
+        Some(retry_after) => Response::Throttled(retry_after, retry_items),
+        None if !retry_items.is_empty() => Response::Retry(retry_items),
+        None if success => Response::Success,
+        None => Response::NoRetry,
+    }
+}
+
+/// Parses a value of Retry-After header. Returns `None` when header contains neither a valid date nor
+/// a number of seconds to wait.
+fn parse_retry_after(value: &HeaderValue) -> Option<DateTime<Utc>> {
+    let value = value.to_str().ok()?;
+    if let Ok(seconds) = value.trim().parse::<u32>() {
+        Some(time::now() + chrono::Duration::seconds(seconds.into()))
+    } else {
+        DateTime::parse_from_rfc2822(value)
+            .ok()
+            .map(|date| date.with_timezone(&Utc))
+    }
+}
+
+/// Returns the beginning of a response body to attach to diagnostics messages. Large bodies like
+/// HTML error pages are truncated to `BODY_SNIPPET_LEN` chars.
+pub(crate) fn snippet(body: &str) -> &str {
+    match body.char_indices().nth(BODY_SNIPPET_LEN) {
+        Some((index, _)) => &body[..index],
+        None => body,
+    }
 }
 
 /// Filters out those telemetry items that cannot be re-sent.
 fn retain_retry_items(items: &mut Vec<Envelope>, content: Transmission) {
     let mut retry_items = Vec::default();
-    for error in content.errors.iter().filter(|error| can_retry_item(error)) {
-        retry_items.push(items.remove(error.index - retry_items.len()));
+    for error in content.errors.iter() {
+        if can_retry_item(error) {
+            retry_items.push(items.remove(error.index - retry_items.len()));
+        } else {
+            debug!(
+                "Telemetry item {} rejected with {}: {}",
+                error.index, error.status_code, error.message
+            );
+            if let Some(Err(violations)) = items.get(error.index - retry_items.len()).map(Envelope::validate) {
+                for violation in violations {
+                    debug!("Telemetry item {} violation {}", error.index, violation);
+                }
+            }
+        }
     }
 
     *items = retry_items;
@@ -136,8 +704,30 @@ fn can_retry_item(item: &TransmissionItem) -> bool {
         || item.status_code == StatusCode::TOO_MANY_REQUESTS
 }
 
+/// Creates an HTTP client that sends requests through the given proxy unless their host bypasses it.
+fn proxy_client(proxy: &Proxy) -> Result<Client> {
+    let url = Url::parse(proxy.url())?;
+    let bypass = proxy.clone();
+    let mut client_proxy = reqwest::Proxy::custom(move |target| match target.host_str() {
+        Some(host) if bypass.bypasses(host) => None,
+        _ => Some(url.clone()),
+    });
+    if let (Some(username), Some(password)) = (proxy.username(), proxy.password()) {
+        client_proxy = client_proxy.basic_auth(username, password);
+    }
+
+    Ok(Client::builder().proxy(client_proxy).build()?)
+}
+
 #[cfg(test)]
 mod tests {
+    use std::{
+        io::Read,
+        sync::{Arc, Mutex},
+    };
+
+    use async_trait::async_trait;
+
     use chrono::TimeZone;
     use http::{Request, StatusCode};
     use hyper::{
@@ -148,6 +738,7 @@ mod tests {
     use test_case::test_case;
 
     use super::*;
+    use crate::sink::SinkError;
 
     #[test_case(items(), StatusCode::OK, None, Some(all_accepted()), Response::Success; "success")]
     #[test_case(items(), StatusCode::PARTIAL_CONTENT, None, Some(partial_some_retries()), Response::Retry(retry_items()); "partial. resend some items")]
@@ -173,9 +764,9 @@ mod tests {
     ) {
         let rt = tokio::runtime::Runtime::new().expect("runtime");
         rt.block_on(async {
-            let url = create_server(status_code, retry_after, body);
+            let url = create_server(status_code, retry_after, body.map(|body| body.to_string()));
 
-            let transmitter = Transmitter::new(&format!("{}/track", url));
+            let transmitter = Transmitter::new(&format!("{}/track", url), HeaderMap::new());
 
             let response = transmitter.send(items).await.unwrap();
 
@@ -183,7 +774,460 @@ mod tests {
         });
     }
 
-    fn create_server(status_code: StatusCode, retry_after: Option<&'static str>, body: Option<Value>) -> String {
+    #[test_case(StatusCode::OK, None, Response::Success; "success")]
+    #[test_case(StatusCode::PARTIAL_CONTENT, None, Response::Retry(items()); "partial. resend everything")]
+    #[test_case(StatusCode::BAD_REQUEST, None, Response::NoRetry; "bad request. no retry")]
+    #[test_case(StatusCode::REQUEST_TIMEOUT, None, Response::Retry(items()); "timeout. resend everything")]
+    #[test_case(StatusCode::REQUEST_TIMEOUT, Some(retry_after_str()), Response::Throttled(retry_after(), items()); "timeout. throttled")]
+    #[test_case(StatusCode::TOO_MANY_REQUESTS, None, Response::Retry(items()); "too many requests. resend everything")]
+    #[test_case(StatusCode::TOO_MANY_REQUESTS, Some("not a date"), Response::Retry(items()); "too many requests. invalid retry-after. resend everything")]
+    #[test_case(StatusCode::INTERNAL_SERVER_ERROR, None, Response::Retry(items()); "server error. resend everything")]
+    #[test_case(StatusCode::SERVICE_UNAVAILABLE, None, Response::Retry(items()); "service unavailable. resend everything")]
+    fn it_handles_html_response_body(status_code: StatusCode, retry_after: Option<&'static str>, expected: Response) {
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        rt.block_on(async {
+            let url = create_server(status_code, retry_after, Some(html_body()));
+
+            let transmitter = Transmitter::new(&format!("{}/track", url), HeaderMap::new());
+
+            let response = transmitter.send(items()).await.unwrap();
+
+            assert_eq!(response, expected);
+        });
+    }
+
+    #[test]
+    fn it_throttles_when_retry_after_contains_seconds() {
+        time::set(Utc.ymd(2017, 8, 9).and_hms(23, 42, 57));
+
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        rt.block_on(async {
+            let url = create_server(StatusCode::TOO_MANY_REQUESTS, Some("60"), None);
+
+            let transmitter = Transmitter::new(&format!("{}/track", url), HeaderMap::new());
+
+            let response = transmitter.send(items()).await.unwrap();
+
+            assert_eq!(response, Response::Throttled(retry_after(), items()));
+        });
+
+        time::reset();
+    }
+
+    #[test]
+    fn it_sends_custom_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ms-client-request-id", HeaderValue::from_static("42"));
+
+        let response = send_expecting_header(Transmitter::new, headers, "x-ms-client-request-id", "42");
+
+        assert_eq!(response, Response::Success);
+    }
+
+    #[test]
+    fn it_sends_sdk_version_as_user_agent() {
+        let response = send_expecting_header(Transmitter::new, HeaderMap::new(), "user-agent", SDK_VERSION);
+
+        assert_eq!(response, Response::Success);
+    }
+
+    #[test]
+    fn it_appends_suffix_to_user_agent() {
+        let user_agent = format!("{} orders/1.4", SDK_VERSION);
+
+        let response = send_expecting_header(
+            |url, headers| Transmitter::new(url, headers).user_agent_suffix(Some("orders/1.4")),
+            HeaderMap::new(),
+            "user-agent",
+            &user_agent,
+        );
+
+        assert_eq!(response, Response::Success);
+    }
+
+    #[tokio::test]
+    async fn it_retries_items_when_sending_exceeds_deadline() {
+        // the listener accepts connections but never responds
+        let server = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", server.local_addr().unwrap());
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let listener = Listener::new(Some(Shared(Arc::new({
+            let events = events.clone();
+            move |event: &ChannelEvent| events.lock().unwrap().push(event.clone())
+        }))));
+        let transmitter = Transmitter::new(&url, HeaderMap::new())
+            .send_deadline(Some(Duration::from_millis(100)))
+            .listener(listener);
+
+        assert_eq!(transmitter.send(items()).await.unwrap(), Response::Retry(items()));
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![ChannelEvent::BatchFailed {
+                items: items().len(),
+                status_code: None
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_writes_items_to_sink_instead_of_server() {
+        struct TestSink(Mutex<Vec<Vec<u8>>>, bool);
+
+        #[async_trait]
+        impl TelemetrySink for TestSink {
+            async fn write(&self, batch: Vec<u8>) -> std::result::Result<(), SinkError> {
+                self.0.lock().unwrap().push(batch);
+                if self.1 {
+                    Ok(())
+                } else {
+                    Err("unavailable".into())
+                }
+            }
+        }
+
+        let sink = Arc::new(TestSink(Mutex::default(), true));
+        let transmitter =
+            Transmitter::new("http://localhost:0/track", HeaderMap::new()).sink(Some(Shared(sink.clone())));
+
+        assert_eq!(transmitter.send(items()).await.unwrap(), Response::Success);
+        assert_eq!(*sink.0.lock().unwrap(), vec![serde_json::to_vec(&items()).unwrap()]);
+
+        let sink = Arc::new(TestSink(Mutex::default(), false));
+        let transmitter = Transmitter::new("http://localhost:0/track", HeaderMap::new()).sink(Some(Shared(sink)));
+
+        assert_eq!(transmitter.send(items()).await.unwrap(), Response::Retry(items()));
+    }
+
+    #[tokio::test]
+    async fn it_retries_items_when_sink_panicked() {
+        struct PanickingSink;
+
+        #[async_trait]
+        impl TelemetrySink for PanickingSink {
+            async fn write(&self, batch: Vec<u8>) -> std::result::Result<(), SinkError> {
+                if !batch.is_empty() {
+                    panic!("sink is broken");
+                }
+                Ok(())
+            }
+        }
+
+        let transmitter =
+            Transmitter::new("http://localhost:0/track", HeaderMap::new()).sink(Some(Shared(Arc::new(PanickingSink))));
+
+        assert_eq!(transmitter.send(items()).await.unwrap(), Response::Retry(items()));
+    }
+
+    #[test]
+    fn it_groups_items_by_instrumentation_key() {
+        let items = vec![item("1", "old"), item("2", "new"), item("3", "old")];
+
+        let groups = group_by_i_key(items);
+
+        assert_eq!(
+            groups,
+            vec![vec![item("1", "old"), item("3", "old")], vec![item("2", "new")]]
+        );
+    }
+
+    #[test]
+    fn it_splits_groups_exceeding_max_batch_size() {
+        let items = vec![item("1", "old"), item("2", "new"), item("3", "old"), item("4", "old")];
+
+        let batches = batches(items, Some(2));
+
+        assert_eq!(
+            batches,
+            vec![
+                vec![item("1", "old"), item("3", "old")],
+                vec![item("4", "old")],
+                vec![item("2", "new")]
+            ]
+        );
+    }
+
+    #[test_case(vec![Response::Success, Response::NoRetry], Response::Success; "success")]
+    #[test_case(vec![Response::NoRetry, Response::NoRetry], Response::NoRetry; "no retry")]
+    #[test_case(vec![Response::Success, Response::Retry(retry_items())], Response::Retry(retry_items()); "retry")]
+    #[test_case(vec![Response::Retry(retry_items()), Response::Throttled(retry_after(), retry_items())], Response::Throttled(retry_after(), [retry_items(), retry_items()].concat()); "throttled")]
+    fn it_merges_responses_for_several_instrumentation_keys(responses: Vec<Response>, expected: Response) {
+        assert_eq!(merge(responses), expected);
+    }
+
+    #[tokio::test]
+    async fn it_sends_items_with_different_instrumentation_keys_separately() {
+        let (url, bodies) = recording_server();
+
+        let transmitter = Transmitter::new(&url, HeaderMap::new());
+        let response = transmitter
+            .send(vec![item("1", "old"), item("2", "new"), item("3", "old")])
+            .await
+            .unwrap();
+
+        assert_eq!(response, Response::Success);
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 2);
+        assert!(bodies[0].contains(r#""iKey":"old""#) && !bodies[0].contains(r#""iKey":"new""#));
+        assert!(bodies[1].contains(r#""iKey":"new""#) && !bodies[1].contains(r#""iKey":"old""#));
+    }
+
+    #[tokio::test]
+    async fn it_splits_items_exceeding_max_request_size() {
+        let (url, bodies) = recording_server();
+        let items = vec![item("1", "key"), item("2", "key"), item("3", "key")];
+        let max_request_size = serde_json::to_vec(&items[..2]).unwrap().len();
+
+        let transmitter = Transmitter::new(&url, HeaderMap::new()).max_request_size(max_request_size);
+        let response = transmitter.send(items).await.unwrap();
+
+        assert_eq!(response, Response::Success);
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(
+            *bodies,
+            vec![
+                serde_json::to_string(&[item("1", "key"), item("2", "key")]).unwrap(),
+                serde_json::to_string(&[item("3", "key")]).unwrap()
+            ]
+        );
+    }
+
+    /// Starts a server that accepts all requests and records their bodies.
+    fn recording_server() -> (String, Arc<Mutex<Vec<String>>>) {
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let make_service = {
+            let bodies = bodies.clone();
+            make_service_fn(move |_| {
+                let bodies = bodies.clone();
+                async move {
+                    Ok::<_, hyper::Error>(service_fn(move |request: Request<Body>| {
+                        let bodies = bodies.clone();
+                        async move {
+                            let body = hyper::body::to_bytes(request).await?;
+                            bodies.lock().unwrap().push(String::from_utf8_lossy(&body).to_string());
+                            Ok::<_, hyper::Error>(hyper::Response::new(Body::empty()))
+                        }
+                    }))
+                }
+            })
+        };
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = format!("http://{}/track", server.local_addr());
+        tokio::spawn(server);
+        (url, bodies)
+    }
+
+    #[test_case(Compression::Gzip, "gzip"; "gzip")]
+    #[test_case(Compression::Deflate, "deflate"; "deflate")]
+    #[cfg_attr(feature = "brotli", test_case(Compression::Brotli, "br"; "brotli"))]
+    fn it_compresses_payload(compression: Compression, encoding: &'static str) {
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        rt.block_on(async {
+            // the server accepts only compressed requests that contain all items
+            let make_service = make_service_fn(move |_| async move {
+                Ok::<_, hyper::Error>(service_fn(move |request: Request<Body>| async move {
+                    let encoded = request
+                        .headers()
+                        .get("content-encoding")
+                        .is_some_and(|value| value == encoding);
+                    let body = hyper::body::to_bytes(request.into_body()).await.unwrap_or_default();
+
+                    let mut content = String::new();
+                    let decoded = match encoding {
+                        "gzip" => flate2::read::GzDecoder::new(body.as_ref()).read_to_string(&mut content),
+                        "deflate" => flate2::read::ZlibDecoder::new(body.as_ref()).read_to_string(&mut content),
+                        #[cfg(feature = "brotli")]
+                        "br" => brotli::Decompressor::new(body.as_ref(), 4096).read_to_string(&mut content),
+                        _ => unreachable!("unexpected encoding {}", encoding),
+                    };
+
+                    let status_code = match serde_json::from_str::<Vec<Value>>(&content) {
+                        Ok(items) if encoded && decoded.is_ok() && items.len() == 5 => StatusCode::OK,
+                        _ => StatusCode::BAD_REQUEST,
+                    };
+                    hyper::Response::builder().status(status_code).body(Body::empty())
+                }))
+            });
+            let server = Server::bind(&([0, 0, 0, 0], 0).into()).serve(make_service);
+            let url = format!("http://{}/track", server.local_addr());
+            tokio::spawn(server);
+
+            let transmitter = Transmitter::new(&url, HeaderMap::new()).compression(compression);
+
+            let response = transmitter.send(items()).await.unwrap();
+
+            assert_eq!(response, Response::Success);
+        });
+    }
+
+    #[test]
+    fn it_does_not_declare_encoding_of_uncompressed_payload() {
+        let transmitter = Transmitter::new("https://localhost/track", HeaderMap::new())
+            .compression(Compression::Gzip)
+            .compression(Compression::None);
+
+        assert!(transmitter.headers.get(CONTENT_ENCODING).is_none());
+    }
+
+    /// Sends items to a server that accepts only requests with the expected header value.
+    fn send_expecting_header<F>(create: F, headers: HeaderMap, name: &'static str, value: &str) -> Response
+    where
+        F: FnOnce(&str, HeaderMap) -> Transmitter,
+    {
+        let value = value.to_string();
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        rt.block_on(async move {
+            let make_service = make_service_fn(move |_| {
+                let value = value.clone();
+                async move {
+                    Ok::<_, hyper::Error>(service_fn(move |request: Request<Body>| {
+                        let status_code = match request.headers().get(name) {
+                            Some(actual) if actual == value.as_str() => StatusCode::OK,
+                            _ => StatusCode::BAD_REQUEST,
+                        };
+                        async move { hyper::Response::builder().status(status_code).body(Body::empty()) }
+                    }))
+                }
+            });
+            let server = Server::bind(&([0, 0, 0, 0], 0).into()).serve(make_service);
+            let url = format!("http://{}/track", server.local_addr());
+            tokio::spawn(server);
+
+            let transmitter = create(&url, headers);
+            transmitter.send(items()).await.unwrap()
+        })
+    }
+
+    #[test]
+    fn it_reuses_serialization_buffer() {
+        let buffer = Buffer::default();
+
+        let (payload, _) = buffer
+            .serialize(&mut items(), usize::MAX, usize::MAX, |payload| Ok(payload.to_vec()))
+            .unwrap();
+        let capacity = buffer.capacity();
+
+        assert_eq!(payload, serde_json::to_vec(&items()).unwrap());
+        assert!(capacity >= payload.len());
+
+        buffer
+            .serialize(&mut items(), usize::MAX, usize::MAX, |payload| Ok(payload.to_vec()))
+            .unwrap();
+        assert_eq!(buffer.capacity(), capacity);
+    }
+
+    #[test]
+    fn it_drops_oversized_items() {
+        let buffer = Buffer::default();
+        let mut items = vec![
+            item("small", "key"),
+            item(&"large".repeat(100), "key"),
+            item("small again", "key"),
+        ];
+        let max_item_size = serde_json::to_vec(&item("small again", "key")).unwrap().len();
+
+        let (payload, _) = buffer
+            .serialize(&mut items, max_item_size, usize::MAX, |payload| Ok(payload.to_vec()))
+            .unwrap();
+
+        let expected = vec![item("small", "key"), item("small again", "key")];
+        assert_eq!(items, expected);
+        assert_eq!(payload, serde_json::to_vec(&expected).unwrap());
+    }
+
+    #[tokio::test]
+    async fn it_notifies_listener_about_oversized_items_dropped() {
+        struct NullSink;
+
+        #[async_trait]
+        impl TelemetrySink for NullSink {
+            async fn write(&self, _: Vec<u8>) -> std::result::Result<(), SinkError> {
+                Ok(())
+            }
+        }
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let listener = Listener::new(Some(Shared(Arc::new({
+            let events = events.clone();
+            move |event: &ChannelEvent| events.lock().unwrap().push(event.clone())
+        }))));
+        let items = vec![item("small", "key"), item(&"large".repeat(100), "key")];
+        let transmitter = Transmitter::new("http://localhost:0/track", HeaderMap::new())
+            .sink(Some(Shared(Arc::new(NullSink))))
+            .max_envelope_size(serde_json::to_vec(&item("small", "key")).unwrap().len())
+            .listener(listener);
+
+        assert_eq!(transmitter.send(items).await.unwrap(), Response::Success);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                ChannelEvent::ItemsDropped { count: 1 },
+                ChannelEvent::BatchSent {
+                    items: 1,
+                    accepted: 1,
+                    status_code: None
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn it_splits_off_items_exceeding_max_payload_size() {
+        let buffer = Buffer::default();
+        let mut items = vec![item("1", "key"), item("2", "key"), item("3", "key")];
+        let max_payload_size = serde_json::to_vec(&items[..2]).unwrap().len();
+
+        let (payload, rest) = buffer
+            .serialize(&mut items, usize::MAX, max_payload_size, |payload| Ok(payload.to_vec()))
+            .unwrap();
+
+        assert_eq!(items, vec![item("1", "key"), item("2", "key")]);
+        assert_eq!(rest, vec![item("3", "key")]);
+        assert_eq!(payload, serde_json::to_vec(&items).unwrap());
+    }
+
+    #[test]
+    fn it_serializes_at_least_one_item_exceeding_max_payload_size() {
+        let buffer = Buffer::default();
+        let mut items = vec![item("1", "key"), item("2", "key")];
+
+        let (payload, rest) = buffer
+            .serialize(&mut items, usize::MAX, 1, |payload| Ok(payload.to_vec()))
+            .unwrap();
+
+        assert_eq!(items, vec![item("1", "key")]);
+        assert_eq!(rest, vec![item("2", "key")]);
+        assert_eq!(payload, serde_json::to_vec(&items).unwrap());
+    }
+
+    #[test]
+    fn it_shrinks_serialization_buffer_after_large_batch() {
+        let buffer = Buffer::default();
+        let mut large: Vec<_> = (0..10_000).flat_map(|_| items()).collect();
+
+        buffer
+            .serialize(&mut large, usize::MAX, usize::MAX, |payload| Ok(payload.to_vec()))
+            .unwrap();
+        assert!(buffer.capacity() > MIN_RETAINED_BUFFER * 2);
+
+        buffer
+            .serialize(&mut items(), usize::MAX, usize::MAX, |payload| Ok(payload.to_vec()))
+            .unwrap();
+        assert!(buffer.capacity() <= MIN_RETAINED_BUFFER * 2);
+
+        buffer.release();
+        assert_eq!(buffer.capacity(), 0);
+    }
+
+    #[test]
+    fn it_truncates_response_body_snippet() {
+        let body = "x".repeat(BODY_SNIPPET_LEN * 2);
+
+        assert_eq!(snippet(&body).len(), BODY_SNIPPET_LEN);
+        assert_eq!(snippet("<html></html>"), "<html></html>");
+    }
+
+    fn create_server(status_code: StatusCode, retry_after: Option<&'static str>, body: Option<String>) -> String {
         let make_service = make_service_fn(move |_| {
             let retry_after = retry_after.map(ToString::to_string);
             let body = body.clone();
@@ -198,7 +1242,7 @@ mod tests {
                             builder = builder.header("Retry-After", retry_after);
                         }
 
-                        let body = body.map(move |body| Body::from(body.to_string())).unwrap_or_default();
+                        let body = body.map(Body::from).unwrap_or_default();
 
                         builder.body(body)
                     }
@@ -214,6 +1258,11 @@ mod tests {
         url
     }
 
+    fn html_body() -> String {
+        "<html><head><title>503 Service Unavailable</title></head><body><h1>Service Unavailable</h1></body></html>"
+            .into()
+    }
+
     fn partial_no_retries() -> Value {
         json!({
             "itemsAccepted": 3,
@@ -305,12 +1354,20 @@ mod tests {
     fn items() -> Vec<Envelope> {
         (0..5)
             .map(|i| Envelope {
-                name: format!("event {}", i),
+                name: format!("event {}", i).into(),
                 ..Envelope::default()
             })
             .collect()
     }
 
+    fn item(name: &str, i_key: &str) -> Envelope {
+        Envelope {
+            name: name.to_string().into(),
+            i_key: Some(i_key.into()),
+            ..Envelope::default()
+        }
+    }
+
     fn retry_items() -> Vec<Envelope> {
         vec![Envelope {
             name: "event 4".into(),
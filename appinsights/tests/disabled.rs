@@ -0,0 +1,66 @@
+#![cfg(feature = "disabled")]
+
+use std::time::Duration;
+
+use appinsights::{
+    connectivity::ConnectivityError, scope::Scope, telemetry::SeverityLevel, FlushReport, TelemetryClient,
+};
+
+#[test]
+fn it_swallows_telemetry_without_runtime() {
+    // no tokio runtime is running here, so any attempt to spawn a submission task would panic
+    let client = TelemetryClient::new("<instrumentation key>".to_string());
+    assert!(!client.is_enabled());
+
+    client.track_event("event happened");
+    client.track_trace("Unable to connect to a gateway", SeverityLevel::Warning);
+    client.track_availability("ping", Duration::from_millis(10), true);
+    client.flush_channel();
+}
+
+#[tokio::test]
+async fn it_completes_flush_and_operations_without_channel() {
+    let client = TelemetryClient::new("<instrumentation key>".to_string());
+
+    assert_eq!(client.flush_and_wait().await, FlushReport::default());
+    assert!(matches!(
+        client.verify_connectivity().await,
+        Err(ConnectivityError::Disabled)
+    ));
+
+    let operation = client.start_operation("process order");
+    let current = operation.run(async { Scope::current() }).await;
+    assert_eq!(current, None);
+    operation.complete();
+
+    client.close_channel().await;
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn it_swallows_telemetry_without_background_thread() {
+    let client = appinsights::blocking::TelemetryClient::new("<instrumentation key>".to_string());
+    assert!(!client.is_enabled());
+
+    client.track_event("event happened");
+    client.flush_channel().expect("flushed");
+    client.close_channel().expect("closed");
+}
+
+#[cfg(feature = "runtime")]
+#[tokio::test]
+async fn it_keeps_runtime_apis_inert() {
+    use std::sync::Arc;
+
+    use appinsights::{heartbeat::Heartbeat, ChannelStats};
+
+    let client = Arc::new(TelemetryClient::new("<instrumentation key>".to_string()));
+    assert_eq!(client.channel_stats(), ChannelStats::default());
+    client.track_channel_stats();
+
+    let heartbeat = Heartbeat::new().spawn(&client, Duration::from_secs(60));
+    tokio::time::timeout(Duration::from_secs(1), heartbeat)
+        .await
+        .expect("completed right away")
+        .expect("not panicked");
+}
@@ -0,0 +1,111 @@
+//! End-to-end tests that submit telemetry to a real Application Insights resource and verify that the
+//! ingestion endpoint accepts it. They are ignored unless the `e2e` feature is enabled and read a connection
+//! string of the resource from the `APPINSIGHTS_E2E_CONNECTION_STRING` environment variable.
+//!
+//! ```sh
+//! APPINSIGHTS_E2E_CONNECTION_STRING="InstrumentationKey=...;IngestionEndpoint=..." \
+//!     cargo test -p appinsights --features e2e --test e2e
+//! ```
+#![cfg(all(feature = "test-util", not(feature = "disabled")))]
+
+use std::{env, time::Duration};
+
+use appinsights::{
+    contracts::Envelope,
+    telemetry::{
+        AvailabilityTelemetry, EventTelemetry, ExceptionTelemetry, MetricTelemetry, PageViewTelemetry,
+        RemoteDependencyTelemetry, RequestTelemetry, SeverityLevel, TraceTelemetry,
+    },
+    test_util, ConnectionString, TelemetryClient, TelemetryConfig, TelemetryContext,
+};
+use hyper::{Method, Uri};
+
+#[tokio::test]
+#[cfg_attr(not(feature = "e2e"), ignore = "requires the e2e feature and a connection string")]
+async fn it_accepts_all_telemetry_types() {
+    let config = config();
+    let context = TelemetryContext::from(&config);
+    let uri = "https://api.github.com/dmolokanov/appinsights-rs"
+        .parse::<Uri>()
+        .unwrap();
+
+    let items: Vec<Envelope> = vec![
+        (context.clone(), EventTelemetry::new("e2e event")).into(),
+        (
+            context.clone(),
+            TraceTelemetry::new("e2e trace", SeverityLevel::Warning),
+        )
+            .into(),
+        (context.clone(), MetricTelemetry::new("e2e_metric", 113.0)).into(),
+        (
+            context.clone(),
+            RequestTelemetry::new(Method::GET, uri.clone(), Duration::from_millis(100), "200"),
+        )
+            .into(),
+        (
+            context.clone(),
+            RemoteDependencyTelemetry::new("GET", "HTTP", Duration::from_millis(50), "api.github.com", true),
+        )
+            .into(),
+        (
+            context.clone(),
+            AvailabilityTelemetry::new("e2e availability", Duration::from_secs(2), true),
+        )
+            .into(),
+        (context.clone(), ExceptionTelemetry::new("e2e", "e2e exception")).into(),
+        (context, PageViewTelemetry::new("e2e page view", uri)).into(),
+    ];
+    for item in &items {
+        assert_eq!(item.validate(), Ok(()), "{}", item.to_pretty_json());
+        #[cfg(feature = "schema")]
+        assert_eq!(item.validate_schema(), Ok(()), "{}", item.to_pretty_json());
+    }
+
+    let response = test_util::submit(&config, items).await.expect("response");
+
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(response.items_received, 8);
+    assert_eq!(response.items_accepted, 8);
+}
+
+#[tokio::test]
+#[cfg_attr(not(feature = "e2e"), ignore = "requires the e2e feature and a connection string")]
+async fn it_reports_rejected_items() {
+    let config = config();
+    let context = TelemetryContext::from(&config);
+    let valid = (context.clone(), EventTelemetry::new("e2e event")).into();
+    let mut invalid: Envelope = (context, EventTelemetry::new("e2e event")).into();
+    invalid.time = "yesterday".into();
+
+    let response = test_util::submit(&config, vec![valid, invalid])
+        .await
+        .expect("response");
+
+    assert_eq!(response.items_received, 2);
+    assert_eq!(response.items_accepted, 1);
+    assert_eq!(response.errors.len(), 1);
+    assert_eq!(response.errors[0].index, 1);
+}
+
+#[tokio::test]
+#[cfg_attr(not(feature = "e2e"), ignore = "requires the e2e feature and a connection string")]
+async fn it_verifies_connectivity() {
+    let client = TelemetryClient::from_config(config());
+
+    let info = client.verify_connectivity().await.expect("endpoint is reachable");
+
+    assert!(info.status_code().is_success());
+}
+
+/// Reads a configuration of the test resource from the environment.
+fn config() -> TelemetryConfig {
+    let connection_string = env::var("APPINSIGHTS_E2E_CONNECTION_STRING")
+        .expect("Set APPINSIGHTS_E2E_CONNECTION_STRING first")
+        .parse::<ConnectionString>()
+        .expect("valid connection string");
+
+    TelemetryConfig::builder()
+        .i_key(connection_string.i_key())
+        .endpoint(connection_string.endpoint())
+        .build()
+}
@@ -0,0 +1,104 @@
+#![cfg(feature = "macros")]
+
+use std::{io, sync::Mutex};
+
+use appinsights::{
+    contracts::{Base, Data, Envelope},
+    instrument_ai,
+    scope::Scope,
+    telemetry::Telemetry,
+    TelemetryContext, Tracker,
+};
+
+#[derive(Default)]
+struct TestTracker(Mutex<Vec<Envelope>>);
+
+impl Tracker for TestTracker {
+    fn track<E>(&self, event: E)
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        let context = TelemetryContext::new("instrumentation".into(), Default::default(), Default::default());
+        self.0.lock().unwrap().push((context, event).into());
+    }
+}
+
+struct Repository {
+    tracker: TestTracker,
+}
+
+impl Repository {
+    #[instrument_ai(client = self.tracker, name = "load user", dependency_type = "SQL")]
+    async fn load(&self, id: u64) -> io::Result<Option<Scope>> {
+        if id == 0 {
+            Err(io::Error::other("not found"))?;
+        }
+        Ok(Scope::current())
+    }
+}
+
+#[instrument_ai(client = *tracker)]
+async fn outer(tracker: &TestTracker) -> Scope {
+    inner(tracker).await;
+    Scope::current().unwrap()
+}
+
+#[instrument_ai(client = *tracker)]
+async fn inner(tracker: &TestTracker) {}
+
+#[tokio::test]
+async fn it_tracks_instrumented_calls() {
+    let repository = Repository {
+        tracker: TestTracker::default(),
+    };
+
+    let scope = repository.load(42).await.unwrap().unwrap();
+    assert!(repository.load(0).await.is_err());
+    assert_eq!(Scope::current(), None);
+
+    let items = repository.tracker.0.into_inner().unwrap();
+    let data = dependencies(&items);
+    assert_eq!(data.len(), 2);
+    assert_eq!(data[0].0, "load user");
+    assert_eq!(data[0].1, "SQL");
+    assert!(data[0].2);
+    assert_eq!(data[0].3.as_deref(), Some(scope.id()));
+    assert!(!data[1].2);
+}
+
+#[tokio::test]
+async fn it_nests_instrumented_calls() {
+    let tracker = TestTracker::default();
+
+    let scope = outer(&tracker).await;
+
+    let items = tracker.0.into_inner().unwrap();
+    let data = dependencies(&items);
+    assert_eq!(data[0].0, "inner");
+    assert_eq!(data[0].1, "InProc");
+    assert_eq!(data[1].0, "outer");
+    assert_eq!(data[1].3.as_deref(), Some(scope.id()));
+
+    let tags = items[0].tags.as_ref().unwrap();
+    assert_eq!(
+        tags.get("ai.operation.id").map(String::as_str),
+        Some(scope.operation_id())
+    );
+    assert_eq!(tags.get("ai.operation.parentId").map(String::as_str), Some(scope.id()));
+}
+
+fn dependencies(items: &[Envelope]) -> Vec<(String, String, bool, Option<String>)> {
+    items
+        .iter()
+        .map(|envelope| match &envelope.data {
+            Some(Base::Data(Data::RemoteDependencyData(data))) => (
+                data.name.clone(),
+                data.type_.clone().unwrap_or_default(),
+                data.success.unwrap_or_default(),
+                data.id.clone(),
+            ),
+            _ => panic!("dependency expected"),
+        })
+        .collect()
+}
@@ -1,3 +1,5 @@
+#![allow(dead_code)]
+
 use std::{
     sync::{Arc, RwLock},
     time::Duration,
@@ -9,11 +11,9 @@ use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
 pub async fn wait_until(entries: &Arc<RwLock<Vec<String>>>, msg: &str, panic_after: Duration) {
     let panic_after = Utc::now() + chrono::Duration::from_std(panic_after).unwrap();
     loop {
-        let entries = entries.read().unwrap();
-        if entries.iter().any(|entry| entry.contains(msg)) {
+        if entries.read().unwrap().iter().any(|entry| entry.contains(msg)) {
             break;
         }
-        drop(entries);
 
         if Utc::now() > panic_after {
             panic!("Test took too long to finish");
@@ -25,11 +25,9 @@ pub async fn wait_until(entries: &Arc<RwLock<Vec<String>>>, msg: &str, panic_aft
 pub fn wait_until_blocking(entries: &Arc<RwLock<Vec<String>>>, msg: &str, panic_after: Duration) {
     let panic_after = Utc::now() + chrono::Duration::from_std(panic_after).unwrap();
     loop {
-        let entries = entries.read().unwrap();
-        if entries.iter().any(|entry| entry.contains(msg)) {
+        if entries.read().unwrap().iter().any(|entry| entry.contains(msg)) {
             break;
         }
-        drop(entries);
 
         if Utc::now() > panic_after {
             panic!("Test took too long to finish");
@@ -1,3 +1,5 @@
+#![cfg(not(feature = "disabled"))]
+
 mod logger;
 
 use std::{
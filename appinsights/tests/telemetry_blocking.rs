@@ -1,3 +1,5 @@
+#![cfg(all(feature = "blocking", not(feature = "disabled")))]
+
 mod logger;
 
 use std::{
@@ -40,7 +42,7 @@ fn it_tracks_all_telemetry_items() {
         true,
     );
 
-    ai.close_channel();
+    ai.close_channel().expect("close telemetry channel");
 
     logger::wait_until_blocking(&entries, "Successfully sent 6 items", Duration::from_secs(10));
 }